@@ -0,0 +1,800 @@
+//! Local analytics over a detected graph, computed in the runner instead of
+//! on the database server.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+
+use crate::graph_analyzer;
+
+/// How many vertices have a certain degree.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DegreeCount {
+    pub degree: usize,
+    pub vertices: usize,
+    pub percent: f64, // Of the largest count, for drawing bars
+}
+
+/// Vertices and edges per collection, i.e. per prefix of their `_id`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct CollectionCount {
+    pub collection: String,
+    pub vertices: usize,
+    pub edges: usize,
+}
+
+/// How many components have a certain number of vertices.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ComponentSize {
+    pub size: usize,
+    pub components: usize,
+}
+
+/// Basic figures of a graph.
+#[derive(Debug, Serialize, Clone)]
+pub struct GraphMetrics {
+    pub vertices: usize,
+    pub edges: usize,
+    pub density: f64, // Edges relative to the possible directed edges
+    pub self_loops: usize,
+    pub average_degree: f64,
+    pub max_degree: usize,
+    pub degree_distribution: Vec<DegreeCount>,
+    pub collections: Vec<CollectionCount>,
+    pub components: usize,
+    pub component_sizes: Vec<ComponentSize>, // Largest components first
+}
+
+/// The collection of a document, the part of an id before the `/`.
+pub(crate) fn collection(id: &str) -> &str {
+    id.split_once('/').map(|(c, _)| c).unwrap_or("")
+}
+
+fn id_of<'a>(document: &'a Value, field: &str) -> Option<&'a str> {
+    document[field].as_str()
+}
+
+/// The degree (incoming plus outgoing edges) of every vertex.
+pub fn degrees(vertices: &[Value], edges: &[Value]) -> HashMap<String, usize> {
+    let mut degrees: HashMap<String, usize> = vertices
+        .iter()
+        .filter_map(|v| id_of(v, "_id"))
+        .map(|id| (id.to_string(), 0))
+        .collect();
+    for edge in edges {
+        for end in ["_from", "_to"] {
+            if let Some(id) = id_of(edge, end) {
+                *degrees.entry(id.to_string()).or_default() += 1;
+            }
+        }
+    }
+    degrees
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// The graph with vertices numbered by their sorted ids, which keeps all
+/// results independent of the order of the documents.
+struct Adjacency {
+    ids: Vec<String>,
+    outgoing: Vec<Vec<(usize, usize)>>, // Target vertex and edge index
+    incoming: Vec<Vec<(usize, usize)>>, // Source vertex and edge index
+    edges: Vec<(usize, usize)>,         // Source and target of every edge
+}
+
+impl Adjacency {
+    fn new(vertices: &[Value], edges: &[Value]) -> Self {
+        let mut ids: Vec<String> = degrees(vertices, edges).into_keys().collect();
+        ids.sort();
+        let index: HashMap<&str, usize> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.as_str(), i))
+            .collect();
+        let mut outgoing = vec![Vec::new(); ids.len()];
+        let mut incoming = vec![Vec::new(); ids.len()];
+        let mut pairs = Vec::new();
+        for (i, edge) in edges.iter().enumerate() {
+            if let (Some(from), Some(to)) = (id_of(edge, "_from"), id_of(edge, "_to")) {
+                let (from, to) = (index[from], index[to]);
+                outgoing[from].push((to, i));
+                incoming[to].push((from, i));
+                pairs.push((from, to));
+            }
+        }
+        Adjacency {
+            ids,
+            outgoing,
+            incoming,
+            edges: pairs,
+        }
+    }
+}
+
+/// The weakly connected components of a graph, i.e. ignoring the direction
+/// of edges. Maps every vertex id to the number of its component; components
+/// are numbered from 0 by decreasing size, ties broken by the smallest id.
+pub fn components(vertices: &[Value], edges: &[Value]) -> HashMap<String, usize> {
+    let Adjacency {
+        ids, edges: pairs, ..
+    } = Adjacency::new(vertices, edges);
+
+    let mut parents: Vec<usize> = (0..ids.len()).collect();
+    for (from, to) in pairs {
+        let (a, b) = (find(&mut parents, from), find(&mut parents, to));
+        // The smaller index becomes the root, so roots are the smallest ids
+        parents[a.max(b)] = a.min(b);
+    }
+
+    let mut sizes: BTreeMap<usize, usize> = BTreeMap::new();
+    let roots: Vec<usize> = (0..ids.len()).map(|i| find(&mut parents, i)).collect();
+    for root in &roots {
+        *sizes.entry(*root).or_default() += 1;
+    }
+    let mut order: Vec<(usize, usize)> = sizes.into_iter().collect();
+    order.sort_by(|(root_a, size_a), (root_b, size_b)| size_b.cmp(size_a).then(root_a.cmp(root_b)));
+    let numbers: HashMap<usize, usize> = order
+        .iter()
+        .enumerate()
+        .map(|(number, (root, _))| (*root, number))
+        .collect();
+
+    ids.into_iter()
+        .zip(roots)
+        .map(|(id, root)| (id, numbers[&root]))
+        .collect()
+}
+
+/// Graphs with more vertices get no betweenness centrality, it takes time
+/// proportional to vertices times edges.
+pub const BETWEENNESS_MAX_VERTICES: usize = 2000;
+
+const PAGERANK_DAMPING: f64 = 0.85;
+const PAGERANK_ITERATIONS: usize = 100;
+const PAGERANK_TOLERANCE: f64 = 1e-10;
+
+/// The PageRank of every vertex, following edges in their direction. The
+/// rank of vertices without outgoing edges is spread over all vertices.
+fn pagerank(graph: &Adjacency) -> Vec<f64> {
+    let n = graph.ids.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let mut ranks = vec![1.0 / n as f64; n];
+    for _ in 0..PAGERANK_ITERATIONS {
+        let dangling: f64 = (0..n)
+            .filter(|&i| graph.outgoing[i].is_empty())
+            .map(|i| ranks[i])
+            .sum();
+        let base = (1.0 - PAGERANK_DAMPING + PAGERANK_DAMPING * dangling) / n as f64;
+        let mut next = vec![base; n];
+        for (from, targets) in graph.outgoing.iter().enumerate() {
+            let share = PAGERANK_DAMPING * ranks[from] / targets.len().max(1) as f64;
+            for (to, _) in targets {
+                next[*to] += share;
+            }
+        }
+        let change: f64 = ranks.iter().zip(&next).map(|(a, b)| (a - b).abs()).sum();
+        ranks = next;
+        if change < PAGERANK_TOLERANCE {
+            break;
+        }
+    }
+    ranks
+}
+
+/// The betweenness centrality of every vertex after Brandes, over directed
+/// shortest paths counted in hops, normalized to the range 0 to 1.
+fn betweenness(graph: &Adjacency) -> Vec<f64> {
+    let n = graph.ids.len();
+    let mut centrality = vec![0.0; n];
+    for source in 0..n {
+        let mut order = Vec::with_capacity(n);
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let mut paths = vec![0.0; n];
+        let mut distance: Vec<Option<usize>> = vec![None; n];
+        paths[source] = 1.0;
+        distance[source] = Some(0);
+        let mut queue = std::collections::VecDeque::from([source]);
+        while let Some(v) = queue.pop_front() {
+            order.push(v);
+            let next = distance[v].map(|d| d + 1);
+            for (w, _) in &graph.outgoing[v] {
+                if distance[*w].is_none() {
+                    distance[*w] = next;
+                    queue.push_back(*w);
+                }
+                if distance[*w] == next {
+                    paths[*w] += paths[v];
+                    predecessors[*w].push(v);
+                }
+            }
+        }
+        let mut dependency = vec![0.0; n];
+        for w in order.into_iter().rev() {
+            for v in &predecessors[w] {
+                dependency[*v] += paths[*v] / paths[w] * (1.0 + dependency[w]);
+            }
+            if w != source {
+                centrality[w] += dependency[w];
+            }
+        }
+    }
+    if n > 2 {
+        let pairs = ((n - 1) * (n - 2)) as f64;
+        centrality.iter_mut().for_each(|c| *c /= pairs);
+    }
+    centrality
+}
+
+/// Adds the results of the local analytics as attributes to every vertex,
+/// so they end up in Cytoscape, the browser viewer and exports:
+/// `component` (the number of its connected component), `degree`,
+/// `pagerank` and, for graphs of up to [`BETWEENNESS_MAX_VERTICES`]
+/// vertices, `betweenness`.
+pub fn annotate(vertices: &mut [Value], edges: &[Value]) {
+    let components = components(vertices, edges);
+    let degrees = degrees(vertices, edges);
+    let graph = Adjacency::new(vertices, edges);
+    let pageranks = pagerank(&graph);
+    let betweenness = if graph.ids.len() <= BETWEENNESS_MAX_VERTICES {
+        betweenness(&graph)
+    } else {
+        Vec::new()
+    };
+
+    for vertex in vertices {
+        let Some(id) = id_of(vertex, "_id").map(str::to_string) else {
+            continue;
+        };
+        let Some(obj) = vertex.as_object_mut() else {
+            continue;
+        };
+        obj.insert("component".to_string(), components[&id].into());
+        obj.insert("degree".to_string(), degrees[&id].into());
+        if let Ok(i) = graph.ids.binary_search(&id) {
+            obj.insert("pagerank".to_string(), pageranks[i].into());
+            if let Some(b) = betweenness.get(i) {
+                obj.insert("betweenness".to_string(), (*b).into());
+            }
+        }
+    }
+}
+
+/// A shortest path between two vertices.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ShortestPath {
+    pub vertices: Vec<String>, // Ids from start to end
+    pub edges: Vec<usize>,     // Positions of the edges in the graph
+    pub length: f64,           // Sum of the weights, or number of edges
+}
+
+/// Vertices still to visit, ordered so the heap pops the nearest first.
+struct Candidate {
+    distance: f64,
+    vertex: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then_with(|| other.vertex.cmp(&self.vertex))
+    }
+}
+
+/// Finds a shortest path from `from` to `to` with Dijkstra's algorithm.
+/// Edges weigh the numeric value of their `weight` attribute, or 1 if it
+/// is not given or missing (which makes it a breadth-first search). Unless
+/// `directed`, edges are also followed against their direction. Returns
+/// `Ok(None)` if there is no path, and an error for unknown vertices or
+/// negative weights.
+pub fn shortest_path(
+    vertices: &[Value],
+    edges: &[Value],
+    from: &str,
+    to: &str,
+    weight: Option<&str>,
+    directed: bool,
+) -> Result<Option<ShortestPath>, String> {
+    let graph = Adjacency::new(vertices, edges);
+    let position = |id: &str| {
+        graph
+            .ids
+            .binary_search_by(|probe| probe.as_str().cmp(id))
+            .map_err(|_| format!("Vertex '{}' is not part of the graph", id))
+    };
+    let (start, end) = (position(from)?, position(to)?);
+    let weight_of = |edge: usize| -> Result<f64, String> {
+        let value = weight.and_then(|w| edges[edge][w].as_f64()).unwrap_or(1.0);
+        if value < 0.0 {
+            return Err(format!("Edge {} has the negative weight {}", edge, value));
+        }
+        Ok(value)
+    };
+
+    let n = graph.ids.len();
+    let mut distance = vec![f64::INFINITY; n];
+    let mut previous: Vec<Option<(usize, usize)>> = vec![None; n];
+    let mut heap = BinaryHeap::from([Candidate {
+        distance: 0.0,
+        vertex: start,
+    }]);
+    distance[start] = 0.0;
+    while let Some(Candidate {
+        distance: d,
+        vertex,
+    }) = heap.pop()
+    {
+        if vertex == end {
+            break;
+        }
+        if d > distance[vertex] {
+            continue; // Already reached on a shorter way
+        }
+        let backwards = if directed {
+            &[][..]
+        } else {
+            &graph.incoming[vertex][..]
+        };
+        for (next, edge) in graph.outgoing[vertex].iter().chain(backwards) {
+            let candidate = d + weight_of(*edge)?;
+            if candidate < distance[*next] {
+                distance[*next] = candidate;
+                previous[*next] = Some((vertex, *edge));
+                heap.push(Candidate {
+                    distance: candidate,
+                    vertex: *next,
+                });
+            }
+        }
+    }
+    if distance[end].is_infinite() {
+        return Ok(None);
+    }
+
+    let mut path = ShortestPath {
+        vertices: vec![graph.ids[end].clone()],
+        edges: Vec::new(),
+        length: distance[end],
+    };
+    let mut current = end;
+    while let Some((vertex, edge)) = previous[current] {
+        path.vertices.push(graph.ids[vertex].clone());
+        path.edges.push(edge);
+        current = vertex;
+    }
+    path.vertices.reverse();
+    path.edges.reverse();
+    Ok(Some(path))
+}
+
+/// Attributes added by the runner, which are ignored when comparing graphs.
+const ANNOTATIONS: &[&str] = &["component", "degree", "pagerank", "betweenness", "diff"];
+
+/// What changed between two graphs.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct DiffSummary {
+    pub added_vertices: usize,
+    pub removed_vertices: usize,
+    pub changed_vertices: usize,
+    pub added_edges: usize,
+    pub removed_edges: usize,
+    pub changed_edges: usize,
+}
+
+/// A document without the attributes added by the runner.
+fn comparable(document: &Value) -> Value {
+    let mut document = document.clone();
+    if let Some(obj) = document.as_object_mut() {
+        for attr in ANNOTATIONS {
+            obj.remove(*attr);
+        }
+    }
+    document
+}
+
+/// Marks every document of `new` as `added`, `changed` or `unchanged`
+/// compared to `old`, and appends those only in `old` as `removed`.
+fn diff_documents(
+    old: &[Value],
+    new: &[Value],
+    key: impl Fn(&Value) -> Option<String>,
+) -> (Vec<Value>, usize, usize, usize) {
+    let old_by_key: HashMap<String, &Value> =
+        old.iter().filter_map(|d| Some((key(d)?, d))).collect();
+    let new_keys: HashSet<String> = new.iter().filter_map(&key).collect();
+    let (mut added, mut changed) = (0, 0);
+    let mark = |document: &Value, status: &str| {
+        let mut document = document.clone();
+        if let Some(obj) = document.as_object_mut() {
+            obj.insert("diff".to_string(), status.into());
+        }
+        document
+    };
+
+    let mut documents: Vec<Value> = new
+        .iter()
+        .map(|d| {
+            let status = match key(d).and_then(|k| old_by_key.get(&k)) {
+                None => {
+                    added += 1;
+                    "added"
+                }
+                Some(before) if comparable(before) != comparable(d) => {
+                    changed += 1;
+                    "changed"
+                }
+                Some(_) => "unchanged",
+            };
+            mark(d, status)
+        })
+        .collect();
+    let removed: Vec<Value> = old
+        .iter()
+        .filter(|d| key(d).is_some_and(|k| !new_keys.contains(&k)))
+        .map(|d| mark(d, "removed"))
+        .collect();
+    let removed_count = removed.len();
+    documents.extend(removed);
+    (documents, added, removed_count, changed)
+}
+
+/// Combines two graphs into one, with an attribute `diff` on every vertex
+/// and edge telling whether it was `added`, `removed`, `changed` or is
+/// `unchanged` in the new graph. Attributes computed by the runner, such as
+/// `pagerank`, are not compared.
+pub fn diff(
+    old_vertices: &[Value],
+    old_edges: &[Value],
+    new_vertices: &[Value],
+    new_edges: &[Value],
+) -> (Vec<Value>, Vec<Value>, DiffSummary) {
+    let vertex_key = |v: &Value| id_of(v, "_id").map(str::to_string);
+    let edge_key = |e: &Value| e.as_object().map(graph_analyzer::edge_key);
+    let (vertices, added_vertices, removed_vertices, changed_vertices) =
+        diff_documents(old_vertices, new_vertices, vertex_key);
+    let (edges, added_edges, removed_edges, changed_edges) =
+        diff_documents(old_edges, new_edges, edge_key);
+    let summary = DiffSummary {
+        added_vertices,
+        removed_vertices,
+        changed_vertices,
+        added_edges,
+        removed_edges,
+        changed_edges,
+    };
+    (vertices, edges, summary)
+}
+
+/// Computes the metrics of a graph as returned by the graph analyzer.
+pub fn metrics(vertices: &[Value], edges: &[Value]) -> GraphMetrics {
+    let degrees = degrees(vertices, edges);
+    let vertex_count = degrees.len();
+
+    let mut histogram: BTreeMap<usize, usize> = BTreeMap::new();
+    for degree in degrees.values() {
+        *histogram.entry(*degree).or_default() += 1;
+    }
+    let largest = histogram.values().copied().max().unwrap_or(0).max(1);
+    let degree_distribution = histogram
+        .into_iter()
+        .map(|(degree, count)| DegreeCount {
+            degree,
+            vertices: count,
+            percent: 100.0 * count as f64 / largest as f64,
+        })
+        .collect();
+
+    let mut collections: BTreeMap<String, CollectionCount> = BTreeMap::new();
+    let mut count = |id: &str, is_edge: bool| {
+        let name = collection(id);
+        let entry = collections
+            .entry(name.to_string())
+            .or_insert_with(|| CollectionCount {
+                collection: name.to_string(),
+                vertices: 0,
+                edges: 0,
+            });
+        if is_edge {
+            entry.edges += 1;
+        } else {
+            entry.vertices += 1;
+        }
+    };
+    for id in degrees.keys() {
+        count(id, false);
+    }
+    for edge in edges {
+        // Constructed edges have no `_id` and are counted without collection
+        count(id_of(edge, "_id").unwrap_or(""), true);
+    }
+
+    let mut component_sizes: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut vertices_per_component: HashMap<usize, usize> = HashMap::new();
+    for component in components(vertices, edges).into_values() {
+        *vertices_per_component.entry(component).or_default() += 1;
+    }
+    for size in vertices_per_component.values() {
+        *component_sizes.entry(*size).or_default() += 1;
+    }
+
+    let possible_edges = vertex_count.saturating_mul(vertex_count.saturating_sub(1));
+    GraphMetrics {
+        vertices: vertex_count,
+        edges: edges.len(),
+        density: if possible_edges == 0 {
+            0.0
+        } else {
+            edges.len() as f64 / possible_edges as f64
+        },
+        self_loops: edges
+            .iter()
+            .filter(|e| id_of(e, "_from").is_some() && id_of(e, "_from") == id_of(e, "_to"))
+            .count(),
+        average_degree: if vertex_count == 0 {
+            0.0
+        } else {
+            degrees.values().sum::<usize>() as f64 / vertex_count as f64
+        },
+        max_degree: degrees.values().copied().max().unwrap_or(0),
+        degree_distribution,
+        collections: collections.into_values().collect(),
+        components: vertices_per_component.len(),
+        component_sizes: component_sizes
+            .into_iter()
+            .rev()
+            .map(|(size, components)| ComponentSize { size, components })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_metrics() {
+        let vertices = vec![
+            json!({ "_id": "people/alice" }),
+            json!({ "_id": "people/bob" }),
+            json!({ "_id": "cities/cologne" }),
+        ];
+        let edges = vec![
+            json!({ "_id": "knows/1", "_from": "people/alice", "_to": "people/bob" }),
+            json!({ "_id": "lives/1", "_from": "people/alice", "_to": "cities/cologne" }),
+            json!({ "_id": "knows/2", "_from": "people/bob", "_to": "people/bob" }),
+        ];
+        let m = metrics(&vertices, &edges);
+        assert_eq!(m.vertices, 3);
+        assert_eq!(m.edges, 3);
+        assert_eq!(m.density, 0.5);
+        assert_eq!(m.self_loops, 1);
+        assert_eq!(m.max_degree, 3);
+        assert_eq!(m.average_degree, 2.0);
+        assert_eq!(
+            m.degree_distribution
+                .iter()
+                .map(|d| (d.degree, d.vertices))
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (2, 1), (3, 1)]
+        );
+        assert_eq!(
+            m.collections,
+            vec![
+                CollectionCount {
+                    collection: "cities".to_string(),
+                    vertices: 1,
+                    edges: 0
+                },
+                CollectionCount {
+                    collection: "knows".to_string(),
+                    vertices: 0,
+                    edges: 2
+                },
+                CollectionCount {
+                    collection: "lives".to_string(),
+                    vertices: 0,
+                    edges: 1
+                },
+                CollectionCount {
+                    collection: "people".to_string(),
+                    vertices: 2,
+                    edges: 0
+                },
+            ]
+        );
+        assert_eq!(m.components, 1);
+    }
+
+    #[test]
+    fn test_components() {
+        let mut vertices = vec![
+            json!({ "_id": "v/a" }),
+            json!({ "_id": "v/b" }),
+            json!({ "_id": "v/c" }),
+            json!({ "_id": "v/d" }),
+            json!({ "_id": "v/e" }),
+        ];
+        let edges = vec![
+            json!({ "_from": "v/d", "_to": "v/c" }),
+            json!({ "_from": "v/e", "_to": "v/d" }),
+            json!({ "_from": "v/a", "_to": "v/b" }),
+            json!({ "_from": "v/x", "_to": "v/x" }),
+        ];
+        annotate(&mut vertices, &edges);
+        let numbers: Vec<_> = vertices.iter().map(|v| v["component"].clone()).collect();
+        assert_eq!(
+            numbers,
+            vec![json!(1), json!(1), json!(0), json!(0), json!(0)]
+        );
+
+        let m = metrics(&vertices, &edges);
+        assert_eq!(m.components, 3);
+        assert_eq!(
+            m.component_sizes,
+            vec![
+                ComponentSize {
+                    size: 3,
+                    components: 1
+                },
+                ComponentSize {
+                    size: 2,
+                    components: 1
+                },
+                ComponentSize {
+                    size: 1,
+                    components: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_centrality() {
+        // A star pointing at its center, plus a chain through the center
+        let mut vertices: Vec<Value> = ["v/a", "v/b", "v/c", "v/hub"]
+            .iter()
+            .map(|id| json!({ "_id": id }))
+            .collect();
+        let edges = vec![
+            json!({ "_from": "v/a", "_to": "v/hub" }),
+            json!({ "_from": "v/b", "_to": "v/hub" }),
+            json!({ "_from": "v/hub", "_to": "v/c" }),
+        ];
+        annotate(&mut vertices, &edges);
+        let hub = &vertices[3];
+        assert_eq!(hub["degree"], 3);
+        // a->c and b->c pass the hub, out of 3 * 2 ordered pairs
+        assert_eq!(hub["betweenness"].as_f64(), Some(2.0 / 6.0));
+        assert_eq!(vertices[0]["betweenness"].as_f64(), Some(0.0));
+
+        let ranks: Vec<f64> = vertices
+            .iter()
+            .map(|v| v["pagerank"].as_f64().unwrap())
+            .collect();
+        assert!((ranks.iter().sum::<f64>() - 1.0).abs() < 1e-6);
+        assert!(ranks[2] > ranks[3] && ranks[3] > ranks[0]);
+        assert_eq!(ranks[0], ranks[1]);
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let vertices: Vec<Value> = ["v/a", "v/b", "v/c", "v/d"]
+            .iter()
+            .map(|id| json!({ "_id": id }))
+            .collect();
+        let edges = vec![
+            json!({ "_from": "v/a", "_to": "v/b", "weight": 1 }),
+            json!({ "_from": "v/b", "_to": "v/c", "weight": 1 }),
+            json!({ "_from": "v/a", "_to": "v/c", "weight": 5 }),
+            json!({ "_from": "v/d", "_to": "v/c", "weight": 1 }),
+        ];
+
+        let hops = shortest_path(&vertices, &edges, "v/a", "v/c", None, true).unwrap();
+        assert_eq!(
+            hops,
+            Some(ShortestPath {
+                vertices: vec!["v/a".to_string(), "v/c".to_string()],
+                edges: vec![2],
+                length: 1.0
+            })
+        );
+
+        let weighted = shortest_path(&vertices, &edges, "v/a", "v/c", Some("weight"), true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(weighted.vertices, vec!["v/a", "v/b", "v/c"]);
+        assert_eq!(weighted.edges, vec![0, 1]);
+        assert_eq!(weighted.length, 2.0);
+
+        // v/d can only be reached against the direction of its edge
+        assert_eq!(
+            shortest_path(&vertices, &edges, "v/a", "v/d", None, true).unwrap(),
+            None
+        );
+        let undirected = shortest_path(&vertices, &edges, "v/a", "v/d", None, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(undirected.vertices, vec!["v/a", "v/c", "v/d"]);
+
+        assert!(shortest_path(&vertices, &edges, "v/a", "v/x", None, true).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let old_vertices = vec![
+            json!({ "_id": "v/a", "age": 30, "pagerank": 0.5 }),
+            json!({ "_id": "v/b", "age": 40 }),
+            json!({ "_id": "v/c" }),
+        ];
+        let old_edges = vec![
+            json!({ "_id": "e/1", "_from": "v/a", "_to": "v/b" }),
+            json!({ "_id": "e/2", "_from": "v/b", "_to": "v/c" }),
+        ];
+        let new_vertices = vec![
+            json!({ "_id": "v/a", "age": 30, "pagerank": 0.3 }),
+            json!({ "_id": "v/b", "age": 41 }),
+            json!({ "_id": "v/d" }),
+        ];
+        let new_edges = vec![
+            json!({ "_id": "e/1", "_from": "v/a", "_to": "v/b" }),
+            json!({ "_id": "e/3", "_from": "v/b", "_to": "v/d" }),
+        ];
+        let (vertices, edges, summary) = diff(&old_vertices, &old_edges, &new_vertices, &new_edges);
+        let status = |documents: &[Value]| -> Vec<String> {
+            documents
+                .iter()
+                .map(|d| {
+                    format!(
+                        "{} {}",
+                        d["_id"].as_str().unwrap(),
+                        d["diff"].as_str().unwrap()
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(
+            status(&vertices),
+            vec!["v/a unchanged", "v/b changed", "v/d added", "v/c removed"]
+        );
+        assert_eq!(
+            status(&edges),
+            vec!["e/1 unchanged", "e/3 added", "e/2 removed"]
+        );
+        assert_eq!(
+            summary,
+            DiffSummary {
+                added_vertices: 1,
+                removed_vertices: 1,
+                changed_vertices: 1,
+                added_edges: 1,
+                removed_edges: 1,
+                changed_edges: 0,
+            }
+        );
+    }
+}
@@ -0,0 +1,1187 @@
+//! A small ArangoDB HTTP client covering what the runner needs: running AQL
+//! through the cursor API and releasing cursors again.
+
+use base64::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, instrument, warn};
+
+use crate::config::{Configuration, QueryDefinition};
+use crate::error::{Error, Result};
+use crate::proxy;
+
+/// The complete result of an AQL query.
+#[derive(Debug)]
+pub struct QueryResult {
+    pub results: Vec<Value>,
+    pub stats: Value, // Execution statistics reported by ArangoDB
+}
+
+/// Cursor options that need the complete result and are thus not supported
+/// by streaming cursors.
+const NON_STREAMING_OPTIONS: &[&str] = &["fullCount", "cache"];
+
+/// Batches of a cursor fetched ahead of the one being handled. One more is
+/// on its way from the server meanwhile.
+const PREFETCH_BATCHES: usize = 1;
+
+/// Takes the results of a query one batch at a time, see
+/// [`Client::execute_batches`].
+pub trait BatchHandler: Send {
+    /// Handles a batch; the query goes on once the returned future is done.
+    fn handle(&mut self, batch: Vec<Value>) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl<F: FnMut(Vec<Value>) -> Result<()> + Send> BatchHandler for F {
+    fn handle(&mut self, batch: Vec<Value>) -> impl Future<Output = Result<()>> + Send {
+        std::future::ready(self(batch))
+    }
+}
+
+/// The `options` of a cursor request.
+#[derive(Debug, Clone, Default)]
+pub struct CursorOptions {
+    pub stream: Option<bool>, // Unset streams unless another option rules it out
+    pub options: serde_json::Map<String, Value>,
+}
+
+impl CursorOptions {
+    /// The options declared by a query.
+    pub fn of(query: &QueryDefinition) -> Self {
+        CursorOptions {
+            stream: query.stream,
+            options: query.aql_options.clone(),
+        }
+    }
+
+    /// Whether the server streams the result instead of computing it all at
+    /// once before the first batch.
+    pub fn streams(&self) -> bool {
+        self.stream.unwrap_or_else(|| {
+            !NON_STREAMING_OPTIONS
+                .iter()
+                .any(|name| is_set(self.options.get(*name)))
+        })
+    }
+
+    /// The options that are set but not supported by a streaming cursor.
+    pub fn conflicts(&self) -> Vec<&'static str> {
+        if !self.streams() {
+            return Vec::new();
+        }
+        NON_STREAMING_OPTIONS
+            .iter()
+            .copied()
+            .filter(|name| is_set(self.options.get(*name)))
+            .collect()
+    }
+
+    fn to_json(&self) -> Value {
+        let mut options = self.options.clone();
+        options.insert("stream".to_string(), Value::Bool(self.streams()));
+        Value::Object(options)
+    }
+}
+
+fn is_set(option: Option<&Value>) -> bool {
+    match option {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+        // e.g. `"cache": "demand"`
+        Some(_) => true,
+    }
+}
+
+/// One batch of a cursor fetched with [`Client::open_cursor`].
+#[derive(Debug)]
+pub struct Batch {
+    pub results: Vec<Value>,
+    pub has_more: bool,
+    pub stats: Value, // Of the query so far; final with the last batch
+}
+
+/// An AQL query running on the server, as listed by `/_api/query/current`,
+/// or one that was slow, as listed by `/_api/query/slow`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningQuery {
+    pub id: String,
+    pub query: String,
+    #[serde(default)]
+    pub database: String,
+    #[serde(default)]
+    pub user: String,
+    #[serde(default)]
+    pub bind_vars: Value,
+    #[serde(default)]
+    pub started: String,
+    #[serde(default)]
+    pub run_time: f64, // Seconds
+    #[serde(default)]
+    pub state: String, // E.g. "executing", or "finished" once slow
+    #[serde(default)]
+    pub stream: bool,
+}
+
+/// What the server is and how it is deployed, from `/_api/version` with
+/// details and `/_admin/status`. Queries behave differently between
+/// versions, editions and single servers and clusters.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ServerInfo {
+    pub version: String,
+    pub license: String,    // "community" or "enterprise"
+    pub deployment: String, // "single", "cluster" or "agency"
+    pub role: String,       // As reported, e.g. "SINGLE" or "COORDINATOR"
+    pub engine: Option<String>,
+    pub host: Option<String>,
+    pub read_only: bool,
+}
+
+impl ServerInfo {
+    /// Combines the answers of both endpoints. Without the status, which
+    /// needs more privileges, the details of the version still tell the role.
+    pub fn from_responses(version: &Value, status: Option<&Value>) -> Self {
+        let details = &version["details"];
+        let text = |value: &Value| value.as_str().map(str::to_string);
+        let role = status
+            .and_then(|s| text(&s["serverInfo"]["role"]))
+            .or_else(|| text(&details["role"]))
+            .unwrap_or_else(|| "SINGLE".to_string());
+        let deployment = match role.as_str() {
+            "COORDINATOR" | "PRIMARY" | "DBSERVER" => "cluster",
+            "AGENT" => "agency",
+            _ => "single",
+        };
+        ServerInfo {
+            version: text(&version["version"]).unwrap_or_else(|| "unknown".to_string()),
+            license: text(&version["license"])
+                .or_else(|| status.and_then(|s| text(&s["license"])))
+                .unwrap_or_else(|| "unknown".to_string()),
+            deployment: deployment.to_string(),
+            role,
+            engine: text(&details["engine"]),
+            host: status
+                .and_then(|s| text(&s["host"]))
+                .or_else(|| text(&details["host"])),
+            read_only: status
+                .and_then(|s| s["serverInfo"]["readOnly"].as_bool())
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// A cursor kept open on the server to fetch its batches one at a time,
+/// e.g. while a user scrolls through the results. It counts as active, and
+/// is thus deleted on shutdown, until it is dropped. Dropped before its last
+/// batch, it is deleted on the server right away.
+pub struct Cursor {
+    id: String,
+    guard: CursorGuard,
+}
+
+/// Keeps track of cursors that are currently being iterated so that they can
+/// be released on the server when the process shuts down.
+#[derive(Default)]
+struct ActiveCursors {
+    ids: Mutex<HashSet<String>>,
+}
+
+/// Removes a cursor from the registry when dropped, regardless of whether the
+/// iteration finished or was aborted by an error. A cursor abandoned before
+/// its last batch, e.g. because the download was cancelled, is deleted on the
+/// server instead of being left to its TTL.
+struct CursorGuard {
+    cursors: Arc<ActiveCursors>,
+    id: String,
+    release: Option<Client>, // Deletes the cursor on drop; `None` once released
+}
+
+impl CursorGuard {
+    /// The server has released the cursor, after its last batch or because
+    /// it was deleted.
+    fn released(&mut self) {
+        self.release = None;
+    }
+}
+
+impl Drop for CursorGuard {
+    fn drop(&mut self) {
+        self.cursors.ids.lock().unwrap().remove(&self.id);
+        let Some(client) = self.release.take() else {
+            return;
+        };
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let id = std::mem::take(&mut self.id);
+            runtime.spawn(async move { client.delete_cursor(&id).await });
+        }
+    }
+}
+
+/// Aborts a spawned task once dropped, e.g. with the future that awaits it.
+struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+impl ActiveCursors {
+    fn register(self: &Arc<Self>, id: &str) -> CursorGuard {
+        self.ids.lock().unwrap().insert(id.to_string());
+        CursorGuard {
+            cursors: Arc::clone(self),
+            id: id.to_string(),
+            release: None,
+        }
+    }
+
+    fn ids(&self) -> Vec<String> {
+        self.ids.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimeoutConfig {
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    #[serde(default)]
+    pub request_timeout_secs: Option<u64>, // Per request incl. the query's first batch; none by default
+    #[serde(default = "default_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64, // Unused connections are closed after this
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        TimeoutConfig {
+            connect_timeout_secs: default_connect_timeout_secs(),
+            request_timeout_secs: None,
+            pool_idle_timeout_secs: default_pool_idle_timeout_secs(),
+        }
+    }
+}
+
+/// Connection to a single ArangoDB endpoint. Cloning is cheap and clones
+/// share the connection pool and the registry of open cursors.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    endpoint: String,
+    base_url: String, // Where requests go, differs from `endpoint` for Unix sockets
+    auth: String,
+    cursors: Arc<ActiveCursors>,
+}
+
+impl Client {
+    /// Creates a client for an endpoint URL ending in `/`, using basic
+    /// authentication and the default timeouts. `unix://` endpoints are
+    /// reached through a bridge, see [`crate::unix_socket`].
+    pub fn new(endpoint: &str, username: &str, password: &str) -> Self {
+        Client::with_timeouts(endpoint, username, password, &TimeoutConfig::default())
+    }
+
+    pub fn with_timeouts(
+        endpoint: &str,
+        username: &str,
+        password: &str,
+        timeouts: &TimeoutConfig,
+    ) -> Self {
+        let base_url = base_url(endpoint);
+        let builder = if base_url == endpoint {
+            proxy::client_builder()
+        } else {
+            // The bridge to a Unix socket is local, never behind a proxy
+            reqwest::Client::builder().no_proxy()
+        };
+        let mut builder = builder
+            .connect_timeout(Duration::from_secs(timeouts.connect_timeout_secs))
+            .pool_idle_timeout(Duration::from_secs(timeouts.pool_idle_timeout_secs));
+        if let Some(secs) = timeouts.request_timeout_secs {
+            builder = builder.timeout(Duration::from_secs(secs));
+        }
+        let http = builder.build().unwrap_or_else(|e| {
+            warn!("Invalid HTTP client settings, using the defaults: {}", e);
+            reqwest::Client::new()
+        });
+        Client {
+            http,
+            endpoint: endpoint.to_string(),
+            base_url,
+            auth: BASE64_STANDARD.encode(format!("{}:{}", username, password)),
+            cursors: Arc::new(ActiveCursors::default()),
+        }
+    }
+
+    pub fn from_config(config: &Configuration) -> Self {
+        Client::with_timeouts(
+            &config.arangodb_endpoint,
+            &config.username,
+            &config.password,
+            &config.timeouts,
+        )
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Starts an authenticated request to a path relative to the endpoint,
+    /// e.g. `_api/version`.
+    pub fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .request(method, format!("{}{}", self.base_url, path))
+            .header("Authorization", format!("Basic {}", self.auth))
+    }
+
+    /// Runs an AQL query with a streaming cursor and drains it.
+    pub async fn execute(
+        &self,
+        query: &str,
+        bind_vars: HashMap<String, Value>,
+    ) -> Result<QueryResult> {
+        self.execute_with(query, bind_vars, &CursorOptions::default())
+            .await
+    }
+
+    /// Runs an AQL query with the given cursor options and drains its cursor.
+    pub async fn execute_with(
+        &self,
+        query: &str,
+        bind_vars: HashMap<String, Value>,
+        options: &CursorOptions,
+    ) -> Result<QueryResult> {
+        let mut results = Vec::new();
+        let stats = self
+            .execute_batches(query, bind_vars, options, |batch: Vec<Value>| {
+                results.extend(batch);
+                Ok(())
+            })
+            .await?;
+        Ok(QueryResult { results, stats })
+    }
+
+    /// Runs an AQL query and hands its results to `on_batch` one batch at a
+    /// time, as they arrive, instead of collecting them. While `on_batch` waits,
+    /// e.g. for the disk, no more than [`PREFETCH_BATCHES`] are fetched
+    /// ahead. Returns the execution statistics.
+    #[instrument(skip(self, bind_vars, options, on_batch))]
+    pub async fn execute_batches(
+        &self,
+        query: &str,
+        bind_vars: HashMap<String, Value>,
+        options: &CursorOptions,
+        mut on_batch: impl BatchHandler,
+    ) -> Result<Value> {
+        let query_request = serde_json::json!({
+            "query": query,
+            "bindVars": bind_vars,
+            "options": options.to_json()
+        });
+
+        let response = self
+            .request(reqwest::Method::POST, "_api/cursor")
+            .json(&query_request)
+            .send()
+            .await?;
+
+        let mut initial_response: Value = response.json().await?;
+        if initial_response["error"].as_bool().unwrap_or(false) {
+            return Err(Error::from_response(&initial_response));
+        }
+        // Handle cursor if more results exist
+        let cursor_id = match initial_response["hasMore"].as_bool() {
+            Some(true) => Some(
+                initial_response["id"]
+                    .as_str()
+                    .ok_or_else(|| Error::Arango {
+                        code: 500,
+                        error_num: 0,
+                        message: "Cursor response without id".to_string(),
+                    })?
+                    .to_string(),
+            ),
+            _ => None,
+        };
+        // From here on the cursor is deleted if the query is abandoned
+        let mut cursor = cursor_id.map(|id| {
+            let guard = self.register_cursor(&id);
+            (id, guard)
+        });
+        let mut rows = 0;
+        if let Value::Array(result) = initial_response["result"].take() {
+            rows += result.len();
+            on_batch.handle(result).await?;
+        }
+        // With streaming cursors the final statistics arrive with the last batch
+        let mut stats = initial_response["extra"]["stats"].clone();
+        debug!(rows, "Received initial batch");
+
+        if let Some((cursor_id, guard)) = cursor.as_mut() {
+            let cursor_id = cursor_id.as_str();
+            // The next batch is fetched while `on_batch` handles this one
+            let (sender, mut batches) = mpsc::channel(PREFETCH_BATCHES);
+            // Dropped before `guard` if the query is abandoned meanwhile
+            let mut prefetch = AbortOnDrop(tokio::spawn(
+                self.clone().prefetch(cursor_id.to_string(), sender),
+            ));
+            let handled = async {
+                while let Some(cursor_response) = batches.recv().await {
+                    let mut cursor_response = cursor_response?;
+                    if let Value::Array(result) = cursor_response["result"].take() {
+                        rows += result.len();
+                        on_batch.handle(result).await?;
+                    }
+                    if let Some(batch_stats) = cursor_response["extra"].get("stats") {
+                        stats = batch_stats.clone();
+                    }
+                    debug!(cursor = cursor_id, rows, "Received cursor batch");
+                }
+                Ok::<_, Error>(())
+            }
+            .await;
+            // Fetching ahead has stopped before the cursor counts as closed
+            prefetch.0.abort();
+            let _ = (&mut prefetch.0).await;
+            guard.released();
+            if handled.is_err() {
+                // Deleted before returning, not in the background
+                self.delete_cursor(cursor_id).await;
+            }
+            handled?;
+        }
+
+        info!(rows, "Query finished");
+        Ok(stats)
+    }
+
+    /// Fetches the batches of a cursor until the last one or an error,
+    /// sending them on. Stops early once nobody takes them any more.
+    async fn prefetch(self, cursor_id: String, batches: mpsc::Sender<Result<Value>>) {
+        loop {
+            let batch = self.read_batch(&cursor_id).await;
+            let more = matches!(&batch, Ok(b) if b["hasMore"].as_bool().unwrap_or(false));
+            if batches.send(batch).await.is_err() || !more {
+                break;
+            }
+        }
+    }
+
+    /// Fetches the next batch of a cursor.
+    async fn read_batch(&self, cursor_id: &str) -> Result<Value> {
+        let response = self
+            .request(reqwest::Method::PUT, &format!("_api/cursor/{}", cursor_id))
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+        if response["error"].as_bool().unwrap_or(false) {
+            return Err(Error::from_response(&response));
+        }
+        Ok(response)
+    }
+
+    /// Runs an AQL query and returns its first batch, with the cursor to
+    /// fetch the others from if there are more. The server keeps the cursor
+    /// for `ttl_secs` after its last use.
+    #[instrument(skip(self, bind_vars, options))]
+    pub async fn open_cursor(
+        &self,
+        query: &str,
+        bind_vars: HashMap<String, Value>,
+        options: &CursorOptions,
+        batch_size: usize,
+        ttl_secs: u64,
+    ) -> Result<(Batch, Option<Cursor>)> {
+        let response: Value = self
+            .request(reqwest::Method::POST, "_api/cursor")
+            .json(&serde_json::json!({
+                "query": query,
+                "bindVars": bind_vars,
+                "batchSize": batch_size,
+                "ttl": ttl_secs,
+                "options": options.to_json()
+            }))
+            .send()
+            .await?
+            .json()
+            .await?;
+        if response["error"].as_bool().unwrap_or(false) {
+            return Err(Error::from_response(&response));
+        }
+        let batch = batch_of(&response);
+        let cursor = match response["id"].as_str() {
+            Some(id) if batch.has_more => Some(Cursor {
+                id: id.to_string(),
+                guard: self.register_cursor(id),
+            }),
+            _ => None,
+        };
+        debug!(rows = batch.results.len(), "Received first batch");
+        Ok((batch, cursor))
+    }
+
+    /// Fetches the next batch of a cursor opened by [`Client::open_cursor`].
+    pub async fn next_batch(&self, cursor: &mut Cursor) -> Result<Batch> {
+        let batch = batch_of(&self.read_batch(&cursor.id).await?);
+        if !batch.has_more {
+            cursor.guard.released();
+        }
+        debug!(
+            cursor = cursor.id.as_str(),
+            rows = batch.results.len(),
+            "Received cursor batch"
+        );
+        Ok(batch)
+    }
+
+    /// The version of the server, e.g. `3.12.4`.
+    pub async fn version(&self) -> Result<String> {
+        let response: Value = self
+            .request(reqwest::Method::GET, "_api/version")
+            .send()
+            .await?
+            .json()
+            .await?;
+        if response["error"].as_bool().unwrap_or(false) {
+            return Err(Error::from_response(&response));
+        }
+        Ok(response["version"]
+            .as_str()
+            .unwrap_or("unknown")
+            .to_string())
+    }
+
+    /// The version, edition and deployment of the server.
+    pub async fn server_info(&self) -> Result<ServerInfo> {
+        let version: Value = self
+            .request(reqwest::Method::GET, "_api/version?details=true")
+            .send()
+            .await?
+            .json()
+            .await?;
+        if version["error"].as_bool().unwrap_or(false) {
+            return Err(Error::from_response(&version));
+        }
+        let status = match self.admin_status().await {
+            Ok(status) => Some(status),
+            Err(e) => {
+                debug!("Server status not available: {}", e);
+                None
+            }
+        };
+        Ok(ServerInfo::from_responses(&version, status.as_ref()))
+    }
+
+    async fn admin_status(&self) -> Result<Value> {
+        let response: Value = self
+            .request(reqwest::Method::GET, "_admin/status")
+            .send()
+            .await?
+            .json()
+            .await?;
+        if response["error"].as_bool().unwrap_or(false) {
+            return Err(Error::from_response(&response));
+        }
+        Ok(response)
+    }
+
+    /// Lets the server parse a query without executing it. Returns the error
+    /// message if the query is invalid.
+    pub async fn parse_query(&self, query: &str) -> Result<Option<String>> {
+        let response: Value = self
+            .request(reqwest::Method::POST, "_api/query")
+            .json(&serde_json::json!({ "query": query }))
+            .send()
+            .await?
+            .json()
+            .await?;
+        if response["error"].as_bool().unwrap_or(false) {
+            return Ok(Some(
+                response["errorMessage"]
+                    .as_str()
+                    .unwrap_or("unknown error")
+                    .to_string(),
+            ));
+        }
+        Ok(None)
+    }
+
+    /// The AQL queries currently running in the database, longest running
+    /// first.
+    pub async fn running_queries(&self) -> Result<Vec<RunningQuery>> {
+        self.list_queries("_api/query/current").await
+    }
+
+    /// The slow query history the database keeps itself, slowest first.
+    /// Queries of all clients show up, not just those of the runner.
+    pub async fn slow_queries(&self) -> Result<Vec<RunningQuery>> {
+        self.list_queries("_api/query/slow").await
+    }
+
+    async fn list_queries(&self, path: &str) -> Result<Vec<RunningQuery>> {
+        let response: Value = self
+            .request(reqwest::Method::GET, path)
+            .send()
+            .await?
+            .json()
+            .await?;
+        if response["error"].as_bool().unwrap_or(false) {
+            return Err(Error::from_response(&response));
+        }
+        let mut queries: Vec<RunningQuery> =
+            serde_json::from_value(response).map_err(|e| Error::Arango {
+                code: 500,
+                error_num: 0,
+                message: format!("Unexpected list of queries: {}", e),
+            })?;
+        queries.sort_by(|a, b| b.run_time.total_cmp(&a.run_time));
+        Ok(queries)
+    }
+
+    /// Kills a running AQL query by its id.
+    pub async fn kill_query(&self, id: &str) -> Result<()> {
+        let response: Value = self
+            .request(reqwest::Method::DELETE, &format!("_api/query/{}", id))
+            .send()
+            .await?
+            .json()
+            .await?;
+        if response["error"].as_bool().unwrap_or(false) {
+            // 1591: no such query, e.g. because it has finished meanwhile
+            if response["errorNum"].as_i64() == Some(1591) {
+                return Err(Error::NotFound(format!("Running query {}", id)));
+            }
+            return Err(Error::from_response(&response));
+        }
+        info!(query = id, "Killed query");
+        Ok(())
+    }
+
+    /// Creates a document or edge collection. Returns `false` if a collection
+    /// of that name exists already.
+    pub async fn create_collection(&self, name: &str, edge: bool) -> Result<bool> {
+        let response: Value = self
+            .request(reqwest::Method::POST, "_api/collection")
+            .json(&serde_json::json!({ "name": name, "type": if edge { 3 } else { 2 } }))
+            .send()
+            .await?
+            .json()
+            .await?;
+        if response["error"].as_bool().unwrap_or(false) {
+            // 1207: duplicate name
+            if response["errorNum"].as_i64() == Some(1207) {
+                return Ok(false);
+            }
+            return Err(Error::from_response(&response));
+        }
+        Ok(true)
+    }
+
+    /// Removes all documents from a collection.
+    pub async fn truncate_collection(&self, name: &str) -> Result<()> {
+        let response: Value = self
+            .request(
+                reqwest::Method::PUT,
+                &format!("_api/collection/{}/truncate", name),
+            )
+            .send()
+            .await?
+            .json()
+            .await?;
+        if response["error"].as_bool().unwrap_or(false) {
+            return Err(Error::from_response(&response));
+        }
+        Ok(())
+    }
+
+    /// Bulk imports documents into a collection through `/_api/import`,
+    /// replacing documents with the same key. Returns the import statistics
+    /// (`created`, `errors`, `updated`, ...).
+    pub async fn import(&self, collection: &str, documents: &[Value]) -> Result<Value> {
+        let body: String = documents.iter().map(|d| format!("{}\n", d)).collect();
+        let response: Value = self
+            .request(
+                reqwest::Method::POST,
+                &format!(
+                    "_api/import?collection={}&type=documents&onDuplicate=replace",
+                    collection
+                ),
+            )
+            .body(body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        if response["error"].as_bool().unwrap_or(false) {
+            return Err(Error::from_response(&response));
+        }
+        Ok(response)
+    }
+
+    /// Counts a cursor as active until the guard is dropped, deleting it on
+    /// the server then unless it was released.
+    fn register_cursor(&self, id: &str) -> CursorGuard {
+        let mut guard = self.cursors.register(id);
+        guard.release = Some(self.clone());
+        guard
+    }
+
+    async fn delete_cursor(&self, id: &str) {
+        let result = self
+            .request(reqwest::Method::DELETE, &format!("_api/cursor/{}", id))
+            .send()
+            .await;
+        match result {
+            Ok(_) => info!(cursor = id, "Deleted cursor"),
+            Err(e) => warn!(cursor = id, "Failed to delete cursor: {}", e),
+        }
+    }
+
+    /// Deletes all cursors that are still being iterated by this client (or
+    /// its clones), e.g. on shutdown.
+    pub async fn delete_active_cursors(&self) {
+        for id in self.cursors.ids() {
+            self.delete_cursor(&id).await;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn base_url(endpoint: &str) -> String {
+    let Some((socket, http_path)) = crate::unix_socket::split_endpoint(endpoint) else {
+        return endpoint.to_string();
+    };
+    match crate::unix_socket::bridge(&socket) {
+        Ok(url) => format!("{}{}", url, http_path),
+        Err(e) => {
+            warn!(socket = %socket.display(), "Failed to bridge to Unix socket: {}", e);
+            endpoint.to_string()
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn base_url(endpoint: &str) -> String {
+    endpoint.to_string()
+}
+
+fn batch_of(response: &Value) -> Batch {
+    Batch {
+        results: response["result"].as_array().cloned().unwrap_or_default(),
+        has_more: response["hasMore"].as_bool().unwrap_or(false),
+        stats: response["extra"]["stats"].clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockArango;
+    use serde_json::json;
+
+    #[test]
+    fn test_guard_unregisters() {
+        let cursors = Arc::new(ActiveCursors::default());
+        let guard = cursors.register("123");
+        let _other = cursors.register("456");
+        assert_eq!(cursors.ids().len(), 2);
+
+        drop(guard);
+        assert_eq!(cursors.ids(), vec!["456".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_pages_through_cursor() {
+        let rows: Vec<Value> = (0..5).map(|i| json!({ "i": i })).collect();
+        let mock = MockArango::new()
+            .batch_size(2)
+            .rows("FOR d IN docs", rows.clone())
+            .start();
+        let client = mock.client();
+
+        let result = client
+            .execute("FOR d IN docs RETURN d", HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(result.results, rows);
+        assert_eq!(result.stats["executionTime"], json!(0.001));
+        assert_eq!(mock.requests().len(), 3);
+        assert_eq!(mock.open_cursors(), 0);
+        assert!(client.cursors.ids().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_execute_batches_prefetches_in_order() {
+        let rows: Vec<Value> = (0..6).map(|i| json!(i)).collect();
+        let mock = MockArango::new()
+            .batch_size(1)
+            .rows("FOR d IN docs", rows.clone())
+            .start();
+        let client = mock.client();
+
+        let mut seen = Vec::new();
+        client
+            .execute_batches(
+                "FOR d IN docs RETURN d",
+                HashMap::new(),
+                &Default::default(),
+                |batch: Vec<Value>| {
+                    seen.extend(batch);
+                    Ok(())
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(seen, rows);
+
+        // Failing to handle a batch stops fetching ahead
+        let mut handled = 0;
+        let err = client
+            .execute_batches(
+                "FOR d IN docs RETURN d",
+                HashMap::new(),
+                &Default::default(),
+                |_: Vec<Value>| {
+                    handled += 1;
+                    match handled {
+                        3 => Err(Error::InvalidParameter("enough".to_string())),
+                        _ => Ok(()),
+                    }
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidParameter(_)));
+        assert_eq!(handled, 3);
+        assert!(client.cursors.ids().is_empty());
+        // The server does not keep the rest of the result
+        assert_eq!(mock.open_cursors(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_cursor_is_deleted() {
+        let mock = MockArango::new()
+            .batch_size(1)
+            .rows("FOR d IN docs", (0..3).map(|i| json!(i)).collect())
+            .start();
+        let client = mock.client();
+
+        // As if the download was cancelled after the first batch
+        let (_, cursor) = client
+            .open_cursor(
+                "FOR d IN docs RETURN d",
+                HashMap::new(),
+                &Default::default(),
+                1,
+                60,
+            )
+            .await
+            .unwrap();
+        assert_eq!(mock.open_cursors(), 1);
+        drop(cursor);
+        for _ in 0..100 {
+            if mock.open_cursors() == 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(mock.open_cursors(), 0);
+        assert!(mock.requests().iter().any(|r| r.starts_with("DELETE")));
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_execution_stops_fetching() {
+        let mock = MockArango::new()
+            .batch_size(1)
+            .rows("FOR d IN docs", (0..10).map(|i| json!(i)).collect())
+            .start();
+        let client = mock.client();
+
+        // The first batch of the cursor is never done with, as if the client
+        // of a download went away
+        struct Stuck(usize);
+        impl BatchHandler for Stuck {
+            async fn handle(&mut self, _: Vec<Value>) -> Result<()> {
+                self.0 += 1;
+                if self.0 == 2 {
+                    std::future::pending::<()>().await;
+                }
+                Ok(())
+            }
+        }
+        let abandoned = tokio::time::timeout(
+            std::time::Duration::from_millis(200),
+            client.execute_batches(
+                "FOR d IN docs RETURN d",
+                HashMap::new(),
+                &Default::default(),
+                Stuck(0),
+            ),
+        )
+        .await;
+        assert!(abandoned.is_err());
+        for _ in 0..100 {
+            if mock.open_cursors() == 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(mock.open_cursors(), 0);
+        // Nothing is fetched once the cursor is deleted
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let requests = mock.requests();
+        assert!(requests.last().unwrap().starts_with("DELETE"));
+    }
+
+    // The mock server needs a thread of its own while a batch is handled
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_execute_batches_fetches_while_handling() {
+        let mock = MockArango::new()
+            .batch_size(1)
+            .rows("FOR d IN docs", (0..4).map(|i| json!(i)).collect())
+            .start();
+        let client = mock.client();
+
+        // While the second batch, the first of the cursor, is handled, the
+        // third one is requested already
+        let mut handled = 0;
+        let mut requested_meanwhile = false;
+        client
+            .execute_batches(
+                "FOR d IN docs RETURN d",
+                HashMap::new(),
+                &Default::default(),
+                |_: Vec<Value>| {
+                    handled += 1;
+                    if handled == 2 {
+                        let started = std::time::Instant::now();
+                        while started.elapsed() < std::time::Duration::from_secs(5) {
+                            if mock.requests().len() >= 3 {
+                                requested_meanwhile = true;
+                                break;
+                            }
+                            std::thread::sleep(std::time::Duration::from_millis(10));
+                        }
+                    }
+                    Ok(())
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(handled, 4);
+        assert!(requested_meanwhile);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_batches() {
+        let rows: Vec<Value> = (0..5).map(|i| json!({ "i": i })).collect();
+        let mock = MockArango::new()
+            .batch_size(2)
+            .rows("FOR d IN docs", rows.clone())
+            .rows("FOR d IN few", vec![json!(1)])
+            .start();
+        let client = mock.client();
+
+        let (first, cursor) = client
+            .open_cursor(
+                "FOR d IN docs RETURN d",
+                HashMap::new(),
+                &Default::default(),
+                2,
+                60,
+            )
+            .await
+            .unwrap();
+        assert_eq!(first.results, rows[..2]);
+        assert!(first.has_more);
+        let mut cursor = cursor.unwrap();
+        assert_eq!(client.cursors.ids().len(), 1);
+
+        let second = client.next_batch(&mut cursor).await.unwrap();
+        assert_eq!(second.results, rows[2..4]);
+        let last = client.next_batch(&mut cursor).await.unwrap();
+        assert_eq!(last.results, rows[4..]);
+        assert!(!last.has_more);
+        drop(cursor);
+        assert!(client.cursors.ids().is_empty());
+        // The server released it already
+        tokio::task::yield_now().await;
+        assert!(!mock.requests().iter().any(|r| r.starts_with("DELETE")));
+
+        // A result fitting into one batch has no cursor to keep
+        let (batch, cursor) = client
+            .open_cursor(
+                "FOR d IN few RETURN d",
+                HashMap::new(),
+                &Default::default(),
+                2,
+                60,
+            )
+            .await
+            .unwrap();
+        assert_eq!(batch.results.len(), 1);
+        assert!(!batch.has_more && cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cursor_options() {
+        let mock = MockArango::new()
+            .rows("FOR d IN docs", vec![json!(1)])
+            .start();
+        let client = mock.client();
+        let full_count = CursorOptions {
+            stream: None,
+            options: json!({ "fullCount": true }).as_object().unwrap().clone(),
+        };
+        assert!(CursorOptions::default().streams());
+        assert!(!full_count.streams() && full_count.conflicts().is_empty());
+
+        client
+            .execute("FOR d IN docs RETURN d", HashMap::new())
+            .await
+            .unwrap();
+        client
+            .execute_with("FOR d IN docs RETURN d", HashMap::new(), &full_count)
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.cursor_options(),
+            [
+                json!({ "stream": true }),
+                json!({ "fullCount": true, "stream": false })
+            ]
+        );
+
+        // Streaming when asked to, even though the server then ignores the option
+        let forced = CursorOptions {
+            stream: Some(true),
+            ..full_count
+        };
+        assert!(forced.streams());
+        assert_eq!(forced.conflicts(), ["fullCount"]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_reports_errors() {
+        let mock = MockArango::new()
+            .error("BROKEN", 400, 1501, "syntax error, unexpected identifier")
+            .rows("FOR d IN docs", (0..3).map(|i| json!(i)).collect())
+            .batch_size(1)
+            .lose_cursors()
+            .start();
+        let client = mock.client();
+
+        let err = client.execute("BROKEN", HashMap::new()).await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Arango {
+                code: 400,
+                error_num: 1501,
+                ..
+            }
+        ));
+
+        // A cursor vanishing mid-iteration fails the query and is unregistered
+        let err = client
+            .execute("FOR d IN docs RETURN d", HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Arango {
+                error_num: 1600,
+                ..
+            }
+        ));
+        assert!(client.cursors.ids().is_empty());
+
+        let err = client.execute("FOR x IN nowhere", HashMap::new()).await;
+        assert!(matches!(err, Err(Error::Arango { code: 404, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        // Accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let endpoint = format!("http://{}/", listener.local_addr().unwrap());
+        let timeouts = TimeoutConfig {
+            request_timeout_secs: Some(1),
+            ..TimeoutConfig::default()
+        };
+        let client = Client::with_timeouts(&endpoint, "root", "", &timeouts);
+        let err = client.version().await.unwrap_err();
+        assert_eq!(err.status(), warp::http::StatusCode::GATEWAY_TIMEOUT);
+        drop(listener);
+    }
+
+    #[test]
+    fn test_server_info() {
+        let version = json!({
+            "server": "arango", "version": "3.12.4", "license": "enterprise",
+            "details": { "engine": "rocksdb", "role": "COORDINATOR", "host": "abc" }
+        });
+        let status = json!({
+            "license": "enterprise", "host": "crdn-1",
+            "serverInfo": { "role": "COORDINATOR", "readOnly": true }
+        });
+        let info = ServerInfo::from_responses(&version, Some(&status));
+        assert_eq!(
+            (info.deployment.as_str(), info.license.as_str()),
+            ("cluster", "enterprise")
+        );
+        assert_eq!(info.host.as_deref(), Some("crdn-1"));
+        assert!(info.read_only);
+
+        // The status may be forbidden; the details still tell the role
+        let single = ServerInfo::from_responses(
+            &json!({ "version": "3.11.0", "license": "community", "details": { "role": "SINGLE" } }),
+            None,
+        );
+        assert_eq!(single.deployment, "single");
+        assert_eq!(single.engine, None);
+        assert!(!single.read_only);
+    }
+
+    #[tokio::test]
+    async fn test_running_queries() {
+        let mock = MockArango::new()
+            .running_query(json!({
+                "id": "12", "query": "FOR v IN 1..10 OUTBOUND @start knows RETURN v",
+                "bindVars": { "start": "people/alice" }, "runTime": 1.5, "state": "executing"
+            }))
+            .running_query(json!({ "id": "13", "query": "RETURN SLEEP(60)", "runTime": 42.0 }))
+            .slow_query(json!({ "id": "9", "query": "RETURN SLEEP(3)", "runTime": 3.0, "state": "finished" }))
+            .start();
+        let client = mock.client();
+
+        let running = client.running_queries().await.unwrap();
+        assert_eq!(
+            running.iter().map(|q| q.id.as_str()).collect::<Vec<_>>(),
+            ["13", "12"]
+        );
+        assert_eq!(running[1].bind_vars["start"], "people/alice");
+        let slow = client.slow_queries().await.unwrap();
+        assert_eq!((slow[0].id.as_str(), slow[0].run_time), ("9", 3.0));
+
+        client.kill_query("13").await.unwrap();
+        assert_eq!(client.running_queries().await.unwrap().len(), 1);
+        assert!(matches!(
+            client.kill_query("13").await,
+            Err(Error::NotFound(_))
+        ));
+    }
+}
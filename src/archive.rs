@@ -0,0 +1,332 @@
+//! A backup of what the runner keeps, as one tar.gz: the query catalog,
+//! the versions of the queries with who changed them and when, the
+//! history of runs and the saved graphs. Restoring it on another
+//! installation hands that installation over, see [`Archive::restore`].
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+use crate::catalog::{self, CatalogBundle};
+use crate::config::{Configuration, LimitsConfig};
+use crate::error::{Error, Result};
+use crate::history::{QueryHistory, RunRecord};
+use crate::saved_graphs::{SavedGraph, SavedGraphs};
+use crate::versions::{QueryVersion, QueryVersions};
+
+/// Marks a tar.gz as an archive of the runner.
+pub const ARCHIVE_FORMAT: &str = "adb_query_runner/archive";
+
+const MANIFEST: &str = "manifest.json";
+const CATALOG: &str = "catalog.json";
+const VERSIONS: &str = "versions.jsonl";
+const HISTORY: &str = "history.jsonl";
+const SAVED_GRAPHS: &str = "saved_graphs/";
+
+/// What an archive holds, written first so that it can be told apart from
+/// other archives.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Manifest {
+    pub format: String,
+    pub exported_at: DateTime<Utc>,
+    pub queries: usize,
+    pub versions: usize,
+    pub runs: usize,
+    pub saved_graphs: usize,
+}
+
+#[derive(Debug)]
+pub struct Archive {
+    pub manifest: Manifest,
+    pub catalog: CatalogBundle,
+    pub versions: Vec<QueryVersion>,
+    pub runs: Vec<RunRecord>,
+    pub saved_graphs: Vec<SavedGraph>,
+}
+
+/// What restoring an archive added, besides the catalog import.
+#[derive(Debug, Serialize, Default)]
+pub struct RestoreReport {
+    pub versions: usize,      // Of queries without versions here
+    pub runs: usize,          // Not recorded here already
+    pub saved_graphs: usize,  // Under new ids
+    pub skipped_graphs: bool, // Saving graphs is off here
+}
+
+impl Archive {
+    /// Everything the runner keeps right now.
+    pub fn collect(
+        config: &Configuration,
+        versions: &QueryVersions,
+        history: &QueryHistory,
+        saved_graphs: &SavedGraphs,
+    ) -> Result<Archive> {
+        let catalog = catalog::export(config);
+        let versions = versions.all();
+        let runs = history.all();
+        let saved_graphs = if saved_graphs.enabled() {
+            let mut graphs = saved_graphs
+                .list()
+                .iter()
+                .map(|summary| saved_graphs.get(summary.id))
+                .collect::<Result<Vec<_>>>()?;
+            graphs.reverse();
+            graphs
+        } else {
+            Vec::new()
+        };
+        Ok(Archive {
+            manifest: Manifest {
+                format: ARCHIVE_FORMAT.to_string(),
+                exported_at: catalog.exported_at,
+                queries: catalog.queries.len(),
+                versions: versions.len(),
+                runs: runs.len(),
+                saved_graphs: saved_graphs.len(),
+            },
+            catalog,
+            versions,
+            runs,
+            saved_graphs,
+        })
+    }
+
+    pub fn to_tar_gz(&self) -> Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mtime = self.manifest.exported_at.timestamp().max(0) as u64;
+        let mut append = |path: &str, contents: Vec<u8>| -> Result<()> {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            header.set_cksum();
+            builder.append_data(&mut header, path, contents.as_slice())?;
+            Ok(())
+        };
+        append(MANIFEST, to_json(&self.manifest)?)?;
+        append(CATALOG, to_json(&self.catalog)?)?;
+        append(VERSIONS, to_json_lines(&self.versions)?)?;
+        append(HISTORY, to_json_lines(&self.runs)?)?;
+        for graph in &self.saved_graphs {
+            append(
+                &format!("{}{}.json", SAVED_GRAPHS, graph.id),
+                to_json(graph)?,
+            )?;
+        }
+        Ok(builder.into_inner()?.finish()?)
+    }
+
+    /// Reads an archive, refusing other files. A few compressed bytes can
+    /// unpack to gigabytes, so the unpacked files are limited to
+    /// `max_archive_entry_bytes` each and `max_archive_bytes` together.
+    pub fn from_tar_gz(bytes: &[u8], limits: &LimitsConfig) -> Result<Archive> {
+        let invalid = |e: std::io::Error| Error::InvalidParameter(format!("Not an archive: {}", e));
+        let mut unpacked = 0;
+        let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+        let mut manifest = None;
+        let mut catalog = None;
+        let mut versions = Vec::new();
+        let mut runs = Vec::new();
+        let mut saved_graphs = Vec::new();
+        for entry in archive.entries().map_err(invalid)? {
+            let mut entry = entry.map_err(invalid)?;
+            let path = entry
+                .path()
+                .map_err(invalid)?
+                .to_string_lossy()
+                .into_owned();
+            let limit = limits
+                .max_archive_entry_bytes
+                .min(limits.max_archive_bytes - unpacked);
+            let too_large = || {
+                Error::TooLarge(format!(
+                    "{} in the archive unpacks to more than {} bytes, at most {} bytes per file and {} bytes together allowed",
+                    path, limit, limits.max_archive_entry_bytes, limits.max_archive_bytes
+                ))
+            };
+            if entry.header().size().map_err(invalid)? > limit {
+                return Err(too_large());
+            }
+            // The header may lie about the size
+            let mut contents = Vec::new();
+            entry
+                .by_ref()
+                .take(limit + 1)
+                .read_to_end(&mut contents)
+                .map_err(invalid)?;
+            if contents.len() as u64 > limit {
+                return Err(too_large());
+            }
+            unpacked += contents.len() as u64;
+            match path.as_str() {
+                MANIFEST => manifest = Some(from_json::<Manifest>(&path, &contents)?),
+                CATALOG => catalog = Some(catalog::parse_bundle(&contents)?),
+                VERSIONS => versions = from_json_lines(&path, &contents)?,
+                HISTORY => runs = from_json_lines(&path, &contents)?,
+                _ if path.starts_with(SAVED_GRAPHS) => {
+                    saved_graphs.push(from_json(&path, &contents)?)
+                }
+                _ => {}
+            }
+        }
+        let manifest = manifest
+            .filter(|m| m.format == ARCHIVE_FORMAT)
+            .ok_or_else(|| Error::InvalidParameter(format!("Not an archive: no {}", MANIFEST)))?;
+        let catalog = catalog
+            .ok_or_else(|| Error::InvalidParameter(format!("The archive has no {}", CATALOG)))?;
+        Ok(Archive {
+            manifest,
+            catalog,
+            versions,
+            runs,
+            saved_graphs,
+        })
+    }
+
+    /// Restores all but the catalog, which is imported like a bundle with
+    /// the strategy the user chose. Versions are restored first, so that
+    /// imported queries keep their history.
+    pub fn restore(
+        self,
+        versions: &QueryVersions,
+        history: &QueryHistory,
+        saved_graphs: &SavedGraphs,
+    ) -> Result<(CatalogBundle, RestoreReport)> {
+        let mut report = RestoreReport {
+            versions: versions.restore(self.versions),
+            runs: history.restore(self.runs),
+            ..RestoreReport::default()
+        };
+        if saved_graphs.enabled() {
+            for graph in self.saved_graphs {
+                saved_graphs.restore(graph)?;
+                report.saved_graphs += 1;
+            }
+        } else {
+            report.skipped_graphs = !self.saved_graphs.is_empty();
+        }
+        Ok((self.catalog, report))
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    serde_json::to_vec_pretty(value).map_err(|e| Error::Config(e.to_string()))
+}
+
+fn to_json_lines<T: Serialize>(values: &[T]) -> Result<Vec<u8>> {
+    let mut lines = Vec::new();
+    for value in values {
+        serde_json::to_writer(&mut lines, value).map_err(|e| Error::Config(e.to_string()))?;
+        lines.push(b'\n');
+    }
+    Ok(lines)
+}
+
+fn from_json<T: DeserializeOwned>(path: &str, contents: &[u8]) -> Result<T> {
+    serde_json::from_slice(contents)
+        .map_err(|e| Error::InvalidParameter(format!("Invalid {} in the archive: {}", path, e)))
+}
+
+fn from_json_lines<T: DeserializeOwned>(path: &str, contents: &[u8]) -> Result<Vec<T>> {
+    contents
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .map(|line| from_json(path, line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryConfig;
+    use crate::saved_graphs::SavedGraphsConfig;
+    use crate::versions::VersionsConfig;
+    use crate::viewer::{GraphRenderer, StoredGraph};
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn test_export_and_restore() {
+        let config: Configuration = serde_json::from_value(json!({
+            "arangodb_endpoint": "http://localhost:8529/",
+            "username": "root",
+            "password": "",
+            "queries": [{ "name": "Friends", "description": "", "query": "RETURN 1", "parameters": [] }]
+        }))
+        .unwrap();
+        let directory = std::env::temp_dir().join(format!("archive-{}", uuid::Uuid::new_v4()));
+        let saved_config = |name: &str| SavedGraphsConfig {
+            directory: Some(directory.join(name).to_str().unwrap().to_string()),
+            ..SavedGraphsConfig::default()
+        };
+
+        let versions = QueryVersions::new(VersionsConfig::default());
+        versions.record(&config.queries[0], "alice", "Edited");
+        let history = QueryHistory::new(HistoryConfig::default());
+        history.record("Friends", Duration::from_millis(12), 3);
+        let saved_graphs = SavedGraphs::new(&saved_config("from"));
+        let graph = StoredGraph {
+            id: 1,
+            query_name: "Friends".to_string(),
+            renderer: GraphRenderer::Vis,
+            timestamp: Utc::now(),
+            vertices: vec![json!({ "_id": "people/a" })],
+            edges: Vec::new(),
+        };
+        saved_graphs.save(&graph, "Before").unwrap();
+
+        let bytes = Archive::collect(&config, &versions, &history, &saved_graphs)
+            .unwrap()
+            .to_tar_gz()
+            .unwrap();
+        let limits = LimitsConfig::default();
+        let archive = Archive::from_tar_gz(&bytes, &limits).unwrap();
+        assert_eq!((archive.manifest.versions, archive.manifest.runs), (1, 1));
+
+        // Restored on an installation that has a graph of its own already
+        let (versions, history) = (
+            QueryVersions::new(VersionsConfig::default()),
+            QueryHistory::new(HistoryConfig::default()),
+        );
+        let saved_graphs = SavedGraphs::new(&saved_config("to"));
+        saved_graphs.save(&graph, "Mine").unwrap();
+        let (catalog, report) = archive.restore(&versions, &history, &saved_graphs).unwrap();
+        assert_eq!(catalog.queries[0].name, "Friends");
+        assert_eq!(
+            (report.versions, report.runs, report.saved_graphs),
+            (1, 1, 1)
+        );
+        assert_eq!(versions.latest("Friends").unwrap().author, "alice");
+        assert_eq!(history.runs("Friends")[0].rows, 3);
+        assert_eq!(saved_graphs.list()[0].name, "Before");
+
+        // Restoring again adds no runs twice
+        let archive = Archive::from_tar_gz(&bytes, &limits).unwrap();
+        let (_, report) = archive.restore(&versions, &history, &saved_graphs).unwrap();
+        assert_eq!((report.versions, report.runs), (0, 0));
+
+        assert!(matches!(
+            Archive::from_tar_gz(b"no archive", &limits),
+            Err(Error::InvalidParameter(_))
+        ));
+        // Files unpacking to more than allowed, each or together
+        let small = |entry, total| LimitsConfig {
+            max_archive_entry_bytes: entry,
+            max_archive_bytes: total,
+            ..LimitsConfig::default()
+        };
+        assert!(matches!(
+            Archive::from_tar_gz(&bytes, &small(100, 1 << 20)),
+            Err(Error::TooLarge(_))
+        ));
+        assert!(matches!(
+            Archive::from_tar_gz(&bytes, &small(1 << 20, 300)),
+            Err(Error::TooLarge(_))
+        ));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+//! Static files embedded in the binary, e.g. stylesheets, scripts, fonts
+//! and images, served with their content type and caching headers.
+
+use warp::http::{header, Response, StatusCode};
+use warp::hyper::Body;
+
+/// How long browsers may use an asset without asking again. The ETag lets
+/// them revalidate cheaply afterwards.
+pub const MAX_AGE_SECS: u64 = 3600;
+
+/// The MIME type of a file, by its extension.
+pub fn content_type(path: &str) -> &'static str {
+    let extension = path.rsplit_once('.').map(|(_, e)| e).unwrap_or_default();
+    match extension.to_ascii_lowercase().as_str() {
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "html" => "text/html; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A strong ETag derived from the contents (64 bit FNV-1a), stable across
+/// restarts of the same binary.
+pub fn etag(contents: &[u8]) -> String {
+    let hash = contents.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("\"{:016x}\"", hash)
+}
+
+/// Whether an `If-None-Match` header matches the ETag of an asset.
+fn matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|t| t.trim().trim_start_matches("W/"))
+        .any(|t| t == "*" || t == etag)
+}
+
+/// The response for the file at `path` with `contents`: the file, `304 Not
+/// Modified` if the browser has it already, or `404 Not Found`.
+pub fn response(
+    path: &str,
+    contents: Option<&[u8]>,
+    if_none_match: Option<&str>,
+) -> Response<Body> {
+    let Some(contents) = contents else {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(Body::from("File not found"))
+            .unwrap_or_default();
+    };
+    let etag = etag(contents);
+    let builder = Response::builder().header(header::ETAG, &etag).header(
+        header::CACHE_CONTROL,
+        format!("public, max-age={}", MAX_AGE_SECS),
+    );
+    if if_none_match.is_some_and(|v| matches(v, &etag)) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap_or_default();
+    }
+    builder
+        .header(header::CONTENT_TYPE, content_type(path))
+        .body(Body::from(contents.to_vec()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSS: &[u8] = b"body { margin: 0; }";
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type("css/style.css"), "text/css; charset=utf-8");
+        assert_eq!(
+            content_type("js/cytoscape.min.js"),
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(content_type("fonts/Inter.WOFF2"), "font/woff2");
+        assert_eq!(content_type("img/logo.png"), "image/png");
+        assert_eq!(content_type("LICENSE"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_response() {
+        let ok = response("css/style.css", Some(CSS), None);
+        assert_eq!(ok.status(), StatusCode::OK);
+        assert_eq!(
+            ok.headers()[header::CONTENT_TYPE],
+            "text/css; charset=utf-8"
+        );
+        let etag = ok.headers()[header::ETAG].to_str().unwrap().to_string();
+        assert!(ok.headers()[header::CACHE_CONTROL]
+            .to_str()
+            .unwrap()
+            .contains("max-age"));
+
+        let cached = response(
+            "css/style.css",
+            Some(CSS),
+            Some(&format!("W/\"x\", {}", etag)),
+        );
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        let changed = response("css/style.css", Some(CSS), Some("\"other\""));
+        assert_eq!(changed.status(), StatusCode::OK);
+
+        assert_eq!(
+            response("css/missing.css", None, None).status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Runs a query over and over with the same bind variables to measure it,
+//! e.g. while trying out indexes. The results are counted and dropped as
+//! they arrive, so that only the query itself is measured.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Instant;
+use tracing::info;
+
+use crate::arango::CursorOptions;
+use crate::config::QueryDefinition;
+use crate::error::{Error, Result};
+use crate::runner::QueryRunner;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BenchmarkConfig {
+    #[serde(default = "default_runs")]
+    pub default_runs: usize, // Unless the form or the command line asks for another number
+    #[serde(default = "default_max_runs")]
+    pub max_runs: usize, // Benchmarks asking for more are refused
+}
+
+fn default_runs() -> usize {
+    10
+}
+
+fn default_max_runs() -> usize {
+    1000
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        BenchmarkConfig {
+            default_runs: default_runs(),
+            max_runs: default_max_runs(),
+        }
+    }
+}
+
+/// One execution of a benchmark.
+#[derive(Debug, Serialize, Clone)]
+pub struct BenchmarkRun {
+    pub duration_ms: f64,       // As seen by the runner, including the transfer
+    pub server_ms: Option<f64>, // `executionTime` reported by ArangoDB
+    pub rows: usize,
+}
+
+/// Distribution of the durations of the runs, in milliseconds.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Latency {
+    pub min: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl Latency {
+    /// Percentiles by the nearest rank; none without values.
+    pub fn of(mut values: Vec<f64>) -> Option<Latency> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let rank = |p: f64| {
+            let index = (p * values.len() as f64).ceil() as usize;
+            values[index.clamp(1, values.len()) - 1]
+        };
+        Some(Latency {
+            min: values[0],
+            median: rank(0.5),
+            p95: rank(0.95),
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub query_name: String,
+    pub runs: Vec<BenchmarkRun>,
+    pub latency: Latency,
+    pub server_latency: Option<Latency>, // If the server reported execution times
+    pub rows: usize,                     // Returned by the last run
+    pub stats: Value,                    // Execution statistics of the last run
+}
+
+/// Executes `query` `runs` times one after the other, failing on the first
+/// failed run.
+pub async fn run(
+    runner: &QueryRunner,
+    config: &BenchmarkConfig,
+    query: &QueryDefinition,
+    bind_vars: HashMap<String, Value>,
+    runs: usize,
+) -> Result<BenchmarkReport> {
+    run_aql(runner, config, query, &query.query, bind_vars, runs).await
+}
+
+/// Like [`run`], but executes `aql` instead of the AQL of `query`, e.g. its
+/// alternative. The options of `query` apply all the same.
+pub async fn run_aql(
+    runner: &QueryRunner,
+    config: &BenchmarkConfig,
+    query: &QueryDefinition,
+    aql: &str,
+    bind_vars: HashMap<String, Value>,
+    runs: usize,
+) -> Result<BenchmarkReport> {
+    if runs == 0 || runs > config.max_runs {
+        return Err(Error::InvalidParameter(format!(
+            "A benchmark takes 1 to {} runs, not {}",
+            config.max_runs, runs
+        )));
+    }
+    runner.check_endpoint(query)?;
+    let options = CursorOptions::of(query);
+    let mut results = Vec::with_capacity(runs);
+    let mut stats = Value::Null;
+    for _ in 0..runs {
+        let started = Instant::now();
+        let mut rows = 0;
+        stats = runner
+            .client()
+            .execute_batches(aql, bind_vars.clone(), &options, |batch: Vec<Value>| {
+                rows += batch.len();
+                Ok(())
+            })
+            .await?;
+        results.push(BenchmarkRun {
+            duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+            server_ms: stats["executionTime"].as_f64().map(|secs| secs * 1000.0),
+            rows,
+        });
+    }
+    let latency = Latency::of(results.iter().map(|r| r.duration_ms).collect())
+        .expect("a benchmark has at least one run");
+    let server_latency = Latency::of(results.iter().filter_map(|r| r.server_ms).collect());
+    info!(
+        query = query.name.as_str(),
+        runs,
+        median_ms = latency.median,
+        "Benchmark finished"
+    );
+    Ok(BenchmarkReport {
+        query_name: query.name.clone(),
+        latency,
+        server_latency,
+        rows: results.last().map_or(0, |r| r.rows),
+        stats,
+        runs: results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Configuration;
+    use crate::mock::MockArango;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_latency() {
+        let values: Vec<f64> = (1..=20).rev().map(f64::from).collect();
+        assert_eq!(
+            Latency::of(values),
+            Some(Latency {
+                min: 1.0,
+                median: 10.0,
+                p95: 19.0,
+                max: 20.0
+            })
+        );
+        assert_eq!(Latency::of(vec![7.0]).unwrap().p95, 7.0);
+        assert!(Latency::of(Vec::new()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_benchmark() {
+        let mock = MockArango::new()
+            .batch_size(2)
+            .rows("FOR d IN docs", (0..5).map(|i| json!(i)).collect())
+            .start();
+        let config: Configuration = serde_json::from_value(json!({
+            "arangodb_endpoint": mock.endpoint(),
+            "username": "root",
+            "password": "",
+            "queries": [{ "name": "docs", "description": "", "query": "FOR d IN docs RETURN d", "parameters": [] }]
+        }))
+        .unwrap();
+        let runner = QueryRunner::with_client(Arc::new(config), mock.client());
+        let query = runner.find("docs").unwrap();
+        let benchmark = BenchmarkConfig::default();
+
+        let report = run(&runner, &benchmark, query, HashMap::new(), 3)
+            .await
+            .unwrap();
+        assert_eq!(report.runs.len(), 3);
+        assert!(report.runs.iter().all(|r| r.rows == 5));
+        assert_eq!(report.server_latency.unwrap().max, 1.0);
+        assert!(report.latency.min <= report.latency.max);
+        // Three runs of three requests each
+        assert_eq!(mock.requests().len(), 9);
+
+        let err = run(&runner, &benchmark, query, HashMap::new(), 0).await;
+        assert!(matches!(err, Err(Error::InvalidParameter(_))));
+    }
+}
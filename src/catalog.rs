@@ -0,0 +1,214 @@
+//! Export and import of the query catalog as a portable JSON bundle, to
+//! share queries between installations. An imported catalog replaces the
+//! `queries` of the running server and, if the configuration was loaded
+//! from a file, of that file.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+use crate::config::{Configuration, QueryDefinition};
+use crate::error::{Error, Result};
+use crate::files::write_atomically;
+use crate::versions::same_definition;
+
+/// Marks a JSON document as a catalog bundle.
+pub const BUNDLE_FORMAT: &str = "adb_query_runner/catalog";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CatalogBundle {
+    pub format: String,
+    pub exported_at: DateTime<Utc>,
+    pub queries: Vec<QueryDefinition>,
+}
+
+/// How an imported bundle is combined with the current catalog.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStrategy {
+    Merge,   // New queries are added, changed ones are reported and kept as they are
+    Replace, // The bundle becomes the catalog
+}
+
+impl ImportStrategy {
+    pub const ALL: &'static [ImportStrategy] = &[ImportStrategy::Merge, ImportStrategy::Replace];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImportStrategy::Merge => "merge",
+            ImportStrategy::Replace => "replace",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ImportStrategy> {
+        ImportStrategy::ALL
+            .iter()
+            .copied()
+            .find(|s| s.name() == name)
+    }
+}
+
+/// What an import changed, by query name.
+#[derive(Debug, Serialize, Default)]
+pub struct ImportReport {
+    pub added: Vec<String>,
+    pub replaced: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub conflicts: Vec<String>, // Differ from the current definition, which was kept
+    pub saved_to: Option<String>, // The configuration file now holding the catalog
+}
+
+/// The queries of a configuration as a bundle.
+pub fn export(config: &Configuration) -> CatalogBundle {
+    CatalogBundle {
+        format: BUNDLE_FORMAT.to_string(),
+        exported_at: Utc::now(),
+        queries: config.queries.clone(),
+    }
+}
+
+/// Reads a bundle, refusing other documents and bundles that define a
+/// query twice.
+pub fn parse_bundle(json: &[u8]) -> Result<CatalogBundle> {
+    let bundle: CatalogBundle = serde_json::from_slice(json)
+        .map_err(|e| Error::InvalidParameter(format!("Not a catalog bundle: {}", e)))?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(Error::InvalidParameter(format!(
+            "Unknown bundle format '{}'",
+            bundle.format
+        )));
+    }
+    let mut names = HashSet::new();
+    if let Some(query) = bundle.queries.iter().find(|q| !names.insert(&q.name)) {
+        return Err(Error::InvalidParameter(format!(
+            "The bundle defines '{}' twice",
+            query.name
+        )));
+    }
+    Ok(bundle)
+}
+
+/// The catalog after importing `imported` into `current`, with what changed.
+pub fn import(
+    current: &[QueryDefinition],
+    imported: Vec<QueryDefinition>,
+    strategy: ImportStrategy,
+) -> (Vec<QueryDefinition>, ImportReport) {
+    let mut report = ImportReport::default();
+    let mut queries = match strategy {
+        ImportStrategy::Merge => current.to_vec(),
+        ImportStrategy::Replace => Vec::new(),
+    };
+    for query in imported {
+        match current.iter().find(|q| q.name == query.name) {
+            None => report.added.push(query.name.clone()),
+            Some(existing) if same_definition(existing, &query) => {
+                report.unchanged.push(query.name.clone());
+                if strategy == ImportStrategy::Merge {
+                    continue;
+                }
+            }
+            Some(_) if strategy == ImportStrategy::Merge => {
+                report.conflicts.push(query.name.clone());
+                continue;
+            }
+            Some(_) => report.replaced.push(query.name.clone()),
+        }
+        queries.push(query);
+    }
+    if strategy == ImportStrategy::Replace {
+        report.removed = current
+            .iter()
+            .filter(|q| !queries.iter().any(|i| i.name == q.name))
+            .map(|q| q.name.clone())
+            .collect();
+    }
+    (queries, report)
+}
+
+/// Writes the queries into a configuration file, leaving its other
+/// settings alone.
+pub fn save(path: &str, queries: &[QueryDefinition]) -> Result<()> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut config: Value = serde_json::from_str(&contents)
+        .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path, e)))?;
+    config["queries"] = serde_json::to_value(queries).map_err(|e| Error::Config(e.to_string()))?;
+    let json = serde_json::to_string_pretty(&config).map_err(|e| Error::Config(e.to_string()))?;
+    write_atomically(path, (json + "\n").as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_import() {
+        let current = vec![
+            QueryDefinition::for_test("a", "RETURN 1", &[]),
+            QueryDefinition::for_test("b", "RETURN 2", &[]),
+        ];
+        let imported = || {
+            vec![
+                QueryDefinition::for_test("b", "RETURN 20", &[]),
+                QueryDefinition::for_test("c", "RETURN 3", &[]),
+            ]
+        };
+
+        let (queries, report) = import(&current, imported(), ImportStrategy::Merge);
+        let names: Vec<&str> = queries.iter().map(|q| q.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!(queries[1].query, "RETURN 2");
+        assert_eq!(report.added, ["c"]);
+        assert_eq!(report.conflicts, ["b"]);
+
+        let (queries, report) = import(&current, imported(), ImportStrategy::Replace);
+        let names: Vec<&str> = queries.iter().map(|q| q.name.as_str()).collect();
+        assert_eq!(names, ["b", "c"]);
+        assert_eq!(queries[0].query, "RETURN 20");
+        assert_eq!(report.replaced, ["b"]);
+        assert_eq!(report.removed, ["a"]);
+        assert!(report.conflicts.is_empty());
+
+        let (queries, report) = import(&current, current.clone(), ImportStrategy::Merge);
+        assert_eq!(queries.len(), 2);
+        assert_eq!(report.unchanged, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_bundle() {
+        let bundle = CatalogBundle {
+            format: BUNDLE_FORMAT.to_string(),
+            exported_at: Utc::now(),
+            queries: vec![QueryDefinition::for_test("a", "RETURN 1", &[])],
+        };
+        let json = serde_json::to_vec(&bundle).unwrap();
+        assert_eq!(parse_bundle(&json).unwrap().queries[0].name, "a");
+
+        let twice = json!({
+            "format": BUNDLE_FORMAT, "exported_at": Utc::now(),
+            "queries": [
+                QueryDefinition::for_test("a", "RETURN 1", &[]),
+                QueryDefinition::for_test("a", "RETURN 2", &[])
+            ]
+        });
+        assert!(parse_bundle(twice.to_string().as_bytes()).is_err());
+        assert!(parse_bundle(br#"{"queries": []}"#).is_err());
+
+        let path = std::env::temp_dir().join(format!("catalog-{}.json", uuid::Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        std::fs::write(
+            path,
+            r#"{"arangodb_endpoint": "http://db:8529/", "queries": []}"#,
+        )
+        .unwrap();
+        save(path, &bundle.queries).unwrap();
+        let saved: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(saved["arangodb_endpoint"], "http://db:8529/");
+        assert_eq!(saved["queries"][0]["name"], "a");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,563 @@
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use serde_json::json;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use adb_query_runner::arango::Client;
+use adb_query_runner::benchmark::{self, Latency};
+use adb_query_runner::catalog;
+use adb_query_runner::compare;
+use adb_query_runner::config::{Configuration, QueryDefinition};
+use adb_query_runner::git_store::GitStore;
+use adb_query_runner::output::{self, OutputFormat};
+use adb_query_runner::regression;
+use adb_query_runner::runner::QueryRunner;
+use adb_query_runner::seed;
+use adb_query_runner::validate::{self, Severity};
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Run preconfigured queries against an ArangoDB database"
+)]
+pub struct Cli {
+    /// Path to the configuration file
+    #[arg(long, global = true, default_value = "config.json")]
+    pub config: String,
+
+    /// Use bundled sample data instead of a database and configuration file
+    #[arg(long, global = true)]
+    pub demo: bool,
+
+    /// Directory with templates and static files overriding the built-in
+    /// ones of the web server, reloaded when they change
+    #[arg(long, global = true)]
+    pub templates_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the web server (default)
+    Serve,
+    /// Execute a configured query and print its results
+    Run {
+        /// Name of the query as given in the configuration
+        name: String,
+        /// Bind parameter as key=value, may be repeated
+        #[arg(long = "param", value_parser = parse_key_value)]
+        params: Vec<(String, String)>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+        format: OutputFormat,
+    },
+    /// Execute a configured query repeatedly and report its latency
+    Benchmark {
+        /// Name of the query as given in the configuration
+        name: String,
+        /// Bind parameter as key=value, may be repeated
+        #[arg(long = "param", value_parser = parse_key_value)]
+        params: Vec<(String, String)>,
+        /// Number of runs (defaults to `default_runs` of the `benchmark` section)
+        #[arg(long)]
+        runs: Option<usize>,
+    },
+    /// Execute a configured query and its alternative, comparing results and latency
+    Compare {
+        /// Name of the query as given in the configuration
+        name: String,
+        /// Bind parameter as key=value, may be repeated
+        #[arg(long = "param", value_parser = parse_key_value)]
+        params: Vec<(String, String)>,
+        /// Number of runs of each variant (defaults to `default_runs` of the `benchmark` section)
+        #[arg(long)]
+        runs: Option<usize>,
+    },
+    /// Run the regression tests declared by the queries and report failures
+    Test {
+        /// Only the tests of this query
+        name: Option<String>,
+        /// Run against this ArangoDB endpoint instead of the configured one
+        #[arg(long)]
+        endpoint: Option<String>,
+    },
+    /// Browse and run queries in an interactive terminal interface
+    Tui,
+    /// Offer the queries as tools over the Model Context Protocol on stdio
+    Mcp,
+    /// List all configured queries
+    List,
+    /// Print the definition of a configured query
+    Show {
+        /// Name of the query as given in the configuration
+        name: String,
+    },
+    /// Add a query to the catalog, with parameters for its bind variables
+    Add {
+        /// Name of the new query
+        name: String,
+        /// The AQL text
+        #[arg(long)]
+        query: String,
+        /// What the query is for
+        #[arg(long, default_value = "")]
+        description: String,
+    },
+    /// Create the collections of the `seed` section and import their documents
+    Seed,
+    /// Generate a starter configuration file
+    Init(InitArgs),
+    /// Validate a configuration file and report problems
+    Check {
+        /// Configuration file to check (defaults to --config)
+        path: Option<String>,
+        /// Also let the ArangoDB server parse every query
+        #[arg(long)]
+        server: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct InitArgs {
+    /// File to write (defaults to --config)
+    pub path: Option<String>,
+    /// ArangoDB endpoint URL
+    #[arg(long)]
+    pub endpoint: Option<String>,
+    /// ArangoDB user name
+    #[arg(long)]
+    pub username: Option<String>,
+    /// Do not prompt for values that were not given as flags
+    #[arg(long)]
+    pub non_interactive: bool,
+    /// Overwrite an existing file
+    #[arg(long)]
+    pub force: bool,
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("expected key=value, got '{}'", s))
+}
+
+pub fn list_queries(config: &Configuration) {
+    for query in &config.queries {
+        println!("{}\t{}", query.name, query.description);
+    }
+}
+
+pub fn show_query(config: &Configuration, name: &str) -> Result<()> {
+    let query = config
+        .find_query(name)
+        .with_context(|| format!("No query named '{}' in configuration", name))?;
+    println!("Name:        {}", query.name);
+    println!("Description: {}", query.description);
+    if query.parameters.is_empty() {
+        println!("Parameters:  none");
+    } else {
+        println!("Parameters:");
+        for param in &query.parameters {
+            println!("  {} ({})", param.name, param.parameter_type);
+        }
+    }
+    println!("Query:");
+    for line in query.query.lines() {
+        println!("  {}", line);
+    }
+    Ok(())
+}
+
+pub async fn run_query(
+    config: Configuration,
+    name: &str,
+    params: Vec<(String, String)>,
+    format: OutputFormat,
+) -> Result<()> {
+    let runner = QueryRunner::new(Arc::new(config));
+    let execution = runner.run(name, params.into_iter().collect()).await?;
+    print!("{}", output::format_results(&execution.results, format)?);
+    Ok(())
+}
+
+/// Runs a query `runs` times and prints the distribution of its latency
+/// and the execution statistics of the last run.
+pub async fn benchmark_query(
+    config: Configuration,
+    name: &str,
+    params: Vec<(String, String)>,
+    runs: Option<usize>,
+) -> Result<()> {
+    let runner = QueryRunner::new(Arc::new(config));
+    let query = runner.find(name)?;
+    let bind_vars = runner.bind_vars(query, params.into_iter().collect())?;
+    let config = &runner.config().benchmark;
+    let runs = runs.unwrap_or(config.default_runs);
+    let report = benchmark::run(&runner, config, query, bind_vars, runs).await?;
+
+    println!("{}: {} runs, {} rows", report.query_name, runs, report.rows);
+    print_latency_header();
+    print_latency("total", &report.latency);
+    if let Some(server) = &report.server_latency {
+        print_latency("server", server);
+    }
+    if let Some(stats) = report.stats.as_object() {
+        println!("Statistics of the last run:");
+        for (name, value) in stats {
+            println!("  {}: {}", name, value);
+        }
+    }
+    Ok(())
+}
+
+fn print_latency_header() {
+    println!(
+        "{:<12}{:>12}{:>12}{:>12}{:>12}",
+        "ms", "min", "median", "p95", "max"
+    );
+}
+
+fn print_latency(label: &str, latency: &Latency) {
+    println!(
+        "{:<12}{:>12.3}{:>12.3}{:>12.3}{:>12.3}",
+        label, latency.min, latency.median, latency.p95, latency.max
+    );
+}
+
+/// Runs a query and its alternative with the same parameters and prints
+/// how their results differ and how long each takes.
+pub async fn compare_query(
+    config: Configuration,
+    name: &str,
+    params: Vec<(String, String)>,
+    runs: Option<usize>,
+) -> Result<()> {
+    let runner = QueryRunner::new(Arc::new(config));
+    let query = runner.find(name)?;
+    let bind_vars = runner.bind_vars(query, params.into_iter().collect())?;
+    let config = &runner.config().benchmark;
+    let runs = runs.unwrap_or(config.default_runs);
+    let comparison = compare::compare(&runner, config, query, bind_vars, runs).await?;
+
+    let diff = &comparison.diff;
+    if diff.identical {
+        println!(
+            "Same results: {} rows in the same order",
+            diff.original_rows
+        );
+    } else if diff.same_rows {
+        println!("Same results: {} rows in another order", diff.original_rows);
+    } else {
+        println!(
+            "Different results: {} of {} rows only in the original, {} of {} only in the alternative",
+            diff.only_in_original, diff.original_rows, diff.only_in_alternative, diff.alternative_rows
+        );
+        for row in &diff.original_examples {
+            println!("- {}", row);
+        }
+        for row in &diff.alternative_examples {
+            println!("+ {}", row);
+        }
+    }
+    println!("{} runs each", runs);
+    print_latency_header();
+    for (label, report) in [
+        ("original", &comparison.original),
+        ("alternative", &comparison.alternative),
+    ] {
+        print_latency(label, &report.latency);
+        if let Some(server) = &report.server_latency {
+            print_latency("  server", server);
+        }
+    }
+    println!(
+        "Speedup of the alternative (median): {:.2}",
+        comparison.speedup
+    );
+    Ok(())
+}
+
+/// Runs the regression tests of all queries, or of the named one, prints
+/// their outcomes and returns whether all passed.
+pub async fn test_queries(
+    mut config: Configuration,
+    name: Option<&str>,
+    endpoint: Option<String>,
+) -> Result<bool> {
+    if let Some(endpoint) = endpoint {
+        config.arangodb_endpoint = endpoint;
+    }
+    let queries: Vec<QueryDefinition> = match name {
+        Some(name) => vec![config
+            .find_query(name)
+            .with_context(|| format!("No query named '{}' in configuration", name))?
+            .clone()],
+        None => config.queries.clone(),
+    };
+    let runner = QueryRunner::new(Arc::new(config));
+    let outcomes = regression::run(&runner, &queries).await;
+
+    for outcome in &outcomes {
+        println!(
+            "{:<6}{} / {} ({} ms)",
+            if outcome.passed() { "ok" } else { "FAIL" },
+            outcome.query_name,
+            outcome.test_name,
+            outcome.duration.as_millis()
+        );
+        for failure in &outcome.failures {
+            println!("      {}", failure);
+        }
+    }
+    let failed = outcomes.iter().filter(|o| !o.passed()).count();
+    println!("{} tests, {} failed", outcomes.len(), failed);
+    Ok(failed == 0)
+}
+
+/// Adds a query to the catalog of the configuration file, or of the git
+/// store if there is one, declaring the parameters its AQL uses.
+pub fn add_query(config: &Configuration, name: &str, aql: &str, description: &str) -> Result<()> {
+    if config.find_query(name).is_some() {
+        anyhow::bail!("A query named '{}' exists already", name);
+    }
+    let parameters = validate::discover_parameters(aql, &[]);
+    let query: QueryDefinition = serde_json::from_value(json!({
+        "name": name,
+        "description": description,
+        "query": aql,
+        "parameters": parameters,
+    }))?;
+    let mut queries = config.queries.clone();
+    queries.push(query);
+
+    match (&config.git_store, &config.source) {
+        (Some(git), _) => {
+            let author = std::env::var("USER").unwrap_or_else(|_| "cli".to_string());
+            GitStore::new(git.clone()).save(&queries, &author, &format!("Add query '{}'", name))?
+        }
+        (None, Some(path)) => catalog::save(path, &queries)?,
+        (None, None) => anyhow::bail!("The configuration was not loaded from a file"),
+    }
+    println!("Added '{}'", name);
+    for parameter in &parameters {
+        println!("  {} ({})", parameter.name, parameter.parameter_type);
+    }
+    Ok(())
+}
+
+/// Loads the `seed` section of the configuration into the database.
+pub async fn seed_database(config: &Configuration) -> Result<()> {
+    if config.seed.is_empty() {
+        anyhow::bail!("The configuration has no seed section");
+    }
+    let client = Client::from_config(config);
+    for report in seed::seed(&client, &config.seed).await? {
+        println!(
+            "{}: {}imported {} documents, {} errors",
+            report.collection,
+            if report.created_collection {
+                "created, "
+            } else {
+                ""
+            },
+            report.imported,
+            report.errors
+        );
+    }
+    Ok(())
+}
+
+/// Prints a validation report for the given configuration file and returns
+/// whether it is free of errors.
+pub async fn check_config(path: &str, server: bool) -> Result<bool> {
+    let config = match Configuration::load(path) {
+        Ok(c) => c,
+        Err(e) => {
+            println!("error: {:#}", e);
+            return Ok(false);
+        }
+    };
+
+    let mut issues = validate::check_configuration(&config);
+    if server {
+        match validate::check_on_server(&config).await {
+            Ok(server_issues) => issues.extend(server_issues),
+            Err(e) => {
+                println!("error: could not reach ArangoDB: {:#}", e);
+                return Ok(false);
+            }
+        }
+    }
+
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    let errors = issues
+        .iter()
+        .filter(|i| i.severity == Severity::Error)
+        .count();
+    println!(
+        "{}: {} queries, {} errors, {} warnings",
+        path,
+        config.queries.len(),
+        errors,
+        issues.len() - errors
+    );
+    Ok(errors == 0)
+}
+
+fn prompt(question: &str, default: &str) -> Result<String> {
+    print!("{} [{}]: ", question, default);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() {
+        default.to_string()
+    } else {
+        answer.to_string()
+    })
+}
+
+/// Builds a minimal configuration with a password placeholder and a single
+/// example query.
+fn starter_config(endpoint: &str, username: &str) -> serde_json::Value {
+    let endpoint = if endpoint.ends_with('/') {
+        endpoint.to_string()
+    } else {
+        format!("{}/", endpoint)
+    };
+    json!({
+        "arangodb_endpoint": endpoint,
+        "username": username,
+        "password": "CHANGE_ME",
+        "queries": [
+            {
+                "name": "List collections",
+                "description": "Show the non-system collections of the database",
+                "query": "FOR c IN COLLECTIONS() FILTER !STARTS_WITH(c.name, @prefix) RETURN c",
+                "parameters": [
+                    {
+                        "name": "prefix",
+                        "parameter_type": "string"
+                    }
+                ]
+            }
+        ]
+    })
+}
+
+pub fn init_config(args: &InitArgs, default_path: &str) -> Result<()> {
+    let path = args.path.as_deref().unwrap_or(default_path);
+    if std::path::Path::new(path).exists() && !args.force {
+        anyhow::bail!("{} already exists, use --force to overwrite it", path);
+    }
+
+    let interactive = !args.non_interactive && std::io::stdin().is_terminal();
+    let value = |given: &Option<String>, question: &str, default: &str| -> Result<String> {
+        match given {
+            Some(v) => Ok(v.clone()),
+            None if interactive => prompt(question, default),
+            None => Ok(default.to_string()),
+        }
+    };
+    let endpoint = value(
+        &args.endpoint,
+        "ArangoDB endpoint",
+        "http://localhost:8529/",
+    )?;
+    let username = value(&args.username, "ArangoDB user", "root")?;
+
+    let config = starter_config(&endpoint, &username);
+    std::fs::write(path, serde_json::to_string_pretty(&config)? + "\n")
+        .with_context(|| format!("Failed to write {}", path))?;
+    println!(
+        "Wrote {}. Set the password before starting the server.",
+        path
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_run_command() {
+        let cli = Cli::parse_from([
+            "adb_query_runner",
+            "run",
+            "Get Users",
+            "--param",
+            "minAge=30",
+            "--param",
+            "filter=a=b",
+            "--format",
+            "csv",
+        ]);
+        match cli.command {
+            Some(Command::Run {
+                name,
+                params,
+                format,
+            }) => {
+                assert_eq!(name, "Get Users");
+                assert_eq!(
+                    params,
+                    vec![
+                        ("minAge".to_string(), "30".to_string()),
+                        ("filter".to_string(), "a=b".to_string())
+                    ]
+                );
+                assert_eq!(format, OutputFormat::Csv);
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+        assert_eq!(cli.config, "config.json");
+    }
+
+    #[test]
+    fn test_starter_config_is_valid() {
+        let config: Configuration =
+            serde_json::from_value(starter_config("http://db:8529", "admin")).unwrap();
+        assert_eq!(config.arangodb_endpoint, "http://db:8529/");
+        assert!(validate::check_configuration(&config).is_empty());
+    }
+
+    #[test]
+    fn test_add_query() {
+        let path = std::env::temp_dir().join(format!("config-{}.json", uuid::Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        std::fs::write(
+            path,
+            serde_json::to_string(&starter_config("http://db:8529", "admin")).unwrap(),
+        )
+        .unwrap();
+        let config = Configuration::load(path).unwrap();
+        add_query(
+            &config,
+            "By age",
+            "FOR u IN @@users FILTER u.age >= @minAge RETURN u",
+            "",
+        )
+        .unwrap();
+        assert!(add_query(&config, "List collections", "RETURN 1", "").is_err());
+
+        let config = Configuration::load(path).unwrap();
+        let query = config.find_query("By age").unwrap();
+        assert!(validate::check_parameters(query).is_empty());
+        assert_eq!(query.parameters[1].parameter_type, "number");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_param() {
+        assert!(
+            Cli::try_parse_from(["adb_query_runner", "run", "q", "--param", "novalue"]).is_err()
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! A/B comparison of a query with its `alternative`, a rewrite of its AQL.
+//! Both variants run with the same bind variables; their results are
+//! compared row by row and both are benchmarked, so that a rewrite can be
+//! validated before it replaces the query in the catalog.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::arango::CursorOptions;
+use crate::benchmark::{self, BenchmarkConfig, BenchmarkReport};
+use crate::config::QueryDefinition;
+use crate::error::{Error, Result};
+use crate::runner::QueryRunner;
+
+/// Rows shown of those that only one of the variants returned.
+const EXAMPLES: usize = 20;
+
+/// How the results of the two variants differ. Rows are compared as
+/// multisets, duplicates counting, so that a different order alone does
+/// not make them differ.
+#[derive(Debug, Serialize)]
+pub struct ResultDiff {
+    pub original_rows: usize,
+    pub alternative_rows: usize,
+    pub identical: bool, // The same rows in the same order
+    pub same_rows: bool, // The same rows, possibly in another order
+    pub only_in_original: usize,
+    pub only_in_alternative: usize,
+    pub original_examples: Vec<Value>, // The first rows only in the original
+    pub alternative_examples: Vec<Value>,
+}
+
+/// Compares the results of the original and the alternative variant.
+pub fn diff_results(original: &[Value], alternative: &[Value]) -> ResultDiff {
+    // Objects serialize with sorted keys, so equal rows give equal strings
+    let key = |row: &Value| row.to_string();
+    let missing = |rows: &[Value], others: &[Value]| {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for row in others {
+            *counts.entry(key(row)).or_default() += 1;
+        }
+        let mut count = 0;
+        let mut examples = Vec::new();
+        for row in rows {
+            match counts.get_mut(&key(row)) {
+                Some(n) if *n > 0 => *n -= 1,
+                _ => {
+                    count += 1;
+                    if examples.len() < EXAMPLES {
+                        examples.push(row.clone());
+                    }
+                }
+            }
+        }
+        (count, examples)
+    };
+    let (only_in_original, original_examples) = missing(original, alternative);
+    let (only_in_alternative, alternative_examples) = missing(alternative, original);
+    ResultDiff {
+        original_rows: original.len(),
+        alternative_rows: alternative.len(),
+        identical: original == alternative,
+        same_rows: only_in_original == 0 && only_in_alternative == 0,
+        only_in_original,
+        only_in_alternative,
+        original_examples,
+        alternative_examples,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub query_name: String,
+    pub alternative_aql: String,
+    pub diff: ResultDiff,
+    pub original: BenchmarkReport,
+    pub alternative: BenchmarkReport,
+    pub speedup: f64, // Median latency of the original over that of the alternative
+}
+
+/// Runs both variants of `query` once to compare their results, then
+/// benchmarks each with `runs` runs.
+pub async fn compare(
+    runner: &QueryRunner,
+    config: &BenchmarkConfig,
+    query: &QueryDefinition,
+    bind_vars: HashMap<String, Value>,
+    runs: usize,
+) -> Result<Comparison> {
+    let alternative_aql = query.alternative.clone().ok_or_else(|| {
+        Error::InvalidParameter(format!("Query '{}' has no alternative", query.name))
+    })?;
+    runner.check_endpoint(query)?;
+    let options = CursorOptions::of(query);
+    let client = runner.client();
+    // Only the results of these first runs are kept
+    let diff = {
+        let original_results = client
+            .execute_with(&query.query, bind_vars.clone(), &options)
+            .await?
+            .results;
+        let alternative_results = client
+            .execute_with(&alternative_aql, bind_vars.clone(), &options)
+            .await
+            .map_err(|e| match e {
+                Error::Arango {
+                    code,
+                    error_num,
+                    message,
+                } => Error::Arango {
+                    code,
+                    error_num,
+                    message: format!("The alternative failed: {}", message),
+                },
+                e => e,
+            })?
+            .results;
+        diff_results(&original_results, &alternative_results)
+    };
+
+    let original = benchmark::run(runner, config, query, bind_vars.clone(), runs).await?;
+    let alternative =
+        benchmark::run_aql(runner, config, query, &alternative_aql, bind_vars, runs).await?;
+    Ok(Comparison {
+        query_name: query.name.clone(),
+        alternative_aql,
+        diff,
+        speedup: original.latency.median / alternative.latency.median.max(f64::EPSILON),
+        original,
+        alternative,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_results() {
+        let rows = [json!({ "a": 1, "b": 2 }), json!(2), json!(2), json!(3)];
+        let diff = diff_results(&rows, &rows);
+        assert!(diff.identical && diff.same_rows);
+
+        // Keys in another order and rows in another order
+        let reordered = [json!(3), json!(2), json!({ "b": 2, "a": 1 }), json!(2)];
+        let diff = diff_results(&rows, &reordered);
+        assert!(!diff.identical && diff.same_rows);
+
+        let other = [json!(2), json!(3), json!(4), json!(4)];
+        let diff = diff_results(&rows, &other);
+        assert!(!diff.same_rows);
+        assert_eq!((diff.only_in_original, diff.only_in_alternative), (2, 2));
+        assert_eq!(
+            diff.original_examples,
+            [json!({ "a": 1, "b": 2 }), json!(2)]
+        );
+        assert_eq!(diff.alternative_examples, [json!(4), json!(4)]);
+    }
+}
@@ -0,0 +1,264 @@
+//! The configuration file format: connection settings, optional features and
+//! the catalog of queries.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::arango::TimeoutConfig;
+use crate::benchmark::BenchmarkConfig;
+use crate::cursors::CursorConfig;
+use crate::cytoscape::{CytoscapeConfig, GraphOptions};
+use crate::document_cache::DocumentCacheConfig;
+use crate::error::{Error, Result};
+use crate::error_reporting::ErrorReportingConfig;
+use crate::explorer::ExplorerConfig;
+use crate::export::ExportConfig;
+use crate::gephi::GephiConfig;
+use crate::git_store::{self, GitStoreConfig};
+use crate::graph_analyzer::GraphDetection;
+use crate::history::HistoryConfig;
+use crate::proposals::ProposalsConfig;
+use crate::proxy::ProxyConfig;
+use crate::regression::QueryTest;
+use crate::renderer::ResultRenderer;
+use crate::saved_graphs::SavedGraphsConfig;
+use crate::search::SearchConfig;
+use crate::seed::SeedCollection;
+use crate::sessions::SessionConfig;
+use crate::slow_log::SlowLogConfig;
+use crate::spill::SpillConfig;
+use crate::templates::Theme;
+use crate::tenants::TenancyConfig;
+use crate::usage::UsageConfig;
+use crate::versions::VersionsConfig;
+use crate::viewer::{GraphRenderer, ViewerConfig};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueryParameter {
+    pub name: String,
+    pub parameter_type: String, // Could be "string", "number", "boolean", etc.
+}
+
+/// A preconfigured AQL query with its declared bind parameters.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueryDefinition {
+    pub name: String,
+    pub description: String,
+    pub query: String,
+    pub parameters: Vec<QueryParameter>,
+    #[serde(default)]
+    pub cytoscape: GraphOptions, // Presentation of graph results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graph_renderer: Option<GraphRenderer>, // Browser graph viewer, see `viewer`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renderer: Option<ResultRenderer>, // Shape of the result page; else detected from the results
+    #[serde(default)]
+    pub graph: GraphDetection, // How graph results are recognized
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_endpoints: Vec<String>, // Endpoint URL prefixes; empty allows all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent: Option<usize>, // Executions at once, further requests wait in line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>, // Streaming cursor; by default unless an option needs the full result
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub aql_options: serde_json::Map<String, serde_json::Value>, // Cursor `options`, e.g. fullCount
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alternative: Option<String>, // AQL of a rewrite to compare with, see `compare`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tests: Vec<QueryTest>, // Regression tests run by `test`, see `regression`
+}
+
+#[cfg(test)]
+impl QueryDefinition {
+    /// A query of the tests, with the given parameters as (name, type) and
+    /// all options at their defaults.
+    pub(crate) fn for_test(name: &str, aql: &str, parameters: &[(&str, &str)]) -> Self {
+        QueryDefinition {
+            name: name.to_string(),
+            description: String::new(),
+            query: aql.to_string(),
+            parameters: parameters
+                .iter()
+                .map(|(name, parameter_type)| QueryParameter {
+                    name: name.to_string(),
+                    parameter_type: parameter_type.to_string(),
+                })
+                .collect(),
+            cytoscape: Default::default(),
+            graph_renderer: None,
+            renderer: None,
+            graph: Default::default(),
+            allowed_endpoints: Vec::new(),
+            max_concurrent: None,
+            stream: None,
+            aql_options: Default::default(),
+            alternative: None,
+            tests: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_level")]
+    pub level: String, // Filter directive, e.g. "info" or "adb_query_runner=debug"
+    #[serde(default)]
+    pub json: bool,
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: default_log_level(),
+            json: false,
+        }
+    }
+}
+
+/// Sizes of request bodies the web interface accepts, so that a huge
+/// pasted parameter cannot exhaust the memory.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LimitsConfig {
+    #[serde(default = "default_max_form_bytes")]
+    pub max_form_bytes: u64, // Parameter forms
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: u64, // JSON requests of the graph viewer
+    #[serde(default = "default_max_archive_bytes")]
+    pub max_archive_bytes: u64, // Unpacked contents of an uploaded archive
+    #[serde(default = "default_max_archive_entry_bytes")]
+    pub max_archive_entry_bytes: u64, // Each file of an uploaded archive, unpacked
+}
+
+fn default_max_form_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_max_body_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_archive_bytes() -> u64 {
+    1024 * 1024 * 1024
+}
+
+fn default_max_archive_entry_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            max_form_bytes: default_max_form_bytes(),
+            max_body_bytes: default_max_body_bytes(),
+            max_archive_bytes: default_max_archive_bytes(),
+            max_archive_entry_bytes: default_max_archive_entry_bytes(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Configuration {
+    pub arangodb_endpoint: String, // e.g. "http://localhost:8529/" or "unix:///tmp/arangodb.sock/"
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub unix_socket: Option<String>, // Serve the web interface here instead of localhost:3030
+    #[serde(default)]
+    pub proxy: ProxyConfig, // Outbound HTTP proxy, see `proxy`
+    #[serde(default)]
+    pub timeouts: TimeoutConfig, // Of the connections to ArangoDB
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub slow_query_log: Option<SlowLogConfig>,
+    #[serde(default)]
+    pub performance_history: HistoryConfig,
+    #[serde(default)]
+    pub benchmark: BenchmarkConfig, // Repeated runs of a query, see `benchmark`
+    #[serde(default)]
+    pub usage: UsageConfig, // Usage statistics per query, see `usage`
+    #[serde(default)]
+    pub query_versions: VersionsConfig, // Earlier definitions of edited queries, see `versions`
+    #[serde(default)]
+    pub proposals: ProposalsConfig, // Changes awaiting review, see `proposals`
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64, // Grace period for in-flight requests on shutdown
+    #[serde(default)]
+    pub error_reporting: ErrorReportingConfig,
+    #[serde(default)]
+    pub cytoscape: CytoscapeConfig,
+    #[serde(default)]
+    pub gephi: GephiConfig,
+    #[serde(default)]
+    pub export: ExportConfig,
+    #[serde(default)]
+    pub seed: Vec<SeedCollection>, // Fixture data loaded by `seed`
+    #[serde(default)]
+    pub viewer: ViewerConfig,
+    #[serde(default)]
+    pub saved_graphs: SavedGraphsConfig, // Graphs saved from the viewer, see `saved_graphs`
+    #[serde(default)]
+    pub document_cache: DocumentCacheConfig, // Fetched stub vertices, see `document_cache`
+    #[serde(default)]
+    pub spill: SpillConfig, // Results too big for memory, see `spill`
+    #[serde(default)]
+    pub explorer: ExplorerConfig, // Traversals of named graphs, see `explorer`
+    #[serde(default)]
+    pub search: SearchConfig, // Searches in ArangoSearch views, see `search`
+    #[serde(default)]
+    pub theme: Theme, // Unless users choose another one
+    #[serde(default)]
+    pub locale: Option<String>, // UI language, else from Accept-Language, see `i18n`
+    #[serde(default)]
+    pub tenancy: Option<TenancyConfig>, // Users mapped to databases, see `tenants`
+    #[serde(default)]
+    pub sessions: SessionConfig, // Remembered parameter values, see `sessions`
+    #[serde(default)]
+    pub cursors: CursorConfig, // Results fetched batch by batch, see `cursors`
+    #[serde(default)]
+    pub git_store: Option<GitStoreConfig>, // Keep the queries in a git repository, see `git_store`
+    #[serde(default)]
+    pub queries: Vec<QueryDefinition>, // Unless the git store has a catalog
+    #[serde(skip)]
+    pub source: Option<String>, // The file it was loaded from
+    #[serde(skip)]
+    pub loaded_at: Option<DateTime<Utc>>,
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+impl Configuration {
+    /// Reads and parses a JSON configuration file. With a git store, the
+    /// queries are read from its repository.
+    #[instrument]
+    pub fn load(path: &str) -> Result<Self> {
+        let config_str = std::fs::read_to_string(path)
+            .map_err(|e| Error::Config(format!("Failed to read {}: {}", path, e)))?;
+        let mut config: Configuration = serde_json::from_str(&config_str)
+            .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path, e)))?;
+        if let Some(git) = &config.git_store {
+            if let Some(queries) = git_store::read_catalog(git)? {
+                config.queries = queries;
+            }
+        }
+        Ok(Configuration {
+            source: Some(path.to_string()),
+            loaded_at: Some(Utc::now()),
+            ..config
+        })
+    }
+
+    /// Looks up a query of the catalog by its name.
+    pub fn find_query(&self, name: &str) -> Option<&QueryDefinition> {
+        self.queries.iter().find(|q| q.name == name)
+    }
+}
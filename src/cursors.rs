@@ -0,0 +1,183 @@
+//! Cursors kept open between requests, so the browser can fetch the
+//! batches of a large result one at a time instead of waiting for all of
+//! them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::arango::Cursor;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CursorConfig {
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64, // Cursors not used for this long are released
+    #[serde(default = "default_max_open")]
+    pub max_open: usize, // The least recently used cursor is released beyond this
+}
+
+fn default_batch_size() -> usize {
+    1000
+}
+
+fn default_idle_timeout_secs() -> u64 {
+    300
+}
+
+fn default_max_open() -> usize {
+    20
+}
+
+impl Default for CursorConfig {
+    fn default() -> Self {
+        CursorConfig {
+            batch_size: default_batch_size(),
+            idle_timeout_secs: default_idle_timeout_secs(),
+            max_open: default_max_open(),
+        }
+    }
+}
+
+/// A cursor with the query it belongs to.
+pub struct OpenCursor {
+    pub cursor: Cursor,
+    pub query_name: String,
+    last_used: Instant,
+}
+
+/// Open cursors by token. A cursor is taken out of the store while its next
+/// batch is fetched, so it is never read by two requests at once.
+pub struct CursorStore {
+    idle_timeout: Duration,
+    max_open: usize,
+    cursors: Mutex<HashMap<String, OpenCursor>>,
+}
+
+impl CursorStore {
+    pub fn new(config: &CursorConfig) -> Self {
+        CursorStore {
+            idle_timeout: Duration::from_secs(config.idle_timeout_secs),
+            max_open: config.max_open,
+            cursors: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Drops idle cursors, and the least recently used ones beyond the
+    /// maximum, which deletes them on the server.
+    fn evict(&self, cursors: &mut HashMap<String, OpenCursor>, room_for: usize) {
+        cursors.retain(|token, open| {
+            let keep = open.last_used.elapsed() < self.idle_timeout;
+            if !keep {
+                info!(token = token.as_str(), "Releasing idle cursor");
+            }
+            keep
+        });
+        while !cursors.is_empty() && cursors.len() + room_for > self.max_open {
+            let oldest = cursors
+                .iter()
+                .min_by_key(|(_, open)| open.last_used)
+                .map(|(token, _)| token.clone())
+                .unwrap_or_default();
+            info!(
+                token = oldest.as_str(),
+                "Releasing least recently used cursor"
+            );
+            cursors.remove(&oldest);
+        }
+    }
+
+    /// Keeps a cursor and returns the token to fetch its batches with.
+    pub fn insert(&self, cursor: Cursor, query_name: &str) -> String {
+        let token = uuid::Uuid::new_v4().to_string();
+        self.put_back(
+            &token,
+            OpenCursor {
+                cursor,
+                query_name: query_name.to_string(),
+                last_used: Instant::now(),
+            },
+        );
+        token
+    }
+
+    /// Takes a cursor out of the store, or `None` if the token is unknown or
+    /// the cursor was released.
+    pub fn take(&self, token: &str) -> Option<OpenCursor> {
+        let mut cursors = self.cursors.lock().unwrap();
+        self.evict(&mut cursors, 0);
+        cursors.remove(token)
+    }
+
+    /// Keeps a cursor again after a batch was fetched from it.
+    pub fn put_back(&self, token: &str, mut open: OpenCursor) {
+        open.last_used = Instant::now();
+        let mut cursors = self.cursors.lock().unwrap();
+        self.evict(&mut cursors, 1);
+        cursors.insert(token.to_string(), open);
+    }
+
+    pub fn len(&self) -> usize {
+        self.cursors.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockArango;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_store() {
+        let mock = MockArango::new()
+            .batch_size(1)
+            .rows("FOR d IN docs", (0..3).map(|i| json!(i)).collect())
+            .start();
+        let client = mock.client();
+        let open = || async {
+            let (_, cursor) = client
+                .open_cursor(
+                    "FOR d IN docs RETURN d",
+                    HashMap::new(),
+                    &Default::default(),
+                    1,
+                    60,
+                )
+                .await
+                .unwrap();
+            cursor.unwrap()
+        };
+
+        let store = CursorStore::new(&CursorConfig {
+            max_open: 2,
+            ..CursorConfig::default()
+        });
+        let first = store.insert(open().await, "q");
+        let second = store.insert(open().await, "q");
+        let taken = store.take(&first).unwrap();
+        assert_eq!(taken.query_name, "q");
+        assert!(store.take(&first).is_none());
+        store.put_back(&first, taken);
+
+        // The least recently used cursor makes room for a new one
+        let third = store.insert(open().await, "q");
+        assert_eq!(store.len(), 2);
+        assert!(store.take(&second).is_none());
+        assert!(store.take(&third).is_some());
+
+        let idle = CursorStore::new(&CursorConfig {
+            idle_timeout_secs: 0,
+            ..CursorConfig::default()
+        });
+        let token = idle.insert(open().await, "q");
+        assert!(idle.take(&token).is_none());
+    }
+}
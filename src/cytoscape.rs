@@ -1,56 +1,1374 @@
-use anyhow::{Context, Result};
+use chrono::Local;
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
-use std::collections::{HashMap, HashSet};
-
-pub async fn send_to_cytoscape(vertices: &Value, edges: &Value) -> Result<()> {
-    let client = Client::new();
-    let base_url = "http://localhost:1234/v1";
-
-    // Helper function to collect attributes from a list of objects
-    fn collect_attributes(objects: &[Value]) -> HashSet<String> {
-        let mut attributes = HashSet::new();
-        for obj in objects {
-            if let Some(map) = obj.as_object() {
-                attributes.extend(
-                    map.keys()
-                        .filter(|k| !k.starts_with('_')) // Exclude ArangoDB system attributes
-                        .cloned(),
-                );
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+use tracing::{debug, info, instrument};
+
+use crate::analytics;
+use crate::error::{Error, Result};
+use crate::proxy;
+
+/// The layout algorithms that ship with Cytoscape.
+pub const LAYOUTS: &[&str] = &[
+    "force-directed",
+    "force-directed-cl",
+    "circular",
+    "grid",
+    "hierarchical",
+    "attribute-circle",
+    "degree-circle",
+    "attributes-layout",
+    "kamada-kawai",
+    "fruchterman-rheingold",
+    "isom",
+    "stacked-node-layout",
+    "cose",
+];
+
+/// The column with the whole document of a node or edge as JSON, including
+/// the system attributes and nested values that have no column of their own.
+const DOCUMENT_COLUMN: &str = "arango_document";
+
+/// The column with the collection of a node, the prefix of its `_id`. Nodes
+/// are colored by it unless other colors are configured.
+const COLLECTION_COLUMN: &str = "arango_collection";
+
+/// Marks networks created by this tool, so that they can be cleaned up.
+const GENERATED_BY: &str = "adb_query_runner";
+
+/// When to delete networks of earlier uploads.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkCleanup {
+    #[default]
+    Never,
+    Before, // Before creating the new network
+    After,  // After the new network was created successfully
+}
+
+/// How edges of several edge collections, i.e. relations, are told apart.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RelationSplit {
+    #[default]
+    None,
+    Styles,   // One network, with an edge color per relation
+    Networks, // One network per relation, with the vertices its edges connect
+}
+
+/// How attributes with objects as values become columns.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NestedAttributes {
+    #[default]
+    Flatten, // A column per nested attribute, e.g. `address.city`, up to `flatten_depth`
+    Json, // A String column with the object as JSON
+}
+
+/// Layout parameters (e.g. `numIterations`, `defaultSpringLength`), keyed
+/// by layout name, as they are only meaningful for one algorithm.
+pub type LayoutParameters = HashMap<String, Map<String, Value>>;
+
+/// Where the CyREST API of Cytoscape is reachable and how graphs are shown.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CytoscapeConfig {
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_api_version")]
+    pub api_version: String,
+    #[serde(default = "default_network_name")]
+    pub network_name: String, // Pattern with {query}, {timestamp} and {params}
+    #[serde(default)]
+    pub cleanup: NetworkCleanup,
+    #[serde(default)]
+    pub keep_networks: usize, // Older networks of ours that cleanup leaves alone
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize, // Elements per request for large graphs, 0 to never split
+    #[serde(default)]
+    pub session_file: Option<String>, // Save the session here after each upload (.cys)
+    #[serde(default)]
+    pub style_file: Option<String>, // Standard visual style, Cytoscape JSON or XML
+    #[serde(default)]
+    pub style_name: Option<String>, // Name of the style in an XML style file
+    #[serde(default = "default_layout")]
+    pub layout: String,
+    #[serde(default)]
+    pub layout_parameters: LayoutParameters,
+    #[serde(default)]
+    pub node_color: Option<ColorMapping>,
+    #[serde(default)]
+    pub edge_width: Option<WidthMapping>,
+    #[serde(default)]
+    pub edge_color: Option<ColorMapping>,
+    #[serde(default)]
+    pub split_relations: RelationSplit, // For graphs with edges of several collections
+    #[serde(default)]
+    pub group_by: Option<String>, // Attribute whose values group vertices into compound nodes
+    #[serde(default)]
+    pub push_by_default: bool, // Tick "Send to Cytoscape" on the forms; else sent from the result page
+    #[serde(default)]
+    pub nested_attributes: NestedAttributes,
+    #[serde(default = "default_flatten_depth")]
+    pub flatten_depth: usize, // Levels of objects flattened; deeper ones become JSON
+}
+
+/// Maps an attribute to a color, either value by value or as a gradient
+/// between two colors over a numeric range.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ColorMapping {
+    Discrete {
+        attribute: String,
+        colors: BTreeMap<String, String>, // Attribute value to color, e.g. "#e74c3c"
+    },
+    Continuous {
+        attribute: String,
+        min_color: String,
+        max_color: String,
+        #[serde(default)]
+        min: Option<f64>, // Defaults to the smallest value in the graph
+        #[serde(default)]
+        max: Option<f64>, // Defaults to the largest value in the graph
+    },
+}
+
+impl ColorMapping {
+    /// A discrete mapping that gives vertices of every collection their own
+    /// color, the same as in the browser viewer.
+    pub fn by_collection(vertices: &[Value]) -> ColorMapping {
+        ColorMapping::Discrete {
+            attribute: COLLECTION_COLUMN.to_string(),
+            colors: crate::viewer::collection_colors(vertices),
+        }
+    }
+
+    /// A discrete mapping that gives edges of every relation their own
+    /// color, see [`with_relations`].
+    pub fn by_relation(edges: &[Value]) -> ColorMapping {
+        ColorMapping::Discrete {
+            attribute: "relation".to_string(),
+            colors: crate::viewer::attribute_colors(edges, "relation"),
+        }
+    }
+
+    /// The CyREST mapping of this color mapping to a visual property.
+    fn to_cyrest(&self, visual_property: &str, objects: &[Value]) -> Value {
+        match self {
+            ColorMapping::Discrete { attribute, colors } => json!({
+                "mappingType": "discrete",
+                "mappingColumn": attribute,
+                "mappingColumnType": column_type(objects, attribute).element,
+                "visualProperty": visual_property,
+                "map": colors
+                    .iter()
+                    .map(|(key, value)| json!({ "key": key, "value": value }))
+                    .collect::<Vec<_>>(),
+            }),
+            ColorMapping::Continuous {
+                attribute,
+                min_color,
+                max_color,
+                min,
+                max,
+            } => {
+                let (min, max) = value_range(objects, attribute, *min, *max);
+                let point = |value: f64, color: &str| json!({ "value": value, "lesser": color, "equal": color, "greater": color });
+                json!({
+                    "mappingType": "continuous",
+                    "mappingColumn": attribute,
+                    "mappingColumnType": numeric_column_type(objects, attribute),
+                    "visualProperty": visual_property,
+                    "points": [point(min, min_color), point(max, max_color)],
+                })
+            }
+        }
+    }
+}
+
+/// Maps a numeric attribute linearly to a width, e.g. of edges.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WidthMapping {
+    pub attribute: String,
+    #[serde(default = "default_min_width")]
+    pub min_width: f64,
+    #[serde(default = "default_max_width")]
+    pub max_width: f64,
+    #[serde(default)]
+    pub min: Option<f64>, // Defaults to the smallest value in the graph
+    #[serde(default)]
+    pub max: Option<f64>, // Defaults to the largest value in the graph
+}
+
+fn default_min_width() -> f64 {
+    1.0
+}
+
+fn default_max_width() -> f64 {
+    8.0
+}
+
+impl WidthMapping {
+    fn to_cyrest(&self, visual_property: &str, objects: &[Value]) -> Value {
+        let (min, max) = value_range(objects, &self.attribute, self.min, self.max);
+        let point = |value: f64, width: f64| json!({ "value": value, "lesser": width, "equal": width, "greater": width });
+        json!({
+            "mappingType": "continuous",
+            "mappingColumn": self.attribute,
+            "mappingColumnType": numeric_column_type(objects, &self.attribute),
+            "visualProperty": visual_property,
+            "points": [point(min, self.min_width), point(max, self.max_width)],
+        })
+    }
+}
+
+/// The range of a numeric attribute over some objects, unless configured.
+fn value_range(
+    objects: &[Value],
+    attribute: &str,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> (f64, f64) {
+    let values = objects.iter().filter_map(|o| o[attribute].as_f64());
+    let min = min.unwrap_or_else(|| values.clone().fold(f64::INFINITY, f64::min));
+    let max = max.unwrap_or_else(|| values.fold(f64::NEG_INFINITY, f64::max));
+    // Without numeric values there is no range to map
+    if min <= max {
+        (min, max)
+    } else {
+        (0.0, 0.0)
+    }
+}
+
+/// Per-query overrides of the presentation settings in [`CytoscapeConfig`].
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GraphOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub layout_parameters: LayoutParameters,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_color: Option<ColorMapping>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edge_width: Option<WidthMapping>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edge_color: Option<ColorMapping>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split_relations: Option<RelationSplit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<String>,
+}
+
+/// The presentation settings for one upload, with all defaults resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Presentation {
+    pub network_name: String,
+    pub append_to: Option<i64>, // SUID of an existing network to merge into
+
+    pub layout: String,
+    pub layout_parameters: Map<String, Value>,
+    pub node_color: Option<ColorMapping>,
+    pub edge_width: Option<WidthMapping>,
+    pub edge_color: Option<ColorMapping>,
+    pub split_relations: RelationSplit, // Never applied to a network appended to
+    pub directed: Option<bool>,         // Arrows or none; else as the style has it
+    pub group_by: Option<String>,
+}
+
+fn default_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_port() -> u16 {
+    1234
+}
+
+fn default_api_version() -> String {
+    "v1".to_string()
+}
+
+fn default_batch_size() -> usize {
+    5000
+}
+
+fn default_network_name() -> String {
+    "{query} {timestamp} ({params})".to_string()
+}
+
+fn default_layout() -> String {
+    "force-directed".to_string()
+}
+
+fn default_flatten_depth() -> usize {
+    3
+}
+
+impl Default for CytoscapeConfig {
+    fn default() -> Self {
+        CytoscapeConfig {
+            host: default_host(),
+            port: default_port(),
+            api_version: default_api_version(),
+            network_name: default_network_name(),
+            cleanup: NetworkCleanup::Never,
+            keep_networks: 0,
+            batch_size: default_batch_size(),
+            session_file: None,
+            style_file: None,
+            style_name: None,
+            layout: default_layout(),
+            layout_parameters: LayoutParameters::new(),
+            node_color: None,
+            edge_width: None,
+            edge_color: None,
+            split_relations: RelationSplit::None,
+            group_by: None,
+            push_by_default: false,
+            nested_attributes: NestedAttributes::Flatten,
+            flatten_depth: default_flatten_depth(),
+        }
+    }
+}
+
+impl CytoscapeConfig {
+    /// The CyREST base URL, e.g. `http://localhost:1234/v1`.
+    pub fn base_url(&self) -> String {
+        format!("http://{}:{}/{}", self.host, self.port, self.api_version)
+    }
+
+    /// Names a network after the query that produced it, following the
+    /// configured pattern. Parameters are summarized as `name=value` pairs.
+    pub fn network_name(&self, query_name: &str, bind_vars: &HashMap<String, Value>) -> String {
+        let mut params: Vec<String> = bind_vars
+            .iter()
+            .map(|(k, v)| match v {
+                Value::String(s) => format!("{}={}", k, s),
+                other => format!("{}={}", k, other),
+            })
+            .collect();
+        params.sort();
+        let name = self
+            .network_name
+            .replace("{query}", query_name)
+            .replace(
+                "{timestamp}",
+                &Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            )
+            .replace("{params}", &params.join(", "));
+        // Drop the parentheses of the default pattern for queries without parameters
+        name.replace(" ()", "").trim().to_string()
+    }
+
+    /// Resolves the layout for a query: an explicitly chosen layout wins over
+    /// the query's setting, which wins over the global default. Parameters
+    /// of the query override global ones for the same layout.
+    pub fn presentation(&self, options: &GraphOptions, layout: Option<&str>) -> Presentation {
+        let layout = layout
+            .or(options.layout.as_deref())
+            .unwrap_or(&self.layout)
+            .to_string();
+        let mut layout_parameters = self
+            .layout_parameters
+            .get(&layout)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(overrides) = options.layout_parameters.get(&layout) {
+            layout_parameters.extend(overrides.clone());
+        }
+        Presentation {
+            network_name: "ArangoDB Graph".to_string(),
+            append_to: None,
+            layout,
+            layout_parameters,
+            node_color: options.node_color.clone().or(self.node_color.clone()),
+            edge_width: options.edge_width.clone().or(self.edge_width.clone()),
+            edge_color: options.edge_color.clone().or(self.edge_color.clone()),
+            split_relations: options.split_relations.unwrap_or(self.split_relations),
+            directed: None,
+            group_by: options.group_by.clone().or(self.group_by.clone()),
+        }
+    }
+}
+
+/// The type of a Cytoscape column: `String`, `Boolean`, `Integer`, `Long`
+/// or `Double`, or a list of one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ColumnType {
+    element: &'static str,
+    list: bool,
+}
+
+impl ColumnType {
+    const STRING: ColumnType = ColumnType {
+        element: "String",
+        list: false,
+    };
+
+    /// The type of a value, `None` for null and empty lists, which fit
+    /// any column.
+    fn of(value: &Value) -> Option<ColumnType> {
+        match value {
+            Value::Array(items) => Some(ColumnType {
+                element: items
+                    .iter()
+                    .filter_map(scalar_type)
+                    .reduce(common_scalar_type)?,
+                list: true,
+            }),
+            _ => Some(ColumnType {
+                element: scalar_type(value)?,
+                list: false,
+            }),
+        }
+    }
+
+    /// A type for the values of both types. Lists and single values only
+    /// fit together as JSON strings.
+    fn merge(self, other: ColumnType) -> ColumnType {
+        if self.list != other.list {
+            return ColumnType::STRING;
+        }
+        ColumnType {
+            element: common_scalar_type(self.element, other.element),
+            list: self.list,
+        }
+    }
+
+    /// Converts a value of the column, so that all are of its type.
+    fn coerce(self, value: &Value) -> Value {
+        match value {
+            Value::Array(items) if self.list => Value::Array(
+                items
+                    .iter()
+                    .filter(|item| !item.is_null())
+                    .map(|item| coerce_scalar(self.element, item))
+                    .collect(),
+            ),
+            _ => coerce_scalar(self.element, value),
+        }
+    }
+}
+
+fn scalar_type(value: &Value) -> Option<&'static str> {
+    match value {
+        Value::String(_) => Some("String"),
+        Value::Bool(_) => Some("Boolean"),
+        Value::Number(n) => Some(match n.as_i64() {
+            Some(i) if i32::try_from(i).is_ok() => "Integer",
+            Some(_) => "Long",
+            None => "Double",
+        }),
+        _ => None,
+    }
+}
+
+fn common_scalar_type(a: &'static str, b: &'static str) -> &'static str {
+    match (a, b) {
+        _ if a == b => a,
+        ("Integer", "Long") | ("Long", "Integer") => "Long",
+        ("Integer" | "Long" | "Double", "Integer" | "Long" | "Double") => "Double",
+        _ => "String",
+    }
+}
+
+fn coerce_scalar(element: &str, value: &Value) -> Value {
+    match (element, value) {
+        ("String", Value::String(_)) => value.clone(),
+        ("String", _) => Value::String(value.to_string()),
+        // Written with a fraction, so that Cytoscape does not take the
+        // column for an integer one
+        ("Double", Value::Number(n)) => n.as_f64().map(Value::from).unwrap_or(Value::Null),
+        _ => value.clone(),
+    }
+}
+
+/// The Cytoscape column type for an attribute, fitting all its values.
+/// Defaults to `String` if the type cannot be determined.
+fn column_type(objects: &[Value], attr: &str) -> ColumnType {
+    objects
+        .iter()
+        .filter_map(|o| ColumnType::of(o.as_object()?.get(attr)?))
+        .reduce(ColumnType::merge)
+        .unwrap_or(ColumnType::STRING)
+}
+
+/// The column type for a continuous mapping of an attribute, `Double`
+/// unless all its values are integers.
+fn numeric_column_type(objects: &[Value], attr: &str) -> &'static str {
+    match column_type(objects, attr) {
+        ColumnType {
+            element: element @ ("Integer" | "Long"),
+            list: false,
+        } => element,
+        _ => "Double",
+    }
+}
+
+/// The attributes of a document as columns: system attributes are left
+/// out, they are in the document column, and objects are flattened to
+/// dotted names or written as JSON.
+fn document_attributes(
+    document: &Map<String, Value>,
+    nested: NestedAttributes,
+    depth: usize,
+) -> Map<String, Value> {
+    fn add(
+        row: &mut Map<String, Value>,
+        name: String,
+        value: &Value,
+        nested: NestedAttributes,
+        depth: usize,
+    ) {
+        match value {
+            Value::Object(object) if nested == NestedAttributes::Flatten && depth > 0 => {
+                for (key, value) in object {
+                    add(row, format!("{}.{}", name, key), value, nested, depth - 1);
+                }
+            }
+            Value::Object(_) => {
+                row.insert(name, Value::String(value.to_string()));
+            }
+            // Cytoscape lists hold single values only
+            Value::Array(items) if items.iter().any(|i| i.is_object() || i.is_array()) => {
+                row.insert(name, Value::String(value.to_string()));
             }
+            Value::Null => {}
+            _ => {
+                row.insert(name, value.clone());
+            }
+        }
+    }
+
+    let mut row = Map::new();
+    for (key, value) in document {
+        if !key.starts_with('_') {
+            add(&mut row, key.clone(), value, nested, depth);
+        }
+    }
+    row
+}
+
+/// The columns of rows of attributes, see [`document_attributes`], with
+/// the values of every row converted to the types of the columns.
+fn typed_columns(rows: &mut [Map<String, Value>]) -> BTreeMap<String, ColumnType> {
+    let mut types: BTreeMap<String, Option<ColumnType>> = BTreeMap::new();
+    for row in rows.iter() {
+        for (name, value) in row {
+            let merged = match (types.get(name).copied().flatten(), ColumnType::of(value)) {
+                (Some(a), Some(b)) => Some(a.merge(b)),
+                (a, b) => a.or(b),
+            };
+            types.insert(name.clone(), merged);
+        }
+    }
+    let columns: BTreeMap<String, ColumnType> = types
+        .into_iter()
+        .map(|(name, column)| (name, column.unwrap_or(ColumnType::STRING)))
+        .collect();
+    for row in rows.iter_mut() {
+        for (name, value) in row.iter_mut() {
+            *value = columns[name].coerce(value);
+        }
+    }
+    columns
+}
+
+/// Creates the columns of a node or edge table, along with the document
+/// column. Columns that already exist, e.g. in a network appended to, are
+/// left as they are.
+async fn create_columns(
+    client: &Client,
+    network_url: &str,
+    table: &str,
+    columns: &BTreeMap<String, ColumnType>,
+) -> Result<()> {
+    let document_column = [(DOCUMENT_COLUMN, ColumnType::STRING)];
+    let columns = columns
+        .iter()
+        .map(|(name, column)| (name.as_str(), *column))
+        .chain(document_column);
+    for (name, column) in columns {
+        client
+            .post(format!("{}/tables/{}/columns", network_url, table))
+            .json(&json!({
+                "name": name,
+                "type": column.element,
+                "list": column.list,
+            }))
+            .send()
+            .await?;
+    }
+    Ok(())
+}
+
+/// Builds a CyREST visual style from the configured mappings, or `None` if
+/// there is nothing to map.
+fn style_document(
+    name: &str,
+    presentation: &Presentation,
+    base: Option<&Value>,
+    vertices: &[Value],
+    edges: &[Value],
+) -> Option<Value> {
+    let mut mappings = Vec::new();
+    if let Some(mapping) = &presentation.node_color {
+        mappings.push(mapping.to_cyrest("NODE_FILL_COLOR", vertices));
+    }
+    if let Some(mapping) = &presentation.edge_width {
+        mappings.push(mapping.to_cyrest("EDGE_WIDTH", edges));
+    }
+    if let Some(mapping) = &presentation.edge_color {
+        mappings.push(mapping.to_cyrest("EDGE_STROKE_UNSELECTED_PAINT", edges));
+    }
+    // Arrows at the targets of edges of a directed graph, none otherwise
+    let arrow = presentation.directed.map(|directed| {
+        json!({
+            "visualProperty": "EDGE_TARGET_ARROW_SHAPE",
+            "value": if directed { "DELTA" } else { "NONE" },
+        })
+    });
+    if mappings.is_empty() && arrow.is_none() && base.is_none() {
+        return None;
+    }
+
+    // Mappings of the query replace those of the base style for the same
+    // visual property; everything else of the base style is kept
+    let mut style = base.cloned().unwrap_or_else(|| json!({ "defaults": [] }));
+    let overridden: HashSet<Value> = mappings
+        .iter()
+        .map(|m| m["visualProperty"].clone())
+        .collect();
+    let mut all_mappings: Vec<Value> = style["mappings"]
+        .as_array()
+        .map(|base| {
+            base.iter()
+                .filter(|m| !overridden.contains(&m["visualProperty"]))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    all_mappings.extend(mappings);
+    if let Some(arrow) = arrow {
+        let mut defaults: Vec<Value> = style["defaults"]
+            .as_array()
+            .map(|base| {
+                base.iter()
+                    .filter(|d| d["visualProperty"] != arrow["visualProperty"])
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        defaults.push(arrow);
+        style["defaults"] = json!(defaults);
+    }
+    style["title"] = json!(name);
+    style["mappings"] = json!(all_mappings);
+    Some(style)
+}
+
+/// The configured standard style, see [`CytoscapeConfig::style_file`].
+enum BaseStyle {
+    /// A style in CyREST JSON, merged into the style of each network.
+    Json(Value),
+    /// A style loaded into Cytoscape from XML, applied by name.
+    Loaded(String),
+}
+
+/// Finds the name of the first visual style in a Cytoscape style XML file.
+fn xml_style_name(xml: &str) -> Option<String> {
+    let marker = "<visualStyle name=\"";
+    let start = xml.find(marker)? + marker.len();
+    let end = xml[start..].find('"')?;
+    Some(xml[start..start + end].to_string())
+}
+
+/// Reads the configured style file. JSON styles are parsed here, XML styles
+/// are loaded by Cytoscape itself, so their path must be valid for Cytoscape.
+async fn load_base_style(
+    client: &Client,
+    base_url: &str,
+    config: &CytoscapeConfig,
+) -> Result<Option<BaseStyle>> {
+    let Some(path) = &config.style_file else {
+        return Ok(None);
+    };
+    if path.to_lowercase().ends_with(".json") {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read style file {}", path))?;
+        let style: Value =
+            serde_json::from_str(&text).with_context(|| format!("Invalid style file {}", path))?;
+        // Cytoscape exports styles as a list, CyREST takes a single one
+        let style = match style {
+            Value::Array(styles) => styles.into_iter().next().unwrap_or(Value::Null),
+            style => style,
+        };
+        if !style.is_object() {
+            return Err(Error::Cytoscape(format!(
+                "Style file {} contains no style",
+                path
+            )));
+        }
+        return Ok(Some(BaseStyle::Json(style)));
+    }
+
+    let name = match &config.style_name {
+        Some(name) => name.clone(),
+        None => std::fs::read_to_string(path)
+            .ok()
+            .as_deref()
+            .and_then(xml_style_name)
+            .with_context(|| format!("Set style_name for the style file {}", path))?,
+    };
+    client
+        .post(format!("{}/commands/vizmap/load file", base_url))
+        .json(&json!({ "file": path }))
+        .send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to load style file {}", path))?;
+    Ok(Some(BaseStyle::Loaded(name)))
+}
+
+/// Checks whether CyREST answers at the configured base URL.
+pub async fn is_available(config: &CytoscapeConfig) -> bool {
+    let response = proxy::client()
+        .get(config.base_url())
+        .timeout(Duration::from_secs(2))
+        .send()
+        .await;
+    match response {
+        Ok(r) if r.status().is_success() => true,
+        Ok(r) => {
+            debug!(status = %r.status(), "CyREST answered with an error");
+            false
+        }
+        Err(e) => {
+            debug!("CyREST not reachable: {}", e);
+            false
+        }
+    }
+}
+
+/// A network that currently exists in Cytoscape.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NetworkInfo {
+    #[serde(rename = "SUID")]
+    pub suid: i64,
+    pub name: String,
+}
+
+/// Lists the networks currently loaded in Cytoscape.
+pub async fn list_networks(config: &CytoscapeConfig) -> Result<Vec<NetworkInfo>> {
+    let networks = proxy::client()
+        .get(format!("{}/networks.names", config.base_url()))
+        .timeout(Duration::from_secs(2))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| Error::Cytoscape(e.to_string()))?
+        .json()
+        .await
+        .map_err(|e| Error::Cytoscape(e.to_string()))?;
+    Ok(networks)
+}
+
+/// The image formats CyREST renders network views in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Svg,
+    Pdf,
+}
+
+impl ImageFormat {
+    pub const ALL: &'static [ImageFormat] = &[ImageFormat::Png, ImageFormat::Svg, ImageFormat::Pdf];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+            ImageFormat::Pdf => "pdf",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.name() == name)
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Svg => "image/svg+xml",
+            ImageFormat::Pdf => "application/pdf",
+        }
+    }
+}
+
+/// The first view of a network as rendered by Cytoscape, with its layout
+/// and style.
+pub async fn export_image(
+    config: &CytoscapeConfig,
+    network_suid: i64,
+    format: ImageFormat,
+) -> Result<Vec<u8>> {
+    let bytes = proxy::client()
+        .get(format!(
+            "{}/networks/{}/views/first.{}",
+            config.base_url(),
+            network_suid,
+            format.name()
+        ))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| Error::Cytoscape(format!("Exporting the network view failed: {}", e)))?
+        .bytes()
+        .await
+        .map_err(|e| Error::Cytoscape(e.to_string()))?;
+    Ok(bytes.to_vec())
+}
+
+/// Creates a network in Cytoscape from the given vertex and edge documents,
+/// or merges them into an existing one. Returns the SUIDs of the networks,
+/// more than one if the graph is split by relation.
+pub async fn send_to_cytoscape(
+    config: &CytoscapeConfig,
+    presentation: &Presentation,
+    vertices: &Value,
+    edges: &Value,
+) -> Result<Vec<i64>> {
+    send_with_progress(config, presentation, vertices, edges, &|_| {}).await
+}
+
+/// Like [`send_to_cytoscape`], telling `progress` about each stage of the
+/// upload as it begins, e.g. "Applying layout".
+#[instrument(skip_all, fields(url = %config.base_url()))]
+pub async fn send_with_progress(
+    config: &CytoscapeConfig,
+    presentation: &Presentation,
+    vertices: &Value,
+    edges: &Value,
+    progress: &(dyn Fn(&str) + Send + Sync),
+) -> Result<Vec<i64>> {
+    let split = match presentation.append_to {
+        Some(_) => RelationSplit::None,
+        None => presentation.split_relations,
+    };
+    let uploaded = match split {
+        RelationSplit::None => upload(config, presentation, vertices, edges, progress)
+            .await
+            .map(|suid| vec![suid]),
+        RelationSplit::Styles => {
+            let edges = Value::from(with_relations(edges.as_array().unwrap_or(&Vec::new())));
+            upload(config, presentation, vertices, &edges, progress)
+                .await
+                .map(|suid| vec![suid])
+        }
+        RelationSplit::Networks => {
+            upload_per_relation(config, presentation, vertices, edges, progress).await
+        }
+    };
+    // The requests that failed went to Cytoscape, not to ArangoDB
+    uploaded.map_err(|e| match e {
+        Error::Connection(e) => Error::Cytoscape(e.to_string()),
+        e => e,
+    })
+}
+
+/// The relation of an edge: the collection of its `_id`, or of its `_from`
+/// for edges without one.
+pub fn relation(edge: &Value) -> Option<&str> {
+    ["_id", "_from"]
+        .iter()
+        .find_map(|attr| Some(edge.get(*attr)?.as_str()?.split_once('/')?.0))
+}
+
+/// The edges with their relation as attribute `relation`, unless they have
+/// such an attribute already.
+fn with_relations(edges: &[Value]) -> Vec<Value> {
+    edges
+        .iter()
+        .map(|edge| {
+            let mut edge = edge.clone();
+            let relation = relation(&edge).map(str::to_string);
+            if let (Some(obj), Some(relation)) = (edge.as_object_mut(), relation) {
+                obj.entry("relation").or_insert(json!(relation));
+            }
+            edge
+        })
+        .collect()
+}
+
+/// The graph split by relation: the edges of every relation with the
+/// vertices they connect. Vertices without edges are left out.
+fn split_by_relation(
+    vertices: &[Value],
+    edges: &[Value],
+) -> BTreeMap<String, (Vec<Value>, Vec<Value>)> {
+    let mut groups: BTreeMap<String, (Vec<Value>, Vec<Value>)> = BTreeMap::new();
+    for edge in edges {
+        let relation = relation(edge).unwrap_or("edges").to_string();
+        groups.entry(relation).or_default().1.push(edge.clone());
+    }
+    for (group_vertices, group_edges) in groups.values_mut() {
+        let ends: HashSet<&str> = group_edges
+            .iter()
+            .flat_map(|e| [e["_from"].as_str(), e["_to"].as_str()])
+            .flatten()
+            .collect();
+        group_vertices.extend(
+            vertices
+                .iter()
+                .filter(|v| v["_id"].as_str().is_some_and(|id| ends.contains(id)))
+                .cloned(),
+        );
+    }
+    groups
+}
+
+/// Uploads a graph as one network per relation, named after the relation.
+/// Earlier networks are cleaned up once for all of them.
+async fn upload_per_relation(
+    config: &CytoscapeConfig,
+    presentation: &Presentation,
+    vertices: &Value,
+    edges: &Value,
+    progress: &(dyn Fn(&str) + Send + Sync),
+) -> Result<Vec<i64>> {
+    let vertices_arr = vertices.as_array().context("Vertices must be an array")?;
+    let edges_arr = edges.as_array().context("Edges must be an array")?;
+    let groups = split_by_relation(vertices_arr, edges_arr);
+    if groups.len() < 2 {
+        return Ok(vec![
+            upload(config, presentation, vertices, edges, progress).await?,
+        ]);
+    }
+
+    let client = proxy::client();
+    let base_url = &config.base_url();
+    if config.cleanup == NetworkCleanup::Before {
+        progress("Deleting earlier networks");
+        cleanup_networks(&client, base_url, config.keep_networks, &[]).await?;
+    }
+    let each = CytoscapeConfig {
+        cleanup: NetworkCleanup::Never,
+        ..config.clone()
+    };
+    let mut suids = Vec::new();
+    for (relation, (group_vertices, group_edges)) in groups {
+        let presentation = Presentation {
+            network_name: format!("{} - {}", presentation.network_name, relation),
+            ..presentation.clone()
+        };
+        let suid = upload(
+            &each,
+            &presentation,
+            &Value::from(group_vertices),
+            &Value::from(group_edges),
+            &|stage| progress(&format!("{}: {}", relation, stage)),
+        )
+        .await?;
+        suids.push(suid);
+    }
+    if config.cleanup == NetworkCleanup::After {
+        progress("Deleting earlier networks");
+        cleanup_networks(&client, base_url, config.keep_networks, &suids).await?;
+    }
+    Ok(suids)
+}
+
+/// Chooses which of our networks to delete: all but the `keep` newest ones
+/// (SUIDs grow over time), never the excluded ones.
+fn networks_to_delete(mut suids: Vec<i64>, keep: usize, exclude: &[i64]) -> Vec<i64> {
+    suids.retain(|suid| !exclude.contains(suid));
+    suids.sort_unstable_by(|a, b| b.cmp(a));
+    suids.into_iter().skip(keep).collect()
+}
+
+/// Deletes networks created by this tool, found by their `generated_by`
+/// network attribute. Returns how many were deleted.
+async fn cleanup_networks(
+    client: &Client,
+    base_url: &str,
+    keep: usize,
+    exclude: &[i64],
+) -> Result<usize> {
+    let ours: Vec<i64> = client
+        .get(format!(
+            "{}/networks?column=generated_by&query={}",
+            base_url, GENERATED_BY
+        ))
+        .send()
+        .await?
+        .error_for_status()
+        .context("Failed to list networks")?
+        .json()
+        .await?;
+    let stale = networks_to_delete(ours, keep, exclude);
+    for suid in &stale {
+        client
+            .delete(format!("{}/networks/{}", base_url, suid))
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Failed to delete network {}", suid))?;
+    }
+    if !stale.is_empty() {
+        info!(deleted = stale.len(), "Deleted old networks");
+    }
+    Ok(stale.len())
+}
+
+/// A layout parameter entered as text, e.g. on the parameter form: a
+/// number or boolean if it reads as one, else the text.
+pub fn layout_parameter_value(text: &str) -> Value {
+    let text = text.trim();
+    if let Ok(integer) = text.parse::<i64>() {
+        return json!(integer);
+    }
+    if let Ok(float) = text.parse::<f64>() {
+        return json!(float);
+    }
+    match text {
+        "true" => json!(true),
+        "false" => json!(false),
+        _ => json!(text),
+    }
+}
+
+/// Sets the parameters of a layout before it is applied. CyREST ignores
+/// parameters a layout does not have, so they are checked against those it
+/// lists for the layout first.
+async fn set_layout_parameters(
+    client: &Client,
+    base_url: &str,
+    layout: &str,
+    parameters: &Map<String, Value>,
+) -> Result<()> {
+    let url = format!("{}/apply/layouts/{}/parameters", base_url, layout);
+    let known: Vec<Value> = client
+        .get(&url)
+        .send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to read the parameters of layout '{}'", layout))?
+        .json()
+        .await?;
+    let known: Vec<&str> = known.iter().filter_map(|p| p["name"].as_str()).collect();
+    if let Some(unknown) = parameters
+        .keys()
+        .find(|name| !known.contains(&name.as_str()))
+    {
+        return Err(Error::Cytoscape(format!(
+            "Layout '{}' has no parameter '{}', it has {}",
+            layout,
+            unknown,
+            known.join(", ")
+        )));
+    }
+    let parameters: Vec<Value> = parameters
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect();
+    client
+        .put(&url)
+        .json(&parameters)
+        .send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Invalid parameters for layout '{}'", layout))?;
+    Ok(())
+}
+
+/// Maps the `arango_id` column of a node or edge table to the SUIDs.
+async fn table_ids(
+    client: &Client,
+    network_url: &str,
+    table: &str,
+) -> Result<HashMap<String, i64>> {
+    let rows: Vec<Value> = client
+        .get(format!("{}/tables/{}/rows", network_url, table))
+        .send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to read the {} table", table))?
+        .json()
+        .await?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            Some((
+                row["arango_id"].as_str()?.to_string(),
+                row["SUID"].as_i64()?,
+            ))
+        })
+        .collect())
+}
+
+/// The vertices of every group for compound nodes, by their ArangoDB id:
+/// vertices with the same value of the attribute, if it is a string, number
+/// or boolean, are in one group. The collection column groups vertices by
+/// collection, unless they have an attribute of that name.
+fn vertex_groups(vertices: &[Value], attribute: &str) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for vertex in vertices {
+        let group = match vertex.get(attribute) {
+            None if attribute == COLLECTION_COLUMN => match vertex["_id"].as_str() {
+                Some(id) => analytics::collection(id).to_string(),
+                None => continue,
+            },
+            Some(Value::String(s)) => s.clone(),
+            Some(value @ (Value::Number(_) | Value::Bool(_))) => value.to_string(),
+            _ => continue,
+        };
+        if let Some(id) = vertex["_id"].as_str() {
+            groups.entry(group).or_default().push(id.to_string());
         }
-        attributes
     }
+    groups
+}
+
+/// Sets the attributes of nodes or edges, given as rows with their SUID.
+async fn update_table(
+    client: &Client,
+    network_url: &str,
+    table: &str,
+    rows: Vec<Map<String, Value>>,
+) -> Result<()> {
+    client
+        .put(format!("{}/tables/{}", network_url, table))
+        .json(&json!({ "key": "SUID", "dataKey": "SUID", "data": rows }))
+        .send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to update the {} table", table))?;
+    Ok(())
+}
+
+/// Adds nodes to a network, skipping those whose ArangoDB id is already in
+/// `node_suids`, and records the SUIDs of the new ones there.
+async fn add_nodes(
+    client: &Client,
+    network_url: &str,
+    node_suids: &mut HashMap<String, i64>,
+    nodes: &[Value],
+) -> Result<usize> {
+    let mut seen = HashSet::new();
+    let new_nodes: Vec<&Map<String, Value>> = nodes
+        .iter()
+        .filter_map(|n| n["data"].as_object())
+        .filter(|data| {
+            let id = data["arango_id"].as_str().unwrap_or_default();
+            !node_suids.contains_key(id) && seen.insert(id.to_string())
+        })
+        .collect();
+    if new_nodes.is_empty() {
+        return Ok(0);
+    }
+
+    let names: Vec<&Value> = new_nodes.iter().map(|data| &data["arango_id"]).collect();
+    let created: Vec<Value> = client
+        .post(format!("{}/nodes", network_url))
+        .json(&names)
+        .send()
+        .await?
+        .error_for_status()
+        .context("Failed to add nodes")?
+        .json()
+        .await?;
+    // Created nodes are reported in the order of the request
+    let mut rows = Vec::new();
+    for (data, node) in new_nodes.iter().zip(&created) {
+        let suid = node["SUID"].as_i64().context("Node without SUID")?;
+        node_suids.insert(
+            data["arango_id"].as_str().unwrap_or_default().to_string(),
+            suid,
+        );
+        let mut row = (*data).clone();
+        row.remove("id");
+        row.insert("SUID".to_string(), json!(suid));
+        rows.push(row);
+    }
+    update_table(client, network_url, "defaultnode", rows).await?;
+    Ok(new_nodes.len())
+}
+
+/// Adds edges between known nodes to a network, skipping those whose
+/// ArangoDB id is in `edge_ids`, and records the new ids there.
+async fn add_edges(
+    client: &Client,
+    network_url: &str,
+    node_suids: &HashMap<String, i64>,
+    edge_ids: &mut HashSet<String>,
+    edges: &[Value],
+) -> Result<usize> {
+    let new_edges: Vec<(&Map<String, Value>, i64, i64)> = edges
+        .iter()
+        .filter_map(|e| {
+            let data = e["data"].as_object()?;
+            let id = data["arango_id"].as_str()?;
+            let source = *node_suids.get(data["source"].as_str()?)?;
+            let target = *node_suids.get(data["target"].as_str()?)?;
+            edge_ids
+                .insert(id.to_string())
+                .then_some((data, source, target))
+        })
+        .collect();
+    if new_edges.is_empty() {
+        return Ok(0);
+    }
+
+    let request: Vec<Value> = new_edges
+        .iter()
+        .map(|(_, source, target)| json!({ "source": source, "target": target, "directed": true }))
+        .collect();
+    let created: Vec<Value> = client
+        .post(format!("{}/edges", network_url))
+        .json(&request)
+        .send()
+        .await?
+        .error_for_status()
+        .context("Failed to add edges")?
+        .json()
+        .await?;
+    let mut rows = Vec::new();
+    for ((data, _, _), edge) in new_edges.iter().zip(&created) {
+        let mut row = (*data).clone();
+        row.remove("id");
+        row.remove("source");
+        row.remove("target");
+        row.insert("SUID".to_string(), edge["SUID"].clone());
+        rows.push(row);
+    }
+    update_table(client, network_url, "defaultedge", rows).await?;
+    Ok(new_edges.len())
+}
+
+/// Adds Cytoscape.js elements to a network in batches of `batch_size`
+/// (0 for a single batch), nodes first. Elements whose ArangoDB id is
+/// already present are skipped. Returns the number of added nodes and edges.
+async fn add_elements(
+    client: &Client,
+    network_url: &str,
+    batch_size: usize,
+    mut node_suids: HashMap<String, i64>,
+    mut edge_ids: HashSet<String>,
+    nodes: &[Value],
+    edges: &[Value],
+) -> Result<(usize, usize)> {
+    let batch_size = if batch_size == 0 {
+        usize::MAX
+    } else {
+        batch_size
+    };
+
+    let mut added_nodes = 0;
+    for (i, batch) in nodes.chunks(batch_size).enumerate() {
+        added_nodes += add_nodes(client, network_url, &mut node_suids, batch).await?;
+        info!(
+            done = (i * batch_size + batch.len()),
+            total = nodes.len(),
+            "Uploaded nodes"
+        );
+    }
+    let mut added_edges = 0;
+    for (i, batch) in edges.chunks(batch_size).enumerate() {
+        added_edges += add_edges(client, network_url, &node_suids, &mut edge_ids, batch).await?;
+        info!(
+            done = (i * batch_size + batch.len()),
+            total = edges.len(),
+            "Uploaded edges"
+        );
+    }
+    Ok((added_nodes, added_edges))
+}
+
+/// Adds the given Cytoscape.js elements to an existing network, skipping
+/// nodes and edges whose ArangoDB id is already present. Returns the number
+/// of added nodes and edges.
+async fn append_elements(
+    client: &Client,
+    base_url: &str,
+    network_suid: i64,
+    batch_size: usize,
+    nodes: &[Value],
+    edges: &[Value],
+) -> Result<(usize, usize)> {
+    let network_url = format!("{}/networks/{}", base_url, network_suid);
+    let node_suids = table_ids(client, &network_url, "defaultnode").await?;
+    let edge_ids = table_ids(client, &network_url, "defaultedge")
+        .await?
+        .into_keys()
+        .collect();
+    add_elements(
+        client,
+        &network_url,
+        batch_size,
+        node_suids,
+        edge_ids,
+        nodes,
+        edges,
+    )
+    .await
+}
+
+async fn upload(
+    config: &CytoscapeConfig,
+    presentation: &Presentation,
+    vertices: &Value,
+    edges: &Value,
+    progress: &(dyn Fn(&str) + Send + Sync),
+) -> Result<i64> {
+    let client = proxy::client();
+    let base_url = &config.base_url();
 
     // Get vertices and edges as arrays
     let vertices_arr = vertices.as_array().context("Vertices must be an array")?;
     let edges_arr = edges.as_array().context("Edges must be an array")?;
 
-    // Collect vertex and edge attributes
-    let vertex_attributes = collect_attributes(vertices_arr);
-    let edge_attributes = collect_attributes(edges_arr);
+    // The attributes of vertices and edges, typed by column
+    let attributes = |objects: &[Value]| -> Vec<Map<String, Value>> {
+        objects
+            .iter()
+            .map(|o| {
+                o.as_object()
+                    .map(|o| document_attributes(o, config.nested_attributes, config.flatten_depth))
+                    .unwrap_or_default()
+            })
+            .collect()
+    };
+    let mut vertex_rows = attributes(vertices_arr);
+    let mut edge_rows = attributes(edges_arr);
+    let node_columns = typed_columns(&mut vertex_rows);
+    let edge_columns = typed_columns(&mut edge_rows);
 
     // Prepare vertices for Cytoscape format
     let cytoscape_vertices: Vec<Value> = vertices_arr
         .iter()
-        .filter_map(|v| {
+        .zip(&vertex_rows)
+        .filter_map(|(v, row)| {
             let obj = v.as_object()?;
             let mut node_data = Map::new();
 
             // Use _id as node ID
             if let Some(id) = obj.get("_id") {
                 node_data.insert("id".to_string(), id.clone());
+                node_data.insert("arango_id".to_string(), id.clone()); // Used to deduplicate
                 node_data.insert("name".to_string(), id.clone()); // Use ID as name by default
+                node_data.insert(
+                    COLLECTION_COLUMN.to_string(),
+                    json!(analytics::collection(id.as_str().unwrap_or_default())),
+                );
             } else {
                 return None;
             }
 
             // Add all other attributes
-            for attr in &vertex_attributes {
-                if let Some(value) = obj.get(attr) {
-                    node_data.insert(attr.clone(), value.clone());
-                }
-            }
+            node_data.extend(row.clone());
+            node_data.insert(DOCUMENT_COLUMN.to_string(), json!(v.to_string()));
 
             Some(json!({
                 "data": node_data
@@ -61,25 +1379,24 @@ pub async fn send_to_cytoscape(vertices: &Value, edges: &Value) -> Result<()> {
     // Prepare edges for Cytoscape format
     let cytoscape_edges: Vec<Value> = edges_arr
         .iter()
-        .filter_map(|e| {
+        .zip(&edge_rows)
+        .filter_map(|(e, row)| {
             let obj = e.as_object()?;
             let mut edge_data = Map::new();
 
             // Generate unique edge ID
-            if let Some(id) = obj.get("_key") {
-                edge_data.insert("id".to_string(), id.clone());
-                edge_data.insert("source".to_string(), obj.get("_from").unwrap().clone());
-                edge_data.insert("target".to_string(), obj.get("_to").unwrap().clone());
-            } else {
-                return None;
-            }
+            let id = obj.get("_key")?;
+            edge_data.insert("id".to_string(), id.clone());
+            edge_data.insert(
+                "arango_id".to_string(),
+                obj.get("_id").unwrap_or(id).clone(),
+            );
+            edge_data.insert("source".to_string(), obj.get("_from")?.clone());
+            edge_data.insert("target".to_string(), obj.get("_to")?.clone());
 
             // Add all other attributes
-            for attr in &edge_attributes {
-                if let Some(value) = obj.get(attr) {
-                    edge_data.insert(attr.clone(), value.clone());
-                }
-            }
+            edge_data.extend(row.clone());
+            edge_data.insert(DOCUMENT_COLUMN.to_string(), json!(e.to_string()));
 
             Some(json!({
                 "data": edge_data
@@ -87,122 +1404,751 @@ pub async fn send_to_cytoscape(vertices: &Value, edges: &Value) -> Result<()> {
         })
         .collect();
 
-    // Create network with initial data
-    let network_data = json!({
-        "format_version": "1.0",
-        "generated_by": "adb_query_runner",
-        "target_cytoscapejs_version": "~3.0",
-        "data": {
-            "shared_name": "ArangoDB Graph",
-            "name": "ArangoDB Graph"
-        },
-        "elements": {
-            "nodes": cytoscape_vertices,
-            "edges": cytoscape_edges
-        }
-    });
+    if config.cleanup == NetworkCleanup::Before {
+        progress("Deleting earlier networks");
+        cleanup_networks(
+            &client,
+            base_url,
+            config.keep_networks,
+            presentation.append_to.as_slice(),
+        )
+        .await?;
+    }
 
-    let network_response: Value = client
-        .post(&format!("{}/networks?format=json", base_url))
-        .header("Content-Type", "application/json")
-        .json(&network_data)
-        .send()
-        .await?
-        .json()
+    let network_suid = if let Some(network_suid) = presentation.append_to {
+        progress(&format!(
+            "Adding {} nodes and {} edges to network {}",
+            cytoscape_vertices.len(),
+            cytoscape_edges.len(),
+            network_suid
+        ));
+        progress("Creating attribute columns");
+        let network_url = format!("{}/networks/{}", base_url, network_suid);
+        create_columns(&client, &network_url, "defaultnode", &node_columns).await?;
+        create_columns(&client, &network_url, "defaultedge", &edge_columns).await?;
+        let (nodes, edges) = append_elements(
+            &client,
+            base_url,
+            network_suid,
+            config.batch_size,
+            &cytoscape_vertices,
+            &cytoscape_edges,
+        )
         .await?;
+        info!(network_suid, nodes, edges, "Appended to network");
+        network_suid
+    } else {
+        // Large graphs time out in a single request. They are sent to an
+        // empty network in batches instead.
+        let chunked = config.batch_size > 0
+            && cytoscape_vertices.len() + cytoscape_edges.len() > config.batch_size;
 
-    let network_suid = network_response["networkSUID"]
-        .as_i64()
-        .context("Failed to get network SUID")?;
+        progress(&format!(
+            "Creating a network of {} nodes and {} edges",
+            cytoscape_vertices.len(),
+            cytoscape_edges.len()
+        ));
+        // Create network with initial data
+        let network_data = json!({
+            "format_version": "1.0",
+            "generated_by": "adb_query_runner",
+            "target_cytoscapejs_version": "~3.0",
+            "data": {
+                "shared_name": presentation.network_name,
+                "name": presentation.network_name,
+                "generated_by": GENERATED_BY
+            },
+            "elements": if chunked {
+                json!({ "nodes": [], "edges": [] })
+            } else {
+                json!({ "nodes": cytoscape_vertices, "edges": cytoscape_edges })
+            }
+        });
 
-    println!("Created network with SUID: {}", network_suid);
+        let network_response: Value = client
+            .post(format!("{}/networks?format=json", base_url))
+            .header("Content-Type", "application/json")
+            .json(&network_data)
+            .send()
+            .await?
+            .json()
+            .await?;
 
-    // Create column mappings for vertex attributes
-    let mut node_table_columns = HashMap::new();
-    for attr in &vertex_attributes {
-        // Determine column type based on first non-null value
-        let column_type = vertices_arr
-            .iter()
-            .find_map(|v| {
-                v.as_object()?.get(attr).and_then(|val| match val {
-                    Value::String(_) => Some("String"),
-                    Value::Number(_) => Some("Double"),
-                    Value::Bool(_) => Some("Boolean"),
-                    _ => None,
-                })
-            })
-            .unwrap_or("String"); // Default to String if type cannot be determined
+        let network_suid = network_response["networkSUID"]
+            .as_i64()
+            .context("Failed to get network SUID")?;
 
-        node_table_columns.insert(attr, column_type);
-    }
+        info!(network_suid, "Created network");
 
-    // Create vertex table columns
-    for (attr, col_type) in node_table_columns {
-        client
-            .post(&format!(
-                "{}/networks/{}/tables/defaultnode/columns",
-                base_url, network_suid
-            ))
-            .json(&json!({
-                "name": attr,
-                "type": col_type
-            }))
-            .send()
+        // Before the elements of batches, so that their values find typed
+        // columns
+        progress("Creating attribute columns");
+        let network_url = format!("{}/networks/{}", base_url, network_suid);
+        create_columns(&client, &network_url, "defaultnode", &node_columns).await?;
+        create_columns(&client, &network_url, "defaultedge", &edge_columns).await?;
+        if chunked {
+            add_elements(
+                &client,
+                &network_url,
+                config.batch_size,
+                HashMap::new(),
+                HashSet::new(),
+                &cytoscape_vertices,
+                &cytoscape_edges,
+            )
             .await?;
+        }
+        network_suid
+    };
+
+    if config.cleanup == NetworkCleanup::After {
+        progress("Deleting earlier networks");
+        cleanup_networks(&client, base_url, config.keep_networks, &[network_suid]).await?;
     }
 
-    // Create column mappings for edge attributes
-    let mut edge_table_columns = HashMap::new();
-    for attr in &edge_attributes {
-        // Determine column type based on first non-null value
-        let column_type = edges_arr
-            .iter()
-            .find_map(|e| {
-                e.as_object()?.get(attr).and_then(|val| match val {
-                    Value::String(_) => Some("String"),
-                    Value::Number(_) => Some("Double"),
-                    Value::Bool(_) => Some("Boolean"),
-                    _ => None,
-                })
-            })
-            .unwrap_or("String"); // Default to String if type cannot be determined
+    // Group vertices into compound nodes, one per value of the attribute.
+    // Groups of a network appended to are left as they are.
+    if let (Some(attribute), None) = (&presentation.group_by, presentation.append_to) {
+        let groups = vertex_groups(vertices_arr, attribute);
+        if !groups.is_empty() {
+            progress(&format!("Grouping nodes by {}", attribute));
+            let network_url = format!("{}/networks/{}", base_url, network_suid);
+            let node_suids = table_ids(&client, &network_url, "defaultnode").await?;
+            for (name, ids) in &groups {
+                let nodes: Vec<i64> = ids
+                    .iter()
+                    .filter_map(|id| node_suids.get(id).copied())
+                    .collect();
+                client
+                    .post(format!("{}/groups", network_url))
+                    .json(&json!({ "name": name, "nodes": nodes }))
+                    .send()
+                    .await?
+                    .error_for_status()
+                    .with_context(|| format!("Failed to create the group {}", name))?;
+            }
+            info!(network_suid, groups = groups.len(), "Grouped nodes");
+        }
+    }
 
-        edge_table_columns.insert(attr, column_type);
+    // Apply the layout, setting its parameters first
+    let layout = &presentation.layout;
+    progress(&format!("Applying layout {}", layout));
+    if !presentation.layout_parameters.is_empty() {
+        set_layout_parameters(&client, base_url, layout, &presentation.layout_parameters).await?;
     }
+    client
+        .get(format!(
+            "{}/apply/layouts/{}/{}",
+            base_url, layout, network_suid
+        ))
+        .send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to apply layout '{}'", layout))?;
 
-    // Create edge table columns
-    for (attr, col_type) in edge_table_columns {
+    info!(network_suid, layout = layout.as_str(), "Applied layout");
+
+    // Apply the standard style, if any, and a style for the configured
+    // mappings on top, replacing the style of an earlier upload
+    progress("Applying visual style");
+    let base_style = load_base_style(&client, base_url, config).await?;
+    let base_json = match &base_style {
+        Some(BaseStyle::Loaded(name)) => {
+            client
+                .get(format!(
+                    "{}/apply/styles/{}/{}",
+                    base_url, name, network_suid
+                ))
+                .send()
+                .await?
+                .error_for_status()
+                .with_context(|| format!("Failed to apply visual style {}", name))?;
+            info!(network_suid, style = name.as_str(), "Applied visual style");
+            None
+        }
+        Some(BaseStyle::Json(style)) => Some(style),
+        None => None,
+    };
+    // Unless colors are configured, vertices are colored by collection. The
+    // colors of a network appended to stay as they are.
+    let by_collection;
+    let presentation = if presentation.node_color.is_none()
+        && config.style_file.is_none()
+        && presentation.append_to.is_none()
+    {
+        by_collection = Presentation {
+            node_color: Some(ColorMapping::by_collection(vertices_arr)),
+            ..presentation.clone()
+        };
+        &by_collection
+    } else {
+        presentation
+    };
+    // Likewise edges by relation, if asked for
+    let by_relation;
+    let presentation = if presentation.split_relations == RelationSplit::Styles
+        && presentation.edge_color.is_none()
+        && config.style_file.is_none()
+        && presentation.append_to.is_none()
+    {
+        by_relation = Presentation {
+            edge_color: Some(ColorMapping::by_relation(edges_arr)),
+            ..presentation.clone()
+        };
+        &by_relation
+    } else {
+        presentation
+    };
+    let style_name = format!("adb_query_runner_{}", network_suid);
+    // Mapped by the attributes as they are in the columns
+    let vertex_attributes: Vec<Value> = vertex_rows.into_iter().map(Value::Object).collect();
+    let edge_attributes: Vec<Value> = edge_rows.into_iter().map(Value::Object).collect();
+    if let Some(style) = style_document(
+        &style_name,
+        presentation,
+        base_json,
+        &vertex_attributes,
+        &edge_attributes,
+    ) {
+        if presentation.append_to.is_some() {
+            client
+                .delete(format!("{}/styles/{}", base_url, style_name))
+                .send()
+                .await?;
+        }
         client
-            .post(&format!(
-                "{}/networks/{}/tables/defaultedge/columns",
-                base_url, network_suid
+            .post(format!("{}/styles", base_url))
+            .json(&style)
+            .send()
+            .await?
+            .error_for_status()
+            .context("Failed to create visual style")?;
+        client
+            .get(format!(
+                "{}/apply/styles/{}/{}",
+                base_url, style_name, network_suid
             ))
-            .json(&json!({
-                "name": attr,
-                "type": col_type
-            }))
             .send()
-            .await?;
+            .await?
+            .error_for_status()
+            .context("Failed to apply visual style")?;
+        info!(
+            network_suid,
+            style = style_name.as_str(),
+            "Applied visual style"
+        );
     }
 
-    // Apply a layout
-    client
-        .put(&format!(
-            "{}/networks/{}/layouts/force-directed",
-            base_url, network_suid
-        ))
-        .send()
-        .await?;
+    // Save the session, so that the graph survives a restart of Cytoscape.
+    // The path is interpreted by Cytoscape, i.e. on the machine it runs on.
+    if let Some(session_file) = &config.session_file {
+        progress("Saving session");
+        client
+            .post(format!("{}/session", base_url))
+            .query(&[("file", session_file)])
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Failed to save session to {}", session_file))?;
+        info!(
+            session_file = session_file.as_str(),
+            "Saved Cytoscape session"
+        );
+    }
 
-    println!("Applied force-directed layout");
+    Ok(network_suid)
+}
 
-    Ok(())
+/// Tells what was being done when an upload step failed, e.g. "Failed to
+/// add nodes: <reason>".
+trait Context<T> {
+    fn context(self, what: &str) -> Result<T>;
+    fn with_context(self, what: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T, E: std::fmt::Display> Context<T> for std::result::Result<T, E> {
+    fn context(self, what: &str) -> Result<T> {
+        self.map_err(|e| Error::Cytoscape(format!("{}: {}", what, e)))
+    }
+
+    fn with_context(self, what: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|e| Error::Cytoscape(format!("{}: {}", what(), e)))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, what: &str) -> Result<T> {
+        self.ok_or_else(|| Error::Cytoscape(what.to_string()))
+    }
+
+    fn with_context(self, what: impl FnOnce() -> String) -> Result<T> {
+        self.ok_or_else(|| Error::Cytoscape(what()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_base_url() {
+        let config: CytoscapeConfig =
+            serde_json::from_value(json!({ "host": "viz.example.com", "port": 8080 })).unwrap();
+        assert_eq!(config.base_url(), "http://viz.example.com:8080/v1");
+        assert_eq!(
+            CytoscapeConfig::default().base_url(),
+            "http://localhost:1234/v1"
+        );
+    }
+
+    #[test]
+    fn test_node_color_style() {
+        let vertices = vec![
+            json!({ "_id": "people/a", "city": "Cologne", "age": 20 }),
+            json!({ "_id": "people/b", "city": "Berlin", "age": 60 }),
+        ];
+        let mut presentation =
+            CytoscapeConfig::default().presentation(&GraphOptions::default(), None);
+        assert!(style_document("s", &presentation, None, &vertices, &[]).is_none());
+
+        presentation.node_color = Some(
+            serde_json::from_value(json!({
+                "type": "discrete",
+                "attribute": "city",
+                "colors": { "Berlin": "#3498db", "Cologne": "#e74c3c" }
+            }))
+            .unwrap(),
+        );
+        let style = style_document("s", &presentation, None, &vertices, &[]).unwrap();
+        let mapping = &style["mappings"][0];
+        assert_eq!(mapping["mappingType"], "discrete");
+        assert_eq!(mapping["mappingColumnType"], "String");
+        assert_eq!(
+            mapping["map"][0],
+            json!({ "key": "Berlin", "value": "#3498db" })
+        );
+
+        presentation.node_color = Some(ColorMapping::Continuous {
+            attribute: "age".to_string(),
+            min_color: "#ffffff".to_string(),
+            max_color: "#000000".to_string(),
+            min: None,
+            max: None,
+        });
+        let style = style_document("s", &presentation, None, &vertices, &[]).unwrap();
+        let points = &style["mappings"][0]["points"];
+        assert_eq!(points[0]["value"], 20.0);
+        assert_eq!(points[1]["value"], 60.0);
+        assert_eq!(points[1]["equal"], "#000000");
+    }
+
+    #[test]
+    fn test_edge_width_style() {
+        let edges = vec![
+            json!({ "_from": "a/1", "_to": "a/2", "weight": 0.5 }),
+            json!({ "_from": "a/2", "_to": "a/3", "weight": 2.5 }),
+            json!({ "_from": "a/3", "_to": "a/1" }),
+        ];
+        let options: GraphOptions = serde_json::from_value(json!({
+            "edge_width": { "attribute": "weight", "max_width": 10.0 },
+            "edge_color": {
+                "type": "continuous",
+                "attribute": "weight",
+                "min_color": "#cccccc",
+                "max_color": "#c0392b",
+                "min": 0.0
+            }
+        }))
+        .unwrap();
+        let presentation = CytoscapeConfig::default().presentation(&options, None);
+        let style = style_document("s", &presentation, None, &[], &edges).unwrap();
+
+        let width = &style["mappings"][0];
+        assert_eq!(width["visualProperty"], "EDGE_WIDTH");
+        assert_eq!(
+            width["points"][0],
+            json!({ "value": 0.5, "lesser": 1.0, "equal": 1.0, "greater": 1.0 })
+        );
+        assert_eq!(width["points"][1]["value"], 2.5);
+        assert_eq!(width["points"][1]["equal"], 10.0);
+
+        let color = &style["mappings"][1];
+        assert_eq!(color["visualProperty"], "EDGE_STROKE_UNSELECTED_PAINT");
+        assert_eq!(color["points"][0]["value"], 0.0);
+    }
+
+    #[test]
+    fn test_style_file() {
+        let base = json!({
+            "title": "Team standard",
+            "defaults": [{ "visualProperty": "NODE_SHAPE", "value": "ELLIPSE" }],
+            "mappings": [
+                { "mappingType": "passthrough", "mappingColumn": "name", "visualProperty": "NODE_LABEL" },
+                { "mappingType": "passthrough", "mappingColumn": "color", "visualProperty": "NODE_FILL_COLOR" }
+            ]
+        });
+        let vertices = vec![json!({ "_id": "people/a", "city": "Cologne" })];
+        let mut presentation =
+            CytoscapeConfig::default().presentation(&GraphOptions::default(), None);
+        let style = style_document("s", &presentation, Some(&base), &vertices, &[]).unwrap();
+        assert_eq!(style["title"], "s");
+        assert_eq!(style["defaults"], base["defaults"]);
+        assert_eq!(style["mappings"], base["mappings"]);
+
+        presentation.node_color = Some(ColorMapping::Discrete {
+            attribute: "city".to_string(),
+            colors: BTreeMap::from([("Cologne".to_string(), "#e74c3c".to_string())]),
+        });
+        let style = style_document("s", &presentation, Some(&base), &vertices, &[]).unwrap();
+        let mappings = style["mappings"].as_array().unwrap();
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(mappings[0]["visualProperty"], "NODE_LABEL");
+        assert_eq!(mappings[1]["mappingType"], "discrete");
+
+        // Directed graphs get arrows on top of the defaults of the base
+        presentation.directed = Some(true);
+        let style = style_document("s", &presentation, Some(&base), &vertices, &[]).unwrap();
+        assert_eq!(
+            style["defaults"],
+            json!([
+                { "visualProperty": "NODE_SHAPE", "value": "ELLIPSE" },
+                { "visualProperty": "EDGE_TARGET_ARROW_SHAPE", "value": "DELTA" }
+            ])
+        );
+        presentation.node_color = None;
+        presentation.directed = Some(false);
+        let style = style_document("s", &presentation, None, &vertices, &[]).unwrap();
+        assert_eq!(style["defaults"][0]["value"], "NONE");
+
+        let xml = r#"<vizmap id="VizMap"><visualStyle name="Team standard"><network/></visualStyle></vizmap>"#;
+        assert_eq!(xml_style_name(xml).as_deref(), Some("Team standard"));
+        assert_eq!(xml_style_name("<vizmap/>"), None);
+    }
+
+    #[test]
+    fn test_network_name() {
+        let mut config = CytoscapeConfig::default();
+        let bind_vars = HashMap::from([
+            ("name".to_string(), json!("Alice")),
+            ("minAge".to_string(), json!(30)),
+        ]);
+        let name = config.network_name("Get Users", &bind_vars);
+        assert!(name.starts_with("Get Users 20"), "{}", name);
+        assert!(name.ends_with(" (minAge=30, name=Alice)"), "{}", name);
+        assert!(!config.network_name("All", &HashMap::new()).contains('('));
+
+        config.network_name = "{query}: {params}".to_string();
+        assert_eq!(
+            config.network_name("Get Users", &bind_vars),
+            "Get Users: minAge=30, name=Alice"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_append_elements_skips_known_ids() {
+        use std::sync::{Arc, Mutex};
+        use warp::Filter;
+
+        // A CyREST stand-in whose network already contains people/a
+        let updates = Arc::new(Mutex::new(Vec::<Value>::new()));
+        let recorded = Arc::clone(&updates);
+        let rows = warp::get()
+            .and(warp::path!(
+                "v1" / "networks" / i64 / "tables" / String / "rows"
+            ))
+            .map(|_, table: String| {
+                warp::reply::json(&if table == "defaultnode" {
+                    json!([{ "SUID": 1, "arango_id": "people/a" }])
+                } else {
+                    json!([])
+                })
+            });
+        let nodes = warp::post()
+            .and(warp::path!("v1" / "networks" / i64 / "nodes"))
+            .and(warp::body::json())
+            .map(|_, names: Vec<String>| {
+                let created: Vec<Value> = names
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| json!({ "name": name, "SUID": 100 + i }))
+                    .collect();
+                warp::reply::json(&created)
+            });
+        let edges = warp::post()
+            .and(warp::path!("v1" / "networks" / i64 / "edges"))
+            .and(warp::body::json())
+            .map(|_, requested: Vec<Value>| {
+                let created: Vec<Value> = requested
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| json!({ "SUID": 200 + i, "source": e["source"], "target": e["target"] }))
+                    .collect();
+                warp::reply::json(&created)
+            });
+        let tables = warp::put()
+            .and(warp::path!("v1" / "networks" / i64 / "tables" / String))
+            .and(warp::body::json())
+            .map(move |_, _: String, body: Value| {
+                recorded.lock().unwrap().push(body);
+                warp::reply()
+            });
+        let (addr, server) =
+            warp::serve(rows.or(nodes).or(edges).or(tables)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let node = |id: &str| json!({ "data": { "id": id, "arango_id": id, "name": id } });
+        let edge = |id: &str, from: &str, to: &str| json!({ "data": { "id": id, "arango_id": id, "source": from, "target": to } });
+        let (added_nodes, added_edges) = append_elements(
+            &Client::new(),
+            &format!("http://{}/v1", addr),
+            7,
+            1,
+            &[node("people/a"), node("people/b"), node("people/b")],
+            &[
+                edge("knows/1", "people/a", "people/b"),
+                edge("knows/2", "people/b", "people/unknown"),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!((added_nodes, added_edges), (1, 1));
+
+        let updates = updates.lock().unwrap();
+        assert_eq!(
+            updates[0]["data"],
+            json!([{ "SUID": 100, "arango_id": "people/b", "name": "people/b" }])
+        );
+        assert_eq!(
+            updates[1]["data"],
+            json!([{ "SUID": 200, "arango_id": "knows/1" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_layout_parameters() {
+        use std::sync::{Arc, Mutex};
+        use warp::Filter;
+
+        let recorded = Arc::new(Mutex::new(Vec::<Value>::new()));
+        let set = Arc::clone(&recorded);
+        let list = warp::get()
+            .and(warp::path!(
+                "v1" / "apply" / "layouts" / String / "parameters"
+            ))
+            .map(|_| {
+                warp::reply::json(&json!([
+                    { "name": "numIterations", "type": "int", "value": 100 },
+                    { "name": "defaultSpringLength", "type": "double", "value": 80.0 }
+                ]))
+            });
+        let put = warp::put()
+            .and(warp::path!(
+                "v1" / "apply" / "layouts" / String / "parameters"
+            ))
+            .and(warp::body::json())
+            .map(move |_, body: Value| {
+                set.lock().unwrap().push(body);
+                warp::reply()
+            });
+        let (addr, server) = warp::serve(list.or(put)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let base_url = format!("http://{}/v1", addr);
+
+        let mut parameters = Map::new();
+        parameters.insert("numIterations".to_string(), layout_parameter_value(" 500 "));
+        set_layout_parameters(&Client::new(), &base_url, "force-directed", &parameters)
+            .await
+            .unwrap();
+        assert_eq!(
+            recorded.lock().unwrap()[0],
+            json!([{ "name": "numIterations", "value": 500 }])
+        );
+
+        parameters.insert("nodeRepulsion".to_string(), layout_parameter_value("2.5"));
+        let error = set_layout_parameters(&Client::new(), &base_url, "force-directed", &parameters)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("no parameter 'nodeRepulsion'"));
+        assert_eq!(recorded.lock().unwrap().len(), 1);
+
+        assert_eq!(layout_parameter_value("2.5"), json!(2.5));
+        assert_eq!(layout_parameter_value("true"), json!(true));
+        assert_eq!(layout_parameter_value("fast"), json!("fast"));
+    }
+
+    #[tokio::test]
+    async fn test_export_image() {
+        use warp::Filter;
+
+        let views = warp::get()
+            .and(warp::path!("v1" / "networks" / i64 / "views" / String))
+            .map(|network: i64, file: String| {
+                let status = if network == 7 {
+                    warp::http::StatusCode::OK
+                } else {
+                    warp::http::StatusCode::NOT_FOUND
+                };
+                warp::reply::with_status(file, status)
+            });
+        let (addr, server) = warp::serve(views).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let config = CytoscapeConfig {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            ..CytoscapeConfig::default()
+        };
+        let image = export_image(&config, 7, ImageFormat::Svg).await.unwrap();
+        assert_eq!(image, b"first.svg");
+        assert!(matches!(
+            export_image(&config, 8, ImageFormat::Png).await,
+            Err(Error::Cytoscape(_))
+        ));
+        assert_eq!(ImageFormat::from_name("pdf"), Some(ImageFormat::Pdf));
+        assert_eq!(ImageFormat::from_name("jpeg"), None);
+    }
+
+    #[test]
+    fn test_networks_to_delete() {
+        assert_eq!(networks_to_delete(vec![3, 9, 5, 7], 0, &[9]), vec![7, 5, 3]);
+        assert_eq!(networks_to_delete(vec![3, 9, 5, 7], 2, &[9]), vec![3]);
+        assert_eq!(networks_to_delete(vec![3, 9, 5, 7], 0, &[9, 7]), vec![5, 3]);
+        assert!(networks_to_delete(vec![3], 1, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_presentation() {
+        let config: CytoscapeConfig = serde_json::from_value(json!({
+            "layout": "grid",
+            "layout_parameters": {
+                "force-directed": { "numIterations": 100, "defaultSpringLength": 80 }
+            }
+        }))
+        .unwrap();
+        let options: GraphOptions = serde_json::from_value(json!({
+            "layout": "force-directed",
+            "layout_parameters": { "force-directed": { "numIterations": 500 } }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            config.presentation(&GraphOptions::default(), None).layout,
+            "grid"
+        );
+        let presentation = config.presentation(&options, None);
+        assert_eq!(presentation.layout, "force-directed");
+        assert_eq!(presentation.layout_parameters["numIterations"], 500);
+        assert_eq!(presentation.layout_parameters["defaultSpringLength"], 80);
+
+        let presentation = config.presentation(&options, Some("circular"));
+        assert_eq!(presentation.layout, "circular");
+        assert!(presentation.layout_parameters.is_empty());
+    }
+
+    #[test]
+    fn test_typed_columns() {
+        let documents = [
+            json!({
+                "_id": "people/a", "age": 20, "score": 1, "tags": ["x", "y"],
+                "address": { "city": "Köln", "geo": { "lat": 50.9 } }, "ids": [1, 2]
+            }),
+            json!({
+                "_id": "people/b", "age": 30, "score": 2.5, "tags": "z",
+                "visits": 5_000_000_000_i64, "ids": [], "history": [{ "year": 2020 }]
+            }),
+        ];
+        let mut rows: Vec<Map<String, Value>> = documents
+            .iter()
+            .map(|d| document_attributes(d.as_object().unwrap(), NestedAttributes::Flatten, 1))
+            .collect();
+        // Deeper objects and lists of objects are JSON
+        assert_eq!(rows[0]["address.city"], "Köln");
+        assert_eq!(rows[0]["address.geo"], r#"{"lat":50.9}"#);
+        assert_eq!(rows[1]["history"], r#"[{"year":2020}]"#);
+        assert!(!rows[0].contains_key("_id"));
+
+        let columns = typed_columns(&mut rows);
+        let column = |element, list| ColumnType { element, list };
+        assert_eq!(columns["age"], column("Integer", false));
+        assert_eq!(columns["visits"], column("Long", false));
+        assert_eq!(columns["score"], column("Double", false));
+        assert_eq!(columns["ids"], column("Integer", true));
+        // A list and a single value only fit together as strings
+        assert_eq!(columns["tags"], column("String", false));
+        assert_eq!(rows[0]["tags"], r#"["x","y"]"#);
+        assert_eq!(rows[0]["score"].to_string(), "1.0");
+
+        let json =
+            document_attributes(documents[0].as_object().unwrap(), NestedAttributes::Json, 3);
+        assert_eq!(json["address"], r#"{"city":"Köln","geo":{"lat":50.9}}"#);
+        assert_eq!(json["ids"], json!([1, 2]));
+    }
+
+    #[test]
+    fn test_vertex_groups() {
+        let vertices = [
+            json!({ "_id": "people/a", "city": "Bonn" }),
+            json!({ "_id": "people/b", "city": "Köln" }),
+            json!({ "_id": "people/c", "city": "Bonn" }),
+            json!({ "_id": "people/d", "city": null }),
+            json!({ "_id": "people/e", "city": 7 }),
+        ];
+        let groups = vertex_groups(&vertices, "city");
+        assert_eq!(
+            groups,
+            BTreeMap::from([
+                ("7".to_string(), vec!["people/e".to_string()]),
+                (
+                    "Bonn".to_string(),
+                    vec!["people/a".to_string(), "people/c".to_string()]
+                ),
+                ("Köln".to_string(), vec!["people/b".to_string()]),
+            ])
+        );
+        assert!(vertex_groups(&vertices, "country").is_empty());
+        assert_eq!(
+            vertex_groups(&vertices, COLLECTION_COLUMN)["people"].len(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_split_by_relation() {
+        let vertices = [
+            json!({ "_id": "people/a" }),
+            json!({ "_id": "people/b" }),
+            json!({ "_id": "cities/c" }),
+            json!({ "_id": "people/alone" }),
+        ];
+        let edges = [
+            json!({ "_id": "knows/1", "_from": "people/a", "_to": "people/b" }),
+            json!({ "_id": "lives_in/1", "_from": "people/a", "_to": "cities/c" }),
+            json!({ "_from": "people/b", "_to": "cities/c" }),
+        ];
+        let groups = split_by_relation(&vertices, &edges);
+        let ids = |documents: &[Value]| -> Vec<String> {
+            documents
+                .iter()
+                .map(|d| d["_id"].as_str().unwrap_or("-").to_string())
+                .collect()
+        };
+        assert_eq!(
+            groups.keys().collect::<Vec<_>>(),
+            ["knows", "lives_in", "people"]
+        );
+        assert_eq!(ids(&groups["knows"].0), ["people/a", "people/b"]);
+        assert_eq!(ids(&groups["lives_in"].0), ["people/a", "cities/c"]);
+        assert_eq!(ids(&groups["people"].1), ["-"]);
+
+        let edges = with_relations(&edges);
+        assert_eq!(edges[1]["relation"], "lives_in");
+        let ColorMapping::Discrete { colors, .. } = ColorMapping::by_relation(&edges) else {
+            panic!("by_relation is a discrete mapping");
+        };
+        assert_eq!(colors.len(), 3);
+    }
+
     #[tokio::test]
     async fn test_cytoscape_integration() {
         let vertices = json!([
@@ -231,7 +2177,9 @@ mod tests {
         ]);
 
         // Note: This test will only work if Cytoscape is running with CyREST on port 1234
-        match send_to_cytoscape(&vertices, &edges).await {
+        let config = CytoscapeConfig::default();
+        let presentation = config.presentation(&GraphOptions::default(), None);
+        match send_to_cytoscape(&config, &presentation, &vertices, &edges).await {
             Ok(_) => println!("Successfully sent graph to Cytoscape"),
             Err(e) => println!("Error sending graph to Cytoscape: {}", e),
         }
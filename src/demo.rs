@@ -0,0 +1,295 @@
+//! Demo mode: a small in-process stand-in for ArangoDB that answers a fixed
+//! set of queries from bundled sample data (a social graph of people and a
+//! product list), so the tool can be tried without a database.
+
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use tracing::info;
+use warp::Filter;
+
+use crate::config::Configuration;
+use crate::error::{Error, Result};
+
+const ALL_PEOPLE: &str = "FOR p IN people RETURN p";
+const PEOPLE_BY_AGE: &str = "FOR p IN people FILTER p.age >= @minAge SORT p.age RETURN p";
+const PEOPLE_PER_CITY: &str =
+    "FOR p IN people COLLECT city = p.city WITH COUNT INTO count RETURN { city, count }";
+const PRODUCTS_BY_CATEGORY: &str =
+    "FOR p IN products FILTER p.category == @category SORT p.price RETURN p";
+const FRIENDSHIP_GRAPH: &str =
+    "FOR x IN UNION((FOR p IN people RETURN p), (FOR k IN knows RETURN k)) RETURN x";
+
+fn people() -> Vec<Value> {
+    [
+        ("alice", "Alice", 34, "Cologne"),
+        ("bob", "Bob", 27, "Berlin"),
+        ("carol", "Carol", 45, "Cologne"),
+        ("dave", "Dave", 19, "Munich"),
+        ("erin", "Erin", 52, "Berlin"),
+        ("frank", "Frank", 38, "Hamburg"),
+        ("grace", "Grace", 23, "Munich"),
+        ("heidi", "Heidi", 31, "Cologne"),
+    ]
+    .iter()
+    .map(|(key, name, age, city)| {
+        json!({
+            "_key": key,
+            "_id": format!("people/{}", key),
+            "name": name,
+            "age": age,
+            "city": city,
+        })
+    })
+    .collect()
+}
+
+fn knows() -> Vec<Value> {
+    [
+        ("alice", "bob", 2015),
+        ("alice", "carol", 2009),
+        ("bob", "dave", 2021),
+        ("carol", "erin", 1998),
+        ("erin", "frank", 2012),
+        ("frank", "alice", 2018),
+        ("grace", "dave", 2022),
+        ("heidi", "carol", 2017),
+        ("heidi", "grace", 2020),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, (from, to, since))| {
+        json!({
+            "_key": i.to_string(),
+            "_id": format!("knows/{}", i),
+            "_from": format!("people/{}", from),
+            "_to": format!("people/{}", to),
+            "since": since,
+        })
+    })
+    .collect()
+}
+
+fn products() -> Vec<Value> {
+    [
+        ("Laptop", "electronics", 1199.0),
+        ("Headphones", "electronics", 89.9),
+        ("Monitor", "electronics", 249.0),
+        ("Desk", "furniture", 320.0),
+        ("Chair", "furniture", 145.5),
+        ("Notebook", "office", 3.5),
+        ("Pen", "office", 1.2),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, (name, category, price))| {
+        json!({
+            "_key": i.to_string(),
+            "_id": format!("products/{}", i),
+            "name": name,
+            "category": category,
+            "price": price,
+        })
+    })
+    .collect()
+}
+
+/// Answers one of the demo queries, or `None` for any other query text.
+fn answer(query: &str, bind_vars: &Map<String, Value>) -> Option<Vec<Value>> {
+    let results = match query {
+        ALL_PEOPLE => people(),
+        PEOPLE_BY_AGE => {
+            let min_age = bind_vars.get("minAge").and_then(Value::as_f64)?;
+            let mut matching: Vec<Value> = people()
+                .into_iter()
+                .filter(|p| p["age"].as_f64().unwrap_or(0.0) >= min_age)
+                .collect();
+            matching.sort_by_key(|p| p["age"].as_i64());
+            matching
+        }
+        PEOPLE_PER_CITY => {
+            let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+            for p in people() {
+                *counts.entry(p["city"].as_str()?.to_string()).or_default() += 1;
+            }
+            counts
+                .into_iter()
+                .map(|(city, count)| json!({ "city": city, "count": count }))
+                .collect()
+        }
+        PRODUCTS_BY_CATEGORY => {
+            let category = bind_vars.get("category").and_then(Value::as_str)?;
+            products()
+                .into_iter()
+                .filter(|p| p["category"] == category)
+                .collect()
+        }
+        FRIENDSHIP_GRAPH => people().into_iter().chain(knows()).collect(),
+        _ => return None,
+    };
+    Some(results)
+}
+
+/// Builds the cursor API response for a query request.
+fn cursor_response(request: &Value) -> (Value, warp::http::StatusCode) {
+    let query = request["query"].as_str().unwrap_or_default();
+    let empty = Map::new();
+    let bind_vars = request["bindVars"].as_object().unwrap_or(&empty);
+    match answer(query, bind_vars) {
+        Some(results) => (
+            json!({
+                "error": false,
+                "code": 201,
+                "result": results,
+                "hasMore": false,
+                "extra": {
+                    "stats": {
+                        "scannedFull": results.len(),
+                        "filtered": 0,
+                        "executionTime": 0.0001,
+                    }
+                }
+            }),
+            warp::http::StatusCode::CREATED,
+        ),
+        None => (
+            json!({
+                "error": true,
+                "code": 400,
+                "errorNum": 1501,
+                "errorMessage": "demo mode only answers the bundled demo queries",
+            }),
+            warp::http::StatusCode::BAD_REQUEST,
+        ),
+    }
+}
+
+/// The configuration of the demo, pointing at the given endpoint.
+pub fn configuration(endpoint: &str) -> Result<Configuration> {
+    let config = json!({
+        "arangodb_endpoint": endpoint,
+        "username": "demo",
+        "password": "demo",
+        "queries": [
+            {
+                "name": "All people",
+                "description": "Every person of the sample social network",
+                "query": ALL_PEOPLE,
+                "parameters": []
+            },
+            {
+                "name": "People by minimum age",
+                "description": "People at least the given age, youngest first",
+                "query": PEOPLE_BY_AGE,
+                "parameters": [{ "name": "minAge", "parameter_type": "number" }],
+                "renderer": "table",
+                "tests": [
+                    {
+                        "name": "adults over 40",
+                        "bind_vars": { "minAge": 40 },
+                        "rows": 2,
+                        "invariants": ["age >= 40"]
+                    }
+                ]
+            },
+            {
+                "name": "People per city",
+                "description": "Number of people living in each city",
+                "query": PEOPLE_PER_CITY,
+                "parameters": [],
+                "renderer": "chart"
+            },
+            {
+                "name": "Products by category",
+                "description": "Products of a category (electronics, furniture, office)",
+                "query": PRODUCTS_BY_CATEGORY,
+                "parameters": [{ "name": "category", "parameter_type": "string" }]
+            },
+            {
+                "name": "Friendship graph",
+                "description": "People and who knows whom, as a graph",
+                "query": FRIENDSHIP_GRAPH,
+                "parameters": [],
+                "renderer": "graph",
+                "cytoscape": {
+                    "node_color": {
+                        "type": "discrete",
+                        "attribute": "city",
+                        "colors": {
+                            "Berlin": "#3498db",
+                            "Cologne": "#e74c3c",
+                            "Hamburg": "#2ecc71",
+                            "Munich": "#f1c40f"
+                        }
+                    },
+                    "edge_width": { "attribute": "since", "min_width": 6.0, "max_width": 1.0 }
+                }
+            }
+        ]
+    });
+    serde_json::from_value(config).map_err(|e| Error::Config(e.to_string()))
+}
+
+/// Starts the demo backend on a free local port and returns a configuration
+/// that uses it. The backend runs until the process exits.
+pub async fn start() -> Result<Configuration> {
+    let cursor = warp::post()
+        .and(warp::path!("_api" / "cursor"))
+        .and(warp::body::json())
+        .map(|request: Value| {
+            let (body, status) = cursor_response(&request);
+            warp::reply::with_status(warp::reply::json(&body), status)
+        });
+    let version = warp::get().and(warp::path!("_api" / "version")).map(|| {
+        warp::reply::json(&json!({
+            "server": "arango",
+            "version": "demo",
+            "license": "community",
+            "details": { "role": "SINGLE", "mode": "server" }
+        }))
+    });
+
+    // Demo queries finish at once, so none is ever running or slow
+    let running = warp::get()
+        .and(warp::path!("_api" / "query" / "current"))
+        .or(warp::get().and(warp::path!("_api" / "query" / "slow")))
+        .unify()
+        .map(|| warp::reply::json(&json!([])));
+
+    let (addr, server) =
+        warp::serve(cursor.or(version).or(running)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    info!(%addr, "Demo backend started");
+
+    configuration(&format!("http://{}/", addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demo_queries_are_answered() {
+        let config = configuration("http://localhost/").unwrap();
+        let bind_vars: Map<String, Value> = [
+            ("minAge".to_string(), json!(30)),
+            ("category".to_string(), json!("office")),
+        ]
+        .into_iter()
+        .collect();
+        for query in &config.queries {
+            let results = answer(&query.query, &bind_vars).unwrap();
+            assert!(!results.is_empty(), "{}", query.name);
+        }
+
+        let older = answer(PEOPLE_BY_AGE, &bind_vars).unwrap();
+        assert_eq!(older.len(), 5);
+        assert_eq!(older[0]["name"], "Heidi");
+        assert!(answer("RETURN 1", &bind_vars).is_none());
+    }
+
+    #[test]
+    fn test_demo_graph_is_a_graph() {
+        let results = answer(FRIENDSHIP_GRAPH, &Map::new()).unwrap();
+        assert!(crate::graph_analyzer::is_graph(&results).is_ok());
+    }
+}
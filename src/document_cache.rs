@@ -0,0 +1,173 @@
+//! Documents of stub vertices fetched before, see
+//! `graph_analyzer::fetch_stubs`. Hub vertices show up as stubs in one
+//! result after another, so their documents are kept by `_id` for a while
+//! instead of being looked up again every time.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DocumentCacheConfig {
+    #[serde(default = "default_max_documents")]
+    pub max_documents: usize, // The least recently used document is dropped beyond this; 0 disables
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64, // Documents fetched longer ago are fetched again
+}
+
+fn default_max_documents() -> usize {
+    10_000
+}
+
+fn default_ttl_secs() -> u64 {
+    300
+}
+
+impl Default for DocumentCacheConfig {
+    fn default() -> Self {
+        DocumentCacheConfig {
+            max_documents: default_max_documents(),
+            ttl_secs: default_ttl_secs(),
+        }
+    }
+}
+
+struct CachedDocument {
+    document: Value,
+    fetched_at: Instant,
+    last_used: Instant,
+}
+
+/// Documents by `_id`, of one database, kept in memory only.
+pub struct DocumentCache {
+    config: DocumentCacheConfig,
+    documents: Mutex<HashMap<String, CachedDocument>>,
+}
+
+impl DocumentCache {
+    pub fn new(config: &DocumentCacheConfig) -> Self {
+        DocumentCache {
+            config: config.clone(),
+            documents: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cached documents of `ids`, and the ids that have to be fetched
+    /// because they are not cached or have expired.
+    pub fn lookup(&self, ids: &[String]) -> (Vec<Value>, Vec<String>) {
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        let mut documents = self.documents.lock().unwrap();
+        let mut found = Vec::new();
+        let mut missing = Vec::new();
+        for id in ids {
+            match documents.get_mut(id) {
+                Some(cached) if cached.fetched_at.elapsed() < ttl => {
+                    cached.last_used = Instant::now();
+                    found.push(cached.document.clone());
+                }
+                Some(_) => {
+                    documents.remove(id);
+                    missing.push(id.clone());
+                }
+                None => missing.push(id.clone()),
+            }
+        }
+        (found, missing)
+    }
+
+    /// Keeps fetched documents, dropping the least recently used ones to
+    /// make room.
+    pub fn insert(&self, fetched: &[Value]) {
+        if self.config.max_documents == 0 {
+            return;
+        }
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        let mut documents = self.documents.lock().unwrap();
+        documents.retain(|_, cached| cached.fetched_at.elapsed() < ttl);
+        let now = Instant::now();
+        for document in fetched {
+            let Some(id) = document["_id"].as_str() else {
+                continue;
+            };
+            documents.insert(
+                id.to_string(),
+                CachedDocument {
+                    document: document.clone(),
+                    fetched_at: now,
+                    last_used: now,
+                },
+            );
+        }
+        let excess = documents.len().saturating_sub(self.config.max_documents);
+        if excess > 0 {
+            let mut by_use: Vec<(Instant, String)> = documents
+                .iter()
+                .map(|(id, cached)| (cached.last_used, id.clone()))
+                .collect();
+            by_use.sort();
+            for (_, id) in by_use.into_iter().take(excess) {
+                documents.remove(&id);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cache() {
+        let cache = DocumentCache::new(&DocumentCacheConfig {
+            max_documents: 2,
+            ..DocumentCacheConfig::default()
+        });
+        let (found, missing) = cache.lookup(&ids(&["people/a", "people/b"]));
+        assert!(found.is_empty());
+        assert_eq!(missing, ["people/a", "people/b"]);
+
+        cache.insert(&[
+            json!({ "_id": "people/a", "name": "Alice" }),
+            json!({ "_id": "people/b", "name": "Bob" }),
+        ]);
+        let (found, missing) = cache.lookup(&ids(&["people/a", "people/c"]));
+        assert_eq!(found, [json!({ "_id": "people/a", "name": "Alice" })]);
+        assert_eq!(missing, ["people/c"]);
+
+        // The least recently used document makes room
+        cache.insert(&[json!({ "_id": "people/c" })]);
+        assert_eq!(cache.len(), 2);
+        let (_, missing) = cache.lookup(&ids(&["people/a", "people/b", "people/c"]));
+        assert_eq!(missing, ["people/b"]);
+
+        // Expired documents are fetched again
+        let expiring = DocumentCache::new(&DocumentCacheConfig {
+            ttl_secs: 0,
+            ..DocumentCacheConfig::default()
+        });
+        expiring.insert(&[json!({ "_id": "people/a" })]);
+        assert_eq!(expiring.lookup(&ids(&["people/a"])).1, ["people/a"]);
+
+        let disabled = DocumentCache::new(&DocumentCacheConfig {
+            max_documents: 0,
+            ..DocumentCacheConfig::default()
+        });
+        disabled.insert(&[json!({ "_id": "people/a" })]);
+        assert!(disabled.is_empty());
+    }
+}
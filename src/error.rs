@@ -0,0 +1,133 @@
+//! The error type used throughout the library.
+
+use warp::http::StatusCode;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The configuration file could not be read or is invalid.
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    /// ArangoDB answered with an error document.
+    #[error("ArangoDB error {error_num}: {message}")]
+    Arango {
+        code: u16,
+        error_num: i64,
+        message: String,
+    },
+
+    /// ArangoDB could not be reached or sent an unreadable response.
+    #[error("Request to ArangoDB failed: {0}")]
+    Connection(#[from] reqwest::Error),
+
+    /// A template could not be rendered.
+    #[error("Failed to render page: {0}")]
+    Render(#[from] tera::Error),
+
+    /// A result could not be processed as a graph.
+    #[error("Graph error: {0}")]
+    Graph(String),
+
+    /// Talking to Cytoscape failed.
+    #[error("Cytoscape error: {0}")]
+    Cytoscape(String),
+
+    /// Talking to Gephi failed.
+    #[error("Gephi error: {0}")]
+    Gephi(String),
+
+    /// A parameter is missing or cannot be converted to its declared type.
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+
+    /// Reading or writing a local file or stream failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A git command on the query store failed.
+    #[error("Git error: {0}")]
+    Git(String),
+
+    /// A query or other entity does not exist.
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    /// A request body exceeds the configured limit.
+    #[error("Request too large: {0}")]
+    TooLarge(String),
+
+    /// The user may not do this, e.g. belongs to no tenant.
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Builds an `Error::Arango` from an ArangoDB error document.
+    pub fn from_response(response: &serde_json::Value) -> Self {
+        Error::Arango {
+            code: response["code"].as_u64().unwrap_or(500) as u16,
+            error_num: response["errorNum"].as_i64().unwrap_or(0),
+            message: response["errorMessage"]
+                .as_str()
+                .unwrap_or("unknown error")
+                .to_string(),
+        }
+    }
+
+    /// The HTTP status the web interface answers with for this error.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::InvalidParameter(_) => StatusCode::BAD_REQUEST,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::Forbidden(_) => StatusCode::FORBIDDEN,
+            Error::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            // Errors in the AQL itself (parse errors, unknown collections, ...)
+            Error::Arango { code, .. } if (400..500).contains(code) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            // Hangs of the network, told apart from slow or failing queries
+            Error::Connection(e) if e.is_timeout() => StatusCode::GATEWAY_TIMEOUT,
+            Error::Arango { .. } | Error::Connection(_) | Error::Cytoscape(_) | Error::Gephi(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            Error::Config(_)
+            | Error::Render(_)
+            | Error::Graph(_)
+            | Error::Io(_)
+            | Error::Git(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl warp::reject::Reject for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_status_mapping() {
+        let parse_error = Error::from_response(&json!({
+            "error": true,
+            "code": 400,
+            "errorNum": 1501,
+            "errorMessage": "syntax error"
+        }));
+        assert_eq!(parse_error.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(parse_error.to_string(), "ArangoDB error 1501: syntax error");
+
+        let server_error = Error::from_response(&json!({"code": 503, "errorNum": 1}));
+        assert_eq!(server_error.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            Error::NotFound("query 7".to_string()).status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            Error::TooLarge("2 MiB".to_string()).status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+}
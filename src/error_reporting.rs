@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+use crate::error::{Error, Result};
+use crate::proxy;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ErrorReportingConfig {
+    #[serde(default)]
+    pub sentry_dsn: Option<String>, // e.g. "https://<key>@sentry.example.com/<project>"
+    #[serde(default)]
+    pub webhook_url: Option<String>, // Receives the same event as plain JSON
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+/// How many reported errors are kept for the status page.
+const RECENT_ERRORS: usize = 20;
+
+/// An error as kept for the status page.
+#[derive(Debug, Serialize, Clone)]
+pub struct RecentError {
+    pub timestamp: DateTime<Utc>,
+    pub message: String,
+    pub context: String, // e.g. "route=/execute/3, query=Get Users"
+}
+
+struct SentryTarget {
+    store_url: String,
+    auth_header: String,
+}
+
+/// Reports errors and panics to a Sentry-compatible endpoint and/or a generic
+/// webhook. Reporting happens in the background and never fails the caller.
+pub struct ErrorReporter {
+    config: ErrorReportingConfig,
+    sentry: Option<SentryTarget>,
+    client: reqwest::Client,
+    recent: Mutex<VecDeque<RecentError>>,
+}
+
+/// Splits a DSN of the form `scheme://key@host[:port]/project` into the
+/// store endpoint and the authentication header value.
+fn parse_dsn(dsn: &str) -> Result<SentryTarget> {
+    let invalid = |reason: &str| Error::Config(format!("Invalid Sentry DSN: {}", reason));
+    let (scheme, rest) = dsn
+        .split_once("://")
+        .ok_or_else(|| invalid("lacks a scheme"))?;
+    let (key, rest) = rest
+        .split_once('@')
+        .ok_or_else(|| invalid("lacks a public key"))?;
+    let key = key.split(':').next().unwrap_or(key);
+    let (host, project) = rest
+        .rsplit_once('/')
+        .ok_or_else(|| invalid("lacks a project id"))?;
+    if key.is_empty() || host.is_empty() || project.is_empty() {
+        return Err(invalid("incomplete"));
+    }
+
+    Ok(SentryTarget {
+        store_url: format!("{}://{}/api/{}/store/", scheme, host, project),
+        auth_header: format!(
+            "Sentry sentry_version=7, sentry_key={}, sentry_client=adb_query_runner/{}",
+            key,
+            env!("CARGO_PKG_VERSION")
+        ),
+    })
+}
+
+impl ErrorReporter {
+    pub fn new(config: ErrorReportingConfig) -> Result<Self> {
+        let sentry = match &config.sentry_dsn {
+            Some(dsn) => Some(parse_dsn(dsn)?),
+            None => None,
+        };
+        Ok(ErrorReporter {
+            config,
+            sentry,
+            client: proxy::client(),
+            recent: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sentry.is_some() || self.config.webhook_url.is_some()
+    }
+
+    fn build_event(&self, message: &str, context: &[(&str, &str)]) -> Value {
+        let tags: Map<String, Value> = context
+            .iter()
+            .map(|(k, v)| (k.to_string(), json!(v)))
+            .collect();
+        json!({
+            "event_id": uuid::Uuid::new_v4().simple().to_string(),
+            "timestamp": Utc::now().to_rfc3339(),
+            "level": "error",
+            "platform": "other",
+            "logger": "adb_query_runner",
+            "release": env!("CARGO_PKG_VERSION"),
+            "environment": self.config.environment,
+            "message": { "formatted": message },
+            "tags": tags,
+        })
+    }
+
+    /// The most recently reported errors, newest first. They are kept even
+    /// if reporting is not enabled.
+    pub fn recent(&self) -> Vec<RecentError> {
+        self.recent.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    /// Sends an error event with the given context (e.g. route, query name)
+    /// without waiting for delivery.
+    pub fn report(&self, message: &str, context: &[(&str, &str)]) {
+        {
+            let mut recent = self.recent.lock().unwrap();
+            recent.push_back(RecentError {
+                timestamp: Utc::now(),
+                message: message.to_string(),
+                context: context
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k, v))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            });
+            while recent.len() > RECENT_ERRORS {
+                recent.pop_front();
+            }
+        }
+        if !self.is_enabled() {
+            return;
+        }
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(h) => h,
+            Err(_) => return,
+        };
+
+        let event = self.build_event(message, context);
+        let client = self.client.clone();
+        let sentry = self
+            .sentry
+            .as_ref()
+            .map(|s| (s.store_url.clone(), s.auth_header.clone()));
+        let webhook_url = self.config.webhook_url.clone();
+
+        handle.spawn(async move {
+            if let Some((url, auth)) = sentry {
+                let result = client
+                    .post(&url)
+                    .header("X-Sentry-Auth", auth)
+                    .json(&event)
+                    .send()
+                    .await;
+                if let Err(e) = result {
+                    warn!("Failed to report error to Sentry: {}", e);
+                }
+            }
+            if let Some(url) = webhook_url {
+                if let Err(e) = client.post(&url).json(&event).send().await {
+                    warn!("Failed to report error to webhook: {}", e);
+                }
+            }
+        });
+    }
+}
+
+/// Chains a panic hook that reports every panic before running the default
+/// hook.
+pub fn install_panic_hook(reporter: Arc<ErrorReporter>) {
+    if !reporter.is_enabled() {
+        return;
+    }
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = if let Some(s) = info.payload().downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = info.payload().downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "panic".to_string()
+        };
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+        let thread = std::thread::current();
+        reporter.report(
+            &format!("Panic: {}", message),
+            &[
+                ("location", &location),
+                ("thread", thread.name().unwrap_or("unnamed")),
+            ],
+        );
+        default_hook(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dsn() {
+        let target = parse_dsn("https://abc123@sentry.example.com:9000/42").unwrap();
+        assert_eq!(
+            target.store_url,
+            "https://sentry.example.com:9000/api/42/store/"
+        );
+        assert!(target.auth_header.contains("sentry_key=abc123"));
+
+        assert!(parse_dsn("sentry.example.com/42").is_err());
+        assert!(parse_dsn("https://sentry.example.com/42").is_err());
+    }
+
+    #[test]
+    fn test_event_tags() {
+        let reporter = ErrorReporter::new(ErrorReportingConfig::default()).unwrap();
+        assert!(!reporter.is_enabled());
+
+        let event = reporter.build_event("boom", &[("query", "Get Users")]);
+        assert_eq!(event["message"]["formatted"], "boom");
+        assert_eq!(event["tags"]["query"], "Get Users");
+        assert_eq!(event["event_id"].as_str().unwrap().len(), 32);
+    }
+
+    #[test]
+    fn test_recent() {
+        let reporter = ErrorReporter::new(ErrorReportingConfig::default()).unwrap();
+        for i in 0..RECENT_ERRORS + 2 {
+            reporter.report(&format!("error {}", i), &[("route", "/"), ("query", "q")]);
+        }
+        let recent = reporter.recent();
+        assert_eq!(recent.len(), RECENT_ERRORS);
+        assert_eq!(recent[0].message, format!("error {}", RECENT_ERRORS + 1));
+        assert_eq!(recent[0].context, "route=/, query=q");
+    }
+}
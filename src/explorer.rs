@@ -0,0 +1,301 @@
+//! Exploring named graphs without writing AQL: the user picks a graph, a
+//! start vertex, a direction and a depth, and gets the traversal result
+//! shown like that of any other graph query.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::arango::Client;
+use crate::config::QueryDefinition;
+use crate::error::{Error, Result};
+use crate::graph_analyzer::GraphDetection;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExplorerConfig {
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+    #[serde(default = "default_max_results")]
+    pub max_results: u64, // Paths returned by a traversal at most
+}
+
+fn default_max_depth() -> u32 {
+    5
+}
+
+fn default_max_results() -> u64 {
+    10000
+}
+
+impl Default for ExplorerConfig {
+    fn default() -> Self {
+        ExplorerConfig {
+            max_depth: default_max_depth(),
+            max_results: default_max_results(),
+        }
+    }
+}
+
+/// One edge collection of a named graph and the vertex collections it
+/// connects.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EdgeDefinition {
+    pub collection: String,
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+/// A named graph as described by the gharial API.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NamedGraph {
+    pub name: String,
+    #[serde(default)]
+    pub edge_definitions: Vec<EdgeDefinition>,
+    #[serde(default)]
+    pub orphan_collections: Vec<String>,
+}
+
+impl NamedGraph {
+    /// All vertex collections of the graph, sorted.
+    pub fn vertex_collections(&self) -> Vec<String> {
+        let mut collections: Vec<String> = self
+            .edge_definitions
+            .iter()
+            .flat_map(|d| d.from.iter().chain(&d.to))
+            .chain(&self.orphan_collections)
+            .cloned()
+            .collect();
+        collections.sort();
+        collections.dedup();
+        collections
+    }
+}
+
+/// The direction edges are followed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Outbound,
+    Inbound,
+    Any,
+}
+
+impl Direction {
+    pub const ALL: &'static [Direction] =
+        &[Direction::Outbound, Direction::Inbound, Direction::Any];
+
+    /// The AQL keyword, also used as name in forms.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Outbound => "OUTBOUND",
+            Direction::Inbound => "INBOUND",
+            Direction::Any => "ANY",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        Direction::ALL
+            .iter()
+            .copied()
+            .find(|d| d.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Lists the named graphs of the database.
+pub async fn named_graphs(client: &Client) -> Result<Vec<NamedGraph>> {
+    let response: Value = client
+        .request(reqwest::Method::GET, "_api/gharial")
+        .send()
+        .await?
+        .json()
+        .await?;
+    if response["error"].as_bool().unwrap_or(false) {
+        return Err(Error::from_response(&response));
+    }
+    let mut graphs: Vec<NamedGraph> =
+        serde_json::from_value(response["graphs"].clone()).map_err(|e| Error::Arango {
+            code: 500,
+            error_num: 0,
+            message: format!("Unexpected list of graphs: {}", e),
+        })?;
+    graphs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(graphs)
+}
+
+/// Checks the start vertex and depth of a traversal.
+fn check_traversal(config: &ExplorerConfig, start: &str, depth: u32) -> Result<()> {
+    if depth > config.max_depth {
+        return Err(Error::InvalidParameter(format!(
+            "Depth {} is larger than the maximum of {}",
+            depth, config.max_depth
+        )));
+    }
+    if start.split('/').count() != 2 {
+        return Err(Error::InvalidParameter(format!(
+            "Start vertex '{}' is not a document id like 'collection/key'",
+            start
+        )));
+    }
+    Ok(())
+}
+
+/// A traversal of a named graph as a query that runs through the usual
+/// pipeline, with its bind variables. The start vertex is part of the
+/// result even if it has no neighbors.
+pub fn traversal(
+    config: &ExplorerConfig,
+    graph: &str,
+    start: &str,
+    direction: Direction,
+    depth: u32,
+) -> Result<(QueryDefinition, HashMap<String, Value>)> {
+    check_traversal(config, start, depth)?;
+    let query = QueryDefinition {
+        name: format!("Explore {} from {}", graph, start),
+        description: format!("{} traversal of depth {}", direction.name(), depth),
+        query: format!(
+            "FOR v, e IN 0..@depth {} @start GRAPH @graph\n  LIMIT @limit\n  RETURN {{ vertex: v, edge: e }}",
+            direction.name()
+        ),
+        parameters: Vec::new(),
+        cytoscape: Default::default(),
+        graph_renderer: None,
+        renderer: None,
+        allowed_endpoints: Vec::new(),
+        max_concurrent: None,
+        stream: None,
+        aql_options: Default::default(),
+        alternative: None,
+        tests: Vec::new(),
+        graph: GraphDetection {
+            vertex_only: true,
+            ..GraphDetection::default()
+        },
+    };
+    let bind_vars = HashMap::from([
+        ("graph".to_string(), json!(graph)),
+        ("start".to_string(), json!(start)),
+        ("depth".to_string(), json!(depth)),
+        ("limit".to_string(), json!(config.max_results)),
+    ]);
+    Ok((query, bind_vars))
+}
+
+/// What the neighborhood of a vertex is taken from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeSource {
+    Graph(String),            // A named graph
+    Collections(Vec<String>), // Edge collections, as in an anonymous graph
+}
+
+/// The query for the neighbors of a vertex up to `depth` edges away,
+/// without the vertex itself, with its bind variables.
+pub fn neighborhood(
+    config: &ExplorerConfig,
+    start: &str,
+    direction: Direction,
+    depth: u32,
+    source: &EdgeSource,
+) -> Result<(String, HashMap<String, Value>)> {
+    check_traversal(config, start, depth)?;
+    let mut bind_vars = HashMap::from([
+        ("start".to_string(), json!(start)),
+        ("depth".to_string(), json!(depth.max(1))),
+        ("limit".to_string(), json!(config.max_results)),
+    ]);
+    let edges = match source {
+        EdgeSource::Graph(name) => {
+            bind_vars.insert("graph".to_string(), json!(name));
+            "GRAPH @graph".to_string()
+        }
+        EdgeSource::Collections(collections) => {
+            // Collection names cannot be bind variables in this position
+            let valid = |name: &String| {
+                !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            };
+            if collections.is_empty() || !collections.iter().all(valid) {
+                return Err(Error::InvalidParameter(format!(
+                    "Invalid edge collections {:?}",
+                    collections
+                )));
+            }
+            collections
+                .iter()
+                .map(|c| format!("`{}`", c))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    };
+    let query = format!(
+        "FOR v, e IN 1..@depth {} @start {}\n  LIMIT @limit\n  RETURN {{ vertex: v, edge: e }}",
+        direction.name(),
+        edges
+    );
+    Ok((query, bind_vars))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traversal() {
+        let config = ExplorerConfig::default();
+        let (query, bind_vars) =
+            traversal(&config, "social", "users/alice", Direction::Inbound, 2).unwrap();
+        assert!(query
+            .query
+            .starts_with("FOR v, e IN 0..@depth INBOUND @start GRAPH @graph"));
+        assert_eq!(bind_vars["start"], "users/alice");
+        assert_eq!(bind_vars["depth"], 2);
+        assert!(query.graph.vertex_only);
+
+        assert!(traversal(&config, "social", "users/alice", Direction::Any, 6).is_err());
+        assert!(traversal(&config, "social", "alice", Direction::Any, 1).is_err());
+        assert_eq!(Direction::from_name("any"), Some(Direction::Any));
+    }
+
+    #[test]
+    fn test_named_graph() {
+        let graph: NamedGraph = serde_json::from_value(json!({
+            "_key": "social",
+            "name": "social",
+            "edgeDefinitions": [
+                { "collection": "knows", "from": ["users"], "to": ["users", "cities"] }
+            ],
+            "orphanCollections": ["tags"]
+        }))
+        .unwrap();
+        assert_eq!(graph.vertex_collections(), vec!["cities", "tags", "users"]);
+    }
+
+    #[test]
+    fn test_neighborhood() {
+        let config = ExplorerConfig::default();
+        let collections = EdgeSource::Collections(vec!["follows".to_string(), "knows".to_string()]);
+        let (query, bind_vars) =
+            neighborhood(&config, "users/alice", Direction::Any, 1, &collections).unwrap();
+        assert!(query.starts_with("FOR v, e IN 1..@depth ANY @start `follows`, `knows`"));
+        assert!(!bind_vars.contains_key("graph"));
+
+        let graph = EdgeSource::Graph("social".to_string());
+        let (query, bind_vars) =
+            neighborhood(&config, "users/alice", Direction::Outbound, 2, &graph).unwrap();
+        assert!(query.contains("OUTBOUND @start GRAPH @graph"));
+        assert_eq!(bind_vars["graph"], "social");
+
+        let injection = EdgeSource::Collections(vec!["follows RETURN 1 //".to_string()]);
+        assert!(neighborhood(&config, "users/alice", Direction::Any, 1, &injection).is_err());
+        assert!(neighborhood(
+            &config,
+            "users/alice",
+            Direction::Any,
+            1,
+            &EdgeSource::Collections(vec![])
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,627 @@
+//! Exports of graph results into file formats of other graph tools.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Write as _;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::error::Result;
+use crate::output;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportConfig {
+    #[serde(default)]
+    pub timestamp_attribute: Option<String>, // Edge attribute with the time of an edge
+    #[serde(default)]
+    pub graphviz_command: Option<String>, // e.g. "dot", to show small graphs as SVG
+    #[serde(default = "default_svg_max_elements")]
+    pub svg_max_elements: usize, // Larger graphs are not rendered to SVG
+}
+
+fn default_svg_max_elements() -> usize {
+    200
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        ExportConfig {
+            timestamp_attribute: None,
+            graphviz_command: None,
+            svg_max_elements: default_svg_max_elements(),
+        }
+    }
+}
+
+/// The formats graphs can be exported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Gexf,
+    Dot,
+    Jgf, // JSON Graph Format
+    D3,  // `{nodes, links}` with links referring to nodes by index
+    Csv, // nodes.csv and edges.csv in a zip archive
+}
+
+impl ExportFormat {
+    pub const ALL: &'static [ExportFormat] = &[
+        ExportFormat::Gexf,
+        ExportFormat::Dot,
+        ExportFormat::Jgf,
+        ExportFormat::D3,
+        ExportFormat::Csv,
+    ];
+
+    /// The name used in URLs.
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Gexf => "gexf",
+            ExportFormat::Dot => "dot",
+            ExportFormat::Jgf => "jgf",
+            ExportFormat::D3 => "d3",
+            ExportFormat::Csv => "csv",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Gexf => "gexf",
+            ExportFormat::Dot => "dot",
+            ExportFormat::Jgf | ExportFormat::D3 => "json",
+            ExportFormat::Csv => "zip",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.name() == name)
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Gexf => "application/gexf+xml",
+            ExportFormat::Dot => "text/vnd.graphviz",
+            ExportFormat::Jgf | ExportFormat::D3 => "application/json",
+            ExportFormat::Csv => "application/zip",
+        }
+    }
+}
+
+/// Exports a graph in the given format.
+pub fn export(
+    format: ExportFormat,
+    config: &ExportConfig,
+    title: &str,
+    vertices: &[Value],
+    edges: &[Value],
+) -> Result<Vec<u8>> {
+    Ok(match format {
+        ExportFormat::Gexf => to_gexf(
+            title,
+            vertices,
+            edges,
+            config.timestamp_attribute.as_deref(),
+        )
+        .into_bytes(),
+        ExportFormat::Dot => to_dot(title, vertices, edges).into_bytes(),
+        ExportFormat::Jgf => to_json_bytes(&to_jgf(title, vertices, edges)),
+        ExportFormat::D3 => to_json_bytes(&to_d3(vertices, edges)),
+        ExportFormat::Csv => to_csv_zip(vertices, edges)?,
+    })
+}
+
+/// Escapes text for use in XML attributes and content.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A value as plain text: strings without quotes, everything else as JSON.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The label of a vertex: its `name` attribute, else its `_key` or `_id`.
+fn vertex_label(vertex: &Value) -> String {
+    ["name", "label", "_key", "_id"]
+        .iter()
+        .map(|attr| &vertex[*attr])
+        .find(|v| v.is_string() || v.is_number())
+        .map(text)
+        .unwrap_or_default()
+}
+
+/// The GEXF type of an attribute, from the values it has in the graph.
+fn gexf_type<'a>(values: impl Iterator<Item = &'a Value>) -> &'static str {
+    let mut kind = None;
+    for value in values.filter(|v| !v.is_null()) {
+        let this = match value {
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_i64() => "long",
+            Value::Number(_) => "double",
+            _ => "string",
+        };
+        kind = match (kind, this) {
+            (None, this) => Some(this),
+            (Some(a), b) if a == b => Some(a),
+            (Some("long"), "double") | (Some("double"), "long") => Some("double"),
+            _ => return "string",
+        };
+    }
+    kind.unwrap_or("string")
+}
+
+/// The user attributes of some documents with their GEXF types. System
+/// attributes (starting with `_`) are left out.
+fn gexf_attributes(documents: &[Value], skip: Option<&str>) -> BTreeMap<String, &'static str> {
+    let mut names = std::collections::BTreeSet::new();
+    for document in documents {
+        if let Some(obj) = document.as_object() {
+            names.extend(
+                obj.keys()
+                    .filter(|k| !k.starts_with('_') && Some(k.as_str()) != skip)
+                    .cloned(),
+            );
+        }
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            let kind = gexf_type(documents.iter().map(|d| &d[&name]));
+            (name, kind)
+        })
+        .collect()
+}
+
+fn write_attribute_declarations(
+    out: &mut String,
+    class: &str,
+    attributes: &BTreeMap<String, &'static str>,
+) {
+    if attributes.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "    <attributes class=\"{}\">", class);
+    for (name, kind) in attributes {
+        let _ = writeln!(
+            out,
+            "      <attribute id=\"{0}\" title=\"{0}\" type=\"{1}\"/>",
+            xml_escape(name),
+            kind
+        );
+    }
+    out.push_str("    </attributes>\n");
+}
+
+fn write_attribute_values(
+    out: &mut String,
+    document: &Value,
+    attributes: &BTreeMap<String, &'static str>,
+) {
+    let values: Vec<(&String, &Value)> = attributes
+        .keys()
+        .filter_map(|name| Some((name, document.get(name).filter(|v| !v.is_null())?)))
+        .collect();
+    if values.is_empty() {
+        return;
+    }
+    out.push_str("        <attvalues>\n");
+    for (name, value) in values {
+        let _ = writeln!(
+            out,
+            "          <attvalue for=\"{}\" value=\"{}\"/>",
+            xml_escape(name),
+            xml_escape(&text(value))
+        );
+    }
+    out.push_str("        </attvalues>\n");
+}
+
+/// Writes a graph as GEXF 1.3, e.g. for Gephi. With a `timestamp_attribute`
+/// that edges have, the graph is dynamic and each edge starts at its time.
+pub fn to_gexf(
+    title: &str,
+    vertices: &[Value],
+    edges: &[Value],
+    timestamp_attribute: Option<&str>,
+) -> String {
+    let times: Vec<&Value> = timestamp_attribute
+        .map(|attr| {
+            edges
+                .iter()
+                .map(|e| &e[attr])
+                .filter(|v| !v.is_null())
+                .collect()
+        })
+        .unwrap_or_default();
+    let time_format = if times.is_empty() {
+        None
+    } else if times.iter().all(|t| t.is_number()) {
+        Some("double")
+    } else {
+        Some("datetime")
+    };
+
+    let node_attributes = gexf_attributes(vertices, None);
+    let edge_attributes = gexf_attributes(edges, time_format.and(timestamp_attribute));
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n");
+    let _ = writeln!(
+        out,
+        "  <meta>\n    <creator>{}</creator>\n    <description>{}</description>\n  </meta>",
+        env!("CARGO_PKG_NAME"),
+        xml_escape(title)
+    );
+    match time_format {
+        Some(format) => {
+            let _ = writeln!(
+                out,
+                "  <graph defaultedgetype=\"directed\" mode=\"dynamic\" timeformat=\"{}\">",
+                format
+            );
+        }
+        None => out.push_str("  <graph defaultedgetype=\"directed\" mode=\"static\">\n"),
+    }
+    write_attribute_declarations(&mut out, "node", &node_attributes);
+    write_attribute_declarations(&mut out, "edge", &edge_attributes);
+
+    out.push_str("    <nodes>\n");
+    for vertex in vertices {
+        let Some(id) = vertex["_id"].as_str() else {
+            continue;
+        };
+        let _ = writeln!(
+            out,
+            "      <node id=\"{}\" label=\"{}\">",
+            xml_escape(id),
+            xml_escape(&vertex_label(vertex))
+        );
+        write_attribute_values(&mut out, vertex, &node_attributes);
+        out.push_str("      </node>\n");
+    }
+    out.push_str("    </nodes>\n");
+
+    out.push_str("    <edges>\n");
+    for (i, edge) in edges.iter().enumerate() {
+        let (Some(from), Some(to)) = (edge["_from"].as_str(), edge["_to"].as_str()) else {
+            continue;
+        };
+        let id = edge["_id"]
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("edge/{}", i));
+        let start = match (time_format, timestamp_attribute) {
+            (Some(_), Some(attr)) if !edge[attr].is_null() => {
+                format!(" start=\"{}\"", xml_escape(&text(&edge[attr])))
+            }
+            _ => String::new(),
+        };
+        let _ = writeln!(
+            out,
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\"{}>",
+            xml_escape(&id),
+            xml_escape(from),
+            xml_escape(to),
+            start
+        );
+        write_attribute_values(&mut out, edge, &edge_attributes);
+        out.push_str("      </edge>\n");
+    }
+    out.push_str("    </edges>\n");
+    out.push_str("  </graph>\n</gexf>\n");
+    out
+}
+
+fn to_json_bytes(value: &Value) -> Vec<u8> {
+    serde_json::to_vec_pretty(value).unwrap_or_default()
+}
+
+/// The attributes of a document without the system attributes that are
+/// expressed by the structure of the exported graph.
+fn metadata(document: &Value) -> Map<String, Value> {
+    document
+        .as_object()
+        .map(|obj| {
+            obj.iter()
+                .filter(|(k, _)| !matches!(k.as_str(), "_id" | "_from" | "_to"))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Writes a graph in the JSON Graph Format, version 2.
+pub fn to_jgf(title: &str, vertices: &[Value], edges: &[Value]) -> Value {
+    let nodes: Map<String, Value> = vertices
+        .iter()
+        .filter_map(|v| {
+            let id = v["_id"].as_str()?;
+            Some((
+                id.to_string(),
+                json!({ "label": vertex_label(v), "metadata": metadata(v) }),
+            ))
+        })
+        .collect();
+    let edges: Vec<Value> = edges
+        .iter()
+        .filter_map(|e| {
+            let mut edge = json!({
+                "source": e["_from"].as_str()?,
+                "target": e["_to"].as_str()?,
+                "directed": true,
+                "metadata": metadata(e),
+            });
+            if let Some(id) = e["_id"].as_str() {
+                edge["id"] = json!(id);
+            }
+            Some(edge)
+        })
+        .collect();
+    json!({
+        "graph": {
+            "label": title,
+            "directed": true,
+            "nodes": nodes,
+            "edges": edges,
+        }
+    })
+}
+
+/// Writes a graph as `{nodes: [...], links: [...]}` as used by D3 force
+/// layouts. Links refer to nodes by their index; edges with an end that is
+/// not among the vertices are left out.
+pub fn to_d3(vertices: &[Value], edges: &[Value]) -> Value {
+    let mut index = std::collections::HashMap::new();
+    let mut nodes = Vec::new();
+    for vertex in vertices {
+        let Some(id) = vertex["_id"].as_str() else {
+            continue;
+        };
+        if index.contains_key(id) {
+            continue;
+        }
+        index.insert(id.to_string(), nodes.len());
+        let mut node = metadata(vertex);
+        node.insert("id".to_string(), json!(id));
+        node.insert("label".to_string(), json!(vertex_label(vertex)));
+        nodes.push(Value::Object(node));
+    }
+    let links: Vec<Value> = edges
+        .iter()
+        .filter_map(|e| {
+            let source = *index.get(e["_from"].as_str()?)?;
+            let target = *index.get(e["_to"].as_str()?)?;
+            let mut link = metadata(e);
+            if let Some(id) = e["_id"].as_str() {
+                link.insert("id".to_string(), json!(id));
+            }
+            link.insert("source".to_string(), json!(source));
+            link.insert("target".to_string(), json!(target));
+            Some(Value::Object(link))
+        })
+        .collect();
+    json!({ "nodes": nodes, "links": links })
+}
+
+/// The columns of some documents, with the given ones first.
+fn csv_columns(documents: &[Value], first: &[&str]) -> Vec<String> {
+    let mut columns: Vec<String> = first.iter().map(|c| c.to_string()).collect();
+    columns.extend(
+        output::columns(documents)
+            .into_iter()
+            .filter(|c| !first.contains(&c.as_str())),
+    );
+    columns
+}
+
+/// The vertices as a node list and the edges as an edge list, e.g. for
+/// spreadsheets or the CSV import of other graph databases.
+pub fn to_csv_lists(vertices: &[Value], edges: &[Value]) -> (String, String) {
+    (
+        output::to_csv_with_columns(vertices, &csv_columns(vertices, &["_id"])),
+        output::to_csv_with_columns(edges, &csv_columns(edges, &["_from", "_to", "_id"])),
+    )
+}
+
+/// Packs the node and edge lists as `nodes.csv` and `edges.csv` into a zip
+/// archive.
+pub fn to_csv_zip(vertices: &[Value], edges: &[Value]) -> Result<Vec<u8>> {
+    let (nodes_csv, edges_csv) = to_csv_lists(vertices, edges);
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, content) in [("nodes.csv", nodes_csv), ("edges.csv", edges_csv)] {
+        zip.start_file(name, options)
+            .map_err(|e| std::io::Error::other(format!("Failed to write {}: {}", name, e)))?;
+        zip.write_all(content.as_bytes())?;
+    }
+    let archive = zip
+        .finish()
+        .map_err(|e| std::io::Error::other(format!("Failed to write zip archive: {}", e)))?;
+    Ok(archive.into_inner())
+}
+
+/// Quotes text as a DOT identifier.
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Writes a graph in the DOT language of Graphviz.
+pub fn to_dot(title: &str, vertices: &[Value], edges: &[Value]) -> String {
+    let mut out = format!("digraph {} {{\n", dot_quote(title));
+    out.push_str("  node [shape=ellipse, fontsize=10];\n");
+    for vertex in vertices {
+        if let Some(id) = vertex["_id"].as_str() {
+            let _ = writeln!(
+                out,
+                "  {} [label={}];",
+                dot_quote(id),
+                dot_quote(&vertex_label(vertex))
+            );
+        }
+    }
+    for edge in edges {
+        if let (Some(from), Some(to)) = (edge["_from"].as_str(), edge["_to"].as_str()) {
+            let _ = writeln!(out, "  {} -> {};", dot_quote(from), dot_quote(to));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Lays out a DOT graph with the configured Graphviz command and returns
+/// the SVG, ready to be embedded in a page.
+pub async fn render_svg(command: &str, dot: &str) -> Result<String> {
+    let mut child = tokio::process::Command::new(command)
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    // Written while the output is read, so that neither pipe fills up, and
+    // closed afterwards; both within the timeout
+    let stdin = child.stdin.take();
+    let write = async move {
+        if let Some(mut stdin) = stdin {
+            stdin.write_all(dot.as_bytes()).await?;
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    let (written, output) = tokio::time::timeout(Duration::from_secs(10), async {
+        tokio::join!(write, child.wait_with_output())
+    })
+    .await
+    .map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("{} timed out", command),
+        )
+    })?;
+    let output = output?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "{} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    written?;
+    let svg = String::from_utf8_lossy(&output.stdout);
+    // Drop the XML declaration and doctype in front of the svg element
+    match svg.find("<svg") {
+        Some(start) => Ok(svg[start..].to_string()),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} produced no SVG", command),
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_gexf() {
+        let vertices = vec![
+            json!({ "_id": "people/alice", "_key": "alice", "name": "Alice & Co", "age": 34 }),
+            json!({ "_id": "people/bob", "_key": "bob", "age": 27.5 }),
+        ];
+        let edges = vec![
+            json!({ "_id": "knows/1", "_from": "people/alice", "_to": "people/bob", "since": 2015 }),
+        ];
+        let gexf = to_gexf("Friends", &vertices, &edges, None);
+        assert!(gexf.contains("mode=\"static\""));
+        assert!(gexf.contains("<attribute id=\"age\" title=\"age\" type=\"double\"/>"));
+        assert!(gexf.contains("<attribute id=\"name\" title=\"name\" type=\"string\"/>"));
+        assert!(gexf.contains("<node id=\"people/alice\" label=\"Alice &amp; Co\">"));
+        assert!(gexf.contains("<node id=\"people/bob\" label=\"bob\">"));
+        assert!(gexf.contains("<attvalue for=\"since\" value=\"2015\"/>"));
+        assert!(!gexf.contains("_key"));
+
+        let gexf = to_gexf("Friends", &vertices, &edges, Some("since"));
+        assert!(gexf.contains("mode=\"dynamic\" timeformat=\"double\""));
+        assert!(gexf.contains("target=\"people/bob\" start=\"2015\">"));
+        assert!(!gexf.contains("for=\"since\""));
+    }
+
+    #[test]
+    fn test_jgf_and_d3() {
+        let vertices = vec![
+            json!({ "_id": "people/alice", "_key": "alice", "name": "Alice" }),
+            json!({ "_id": "people/bob", "_key": "bob" }),
+        ];
+        let edges = vec![
+            json!({ "_id": "knows/1", "_from": "people/bob", "_to": "people/alice", "since": 2015 }),
+            json!({ "_from": "people/alice", "_to": "people/carol" }),
+        ];
+
+        let jgf = to_jgf("Friends", &vertices, &edges);
+        assert_eq!(jgf["graph"]["nodes"]["people/alice"]["label"], "Alice");
+        assert_eq!(
+            jgf["graph"]["nodes"]["people/bob"]["metadata"]["_key"],
+            "bob"
+        );
+        assert_eq!(jgf["graph"]["edges"][0]["id"], "knows/1");
+        assert_eq!(jgf["graph"]["edges"][0]["metadata"]["since"], 2015);
+        assert_eq!(jgf["graph"]["edges"].as_array().unwrap().len(), 2);
+
+        let d3 = to_d3(&vertices, &edges);
+        assert_eq!(d3["nodes"][1]["id"], "people/bob");
+        assert_eq!(d3["links"].as_array().unwrap().len(), 1);
+        assert_eq!(d3["links"][0]["source"], 1);
+        assert_eq!(d3["links"][0]["target"], 0);
+        assert_eq!(d3["links"][0]["since"], 2015);
+    }
+
+    #[test]
+    fn test_csv_lists() {
+        let vertices = vec![
+            json!({ "_id": "people/alice", "_key": "alice", "name": "Alice, A." }),
+            json!({ "_id": "people/bob", "_key": "bob", "age": 27 }),
+        ];
+        let edges = vec![json!({ "_id": "knows/1", "_from": "people/bob", "_to": "people/alice" })];
+        let (nodes, edges_csv) = to_csv_lists(&vertices, &edges);
+        assert_eq!(
+            nodes,
+            "_id,_key,age,name\npeople/alice,alice,,\"Alice, A.\"\npeople/bob,bob,27,\n"
+        );
+        assert_eq!(
+            edges_csv,
+            "_from,_to,_id\npeople/bob,people/alice,knows/1\n"
+        );
+
+        let archive = to_csv_zip(&vertices, &edges).unwrap();
+        assert!(archive.starts_with(b"PK"));
+    }
+
+    #[test]
+    fn test_dot() {
+        let vertices = vec![json!({ "_id": "people/alice", "name": "Alice \"Al\"" })];
+        let edges = vec![json!({ "_from": "people/alice", "_to": "people/bob" })];
+        let dot = to_dot("Friends", &vertices, &edges);
+        assert!(dot.starts_with("digraph \"Friends\" {"));
+        assert!(dot.contains("\"people/alice\" [label=\"Alice \\\"Al\\\"\"];"));
+        assert!(dot.contains("\"people/alice\" -> \"people/bob\";"));
+    }
+}
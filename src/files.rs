@@ -0,0 +1,92 @@
+//! Files the runner keeps, e.g. the catalog, the proposals or saved graphs,
+//! are replaced as a whole whenever they change.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Replaces the file at `path` with `bytes`, so that it holds either the
+/// old or the new contents even if the process or the machine crashes
+/// meanwhile. The bytes are written into a file of their own beside it,
+/// synced to disk and only then renamed over the old file. Every write
+/// gets its own temporary file, so that concurrent writes do not mix.
+pub fn write_atomically(path: impl AsRef<Path>, bytes: &[u8]) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.{}.tmp", name, Uuid::new_v4()));
+    let written = File::create(&temporary).and_then(|mut file| {
+        file.write_all(bytes)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| std::fs::rename(&temporary, path)) {
+        let _ = std::fs::remove_file(&temporary);
+        return Err(e);
+    }
+    // The rename itself is only durable once the directory is synced
+    #[cfg(unix)]
+    if let Some(directory) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        File::open(directory)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// A name for the files of a tenant, with all characters but letters,
+/// digits, `-` and `_` replaced by `_`.
+pub fn tenant_file_name(tenant: &str) -> String {
+    tenant
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// The file of a tenant beside the file at `path`, named after the tenant
+/// before the extension, e.g. `usage.acme.json` for `usage.json`.
+pub fn tenant_file(path: &str, tenant: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!(
+            "{}.{}.{}",
+            stem,
+            tenant_file_name(tenant),
+            extension.to_string_lossy()
+        ),
+        None => format!("{}.{}", stem, tenant_file_name(tenant)),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomically() {
+        let directory = std::env::temp_dir().join(format!("files-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("catalog.json");
+
+        write_atomically(&path, b"old").unwrap();
+        write_atomically(&path, b"new").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+
+        let missing = directory.join("missing").join("catalog.json");
+        assert!(write_atomically(&missing, b"new").is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_tenant_file() {
+        assert_eq!(
+            tenant_file("data/usage.json", "acme"),
+            "data/usage.acme.json"
+        );
+        assert_eq!(tenant_file("history", "a/b"), "history.a_b");
+    }
+}
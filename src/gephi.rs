@@ -0,0 +1,189 @@
+//! Pushes graph results to Gephi through the Graph Streaming plugin, as an
+//! alternative to Cytoscape.
+//!
+//! The plugin takes a stream of JSON events, one per line, such as
+//! `{"an": {...}}` to add nodes or `{"ae": {...}}` to add edges.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+use tracing::{debug, info, instrument};
+
+use crate::error::{Error, Result};
+use crate::proxy;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GephiConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_url")]
+    pub url: String, // Where the streaming master server of Gephi listens
+    #[serde(default = "default_workspace")]
+    pub workspace: String,
+    #[serde(default = "default_clear")]
+    pub clear: bool, // Remove the previous graph from the workspace first
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64, // For sending a graph
+}
+
+fn default_url() -> String {
+    "http://localhost:8080".to_string()
+}
+
+fn default_workspace() -> String {
+    "workspace1".to_string()
+}
+
+fn default_clear() -> bool {
+    true
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+impl Default for GephiConfig {
+    fn default() -> Self {
+        GephiConfig {
+            enabled: false,
+            url: default_url(),
+            workspace: default_workspace(),
+            clear: default_clear(),
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+}
+
+impl GephiConfig {
+    /// The URL graph updates are posted to.
+    pub fn update_url(&self) -> String {
+        format!(
+            "{}/{}?operation=updateGraph",
+            self.url.trim_end_matches('/'),
+            self.workspace
+        )
+    }
+}
+
+/// Attributes as Gephi shows them. Nested values are passed as JSON text,
+/// since Gephi only knows scalar attribute types.
+fn attributes(document: &Map<String, Value>) -> Map<String, Value> {
+    document
+        .iter()
+        .filter(|(k, _)| !matches!(k.as_str(), "_from" | "_to"))
+        .map(|(k, v)| match v {
+            Value::Object(_) | Value::Array(_) => (k.clone(), json!(v.to_string())),
+            _ => (k.clone(), v.clone()),
+        })
+        .collect()
+}
+
+/// The streaming events that add the vertices and edges. Documents without
+/// `_id` (or `_from` and `_to`) are skipped.
+fn graph_events(vertices: &[Value], edges: &[Value]) -> Vec<Value> {
+    let nodes = vertices.iter().filter_map(|v| {
+        let obj = v.as_object()?;
+        let id = obj.get("_id")?.as_str()?;
+        let mut attrs = attributes(obj);
+        let label = obj
+            .get("name")
+            .or_else(|| obj.get("_key"))
+            .cloned()
+            .unwrap_or_else(|| json!(id));
+        attrs.insert("label".to_string(), label);
+        Some(json!({ "an": { id: attrs } }))
+    });
+    let edges = edges.iter().enumerate().filter_map(|(i, e)| {
+        let obj = e.as_object()?;
+        let id = obj
+            .get("_id")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("edge/{}", i));
+        let mut attrs = attributes(obj);
+        attrs.insert("source".to_string(), obj.get("_from")?.clone());
+        attrs.insert("target".to_string(), obj.get("_to")?.clone());
+        attrs.insert("directed".to_string(), json!(true));
+        Some(json!({ "ae": { id: attrs } }))
+    });
+    nodes.chain(edges).collect()
+}
+
+/// Checks whether the streaming master server of Gephi answers at all.
+pub async fn is_available(config: &GephiConfig) -> bool {
+    let response = proxy::client()
+        .get(&config.url)
+        .timeout(Duration::from_secs(2))
+        .send()
+        .await;
+    match response {
+        Ok(_) => true,
+        Err(e) => {
+            debug!("Gephi not reachable: {}", e);
+            false
+        }
+    }
+}
+
+/// Sends a graph to the configured Gephi workspace.
+#[instrument(skip_all, fields(url = config.url.as_str(), workspace = config.workspace.as_str()))]
+pub async fn send_to_gephi(
+    config: &GephiConfig,
+    vertices: &[Value],
+    edges: &[Value],
+) -> Result<()> {
+    let mut events = Vec::new();
+    if config.clear {
+        events.push(json!({ "dn": { "filter": "ALL" } }));
+    }
+    events.extend(graph_events(vertices, edges));
+    let body: String = events.iter().map(|e| format!("{}\r\n", e)).collect();
+
+    proxy::client_builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .build()
+        .map_err(|e| Error::Gephi(e.to_string()))?
+        .post(config.update_url())
+        .body(body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| Error::Gephi(e.to_string()))?;
+    info!(
+        vertices = vertices.len(),
+        edges = edges.len(),
+        "Sent graph to Gephi"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph_events() {
+        let vertices = vec![
+            json!({ "_id": "people/alice", "_key": "alice", "tags": ["a", "b"] }),
+            json!({ "_key": "no id" }),
+        ];
+        let edges = vec![json!({ "_from": "people/alice", "_to": "people/bob", "since": 2015 })];
+        let events = graph_events(&vertices, &edges);
+        assert_eq!(events.len(), 2);
+
+        let node = &events[0]["an"]["people/alice"];
+        assert_eq!(node["label"], "alice");
+        assert_eq!(node["tags"], "[\"a\",\"b\"]");
+
+        let edge = &events[1]["ae"]["edge/0"];
+        assert_eq!(edge["source"], "people/alice");
+        assert_eq!(edge["directed"], true);
+        assert_eq!(edge["since"], 2015);
+        assert!(edge.get("_from").is_none());
+
+        assert_eq!(
+            GephiConfig::default().update_url(),
+            "http://localhost:8080/workspace1?operation=updateGraph"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! The query catalog kept in a git repository instead of the configuration
+//! file. Changes made in the web interface become commits authored by the
+//! user who made them, and commits made elsewhere are picked up on an
+//! interval, pulled from the upstream branch if there is one.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::config::QueryDefinition;
+use crate::error::{Error, Result};
+use crate::files::write_atomically;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GitStoreConfig {
+    pub repository: String, // Path of a checked out repository
+    #[serde(default = "default_file")]
+    pub file: String, // The catalog in the repository, a JSON array of queries
+    #[serde(default = "default_refresh_secs")]
+    pub refresh_secs: u64, // How often to pull and reread the catalog; 0 never
+    #[serde(default)]
+    pub push: bool, // Push commits to the upstream branch
+    #[serde(default = "default_committer_name")]
+    pub committer_name: String,
+    #[serde(default = "default_committer_email")]
+    pub committer_email: String,
+}
+
+fn default_file() -> String {
+    "queries.json".to_string()
+}
+
+fn default_refresh_secs() -> u64 {
+    60
+}
+
+fn default_committer_name() -> String {
+    "adb_query_runner".to_string()
+}
+
+fn default_committer_email() -> String {
+    "adb_query_runner@localhost".to_string()
+}
+
+impl GitStoreConfig {
+    pub fn catalog_path(&self) -> PathBuf {
+        Path::new(&self.repository).join(&self.file)
+    }
+}
+
+/// Reads the catalog from the repository, `None` if it has none yet.
+pub fn read_catalog(config: &GitStoreConfig) -> Result<Option<Vec<QueryDefinition>>> {
+    let path = config.catalog_path();
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+pub struct GitStore {
+    config: GitStoreConfig,
+    lock: Mutex<()>, // One git command sequence at a time
+}
+
+impl GitStore {
+    pub fn new(config: GitStoreConfig) -> Self {
+        GitStore {
+            config,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn config(&self) -> &GitStoreConfig {
+        &self.config
+    }
+
+    fn git(&self, args: &[&str], author: Option<&str>) -> Result<String> {
+        let mut command = Command::new("git");
+        command
+            .arg("-C")
+            .arg(&self.config.repository)
+            .args(args)
+            .env("GIT_COMMITTER_NAME", &self.config.committer_name)
+            .env("GIT_COMMITTER_EMAIL", &self.config.committer_email);
+        if let Some(author) = author {
+            // Users are known by name only
+            command
+                .env("GIT_AUTHOR_NAME", author)
+                .env("GIT_AUTHOR_EMAIL", "");
+        }
+        let output = command
+            .output()
+            .map_err(|e| Error::Git(format!("Failed to run git: {}", e)))?;
+        if !output.status.success() {
+            return Err(Error::Git(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn has_upstream(&self) -> bool {
+        self.git(&["rev-parse", "--abbrev-ref", "@{upstream}"], None)
+            .is_ok()
+    }
+
+    /// Writes the catalog and commits it. Nothing is committed if the
+    /// catalog did not change. Commits are pushed separately, see
+    /// [`GitStore::push`].
+    pub fn save(&self, queries: &[QueryDefinition], author: &str, message: &str) -> Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        let json =
+            serde_json::to_string_pretty(queries).map_err(|e| Error::Config(e.to_string()))?;
+        write_atomically(self.config.catalog_path(), (json + "\n").as_bytes())?;
+
+        let file = self.config.file.as_str();
+        self.git(&["add", "--", file], None)?;
+        // Succeeds only without staged changes
+        if self
+            .git(&["diff", "--cached", "--quiet", "--", file], None)
+            .is_ok()
+        {
+            return Ok(());
+        }
+        self.git(
+            &["commit", "--quiet", "-m", message, "--", file],
+            Some(author),
+        )?;
+        info!(file, author, message, "Committed query catalog");
+        Ok(())
+    }
+
+    /// Pushes the commits to the upstream branch, if configured and there is
+    /// one. Unlike saving and pulling, this does not wait for other git
+    /// commands, as it leaves the working tree and the index alone; a slow
+    /// remote holds up nothing else. Commits not pushed go along with the
+    /// next push.
+    pub fn push(&self) {
+        if !self.config.push || !self.has_upstream() {
+            return;
+        }
+        if let Err(e) = self.git(&["push", "--quiet"], None) {
+            warn!("Failed to push query catalog: {}", e);
+        }
+    }
+
+    /// Pulls from the upstream branch, if there is one.
+    pub fn pull(&self) -> Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        if self.has_upstream() {
+            self.git(&["pull", "--quiet", "--ff-only"], None)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(repository: &Path) -> GitStoreConfig {
+        serde_json::from_value(json!({"repository": repository})).unwrap()
+    }
+
+    #[test]
+    fn test_save_and_pull() {
+        let dir = std::env::temp_dir().join(format!("git-store-{}", uuid::Uuid::new_v4()));
+        let origin = dir.join("origin");
+        let clone = dir.join("clone");
+        std::fs::create_dir_all(&origin).unwrap();
+        let origin_store = GitStore::new(config(&origin));
+        origin_store.git(&["init", "--quiet"], None).unwrap();
+        assert!(read_catalog(origin_store.config()).unwrap().is_none());
+
+        origin_store
+            .save(
+                &[QueryDefinition::for_test("a", "RETURN 1", &[])],
+                "alice",
+                "Add 'a'",
+            )
+            .unwrap();
+        // Saving the same catalog again makes no commit
+        origin_store
+            .save(
+                &[QueryDefinition::for_test("a", "RETURN 1", &[])],
+                "alice",
+                "Add 'a'",
+            )
+            .unwrap();
+        let log = origin_store
+            .git(&["log", "--format=%an: %s"], None)
+            .unwrap();
+        assert_eq!(log, "alice: Add 'a'\n");
+
+        // A clone picks up commits made in the origin
+        GitStore::new(config(&dir))
+            .git(
+                &["clone", "--quiet", origin.to_str().unwrap(), "clone"],
+                None,
+            )
+            .unwrap();
+        let clone_store = GitStore::new(config(&clone));
+        origin_store
+            .save(
+                &[QueryDefinition::for_test("a", "RETURN 2", &[])],
+                "bob",
+                "Change 'a'",
+            )
+            .unwrap();
+        clone_store.pull().unwrap();
+        let queries = read_catalog(clone_store.config()).unwrap().unwrap();
+        assert_eq!(queries[0].query, "RETURN 2");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,90 +1,377 @@
-use serde_json::{json, Value};
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, info, instrument};
 
-pub fn is_graph(array: &Vec<Value>) -> Result<(Value, Value), Value> {
+use crate::arango::Client;
+use crate::document_cache::DocumentCache;
+use crate::error;
+
+/// How the graph in the results of a query is recognized. By default
+/// vertices and edges are ArangoDB documents, but other attributes can be
+/// used, e.g. for `{id, source, target}` objects built in AQL.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GraphDetection {
+    #[serde(default = "default_id_field")]
+    pub id_field: String,
+    #[serde(default = "default_from_field")]
+    pub from_field: String,
+    #[serde(default = "default_to_field")]
+    pub to_field: String,
+    #[serde(default)]
+    pub lenient: bool, // Skip items that are neither vertex nor edge
+    #[serde(default)]
+    pub vertex_only: bool, // Accept results without edges, as isolated vertices
+    #[serde(default)]
+    pub fetch_stubs: bool, // Load the documents of vertices only known from edges
+    #[serde(default)]
+    pub directed: Option<bool>, // Arrows if directed; if not, reciprocal edges are collapsed
+}
+
+fn default_id_field() -> String {
+    "_id".to_string()
+}
+
+fn default_from_field() -> String {
+    "_from".to_string()
+}
+
+fn default_to_field() -> String {
+    "_to".to_string()
+}
+
+impl Default for GraphDetection {
+    fn default() -> Self {
+        GraphDetection {
+            id_field: default_id_field(),
+            from_field: default_from_field(),
+            to_field: default_to_field(),
+            lenient: false,
+            vertex_only: false,
+            fetch_stubs: false,
+            directed: None,
+        }
+    }
+}
+
+impl GraphDetection {
+    /// Whether vertices and edges are ArangoDB documents.
+    fn uses_document_fields(&self) -> bool {
+        self.id_field == "_id" && self.from_field == "_from" && self.to_field == "_to"
+    }
+
+    /// Whether an object is a vertex or an edge.
+    fn is_document(&self, obj: &Map<String, Value>) -> bool {
+        obj.contains_key(&self.id_field)
+            || obj.contains_key(&self.from_field)
+            || obj.contains_key(&self.to_field)
+    }
+
+    /// Copies the configured fields to `_id`, `_from` and `_to`, so that
+    /// the rest of the pipeline sees ArangoDB-shaped documents. Numbers are
+    /// accepted as identifiers and turned into strings.
+    fn normalize(&self, item: &Value) -> Value {
+        let Some(obj) = item.as_object() else {
+            return item.clone();
+        };
+        if self.uses_document_fields() {
+            return item.clone();
+        }
+        let mut normalized = obj.clone();
+        for (field, target) in [
+            (&self.id_field, "_id"),
+            (&self.from_field, "_from"),
+            (&self.to_field, "_to"),
+        ] {
+            if let Some(value) = obj.get(field) {
+                let value = match value {
+                    Value::Number(n) => json!(n.to_string()),
+                    other => other.clone(),
+                };
+                normalized.insert(target.to_string(), value);
+            }
+        }
+        Value::Object(normalized)
+    }
+}
+
+/// Collects the documents nested in a value: arrays and objects that are
+/// not documents themselves are searched recursively, other values are
+/// ignored.
+fn collect_documents(value: &Value, detection: &GraphDetection, documents: &mut Vec<Value>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                collect_documents(item, detection, documents);
+            }
+        }
+        Value::Object(obj) if detection.is_document(obj) => documents.push(value.clone()),
+        Value::Object(obj) => {
+            for nested in obj.values() {
+                collect_documents(nested, detection, documents);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Flattens the shapes graph queries return into plain documents, e.g. path
+/// objects `{vertices: [...], edges: [...]}` from `RETURN p`, `{vertex,
+/// edge}` pairs, `RETURN {nodes: (...), edges: (...)}` or arrays of arrays.
+/// Items without any documents in them are kept as they are, so that they
+/// are reported.
+fn flatten(array: &[Value], detection: &GraphDetection) -> Vec<Value> {
+    let mut items = Vec::with_capacity(array.len());
+    for item in array {
+        if matches!(item, Value::Object(obj) if detection.is_document(obj)) {
+            items.push(item.clone());
+            continue;
+        }
+        let mut documents = Vec::new();
+        collect_documents(item, detection, &mut documents);
+        if documents.is_empty() {
+            items.push(item.clone());
+        } else {
+            items.extend(documents);
+        }
+    }
+    items
+}
+
+/// Identifies an edge for deduplication: by `_id`, else by `_from`, `_to`
+/// and `_key`. Edges with neither `_id` nor `_key` are only duplicates if
+/// they are equal.
+pub(crate) fn edge_key(edge: &Map<String, Value>) -> String {
+    if let Some(id) = edge.get("_id").and_then(Value::as_str) {
+        return id.to_string();
+    }
+    match edge.get("_key") {
+        Some(key) => format!("{}\n{}\n{}", edge["_from"], edge["_to"], key),
+        None => Value::Object(edge.clone()).to_string(),
+    }
+}
+
+/// The graph found in a result.
+#[derive(Debug, Clone)]
+pub struct AnalyzedGraph {
+    pub vertices: Value,
+    pub edges: Value,
+    pub skipped: Vec<Value>, // Items left out in lenient mode, with the reason
+    pub stubs: Vec<String>,  // Ids of vertices only known from edges
+}
+
+/// A deterministic `_key` for an edge that has none, e.g. one constructed
+/// in AQL, derived from its `_from`, `_to` and other attributes. Equal
+/// edges get equal keys, but those are deduplicated anyway.
+fn synthetic_edge_key(edge: &Map<String, Value>) -> String {
+    // FNV-1a, which unlike the std hasher is stable across Rust releases
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in Value::Object(edge.clone()).to_string().bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("synthetic-{:016x}", hash)
+}
+
+/// What an item of a result is.
+enum Element<'a> {
+    Vertex(&'a str),
+    Edge(&'a Map<String, Value>, &'a str, &'a str),
+}
+
+/// Classifies an item as vertex or edge, or returns why it is neither.
+fn classify(item: &Value, check_format: bool) -> Result<Element<'_>, &'static str> {
+    // Verify identifiers contain exactly one '/', like `collection/key`
+    let well_formed = |id: &str| !check_format || id.chars().filter(|&c| c == '/').count() == 1;
+
+    // Each item must be an object
+    let obj = item
+        .as_object()
+        .ok_or("Array contains non-object elements")?;
+
+    // Check if it's an edge (has both _from and _to)
+    if let (Some(from), Some(to)) = (obj.get("_from"), obj.get("_to")) {
+        let (Some(from), Some(to)) = (from.as_str(), to.as_str()) else {
+            return Err("Edge _from or _to is not a string");
+        };
+        if !well_formed(from) || !well_formed(to) {
+            return Err("Edge _from or _to has invalid format");
+        }
+        Ok(Element::Edge(obj, from, to))
+    }
+    // Check if it's a vertex (has _id)
+    else if let Some(id) = obj.get("_id") {
+        let id = id.as_str().ok_or("Vertex _id is not a string")?;
+        if !well_formed(id) {
+            return Err("Vertex _id has invalid format");
+        }
+        Ok(Element::Vertex(id))
+    } else {
+        Err("Object is neither vertex nor edge")
+    }
+}
+
+/// Checks whether results form a graph of ArangoDB documents and returns
+/// its vertices and edges, or the reason why not.
+pub fn is_graph(array: &[Value]) -> Result<(Value, Value), Value> {
+    analyze(array, &GraphDetection::default()).map(|graph| (graph.vertices, graph.edges))
+}
+
+/// Like [`is_graph`], but recognizes vertices and edges as configured. The
+/// returned documents always have `_id`, `_from` and `_to`.
+#[instrument(skip_all, fields(items = array.len()))]
+pub fn analyze(array: &[Value], detection: &GraphDetection) -> Result<AnalyzedGraph, Value> {
+    // Identifiers other than `_id` need not look like `collection/key`
+    let check_format = detection.uses_document_fields();
     // Prepare vectors for vertices and edges
     let mut vertices = Vec::new();
     let mut edges = Vec::new();
+    let mut skipped = Vec::new();
     let mut vertex_ids: HashSet<String> = HashSet::new();
     let mut vertex_ids_needed: HashSet<String> = HashSet::new();
+    let mut edge_keys: HashSet<String> = HashSet::new();
+    // Of undirected graphs: relation, `_from` and `_to` of the edges kept
+    let mut connections: HashSet<(String, String, String)> = HashSet::new();
+    let mut collapsed = 0;
 
-    // Process each element
-    for item in array {
-        // Each item must be an object
-        let obj = match item.as_object() {
-            Some(o) => o,
-            None => {
-                return Err(json!({
-                    "error": "Array contains non-object elements",
-                    "value": item
-                }))
-            }
-        };
-
-        // Check if it's an edge (has both _from and _to)
-        if let (Some(from), Some(to)) = (obj.get("_from"), obj.get("_to")) {
-            // Verify _from and _to are strings containing exactly one '/'
-            if let (Some(from_str), Some(to_str)) = (from.as_str(), to.as_str()) {
-                if from_str.chars().filter(|&c| c == '/').count() != 1
-                    || to_str.chars().filter(|&c| c == '/').count() != 1
-                {
-                    return Err(json!({
-                        "error": "Edge _from or _to has invalid format",
-                        "value": item
-                    }));
+    // Process each element, with nested documents taken out
+    let items: Vec<Value> = flatten(array, detection)
+        .iter()
+        .map(|item| detection.normalize(item))
+        .collect();
+    for item in &items {
+        match classify(item, check_format) {
+            Ok(Element::Edge(obj, from, to)) => {
+                vertex_ids_needed.insert(from.to_string());
+                vertex_ids_needed.insert(to.to_string());
+                // In an undirected graph, b -> a is the same as a -> b
+                if detection.directed == Some(false) && from != to {
+                    let relation = obj
+                        .get("_id")
+                        .and_then(Value::as_str)
+                        .and_then(|id| id.split_once('/'))
+                        .map_or("", |(collection, _)| collection);
+                    let reverse = (relation.to_string(), to.to_string(), from.to_string());
+                    if connections.contains(&reverse) {
+                        collapsed += 1;
+                        continue;
+                    }
+                    connections.insert((relation.to_string(), from.to_string(), to.to_string()));
+                }
+                // Traversals return the same edge many times
+                if edge_keys.insert(edge_key(obj)) {
+                    let mut edge = obj.clone();
+                    if !edge.contains_key("_key") {
+                        edge.insert("_key".to_string(), json!(synthetic_edge_key(obj)));
+                    }
+                    edges.push(Value::Object(edge));
                 }
-                vertex_ids_needed.insert(from_str.to_string());
-                vertex_ids_needed.insert(to_str.to_string());
-                edges.push(item.clone());
-            } else {
-                return Err(json!({
-                    "error": "Edge _from or _to is not a string",
-                    "value": item
-                }));
             }
-        }
-        // Check if it's a vertex (has _id)
-        else if let Some(id) = obj.get("_id") {
-            // Verify _id is a string containing exactly one '/'
-            if let Some(id_str) = id.as_str() {
-                if id_str.chars().filter(|&c| c == '/').count() != 1 {
-                    return Err(json!({
-                        "error": "Vertex _id has invalid format",
-                        "value": item
-                    }));
+            Ok(Element::Vertex(id)) => {
+                if vertex_ids.insert(id.to_string()) {
+                    vertices.push(item.clone());
                 }
-                vertex_ids.insert(id_str.to_string());
-                vertices.push(item.clone());
-            } else {
-                return Err(json!({
-                    "error": "Vertex _id is not a string",
-                    "value": item
-                }));
             }
-        } else {
-            return Err(json!({
-                "error": "Object is neither vertex nor edge",
-                "value": item
-            }));
+            // In lenient mode, one bad item does not spoil the graph
+            Err(reason) if detection.lenient => {
+                skipped.push(json!({ "error": reason, "value": item }));
+            }
+            Err(reason) => return Err(json!({ "error": reason, "value": item })),
         }
     }
 
     // If we got here but found no vertices or edges, it's not a graph
-    if edges.is_empty() {
+    if vertices.is_empty() && edges.is_empty() && detection.vertex_only {
+        return Err(json!({
+            "error": "Array contains no valid vertices",
+        }));
+    }
+    if edges.is_empty() && !detection.vertex_only {
         return Err(json!({
             "error": "Array contains no valid edges",
         }));
     }
 
     // Now add vertices that occur in edges but are not explicitly mentioned:
-    for id in vertex_ids_needed.difference(&vertex_ids) {
+    let mut stubs: Vec<String> = vertex_ids_needed.difference(&vertex_ids).cloned().collect();
+    stubs.sort();
+    for id in &stubs {
         vertices.push(json!({
             "_id": id
         }));
     }
 
+    debug!(
+        vertices = vertices.len(),
+        edges = edges.len(),
+        skipped = skipped.len(),
+        collapsed,
+        "Detected graph"
+    );
+
     // Return success with vertices and edges
-    Ok((json!(vertices), json!(edges)))
+    Ok(AnalyzedGraph {
+        vertices: json!(vertices),
+        edges: json!(edges),
+        skipped,
+        stubs,
+    })
+}
+
+/// How many stub vertices are fetched with one query.
+const FETCH_BATCH_SIZE: usize = 1000;
+
+/// Replaces stub vertices by the documents in `documents` with the same
+/// `_id`. Returns how many were replaced; the others stay stubs.
+fn fill_stubs(graph: &mut AnalyzedGraph, documents: Vec<Value>) -> usize {
+    let mut by_id: HashMap<String, Value> = documents
+        .into_iter()
+        .filter_map(|d| Some((d["_id"].as_str()?.to_string(), d)))
+        .filter(|(id, _)| graph.stubs.contains(id))
+        .collect();
+    let mut filled = HashSet::new();
+    for vertex in graph.vertices.as_array_mut().into_iter().flatten() {
+        let Some(id) = vertex["_id"].as_str().map(str::to_string) else {
+            continue;
+        };
+        if let Some(document) = by_id.remove(&id) {
+            *vertex = document;
+            filled.insert(id);
+        }
+    }
+    graph.stubs.retain(|id| !filled.contains(id));
+    filled.len()
+}
+
+/// Loads the documents of the stub vertices of a graph from the database,
+/// so that they have their attributes for labels and styles. Documents in
+/// the cache are not loaded again, and loaded ones are added to it. Stubs
+/// whose documents no longer exist are kept. Returns the number of
+/// vertices that were filled in.
+#[instrument(skip_all, fields(stubs = graph.stubs.len()))]
+pub async fn fetch_stubs(
+    client: &Client,
+    cache: &DocumentCache,
+    graph: &mut AnalyzedGraph,
+) -> error::Result<usize> {
+    let (mut documents, missing) = cache.lookup(&graph.stubs);
+    let cached = documents.len();
+    for ids in missing.chunks(FETCH_BATCH_SIZE) {
+        let bind_vars = HashMap::from([("ids".to_string(), json!(ids))]);
+        let result = client
+            .execute("FOR d IN DOCUMENT(@ids) RETURN d", bind_vars)
+            .await?;
+        let fetched: Vec<Value> = result
+            .results
+            .into_iter()
+            .filter(Value::is_object)
+            .collect();
+        cache.insert(&fetched);
+        documents.extend(fetched);
+    }
+    let filled = fill_stubs(graph, documents);
+    info!(filled, cached, "Fetched stub vertices");
+    Ok(filled)
 }
 
 #[cfg(test)]
@@ -110,22 +397,203 @@ mod tests {
             }),
         ];
 
-        let result = is_graph(&input).unwrap();
-        let result_obj = result.as_object().unwrap();
+        let (vertices, edges) = is_graph(&input).unwrap();
 
-        assert_eq!(
-            result_obj
-                .get("vertices")
-                .unwrap()
+        assert_eq!(vertices.as_array().unwrap().len(), 2);
+        assert_eq!(edges.as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_documents_are_kept_as_they_are() {
+        let input = vec![
+            json!({
+                "_id": "people/alice",
+                "collection": "</script><script>alert(1)</script>"
+            }),
+            json!({ "_from": "people/alice", "_to": "people/bob" }),
+        ];
+        let (vertices, _) = is_graph(&input).unwrap();
+        assert_eq!(vertices[0], input[0]);
+        assert_eq!(vertices[1], json!({ "_id": "people/bob" }));
+    }
+
+    #[test]
+    fn test_path_objects() {
+        let alice = json!({ "_id": "people/alice" });
+        let bob = json!({ "_id": "people/bob" });
+        let knows = json!({ "_key": "1", "_from": "people/alice", "_to": "people/bob" });
+        let input = vec![
+            json!({ "vertices": [alice.clone(), bob.clone()], "edges": [knows.clone()] }),
+            json!({ "vertices": [alice.clone()], "edges": [] }),
+        ];
+        let (vertices, edges) = is_graph(&input).unwrap();
+        assert_eq!(vertices.as_array().unwrap().len(), 2);
+        assert_eq!(edges, json!([knows.clone()]));
+
+        let input = vec![
+            json!({ "vertex": alice, "edge": null }),
+            json!({ "vertex": bob, "edge": knows }),
+        ];
+        let (vertices, edges) = is_graph(&input).unwrap();
+        assert_eq!(vertices.as_array().unwrap().len(), 2);
+        assert_eq!(edges.as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_nested_results() {
+        let input = vec![json!({
+            "nodes": [{ "_id": "people/alice" }, [{ "_id": "people/bob" }]],
+            "edges": [{ "_from": "people/alice", "_to": "people/bob", "tags": [1, 2] }],
+            "count": 2
+        })];
+        let (vertices, edges) = is_graph(&input).unwrap();
+        assert_eq!(vertices.as_array().unwrap().len(), 2);
+        assert_eq!(edges[0]["tags"], json!([1, 2]));
+
+        let input = vec![json!([[{ "_from": "a/1", "_to": "a/2" }]])];
+        assert!(is_graph(&input).is_ok());
+
+        // Objects without documents in them still make it not a graph
+        let input = vec![
+            json!({ "_from": "a/1", "_to": "a/2" }),
+            json!({ "stats": { "count": 1 } }),
+        ];
+        assert!(is_graph(&input).is_err());
+    }
+
+    #[test]
+    fn test_duplicates_are_removed() {
+        let input = vec![
+            json!({ "_id": "people/alice", "name": "Alice" }),
+            json!({ "_id": "people/alice", "name": "Alice" }),
+            json!({ "_id": "knows/1", "_from": "people/alice", "_to": "people/bob" }),
+            json!({ "_id": "knows/1", "_from": "people/alice", "_to": "people/bob" }),
+            json!({ "_key": "2", "_from": "people/bob", "_to": "people/alice" }),
+            json!({ "_key": "2", "_from": "people/bob", "_to": "people/alice" }),
+            json!({ "_from": "people/bob", "_to": "people/carol", "w": 1 }),
+            json!({ "_from": "people/bob", "_to": "people/carol", "w": 2 }),
+        ];
+        let (vertices, edges) = is_graph(&input).unwrap();
+        assert_eq!(vertices.as_array().unwrap().len(), 3);
+        assert_eq!(edges.as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_undirected_collapses_reciprocal_edges() {
+        let input = vec![
+            json!({ "_id": "knows/1", "_from": "people/alice", "_to": "people/bob" }),
+            json!({ "_id": "knows/2", "_from": "people/bob", "_to": "people/alice" }),
+            json!({ "_id": "likes/1", "_from": "people/bob", "_to": "people/alice" }),
+            json!({ "_id": "knows/3", "_from": "people/alice", "_to": "people/bob" }),
+            json!({ "_id": "knows/4", "_from": "people/bob", "_to": "people/bob" }),
+            json!({ "_id": "knows/5", "_from": "people/bob", "_to": "people/bob" }),
+        ];
+        let ids = |detection: &GraphDetection| -> Vec<String> {
+            let graph = analyze(&input, detection).unwrap();
+            graph
+                .edges
                 .as_array()
                 .unwrap()
-                .len(),
-            2
-        );
+                .iter()
+                .map(|e| e["_id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let undirected = GraphDetection {
+            directed: Some(false),
+            ..GraphDetection::default()
+        };
+        // Parallel edges and those of other relations are kept
         assert_eq!(
-            result_obj.get("edges").unwrap().as_array().unwrap().len(),
-            1
+            ids(&undirected),
+            ["knows/1", "likes/1", "knows/3", "knows/4", "knows/5"]
         );
+        assert_eq!(ids(&GraphDetection::default()).len(), 6);
+    }
+
+    #[test]
+    fn test_custom_fields() {
+        let detection: GraphDetection = serde_json::from_value(
+            json!({ "id_field": "id", "from_field": "source", "to_field": "target" }),
+        )
+        .unwrap();
+        let input = vec![
+            json!({ "id": "alice", "city": "Cologne" }),
+            json!({ "id": 7 }),
+            json!({ "source": "alice", "target": 7, "count": 3 }),
+        ];
+        let AnalyzedGraph {
+            vertices, edges, ..
+        } = analyze(&input, &detection).unwrap();
+        assert_eq!(vertices[0]["_id"], "alice");
+        assert_eq!(vertices[1]["_id"], "7");
+        assert_eq!(edges[0]["_from"], "alice");
+        assert_eq!(edges[0]["_to"], "7");
+        assert_eq!(edges[0]["count"], 3);
+
+        // The default fields do not match these objects
+        assert!(is_graph(&input).is_err());
+    }
+
+    #[test]
+    fn test_lenient_mode() {
+        let input = vec![
+            json!({ "_id": "people/alice" }),
+            json!({ "_id": "no_slash" }),
+            json!("text"),
+            json!({ "_from": "people/alice", "_to": "people/bob" }),
+        ];
+        assert!(is_graph(&input).is_err());
+
+        let detection = GraphDetection {
+            lenient: true,
+            ..GraphDetection::default()
+        };
+        let graph = analyze(&input, &detection).unwrap();
+        assert_eq!(graph.vertices.as_array().unwrap().len(), 2);
+        assert_eq!(graph.skipped.len(), 2);
+        assert_eq!(graph.skipped[0]["error"], "Vertex _id has invalid format");
+        assert_eq!(graph.skipped[1]["value"], "text");
+    }
+
+    #[test]
+    fn test_synthetic_edge_keys() {
+        let input = vec![
+            json!({ "_from": "people/alice", "_to": "people/bob" }),
+            json!({ "_from": "people/alice", "_to": "people/bob", "weight": 2 }),
+            json!({ "_key": "k", "_from": "people/bob", "_to": "people/alice" }),
+        ];
+        let (_, edges) = is_graph(&input).unwrap();
+        let keys: Vec<&str> = edges
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["_key"].as_str().unwrap())
+            .collect();
+        assert!(keys[0].starts_with("synthetic-"));
+        assert_ne!(keys[0], keys[1]);
+        assert_eq!(keys[2], "k");
+
+        // The same edge gets the same key every time
+        let (_, again) = is_graph(&input).unwrap();
+        assert_eq!(again[0]["_key"], keys[0]);
+    }
+
+    #[test]
+    fn test_vertex_only() {
+        let input = vec![
+            json!({ "_id": "people/alice", "cluster": 1 }),
+            json!({ "_id": "people/bob", "cluster": 2 }),
+        ];
+        assert!(is_graph(&input).is_err());
+
+        let detection = GraphDetection {
+            vertex_only: true,
+            ..GraphDetection::default()
+        };
+        let graph = analyze(&input, &detection).unwrap();
+        assert_eq!(graph.vertices.as_array().unwrap().len(), 2);
+        assert!(graph.edges.as_array().unwrap().is_empty());
+        assert!(analyze(&[], &detection).is_err());
     }
 
     #[test]
@@ -178,4 +646,26 @@ mod tests {
 
         assert!(is_graph(&input).is_err());
     }
+
+    #[test]
+    fn test_fill_stubs() {
+        let input = vec![
+            json!({ "_id": "people/alice", "name": "Alice" }),
+            json!({ "_from": "people/alice", "_to": "people/bob" }),
+            json!({ "_from": "people/alice", "_to": "people/carol" }),
+        ];
+        let mut graph = analyze(&input, &GraphDetection::default()).unwrap();
+        assert_eq!(graph.stubs, vec!["people/bob", "people/carol"]);
+
+        let documents = vec![
+            json!({ "_id": "people/bob", "_key": "bob", "name": "Bob" }),
+            json!({ "_id": "people/alice", "name": "Not a stub" }),
+        ];
+        assert_eq!(fill_stubs(&mut graph, documents), 1);
+        assert_eq!(graph.stubs, vec!["people/carol"]);
+        let vertices = graph.vertices.as_array().unwrap();
+        assert_eq!(vertices[0]["name"], "Alice");
+        let bob = vertices.iter().find(|v| v["_id"] == "people/bob").unwrap();
+        assert_eq!(bob["name"], "Bob");
+    }
 }
@@ -0,0 +1,249 @@
+//! Narrowing down graph results by attribute predicates such as
+//! `weight > 0.5` or `type == "knows"` before looking at them.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::error::{Error, Result};
+
+/// How many distinct values of an attribute are listed as examples.
+const EXAMPLE_VALUES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    /// Longer operators first, so `>=` is not taken for `>`.
+    const ALL: &'static [(&'static str, Operator)] = &[
+        ("==", Operator::Equal),
+        ("!=", Operator::NotEqual),
+        (">=", Operator::GreaterOrEqual),
+        ("<=", Operator::LessOrEqual),
+        (">", Operator::Greater),
+        ("<", Operator::Less),
+    ];
+}
+
+/// A condition on one attribute of a vertex or edge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    attribute: Vec<String>, // Path into nested objects, written as `a.b`
+    operator: Operator,
+    value: Value,
+}
+
+impl Predicate {
+    /// Parses `<attribute> <operator> <value>`, where the operator is one of
+    /// `==`, `!=`, `<`, `<=`, `>` and `>=`. The value is read as JSON, e.g.
+    /// `0.5`, `"knows"` or `null`; anything else counts as a string.
+    pub fn parse(text: &str) -> Result<Predicate> {
+        let (position, symbol, operator) = Operator::ALL
+            .iter()
+            .filter_map(|(symbol, operator)| Some((text.find(symbol)?, *symbol, *operator)))
+            .min_by_key(|(position, symbol, _)| (*position, usize::MAX - symbol.len()))
+            .ok_or_else(|| {
+                Error::InvalidParameter(format!("No comparison operator in filter '{}'", text))
+            })?;
+        let attribute = text[..position].trim();
+        if attribute.is_empty() {
+            return Err(Error::InvalidParameter(format!(
+                "No attribute in filter '{}'",
+                text
+            )));
+        }
+        let value = text[position + symbol.len()..].trim();
+        Ok(Predicate {
+            attribute: attribute.split('.').map(str::to_string).collect(),
+            operator,
+            value: serde_json::from_str(value).unwrap_or_else(|_| Value::from(value)),
+        })
+    }
+
+    /// Parses one predicate per line, skipping empty lines.
+    pub fn parse_lines(text: &str) -> Result<Vec<Predicate>> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(Predicate::parse)
+            .collect()
+    }
+
+    /// Whether a document fulfills the predicate. A missing attribute is
+    /// `null`; values that cannot be ordered, such as a number and a
+    /// string, fulfill no ordering comparison.
+    pub fn matches(&self, document: &Value) -> bool {
+        let actual = self
+            .attribute
+            .iter()
+            .try_fold(document, |value, name| value.get(name))
+            .unwrap_or(&Value::Null);
+        let ordering = match (actual, &self.value) {
+            (Value::Number(a), Value::Number(b)) => a
+                .as_f64()
+                .zip(b.as_f64())
+                .and_then(|(a, b)| a.partial_cmp(&b)),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (a, b) if a == b => Some(std::cmp::Ordering::Equal),
+            _ => None,
+        };
+        match self.operator {
+            Operator::Equal => ordering.is_some_and(|o| o.is_eq()),
+            Operator::NotEqual => !ordering.is_some_and(|o| o.is_eq()),
+            Operator::Less => ordering.is_some_and(|o| o.is_lt()),
+            Operator::LessOrEqual => ordering.is_some_and(|o| o.is_le()),
+            Operator::Greater => ordering.is_some_and(|o| o.is_gt()),
+            Operator::GreaterOrEqual => ordering.is_some_and(|o| o.is_ge()),
+        }
+    }
+}
+
+/// Keeps the vertices fulfilling all vertex predicates and the edges
+/// fulfilling all edge predicates whose ends are both kept.
+pub fn filter(
+    vertices: &[Value],
+    edges: &[Value],
+    vertex_predicates: &[Predicate],
+    edge_predicates: &[Predicate],
+) -> (Vec<Value>, Vec<Value>) {
+    let vertices: Vec<Value> = vertices
+        .iter()
+        .filter(|v| vertex_predicates.iter().all(|p| p.matches(v)))
+        .cloned()
+        .collect();
+    let kept: HashSet<&str> = vertices.iter().filter_map(|v| v["_id"].as_str()).collect();
+    let edges = edges
+        .iter()
+        .filter(|e| {
+            let ends = [&e["_from"], &e["_to"]];
+            ends.iter()
+                .all(|end| end.as_str().is_some_and(|id| kept.contains(id)))
+                && edge_predicates.iter().all(|p| p.matches(e))
+        })
+        .cloned()
+        .collect();
+    (vertices, edges)
+}
+
+/// What values an attribute has across vertices or edges.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AttributeSummary {
+    pub name: String,
+    pub count: usize,       // Documents that have the attribute
+    pub types: Vec<String>, // JSON types of its values
+    pub min: Option<f64>,   // Of the numeric values
+    pub max: Option<f64>,
+    pub distinct: usize,
+    pub examples: Vec<String>, // The first few distinct values
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Summarizes the attributes of documents, in alphabetical order. System
+/// attributes starting with `_` are left out.
+pub fn summarize(documents: &[Value]) -> Vec<AttributeSummary> {
+    let mut values: BTreeMap<&str, Vec<&Value>> = BTreeMap::new();
+    for obj in documents.iter().filter_map(Value::as_object) {
+        for (name, value) in obj.iter().filter(|(name, _)| !name.starts_with('_')) {
+            values.entry(name).or_default().push(value);
+        }
+    }
+    values
+        .into_iter()
+        .map(|(name, values)| {
+            let numbers = values.iter().filter_map(|v| v.as_f64());
+            let distinct: BTreeSet<String> = values.iter().map(|v| v.to_string()).collect();
+            AttributeSummary {
+                name: name.to_string(),
+                count: values.len(),
+                types: values
+                    .iter()
+                    .map(|v| type_name(v))
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+                min: numbers.clone().reduce(f64::min),
+                max: numbers.reduce(f64::max),
+                distinct: distinct.len(),
+                examples: distinct.into_iter().take(EXAMPLE_VALUES).collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_predicates() {
+        let edge = json!({ "weight": 0.7, "type": "knows", "meta": { "since": 2015 } });
+        let holds = |text: &str| Predicate::parse(text).unwrap().matches(&edge);
+        assert!(holds("weight > 0.5"));
+        assert!(holds("weight>=0.7"));
+        assert!(!holds("weight < 0.5"));
+        assert!(holds("type == \"knows\""));
+        assert!(holds("type == knows"));
+        assert!(holds("type != likes"));
+        assert!(holds("meta.since <= 2015"));
+        assert!(holds("missing == null"));
+        assert!(!holds("type > 1"));
+        assert!(holds("type != 1"));
+
+        assert!(Predicate::parse("weight 0.5").is_err());
+        assert!(Predicate::parse("> 0.5").is_err());
+        assert_eq!(
+            Predicate::parse_lines("a == 1\n\n b < 2 ").unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_filter_and_summary() {
+        let vertices = vec![
+            json!({ "_id": "v/a", "age": 30 }),
+            json!({ "_id": "v/b", "age": 50 }),
+            json!({ "_id": "v/c", "age": "unknown" }),
+        ];
+        let edges = vec![
+            json!({ "_from": "v/a", "_to": "v/b", "weight": 1 }),
+            json!({ "_from": "v/a", "_to": "v/c", "weight": 2 }),
+            json!({ "_from": "v/b", "_to": "v/a", "weight": 3 }),
+        ];
+        let (v, e) = filter(
+            &vertices,
+            &edges,
+            &Predicate::parse_lines("age >= 30").unwrap(),
+            &Predicate::parse_lines("weight < 3").unwrap(),
+        );
+        assert_eq!(v.len(), 2);
+        assert_eq!(e, vec![edges[0].clone()]);
+
+        let summary = summarize(&vertices);
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].name, "age");
+        assert_eq!(summary[0].count, 3);
+        assert_eq!(summary[0].types, vec!["number", "string"]);
+        assert_eq!((summary[0].min, summary[0].max), (Some(30.0), Some(50.0)));
+        assert_eq!(summary[0].examples, vec!["\"unknown\"", "30", "50"]);
+    }
+}
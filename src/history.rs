@@ -0,0 +1,278 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::error::{Error, Result};
+use crate::files::tenant_file;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryConfig {
+    #[serde(default)]
+    pub file: Option<String>, // Persist runs as JSON lines and reload them at startup
+    #[serde(default = "default_keep_per_query")]
+    pub keep_per_query: usize,
+}
+
+fn default_keep_per_query() -> usize {
+    500
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            file: None,
+            keep_per_query: default_keep_per_query(),
+        }
+    }
+}
+
+impl HistoryConfig {
+    /// The runs of a tenant go to a file of their own, see [`tenant_file`].
+    pub fn for_tenant(&self, tenant: &str) -> HistoryConfig {
+        HistoryConfig {
+            file: self.file.as_ref().map(|f| tenant_file(f, tenant)),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RunRecord {
+    pub query_name: String,
+    pub timestamp: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub rows: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PerformanceSummary {
+    pub runs: usize,
+    pub min_ms: u64,
+    pub avg_ms: u64,
+    pub max_ms: u64,
+    pub duration_points: String, // SVG polyline points
+    pub rows_points: String,
+}
+
+pub const CHART_WIDTH: f64 = 600.0;
+pub const CHART_HEIGHT: f64 = 150.0;
+
+pub struct QueryHistory {
+    config: HistoryConfig,
+    runs: Mutex<HashMap<String, VecDeque<RunRecord>>>,
+}
+
+impl QueryHistory {
+    pub fn new(config: HistoryConfig) -> Self {
+        let history = QueryHistory {
+            config,
+            runs: Mutex::new(HashMap::new()),
+        };
+        if let Some(file) = &history.config.file {
+            match load_from_file(file) {
+                Ok(records) => {
+                    info!(
+                        file = file.as_str(),
+                        runs = records.len(),
+                        "Loaded query history"
+                    );
+                    for record in records {
+                        history.push(record);
+                    }
+                }
+                Err(e) => warn!(file = file.as_str(), "Could not load query history: {}", e),
+            }
+        }
+        history
+    }
+
+    fn push(&self, record: RunRecord) {
+        let mut runs = self.runs.lock().unwrap();
+        let entries = runs.entry(record.query_name.clone()).or_default();
+        entries.push_back(record);
+        while entries.len() > self.config.keep_per_query {
+            entries.pop_front();
+        }
+    }
+
+    pub fn record(&self, query_name: &str, duration: Duration, rows: usize) {
+        let record = RunRecord {
+            query_name: query_name.to_string(),
+            timestamp: Utc::now(),
+            duration_ms: duration.as_millis() as u64,
+            rows,
+        };
+        if let Some(file) = &self.config.file {
+            if let Err(e) = append_to_file(file, &record) {
+                warn!(file = file.as_str(), "Failed to persist run: {}", e);
+            }
+        }
+        self.push(record);
+    }
+
+    /// All recorded runs, by query and oldest first.
+    pub fn all(&self) -> Vec<RunRecord> {
+        let runs = self.runs.lock().unwrap();
+        let mut names: Vec<&String> = runs.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .flat_map(|name| runs[name].iter().cloned())
+            .collect()
+    }
+
+    /// Adds runs recorded elsewhere, e.g. from an archive of another
+    /// installation, skipping those recorded here already. Returns how many
+    /// were added.
+    pub fn restore(&self, records: Vec<RunRecord>) -> usize {
+        let mut runs = self.runs.lock().unwrap();
+        let mut restored = 0;
+        for record in records {
+            let entries = runs.entry(record.query_name.clone()).or_default();
+            if entries.iter().any(|r| r.timestamp == record.timestamp) {
+                continue;
+            }
+            if let Some(file) = &self.config.file {
+                if let Err(e) = append_to_file(file, &record) {
+                    warn!(file = file.as_str(), "Failed to persist run: {}", e);
+                }
+            }
+            entries.push_back(record);
+            restored += 1;
+        }
+        for entries in runs.values_mut() {
+            entries.make_contiguous().sort_by_key(|r| r.timestamp);
+            while entries.len() > self.config.keep_per_query {
+                entries.pop_front();
+            }
+        }
+        restored
+    }
+
+    /// Returns all recorded runs of a query, oldest first.
+    pub fn runs(&self, query_name: &str) -> Vec<RunRecord> {
+        self.runs
+            .lock()
+            .unwrap()
+            .get(query_name)
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+pub fn summarize(runs: &[RunRecord]) -> Option<PerformanceSummary> {
+    if runs.is_empty() {
+        return None;
+    }
+    let durations: Vec<f64> = runs.iter().map(|r| r.duration_ms as f64).collect();
+    let rows: Vec<f64> = runs.iter().map(|r| r.rows as f64).collect();
+    let total: u64 = runs.iter().map(|r| r.duration_ms).sum();
+
+    Some(PerformanceSummary {
+        runs: runs.len(),
+        min_ms: runs.iter().map(|r| r.duration_ms).min().unwrap_or(0),
+        avg_ms: total / runs.len() as u64,
+        max_ms: runs.iter().map(|r| r.duration_ms).max().unwrap_or(0),
+        duration_points: polyline_points(&durations),
+        rows_points: polyline_points(&rows),
+    })
+}
+
+/// Scales a series of values into the chart area, with the first value on the
+/// left and zero at the bottom edge.
+fn polyline_points(values: &[f64]) -> String {
+    let max = values.iter().cloned().fold(0.0, f64::max);
+    let step = if values.len() > 1 {
+        CHART_WIDTH / (values.len() - 1) as f64
+    } else {
+        0.0
+    };
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let y = if max > 0.0 {
+                CHART_HEIGHT - v / max * CHART_HEIGHT
+            } else {
+                CHART_HEIGHT
+            };
+            format!("{:.1},{:.1}", i as f64 * step, y)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn load_from_file(path: &str) -> Result<Vec<RunRecord>> {
+    let file = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut records = Vec::new();
+    for line in std::io::BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|e| Error::Config(format!("Invalid history entry in {}: {}", path, e)))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn append_to_file(path: &str, record: &RunRecord) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(
+        file,
+        "{}",
+        serde_json::to_string(record).map_err(std::io::Error::from)?
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_limit() {
+        let history = QueryHistory::new(HistoryConfig {
+            file: None,
+            keep_per_query: 2,
+        });
+        history.record("q", Duration::from_millis(10), 1);
+        history.record("q", Duration::from_millis(20), 2);
+        history.record("q", Duration::from_millis(30), 3);
+        history.record("other", Duration::from_millis(5), 0);
+
+        let runs = history.runs("q");
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].rows, 2);
+        assert_eq!(runs[1].rows, 3);
+        assert!(history.runs("unknown").is_empty());
+    }
+
+    #[test]
+    fn test_summarize() {
+        let history = QueryHistory::new(HistoryConfig::default());
+        history.record("q", Duration::from_millis(10), 5);
+        history.record("q", Duration::from_millis(30), 0);
+
+        let summary = summarize(&history.runs("q")).unwrap();
+        assert_eq!(summary.runs, 2);
+        assert_eq!(summary.min_ms, 10);
+        assert_eq!(summary.avg_ms, 20);
+        assert_eq!(summary.max_ms, 30);
+        assert_eq!(summary.duration_points, "0.0,100.0 600.0,0.0");
+        assert_eq!(summary.rows_points, "0.0,0.0 600.0,150.0");
+        assert!(summarize(&[]).is_none());
+    }
+}
@@ -0,0 +1,105 @@
+//! Translations of the web interface. Every locale is a flat JSON object of
+//! texts by key in `locales/{locale}.json` next to the templates, which see
+//! the texts of the chosen locale as `tr`. Keys missing in a locale fall
+//! back to English.
+
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::error::{Error, Result};
+
+/// The locale every other one falls back to.
+pub const DEFAULT_LOCALE: &str = "en";
+
+#[derive(Debug, Default)]
+pub struct Locales {
+    texts: BTreeMap<String, Map<String, Value>>,
+}
+
+impl Locales {
+    /// Adds (or replaces) a locale from the contents of its JSON file.
+    pub fn add(&mut self, locale: &str, json: &str) -> Result<()> {
+        let texts: Map<String, Value> = serde_json::from_str(json)
+            .map_err(|e| Error::Config(format!("Invalid locale {}: {}", locale, e)))?;
+        self.texts.insert(locale.to_string(), texts);
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.texts.keys().map(String::as_str).collect()
+    }
+
+    /// The texts of a locale, completed by those of the default locale.
+    pub fn texts(&self, locale: &str) -> Map<String, Value> {
+        let mut texts = self.texts.get(DEFAULT_LOCALE).cloned().unwrap_or_default();
+        if let Some(own) = self.texts.get(locale) {
+            texts.extend(own.clone());
+        }
+        texts
+    }
+
+    /// The best available locale for an `Accept-Language` header, e.g.
+    /// `de-CH, de;q=0.9, en;q=0.8`. Regional variants fall back to their
+    /// language.
+    pub fn negotiate(&self, accept_language: &str) -> Option<String> {
+        let mut wanted: Vec<(f32, &str)> = accept_language
+            .split(',')
+            .filter_map(|part| {
+                let mut fields = part.split(';').map(str::trim);
+                let tag = fields.next().filter(|t| !t.is_empty())?;
+                let quality = fields
+                    .find_map(|f| f.strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+                Some((quality, tag))
+            })
+            .filter(|(quality, _)| *quality > 0.0)
+            .collect();
+        // Stable, so equally weighted tags keep their order
+        wanted.sort_by(|a, b| b.0.total_cmp(&a.0));
+        wanted.iter().find_map(|(_, tag)| {
+            let tag = tag.to_ascii_lowercase();
+            let language = tag.split('-').next().unwrap_or_default();
+            let found = [tag.as_str(), language]
+                .into_iter()
+                .find(|l| self.texts.contains_key(*l))
+                .map(str::to_string);
+            found
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locales() -> Locales {
+        let mut locales = Locales::default();
+        locales
+            .add("en", r#"{"back": "Back to Menu", "run": "Execute Query"}"#)
+            .unwrap();
+        locales.add("de", r#"{"back": "Zurück zum Menü"}"#).unwrap();
+        locales
+    }
+
+    #[test]
+    fn test_texts() {
+        let texts = locales().texts("de");
+        assert_eq!(texts["back"], "Zurück zum Menü");
+        assert_eq!(texts["run"], "Execute Query");
+        assert_eq!(locales().texts("fr")["back"], "Back to Menu");
+        assert!(Locales::default().add("xx", "[1]").is_err());
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let locales = locales();
+        assert_eq!(locales.negotiate("de-CH, en;q=0.8").as_deref(), Some("de"));
+        assert_eq!(
+            locales.negotiate("fr, en;q=0.5, de;q=0.7").as_deref(),
+            Some("de")
+        );
+        assert_eq!(locales.negotiate("EN-us").as_deref(), Some("en"));
+        assert_eq!(locales.negotiate("fr, de;q=0").as_deref(), None);
+        assert_eq!(locales.negotiate("").as_deref(), None);
+    }
+}
@@ -0,0 +1,228 @@
+//! An overview of the indexes of every collection next to the configured
+//! queries that use the collection, to see why a query might be slow.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+use crate::arango::Client;
+use crate::config::QueryDefinition;
+use crate::error::{Error, Result};
+
+/// One index as described by `/_api/index`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexInfo {
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename(deserialize = "type"))]
+    pub kind: String, // primary, edge, persistent, inverted, ...
+    #[serde(default)]
+    pub fields: Vec<Value>, // Attribute names, or objects for inverted indexes
+    #[serde(default)]
+    pub unique: bool,
+    #[serde(default)]
+    pub sparse: bool,
+}
+
+/// A configured query, by its position in the configuration.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct QueryReference {
+    pub index: usize,
+    pub name: String,
+}
+
+/// The indexes of a collection and the queries using it.
+#[derive(Debug, Serialize, Clone)]
+pub struct CollectionIndexes {
+    pub name: String,
+    pub edge: bool,
+    pub indexes: Vec<IndexInfo>,
+    pub queries: Vec<QueryReference>,
+    pub hint: Option<String>,
+}
+
+/// The identifiers of an AQL text, including quoted names like
+/// `` `my-collection` ``. String literals and comments are skipped.
+fn identifiers(aql: &str) -> HashSet<&str> {
+    let mut names = HashSet::new();
+    let mut chars = aql.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '"' | '\'' | '`' | '´' => {
+                let mut escaped = false;
+                let mut end = aql.len();
+                for (i, d) in chars.by_ref() {
+                    if d == c && !escaped {
+                        end = i;
+                        break;
+                    }
+                    escaped = d == '\\' && !escaped;
+                }
+                if c == '`' || c == '´' {
+                    names.insert(&aql[start + c.len_utf8()..end]);
+                }
+            }
+            '/' if chars.peek().is_some_and(|(_, d)| *d == '/') => {
+                chars.by_ref().find(|(_, d)| *d == '\n');
+            }
+            '/' if chars.peek().is_some_and(|(_, d)| *d == '*') => {
+                chars.next();
+                let mut star = false;
+                for (_, d) in chars.by_ref() {
+                    if star && d == '/' {
+                        break;
+                    }
+                    star = d == '*';
+                }
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '@' => {
+                let mut end = aql.len();
+                while let Some((i, d)) = chars.peek() {
+                    if d.is_alphanumeric() || *d == '_' {
+                        chars.next();
+                    } else {
+                        end = *i;
+                        break;
+                    }
+                }
+                let word = &aql[start..end];
+                if !word.starts_with('@') {
+                    names.insert(word);
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// The configured queries whose AQL mentions a collection. Collections
+/// passed as bind parameters (`@@collection`) are not recognized.
+pub fn queries_using(queries: &[QueryDefinition], collection: &str) -> Vec<QueryReference> {
+    queries
+        .iter()
+        .enumerate()
+        .filter(|(_, q)| identifiers(&q.query).contains(collection))
+        .map(|(index, q)| QueryReference {
+            index,
+            name: q.name.clone(),
+        })
+        .collect()
+}
+
+/// A hint for collections used by queries but indexed by their key (and
+/// for edges `_from` and `_to`) only.
+fn hint(indexes: &[IndexInfo], queries: &[QueryReference]) -> Option<String> {
+    let only_builtin = indexes
+        .iter()
+        .all(|i| i.kind == "primary" || i.kind == "edge");
+    (only_builtin && !queries.is_empty()).then(|| {
+        "Only the built-in indexes exist, so FILTERs on other attributes scan the whole \
+         collection. EXPLAIN shows this as an EnumerateCollectionNode."
+            .to_string()
+    })
+}
+
+async fn get(client: &Client, path: &str) -> Result<Value> {
+    let response: Value = client
+        .request(reqwest::Method::GET, path)
+        .send()
+        .await?
+        .json()
+        .await?;
+    if response["error"].as_bool().unwrap_or(false) {
+        return Err(Error::from_response(&response));
+    }
+    Ok(response)
+}
+
+/// The indexes of all non-system collections, sorted by name, with the
+/// queries using each.
+pub async fn overview(
+    client: &Client,
+    queries: &[QueryDefinition],
+) -> Result<Vec<CollectionIndexes>> {
+    let response = get(client, "_api/collection?excludeSystem=true").await?;
+    let mut collections: Vec<(String, bool)> = response["result"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|c| Some((c["name"].as_str()?.to_string(), c["type"] == 3)))
+        .collect();
+    collections.sort();
+
+    let mut overview = Vec::new();
+    for (name, edge) in collections {
+        let response = get(client, &format!("_api/index?collection={}", name)).await?;
+        let indexes: Vec<IndexInfo> =
+            serde_json::from_value(response["indexes"].clone()).map_err(|e| Error::Arango {
+                code: 500,
+                error_num: 0,
+                message: format!("Unexpected list of indexes: {}", e),
+            })?;
+        let queries = queries_using(queries, &name);
+        overview.push(CollectionIndexes {
+            hint: hint(&indexes, &queries),
+            name,
+            edge,
+            indexes,
+            queries,
+        });
+    }
+    Ok(overview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_queries_using() {
+        let query = |name: &str, aql: &str| QueryDefinition::for_test(name, aql, &[]);
+        let queries = vec![
+            query("Users", "FOR u IN users FILTER u.name == 'orders' RETURN u"),
+            query("Orders", "FOR o IN `orders` RETURN o // users"),
+            query(
+                "Traversal",
+                "FOR v IN 1..2 OUTBOUND @start follows /* users */ RETURN v",
+            ),
+            query("Dynamic", "FOR d IN @@users RETURN d"),
+        ];
+        let names = |collection: &str| -> Vec<String> {
+            queries_using(&queries, collection)
+                .into_iter()
+                .map(|r| r.name)
+                .collect()
+        };
+        assert_eq!(names("users"), vec!["Users"]);
+        assert_eq!(names("orders"), vec!["Orders"]);
+        assert_eq!(names("follows"), vec!["Traversal"]);
+        assert!(names("u").contains(&"Users".to_string()));
+    }
+
+    #[test]
+    fn test_hint() {
+        let indexes: Vec<IndexInfo> = serde_json::from_value(json!([
+            { "id": "users/0", "name": "primary", "type": "primary", "fields": ["_key"], "unique": true },
+        ]))
+        .unwrap();
+        let used = vec![QueryReference {
+            index: 0,
+            name: "Users".to_string(),
+        }];
+        assert!(hint(&indexes, &used).is_some());
+        assert!(hint(&indexes, &[]).is_none());
+
+        let mut indexed = indexes.clone();
+        indexed.push(IndexInfo {
+            name: "idx_age".to_string(),
+            kind: "persistent".to_string(),
+            fields: vec![json!("age")],
+            unique: false,
+            sparse: false,
+        });
+        assert!(hint(&indexed, &used).is_none());
+    }
+}
@@ -0,0 +1,60 @@
+//! Run a catalog of preconfigured AQL queries against an ArangoDB database.
+//!
+//! The catalog is described by a [`config::Configuration`]. Queries are
+//! executed with [`runner::QueryRunner`], which uses the small
+//! [`arango::Client`] underneath. The warp based web interface lives in
+//! [`web`] and is optional for programs embedding the library.
+
+pub mod analytics;
+pub mod arango;
+pub mod archive;
+pub mod assets;
+pub mod benchmark;
+pub mod catalog;
+pub mod compare;
+pub mod config;
+pub mod cursors;
+pub mod cytoscape;
+pub mod demo;
+pub mod document_cache;
+pub mod error;
+pub mod error_reporting;
+pub mod explorer;
+pub mod export;
+pub mod files;
+pub mod gephi;
+pub mod git_store;
+pub mod graph_analyzer;
+pub mod graph_filter;
+pub mod history;
+pub mod i18n;
+pub mod indexes;
+pub mod materialize;
+pub mod mcp;
+#[cfg(any(test, feature = "mock-server"))]
+pub mod mock;
+pub mod output;
+pub mod proposals;
+pub mod proxy;
+pub mod queue;
+pub mod regression;
+pub mod renderer;
+pub mod runner;
+pub mod saved_graphs;
+pub mod search;
+pub mod seed;
+pub mod sessions;
+pub mod shutdown;
+pub mod slow_log;
+pub mod spill;
+pub mod status;
+pub mod templates;
+pub mod tenants;
+#[cfg(unix)]
+pub mod unix_socket;
+pub mod uploads;
+pub mod usage;
+pub mod validate;
+pub mod versions;
+pub mod viewer;
+pub mod web;
@@ -0,0 +1,165 @@
+//! Saving query results into a collection, e.g. to keep expensive
+//! intermediate results on the server for later queries.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::{info, instrument};
+
+use crate::arango::Client;
+use crate::error::{Error, Result};
+use crate::spill::SpillFile;
+
+/// How many documents are sent to `/_api/import` at once.
+const IMPORT_BATCH_SIZE: usize = 10000;
+
+/// How many spilled results are read from their file at once.
+const SPILLED_CHUNK_SIZE: usize = 10 * IMPORT_BATCH_SIZE;
+
+/// What happens to documents already in the collection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SaveMode {
+    Overwrite, // The collection is truncated first
+    Append,    // Documents with the same `_key` are replaced
+}
+
+impl SaveMode {
+    pub const ALL: &'static [SaveMode] = &[SaveMode::Append, SaveMode::Overwrite];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SaveMode::Overwrite => "overwrite",
+            SaveMode::Append => "append",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SaveMode> {
+        SaveMode::ALL.iter().copied().find(|m| m.name() == name)
+    }
+}
+
+/// What saving results into a collection did.
+#[derive(Debug, Serialize)]
+pub struct SaveReport {
+    pub collection: String,
+    pub created_collection: bool,
+    pub imported: u64,
+    pub errors: u64,
+}
+
+/// The documents to store for query results. Documents keep their `_key`
+/// (and edges `_from` and `_to`), but not their `_id` and `_rev`, which
+/// belong to their original collection. Other values are wrapped as
+/// `{"value": ...}`. Also returns whether all documents are edges.
+pub fn documents(results: &[Value]) -> (Vec<Value>, bool) {
+    let documents: Vec<Value> = results
+        .iter()
+        .map(|result| match result.as_object() {
+            Some(obj) => {
+                let mut obj = obj.clone();
+                obj.remove("_id");
+                obj.remove("_rev");
+                Value::Object(obj)
+            }
+            None => json!({ "value": result }),
+        })
+        .collect();
+    let edge = !documents.is_empty()
+        && documents
+            .iter()
+            .all(|d| d["_from"].is_string() && d["_to"].is_string());
+    (documents, edge)
+}
+
+/// Writes query results into a collection, creating it if needed. An edge
+/// collection is created if all results are edges.
+#[instrument(skip(client, results), fields(results = results.len()))]
+pub async fn save(
+    client: &Client,
+    collection: &str,
+    mode: SaveMode,
+    results: &[Value],
+) -> Result<SaveReport> {
+    if collection.is_empty()
+        || collection.starts_with('_')
+        || !collection
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(Error::InvalidParameter(format!(
+            "Invalid collection name '{}'",
+            collection
+        )));
+    }
+    let (documents, edge) = documents(results);
+    let created_collection = client.create_collection(collection, edge).await?;
+    if mode == SaveMode::Overwrite && !created_collection {
+        client.truncate_collection(collection).await?;
+    }
+    let mut report = SaveReport {
+        collection: collection.to_string(),
+        created_collection,
+        imported: 0,
+        errors: 0,
+    };
+    for batch in documents.chunks(IMPORT_BATCH_SIZE) {
+        let stats = client.import(collection, batch).await?;
+        report.imported +=
+            stats["created"].as_u64().unwrap_or(0) + stats["updated"].as_u64().unwrap_or(0);
+        report.errors += stats["errors"].as_u64().unwrap_or(0);
+    }
+    info!(
+        collection,
+        mode = mode.name(),
+        imported = report.imported,
+        errors = report.errors,
+        "Saved results"
+    );
+    Ok(report)
+}
+
+/// Saves results spilled to disk like [`save`], reading them a chunk at a
+/// time instead of all at once.
+pub async fn save_spilled(
+    client: &Client,
+    collection: &str,
+    mode: SaveMode,
+    file: &SpillFile,
+) -> Result<SaveReport> {
+    let (mut reader, chunk) = file.reader()?.read_chunk(SPILLED_CHUNK_SIZE).await?;
+    let mut report = save(client, collection, mode, &chunk).await?;
+    loop {
+        let chunk;
+        (reader, chunk) = reader.read_chunk(SPILLED_CHUNK_SIZE).await?;
+        if chunk.is_empty() {
+            break;
+        }
+        // Overwriting is done by the first chunk
+        let more = save(client, collection, SaveMode::Append, &chunk).await?;
+        report.imported += more.imported;
+        report.errors += more.errors;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_documents() {
+        let (docs, edge) = documents(&[
+            json!({ "_id": "users/a", "_key": "a", "_rev": "_x", "age": 3 }),
+            json!(42),
+        ]);
+        assert_eq!(
+            docs,
+            vec![json!({ "_key": "a", "age": 3 }), json!({ "value": 42 })]
+        );
+        assert!(!edge);
+
+        let (docs, edge) = documents(&[json!({ "_id": "e/1", "_from": "v/a", "_to": "v/b" })]);
+        assert_eq!(docs, vec![json!({ "_from": "v/a", "_to": "v/b" })]);
+        assert!(edge);
+        assert_eq!(SaveMode::from_name("overwrite"), Some(SaveMode::Overwrite));
+    }
+}
@@ -0,0 +1,237 @@
+//! A Model Context Protocol server on stdin/stdout. Every configured query is
+//! offered as a tool, so that assistants can discover and run the catalog.
+//!
+//! Messages are JSON-RPC 2.0, one per line. Log output goes to stderr and
+//! does not interfere with the protocol.
+
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::{debug, info, warn};
+
+use crate::config::{Configuration, QueryDefinition};
+use crate::error::Result;
+use crate::runner::QueryRunner;
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Derives a tool name from a query name. Tool names are restricted to
+/// ASCII letters, digits, `_` and `-`.
+pub fn tool_name(query_name: &str) -> String {
+    let name: String = query_name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect();
+    if name.is_empty() {
+        "query".to_string()
+    } else {
+        name
+    }
+}
+
+/// Describes a query as an MCP tool with a JSON schema for its parameters.
+fn tool_definition(query: &QueryDefinition) -> Value {
+    let properties: Map<String, Value> = query
+        .parameters
+        .iter()
+        .map(|p| {
+            let schema_type = match p.parameter_type.as_str() {
+                "number" | "boolean" => p.parameter_type.as_str(),
+                _ => "string",
+            };
+            (p.name.clone(), json!({ "type": schema_type }))
+        })
+        .collect();
+    let required: Vec<&str> = query.parameters.iter().map(|p| p.name.as_str()).collect();
+
+    json!({
+        "name": tool_name(&query.name),
+        "description": if query.description.is_empty() {
+            query.name.clone()
+        } else {
+            format!("{}: {}", query.name, query.description)
+        },
+        "inputSchema": {
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        }
+    })
+}
+
+/// Handles MCP requests using a [`QueryRunner`].
+pub struct McpServer {
+    runner: QueryRunner,
+}
+
+impl McpServer {
+    pub fn new(config: Arc<Configuration>) -> Self {
+        McpServer {
+            runner: QueryRunner::new(config),
+        }
+    }
+
+    fn find_tool(&self, name: &str) -> Option<&QueryDefinition> {
+        self.runner
+            .config()
+            .queries
+            .iter()
+            .find(|q| tool_name(&q.name) == name)
+    }
+
+    /// Answers a single JSON-RPC message. Notifications get no answer.
+    pub async fn handle(&self, message: &Value) -> Option<Value> {
+        let id = message.get("id").cloned()?;
+        let method = message["method"].as_str().unwrap_or_default();
+        debug!(method, "MCP request");
+
+        let outcome = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                }
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({
+                "tools": self
+                    .runner
+                    .config()
+                    .queries
+                    .iter()
+                    .map(tool_definition)
+                    .collect::<Vec<_>>()
+            })),
+            "tools/call" => self.call_tool(&message["params"]).await,
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+        };
+
+        Some(match outcome {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message }
+            }),
+        })
+    }
+
+    async fn call_tool(&self, params: &Value) -> std::result::Result<Value, (i64, String)> {
+        let name = params["name"].as_str().unwrap_or_default();
+        let query = self
+            .find_tool(name)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown tool '{}'", name)))?;
+
+        // The runner takes parameters as strings, like from a form, and
+        // converts them to the declared types itself
+        let arguments: HashMap<String, String> = params["arguments"]
+            .as_object()
+            .map(|args| {
+                args.iter()
+                    .map(|(k, v)| match v {
+                        Value::String(s) => (k.clone(), s.clone()),
+                        other => (k.clone(), other.to_string()),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Failures of the query are reported to the model, not as protocol errors
+        let (text, is_error) = match self.runner.run(&query.name, arguments).await {
+            Ok(execution) => {
+                info!(
+                    query = query.name.as_str(),
+                    rows = execution.results.len(),
+                    "Executed query for MCP client"
+                );
+                (
+                    serde_json::to_string_pretty(&execution.results).unwrap_or_default(),
+                    false,
+                )
+            }
+            Err(e) => {
+                warn!(query = query.name.as_str(), "Query failed: {}", e);
+                (e.to_string(), true)
+            }
+        };
+        Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": is_error,
+        }))
+    }
+}
+
+/// Serves MCP on stdin/stdout until stdin is closed.
+pub async fn serve_stdio(config: Configuration) -> Result<()> {
+    let server = McpServer::new(Arc::new(config));
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    info!("MCP server listening on stdin");
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle(&message).await,
+            Err(e) => Some(json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": PARSE_ERROR, "message": e.to_string() }
+            })),
+        };
+        if let Some(response) = response {
+            stdout
+                .write_all(format!("{}\n", response).as_bytes())
+                .await?;
+            stdout.flush().await?;
+        }
+    }
+    info!("MCP client disconnected");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_name() {
+        assert_eq!(tool_name("Get Users"), "get_users");
+        assert_eq!(tool_name("top-10 (by age)"), "top-10__by_age_");
+        assert_eq!(tool_name("  "), "query");
+    }
+
+    #[test]
+    fn test_tool_definition() {
+        let mut query = QueryDefinition::for_test(
+            "Get Users",
+            "FOR u IN users FILTER u.age >= @minAge RETURN u",
+            &[("minAge", "number")],
+        );
+        query.description = "Users above an age".to_string();
+        let tool = tool_definition(&query);
+        assert_eq!(tool["name"], "get_users");
+        assert_eq!(
+            tool["inputSchema"]["properties"]["minAge"]["type"],
+            "number"
+        );
+        assert_eq!(tool["inputSchema"]["required"], json!(["minAge"]));
+    }
+}
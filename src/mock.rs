@@ -0,0 +1,302 @@
+//! An in-process mock of the ArangoDB cursor API for tests, and of the
+//! lists of running and slow queries.
+//!
+//! Queries are answered from canned responses, matched by a substring of the
+//! query text. Results are handed out in batches like a real server does, so
+//! the cursor loop of [`Client::execute`] is exercised end to end. Available
+//! in unit tests and with the `mock-server` feature.
+
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use warp::http::StatusCode;
+use warp::Filter;
+
+use crate::arango::Client;
+
+#[derive(Clone)]
+enum Canned {
+    Rows(Vec<Value>),
+    Error {
+        code: u16,
+        error_num: i64,
+        message: String,
+    },
+}
+
+#[derive(Default)]
+struct State {
+    canned: Vec<(String, Canned)>,
+    batch_size: usize,
+    lose_cursors: bool,
+    cursors: Mutex<HashMap<String, VecDeque<Value>>>,
+    next_cursor_id: AtomicU64,
+    requests: Mutex<Vec<String>>,
+    options: Mutex<Vec<Value>>,
+    running: Mutex<Vec<Value>>, // As listed by `/_api/query/current`
+    slow: Vec<Value>,           // As listed by `/_api/query/slow`
+}
+
+type Reply = warp::reply::WithStatus<warp::reply::Json>;
+
+fn reply(body: Value, status: StatusCode) -> Reply {
+    warp::reply::with_status(warp::reply::json(&body), status)
+}
+
+fn error_reply(code: u16, error_num: i64, message: &str) -> Reply {
+    reply(
+        json!({
+            "error": true,
+            "code": code,
+            "errorNum": error_num,
+            "errorMessage": message,
+        }),
+        StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+    )
+}
+
+impl State {
+    fn log(&self, request: String) {
+        self.requests.lock().unwrap().push(request);
+    }
+
+    /// Hands out the next batch of a cursor, dropping it once exhausted.
+    fn batch(&self, id: &str, mut rows: VecDeque<Value>, code: u16) -> Reply {
+        let batch: Vec<Value> = rows.drain(..rows.len().min(self.batch_size)).collect();
+        let has_more = !rows.is_empty();
+        let mut body = json!({
+            "error": false,
+            "code": code,
+            "result": batch,
+            "hasMore": has_more,
+        });
+        if has_more {
+            body["id"] = json!(id);
+            self.cursors.lock().unwrap().insert(id.to_string(), rows);
+        } else {
+            body["extra"] = json!({ "stats": { "executionTime": 0.001 } });
+        }
+        reply(body, StatusCode::from_u16(code).unwrap())
+    }
+
+    fn create_cursor(&self, request: &Value) -> Reply {
+        let query = request["query"].as_str().unwrap_or_default();
+        self.log(format!("POST {}", query));
+        self.options
+            .lock()
+            .unwrap()
+            .push(request["options"].clone());
+        let canned = self
+            .canned
+            .iter()
+            .find(|(pattern, _)| query.contains(pattern.as_str()))
+            .map(|(_, canned)| canned.clone());
+        match canned {
+            Some(Canned::Rows(rows)) => {
+                let id = self.next_cursor_id.fetch_add(1, Ordering::SeqCst) + 1;
+                self.batch(&id.to_string(), rows.into(), 201)
+            }
+            Some(Canned::Error {
+                code,
+                error_num,
+                message,
+            }) => error_reply(code, error_num, &message),
+            None => error_reply(404, 1203, "collection or view not found"),
+        }
+    }
+
+    fn next_batch(&self, id: &str) -> Reply {
+        self.log(format!("PUT {}", id));
+        let rows = self.cursors.lock().unwrap().remove(id);
+        match rows.filter(|_| !self.lose_cursors) {
+            Some(rows) => self.batch(id, rows, 200),
+            None => error_reply(404, 1600, "cursor not found"),
+        }
+    }
+
+    fn delete_cursor(&self, id: &str) -> Reply {
+        self.log(format!("DELETE {}", id));
+        match self.cursors.lock().unwrap().remove(id) {
+            Some(_) => reply(
+                json!({ "error": false, "code": 202, "id": id }),
+                StatusCode::ACCEPTED,
+            ),
+            None => error_reply(404, 1600, "cursor not found"),
+        }
+    }
+
+    fn kill_query(&self, id: &str) -> Reply {
+        self.log(format!("KILL {}", id));
+        let mut running = self.running.lock().unwrap();
+        match running.iter().position(|q| q["id"] == id) {
+            Some(index) => {
+                running.remove(index);
+                reply(json!({ "error": false, "code": 200 }), StatusCode::OK)
+            }
+            None => error_reply(404, 1591, "query ID not found"),
+        }
+    }
+}
+
+/// Configures canned responses before starting a [`MockServer`].
+pub struct MockArango {
+    state: State,
+}
+
+impl Default for MockArango {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockArango {
+    pub fn new() -> Self {
+        MockArango {
+            state: State {
+                batch_size: 1000,
+                ..State::default()
+            },
+        }
+    }
+
+    /// Number of rows per batch, to force several round trips.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.state.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Lets every cursor vanish after its first batch, as happens when a
+    /// cursor times out on the server or a coordinator fails over.
+    pub fn lose_cursors(mut self) -> Self {
+        self.state.lose_cursors = true;
+        self
+    }
+
+    /// Answers queries containing `pattern` with the given rows.
+    pub fn rows(mut self, pattern: &str, rows: Vec<Value>) -> Self {
+        self.state
+            .canned
+            .push((pattern.to_string(), Canned::Rows(rows)));
+        self
+    }
+
+    /// Answers queries containing `pattern` with an error document.
+    pub fn error(mut self, pattern: &str, code: u16, error_num: i64, message: &str) -> Self {
+        self.state.canned.push((
+            pattern.to_string(),
+            Canned::Error {
+                code,
+                error_num,
+                message: message.to_string(),
+            },
+        ));
+        self
+    }
+
+    /// Lists a query as running on the server until it is killed.
+    pub fn running_query(self, query: Value) -> Self {
+        self.state.running.lock().unwrap().push(query);
+        self
+    }
+
+    /// Lists a query in the slow query history of the server.
+    pub fn slow_query(mut self, query: Value) -> Self {
+        self.state.slow.push(query);
+        self
+    }
+
+    /// Starts the server on a free local port.
+    pub fn start(self) -> MockServer {
+        let state = Arc::new(self.state);
+        let with_state = {
+            let state = Arc::clone(&state);
+            warp::any().map(move || Arc::clone(&state))
+        };
+
+        let create = warp::post()
+            .and(warp::path!("_api" / "cursor"))
+            .and(warp::body::json())
+            .and(with_state.clone())
+            .map(|request: Value, state: Arc<State>| state.create_cursor(&request));
+        let next = warp::put()
+            .and(warp::path!("_api" / "cursor" / String))
+            .and(with_state.clone())
+            .map(|id: String, state: Arc<State>| state.next_batch(&id));
+        let delete = warp::delete()
+            .and(warp::path!("_api" / "cursor" / String))
+            .and(with_state.clone())
+            .map(|id: String, state: Arc<State>| state.delete_cursor(&id));
+        let current = warp::get()
+            .and(warp::path!("_api" / "query" / "current"))
+            .and(with_state.clone())
+            .map(|state: Arc<State>| {
+                reply(
+                    Value::from(state.running.lock().unwrap().clone()),
+                    StatusCode::OK,
+                )
+            });
+        let slow = warp::get()
+            .and(warp::path!("_api" / "query" / "slow"))
+            .and(with_state.clone())
+            .map(|state: Arc<State>| reply(Value::from(state.slow.clone()), StatusCode::OK));
+        let kill = warp::delete()
+            .and(warp::path!("_api" / "query" / String))
+            .and(with_state)
+            .map(|id: String, state: Arc<State>| state.kill_query(&id));
+
+        let (shutdown, stopped) = tokio::sync::oneshot::channel::<()>();
+        let (addr, server) = warp::serve(create.or(next).or(delete).or(current).or(slow).or(kill))
+            .bind_with_graceful_shutdown(([127, 0, 0, 1], 0), async {
+                stopped.await.ok();
+            });
+        tokio::spawn(server);
+
+        MockServer {
+            endpoint: format!("http://{}/", addr),
+            state,
+            shutdown: Some(shutdown),
+        }
+    }
+}
+
+/// A running mock server. It stops when dropped.
+pub struct MockServer {
+    endpoint: String,
+    state: Arc<State>,
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// A client connected to the mock.
+    pub fn client(&self) -> Client {
+        Client::new(&self.endpoint, "root", "")
+    }
+
+    /// Cursors that have batches left on the server.
+    pub fn open_cursors(&self) -> usize {
+        self.state.cursors.lock().unwrap().len()
+    }
+
+    /// The requests received so far, e.g. `"POST <query>"` or `"PUT <id>"`.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// The `options` of the cursors created so far.
+    pub fn cursor_options(&self) -> Vec<Value> {
+        self.state.options.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use clap::ValueEnum;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use crate::error::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    Table,
+}
+
+pub fn format_results(results: &[Value], format: OutputFormat) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Json => {
+            serde_json::to_string_pretty(results).map_err(std::io::Error::from)? + "\n"
+        }
+        OutputFormat::Csv => to_csv(results),
+        OutputFormat::Table => to_table(results),
+    })
+}
+
+/// One compact JSON document per line, e.g. for streamed downloads.
+pub fn to_json_lines(results: &[Value]) -> String {
+    let mut lines = String::new();
+    for row in results {
+        lines.push_str(&row.to_string());
+        lines.push('\n');
+    }
+    lines
+}
+
+/// Collects the union of all attribute names of object rows, sorted. Rows that
+/// are not objects are shown in a single "value" column.
+pub fn columns(results: &[Value]) -> Vec<String> {
+    let mut columns = BTreeSet::new();
+    for row in results {
+        match row.as_object() {
+            Some(obj) => columns.extend(obj.keys().cloned()),
+            None => {
+                columns.insert("value".to_string());
+            }
+        }
+    }
+    columns.into_iter().collect()
+}
+
+pub fn cell(row: &Value, column: &str) -> String {
+    let value = match row.as_object() {
+        Some(obj) => obj.get(column),
+        None if column == "value" => Some(row),
+        None => None,
+    };
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+    }
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn to_csv(results: &[Value]) -> String {
+    to_csv_with_columns(results, &columns(results))
+}
+
+/// Writes the given columns of the rows as CSV, in that order.
+pub fn to_csv_with_columns(results: &[Value], columns: &[String]) -> String {
+    let mut out = String::new();
+    let header: Vec<String> = columns.iter().map(|c| csv_escape(c)).collect();
+    out.push_str(&header.join(","));
+    out.push('\n');
+    for row in results {
+        let fields: Vec<String> = columns.iter().map(|c| csv_escape(&cell(row, c))).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+pub fn to_table(results: &[Value]) -> String {
+    let columns = columns(results);
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .map(|row| columns.iter().map(|c| cell(row, c)).collect())
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            rows.iter()
+                .map(|r| r[i].chars().count())
+                .chain(std::iter::once(c.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let format_line = |cells: &[String]| -> String {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<width$}", c, width = w))
+            .collect();
+        padded.join(" | ").trim_end().to_string() + "\n"
+    };
+
+    let mut out = format_line(&columns);
+    let separator: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    out.push_str(&separator.join("-+-"));
+    out.push('\n');
+    for row in &rows {
+        out.push_str(&format_line(row));
+    }
+    out.push_str(&format!("({} rows)\n", rows.len()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_csv() {
+        let results = vec![
+            json!({"name": "Alice", "age": 30}),
+            json!({"name": "Bob, Jr.", "tags": ["a"]}),
+        ];
+        assert_eq!(
+            to_csv(&results),
+            "age,name,tags\n30,Alice,\n,\"Bob, Jr.\",\"[\"\"a\"\"]\"\n"
+        );
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let results = vec![json!({ "a": 1 }), json!("x")];
+        assert_eq!(to_json_lines(&results), "{\"a\":1}\n\"x\"\n");
+        assert_eq!(to_json_lines(&[]), "");
+    }
+
+    #[test]
+    fn test_table() {
+        let results = vec![json!({"name": "Alice", "age": 30}), json!(5)];
+        assert_eq!(
+            to_table(&results),
+            "age | name  | value\n----+-------+------\n30  | Alice |\n    |       | 5\n(2 rows)\n"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! Changes to the catalog proposed by users who may not change it directly,
+//! see `tenants`. A proposal waits until an admin approves it, which makes
+//! the query executable by everyone, or rejects it.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::config::QueryDefinition;
+use crate::error::{Error, Result};
+use crate::files::write_atomically;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ProposalsConfig {
+    #[serde(default)]
+    pub file: Option<String>, // Keep proposals across restarts
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProposalStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Proposal {
+    pub id: u64,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+    pub base: Option<QueryDefinition>, // The definition it changes; none for a new query
+    pub definition: QueryDefinition,
+    pub status: ProposalStatus,
+    #[serde(default)]
+    pub reviewer: Option<String>,
+    #[serde(default)]
+    pub reviewed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub comment: String, // Given by the reviewer
+}
+
+pub struct Proposals {
+    config: ProposalsConfig,
+    proposals: Mutex<Vec<Proposal>>,
+}
+
+impl Proposals {
+    pub fn new(config: ProposalsConfig) -> Self {
+        let mut proposals = Vec::new();
+        if let Some(file) = &config.file {
+            match load_from_file(file) {
+                Ok(loaded) => {
+                    info!(
+                        file = file.as_str(),
+                        proposals = loaded.len(),
+                        "Loaded query proposals"
+                    );
+                    proposals = loaded;
+                }
+                Err(e) => warn!(
+                    file = file.as_str(),
+                    "Could not load query proposals: {}", e
+                ),
+            }
+        }
+        Proposals {
+            config,
+            proposals: Mutex::new(proposals),
+        }
+    }
+
+    fn persist(&self, proposals: &[Proposal]) {
+        if let Some(file) = &self.config.file {
+            if let Err(e) = save_to_file(file, proposals) {
+                warn!(file = file.as_str(), "Failed to persist proposals: {}", e);
+            }
+        }
+    }
+
+    /// Files a proposal to change `base` into `definition`, or to add it if
+    /// there is no base. Returns its id.
+    pub fn propose(
+        &self,
+        author: &str,
+        base: Option<QueryDefinition>,
+        definition: QueryDefinition,
+    ) -> u64 {
+        let mut proposals = self.proposals.lock().unwrap();
+        let id = proposals.iter().map(|p| p.id).max().unwrap_or(0) + 1;
+        proposals.push(Proposal {
+            id,
+            author: author.to_string(),
+            created_at: Utc::now(),
+            base,
+            definition,
+            status: ProposalStatus::Pending,
+            reviewer: None,
+            reviewed_at: None,
+            comment: String::new(),
+        });
+        self.persist(&proposals);
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Result<Proposal> {
+        self.proposals
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|p| p.id == id)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("Proposal {}", id)))
+    }
+
+    /// All proposals, newest first.
+    pub fn list(&self) -> Vec<Proposal> {
+        let mut proposals = self.proposals.lock().unwrap().clone();
+        proposals.reverse();
+        proposals
+    }
+
+    pub fn pending(&self) -> usize {
+        self.proposals
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.status == ProposalStatus::Pending)
+            .count()
+    }
+
+    /// Approves or rejects a pending proposal.
+    pub fn review(
+        &self,
+        id: u64,
+        status: ProposalStatus,
+        reviewer: &str,
+        comment: &str,
+    ) -> Result<Proposal> {
+        let mut proposals = self.proposals.lock().unwrap();
+        let proposal = proposals
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or_else(|| Error::NotFound(format!("Proposal {}", id)))?;
+        if proposal.status != ProposalStatus::Pending {
+            return Err(Error::InvalidParameter(format!(
+                "Proposal {} was already reviewed",
+                id
+            )));
+        }
+        proposal.status = status;
+        proposal.reviewer = Some(reviewer.to_string());
+        proposal.reviewed_at = Some(Utc::now());
+        proposal.comment = comment.to_string();
+        let reviewed = proposal.clone();
+        self.persist(&proposals);
+        Ok(reviewed)
+    }
+}
+
+fn load_from_file(path: &str) -> Result<Vec<Proposal>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    serde_json::from_str(&contents)
+        .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path, e)))
+}
+
+fn save_to_file(path: &str, proposals: &[Proposal]) -> Result<()> {
+    let json = serde_json::to_string_pretty(proposals).map_err(|e| Error::Config(e.to_string()))?;
+    write_atomically(path, (json + "\n").as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_review() {
+        let path = std::env::temp_dir().join(format!("proposals-{}.json", uuid::Uuid::new_v4()));
+        let config = ProposalsConfig {
+            file: Some(path.to_str().unwrap().to_string()),
+        };
+        let proposals = Proposals::new(config.clone());
+        let first = proposals.propose(
+            "carol",
+            Some(QueryDefinition::for_test("q", "RETURN 1", &[])),
+            QueryDefinition::for_test("q", "RETURN 2", &[]),
+        );
+        let second = proposals.propose(
+            "carol",
+            None,
+            QueryDefinition::for_test("q", "RETURN 3", &[]),
+        );
+        assert_eq!(proposals.pending(), 2);
+        assert_eq!(proposals.list()[0].id, second);
+
+        let reviewed = proposals
+            .review(first, ProposalStatus::Approved, "alice", "")
+            .unwrap();
+        assert_eq!(reviewed.reviewer.as_deref(), Some("alice"));
+        // Reviewed once only
+        assert!(matches!(
+            proposals.review(first, ProposalStatus::Rejected, "bob", "no"),
+            Err(Error::InvalidParameter(_))
+        ));
+        assert!(matches!(proposals.get(7), Err(Error::NotFound(_))));
+
+        // Kept across restarts
+        let reloaded = Proposals::new(config);
+        assert_eq!(reloaded.pending(), 1);
+        assert_eq!(
+            reloaded.get(first).unwrap().status,
+            ProposalStatus::Approved
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+//! The outbound HTTP proxy for all connections of the runner: to ArangoDB,
+//! Cytoscape, Gephi and the error reporting endpoints. Without a configured
+//! proxy, the usual `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` environment
+//! variables apply.
+
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ProxyConfig {
+    #[serde(default)]
+    pub url: Option<String>, // e.g. "http://proxy.example.com:3128"
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub no_proxy: Vec<String>, // Hosts, domains (".example.com") and IP ranges reached directly
+}
+
+/// The installed settings, and the client all connections share once
+/// [`install`] was called.
+static INSTALLED: RwLock<Option<(ProxyConfig, reqwest::Client)>> = RwLock::new(None);
+
+/// A client builder that goes through the configured proxy.
+pub fn builder(config: &ProxyConfig) -> Result<reqwest::ClientBuilder> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(url) = &config.url {
+        let mut proxy = reqwest::Proxy::all(url)
+            .map_err(|e| Error::Config(format!("Invalid proxy URL '{}': {}", url, e)))?;
+        if let Some(username) = &config.username {
+            proxy = proxy.basic_auth(username, config.password.as_deref().unwrap_or_default());
+        }
+        proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&config.no_proxy.join(",")));
+        builder = builder.proxy(proxy);
+    }
+    Ok(builder)
+}
+
+/// Makes all clients created by [`client`] from now on use the proxy.
+pub fn install(config: &ProxyConfig) -> Result<()> {
+    let client = builder(config)?
+        .build()
+        .map_err(|e| Error::Config(format!("Invalid proxy settings: {}", e)))?;
+    *INSTALLED.write().unwrap() = Some((config.clone(), client));
+    Ok(())
+}
+
+/// An HTTP client for outbound connections. Clones share their connection
+/// pool.
+pub fn client() -> reqwest::Client {
+    INSTALLED
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(|(_, client)| client.clone())
+        .unwrap_or_default()
+}
+
+/// A builder for clients with settings of their own, e.g. timeouts, that
+/// go through the installed proxy.
+pub fn client_builder() -> reqwest::ClientBuilder {
+    match &*INSTALLED.read().unwrap() {
+        // Checked by `install`
+        Some((config, _)) => builder(config).unwrap_or_default(),
+        None => reqwest::Client::builder(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A proxy answering one request, returning the request line.
+    async fn proxy() -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let read = stream.read(&mut request).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await
+                .unwrap();
+            let request = String::from_utf8_lossy(&request[..read]).into_owned();
+            request.lines().next().unwrap_or_default().to_string()
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_builder() {
+        let (url, handle) = proxy().await;
+        let config = ProxyConfig {
+            url: Some(url),
+            no_proxy: vec!["direct.invalid".to_string()],
+            ..ProxyConfig::default()
+        };
+        let client = builder(&config).unwrap().build().unwrap();
+        let body = client
+            .get("http://arangodb.invalid:8529/_api/version")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, "ok");
+        assert_eq!(
+            handle.await.unwrap(),
+            "GET http://arangodb.invalid:8529/_api/version HTTP/1.1"
+        );
+        // Hosts on the no-proxy list are connected to directly, and so
+        // cannot be resolved here
+        assert!(client.get("http://direct.invalid/").send().await.is_err());
+
+        let invalid = ProxyConfig {
+            url: Some("not a url".to_string()),
+            ..ProxyConfig::default()
+        };
+        assert!(matches!(builder(&invalid), Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,185 @@
+//! Lines for expensive queries declared with `max_concurrent`: beyond that
+//! many executions at once, further requests wait in the order they came
+//! in instead of failing. Every waiting request has a ticket, chosen by the
+//! browser, to ask for its position in the line.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+#[derive(Default)]
+struct Line {
+    running: usize,
+    waiting: VecDeque<(u64, String)>, // Internal id and ticket, first come first
+}
+
+/// The lines of all queries, by query name.
+#[derive(Default)]
+pub struct ExecutionQueue {
+    lines: Mutex<HashMap<String, Line>>,
+    changed: Notify,
+    next_id: AtomicU64,
+}
+
+/// Admission to run a query. The execution counts as running until it is
+/// dropped, which lets the next request in.
+pub struct Permit {
+    queue: Arc<ExecutionQueue>,
+    query_name: String,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut lines = self.queue.lines.lock().unwrap();
+        if let Some(line) = lines.get_mut(&self.query_name) {
+            line.running -= 1;
+            if line.running == 0 && line.waiting.is_empty() {
+                lines.remove(&self.query_name);
+            }
+        }
+        drop(lines);
+        self.queue.changed.notify_waiters();
+    }
+}
+
+/// A request waiting in line. It leaves the line when dropped before it is
+/// admitted, e.g. because the browser went away.
+struct Waiting<'a> {
+    queue: &'a ExecutionQueue,
+    query_name: &'a str,
+    id: u64,
+    admitted: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if self.admitted {
+            return;
+        }
+        let mut lines = self.queue.lines.lock().unwrap();
+        if let Some(line) = lines.get_mut(self.query_name) {
+            line.waiting.retain(|(id, _)| *id != self.id);
+            if line.running == 0 && line.waiting.is_empty() {
+                lines.remove(self.query_name);
+            }
+        }
+        drop(lines);
+        self.queue.changed.notify_waiters();
+    }
+}
+
+impl ExecutionQueue {
+    /// Waits until fewer than `max_concurrent` executions of the query are
+    /// running and all requests that came in earlier were admitted.
+    pub async fn enter(
+        self: &Arc<Self>,
+        query_name: &str,
+        max_concurrent: usize,
+        ticket: &str,
+    ) -> Permit {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.lines
+            .lock()
+            .unwrap()
+            .entry(query_name.to_string())
+            .or_default()
+            .waiting
+            .push_back((id, ticket.to_string()));
+        let mut waiting = Waiting {
+            queue: self,
+            query_name,
+            id,
+            admitted: false,
+        };
+
+        loop {
+            // Registered before looking, so that no change is missed
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            {
+                let mut lines = self.lines.lock().unwrap();
+                let line = lines.entry(query_name.to_string()).or_default();
+                if line.running < max_concurrent.max(1)
+                    && line.waiting.front().is_some_and(|(first, _)| *first == id)
+                {
+                    line.waiting.pop_front();
+                    line.running += 1;
+                    waiting.admitted = true;
+                    drop(lines);
+                    // Everybody behind moved up
+                    self.changed.notify_waiters();
+                    return Permit {
+                        queue: Arc::clone(self),
+                        query_name: query_name.to_string(),
+                    };
+                }
+            }
+            changed.await;
+        }
+    }
+
+    /// The position of a waiting request in its line, 1 for the next one
+    /// to run. `None` if the ticket is not (or no longer) waiting.
+    pub fn position(&self, ticket: &str) -> Option<usize> {
+        self.lines.lock().unwrap().values().find_map(|line| {
+            line.waiting
+                .iter()
+                .position(|(_, t)| t == ticket)
+                .map(|index| index + 1)
+        })
+    }
+
+    /// How many requests are waiting in all lines.
+    pub fn waiting(&self) -> usize {
+        self.lines
+            .lock()
+            .unwrap()
+            .values()
+            .map(|line| line.waiting.len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_line() {
+        let queue = Arc::new(ExecutionQueue::default());
+        let first = queue.enter("q", 1, "a").await;
+        // Other queries have lines of their own
+        let other = queue.enter("other", 1, "x").await;
+
+        let waiting = |ticket: &'static str| {
+            let queue = Arc::clone(&queue);
+            tokio::spawn(async move { queue.enter("q", 1, ticket).await })
+        };
+        let second = waiting("b");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let third = waiting("c");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(queue.position("b"), Some(1));
+        assert_eq!(queue.position("c"), Some(2));
+        assert_eq!(queue.waiting(), 2);
+
+        // A request that goes away leaves the line
+        second.abort();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(queue.position("b"), None);
+        assert_eq!(queue.position("c"), Some(1));
+
+        drop(first);
+        let third = tokio::time::timeout(Duration::from_secs(1), third)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(queue.waiting(), 0);
+        drop(third);
+        drop(other);
+        assert!(queue.lines.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+//! Regression tests of the catalog. A query definition may declare `tests`,
+//! each with bind variables and what the query must return for them: the
+//! exact results, a number of rows or invariants every row fulfills. The
+//! `test` subcommand runs them against a database, e.g. in CI before a
+//! changed catalog is deployed.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::compare;
+use crate::config::QueryDefinition;
+use crate::graph_filter::Predicate;
+use crate::runner::QueryRunner;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueryTest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>, // Else the tests of a query are numbered
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub bind_vars: HashMap<String, Value>, // Passed as they are, e.g. numbers as numbers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<Vec<Value>>, // The exact results
+    #[serde(default)]
+    pub ordered: bool, // Compare `expected` in order; else in any order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_rows: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rows: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invariants: Vec<String>, // Predicates like `age >= 18`, see `graph_filter`
+}
+
+impl QueryTest {
+    /// The name of the test, or its number among those of its query.
+    pub fn label(&self, index: usize) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("#{}", index + 1))
+    }
+
+    /// Why `results` are not what the test expects; empty if they are.
+    pub fn check(&self, results: &[Value]) -> Vec<String> {
+        let mut failures = Vec::new();
+        let rows = results.len();
+        if let Some(expected) = self.rows.filter(|expected| *expected != rows) {
+            failures.push(format!("Expected {} rows, got {}", expected, rows));
+        }
+        if let Some(min) = self.min_rows.filter(|min| rows < *min) {
+            failures.push(format!("Expected at least {} rows, got {}", min, rows));
+        }
+        if let Some(max) = self.max_rows.filter(|max| rows > *max) {
+            failures.push(format!("Expected at most {} rows, got {}", max, rows));
+        }
+        if let Some(expected) = &self.expected {
+            let diff = compare::diff_results(expected, results);
+            if !diff.same_rows {
+                failures.push(format!(
+                    "Results differ: {} expected rows missing, {} unexpected rows",
+                    diff.only_in_original, diff.only_in_alternative
+                ));
+                for row in &diff.original_examples {
+                    failures.push(format!("  missing: {}", row));
+                }
+                for row in &diff.alternative_examples {
+                    failures.push(format!("  unexpected: {}", row));
+                }
+            } else if self.ordered && !diff.identical {
+                failures.push("Results are the expected ones, but in another order".to_string());
+            }
+        }
+        for invariant in &self.invariants {
+            let predicate = match Predicate::parse(invariant) {
+                Ok(predicate) => predicate,
+                Err(e) => {
+                    failures.push(e.to_string());
+                    continue;
+                }
+            };
+            let broken: Vec<usize> = results
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| !predicate.matches(row))
+                .map(|(i, _)| i)
+                .collect();
+            if let Some(first) = broken.first() {
+                failures.push(format!(
+                    "{} of {} rows break '{}', e.g. row {}: {}",
+                    broken.len(),
+                    rows,
+                    invariant,
+                    first + 1,
+                    results[*first]
+                ));
+            }
+        }
+        failures
+    }
+}
+
+/// The outcome of one test.
+#[derive(Debug)]
+pub struct TestOutcome {
+    pub query_name: String,
+    pub test_name: String,
+    pub failures: Vec<String>, // Empty if it passed
+    pub duration: Duration,
+}
+
+impl TestOutcome {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Runs the tests of `queries` one after the other. A query that fails to
+/// execute fails its test.
+pub async fn run(runner: &QueryRunner, queries: &[QueryDefinition]) -> Vec<TestOutcome> {
+    let mut outcomes = Vec::new();
+    for query in queries {
+        for (index, test) in query.tests.iter().enumerate() {
+            let started = Instant::now();
+            let missing: Vec<&str> = query
+                .parameters
+                .iter()
+                .filter(|p| !test.bind_vars.contains_key(&p.name))
+                .map(|p| p.name.as_str())
+                .collect();
+            let failures = if !missing.is_empty() {
+                vec![format!("Missing bind variables: {}", missing.join(", "))]
+            } else {
+                match runner.execute(query, test.bind_vars.clone()).await {
+                    Ok(execution) => test.check(&execution.results),
+                    Err(e) => vec![format!("Execution failed: {}", e)],
+                }
+            };
+            outcomes.push(TestOutcome {
+                query_name: query.name.clone(),
+                test_name: test.label(index),
+                failures,
+                duration: started.elapsed(),
+            });
+        }
+    }
+    outcomes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Configuration;
+    use crate::mock::MockArango;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn test(definition: Value) -> QueryTest {
+        serde_json::from_value(definition).unwrap()
+    }
+
+    #[test]
+    fn test_check() {
+        let results = [json!({ "age": 20 }), json!({ "age": 30 })];
+        let passing = test(json!({
+            "expected": [{ "age": 30 }, { "age": 20 }],
+            "rows": 2,
+            "min_rows": 1,
+            "invariants": ["age >= 18"]
+        }));
+        assert!(passing.check(&results).is_empty());
+
+        let ordered = test(json!({ "expected": [{ "age": 30 }, { "age": 20 }], "ordered": true }));
+        assert_eq!(ordered.check(&results).len(), 1);
+
+        let failures = test(json!({
+            "expected": [{ "age": 20 }],
+            "max_rows": 1,
+            "invariants": ["age > 25", "no operator"]
+        }))
+        .check(&results);
+        assert_eq!(failures[0], "Expected at most 1 rows, got 2");
+        assert!(failures[1].starts_with("Results differ: 0 expected rows missing, 1 unexpected"));
+        assert_eq!(
+            failures[3],
+            "1 of 2 rows break 'age > 25', e.g. row 1: {\"age\":20}"
+        );
+        assert!(failures[4].contains("No comparison operator"));
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let mock = MockArango::new()
+            .rows("FOR d IN docs", vec![json!({ "n": 1 }), json!({ "n": 2 })])
+            .error("BROKEN", 400, 1501, "syntax error")
+            .start();
+        let config: Configuration = serde_json::from_value(json!({
+            "arangodb_endpoint": mock.endpoint(),
+            "username": "root",
+            "password": "",
+            "queries": [
+                {
+                    "name": "docs", "description": "", "parameters": [],
+                    "query": "FOR d IN docs RETURN d",
+                    "tests": [{ "name": "two", "rows": 2 }, { "invariants": ["n == 1"] }]
+                },
+                {
+                    "name": "broken", "description": "", "query": "BROKEN @x",
+                    "parameters": [{ "name": "x", "parameter_type": "number" }],
+                    "tests": [{}, { "bind_vars": { "x": 1 } }]
+                }
+            ]
+        }))
+        .unwrap();
+        let config = Arc::new(config);
+        let runner = QueryRunner::with_client(Arc::clone(&config), mock.client());
+
+        let outcomes = run(&runner, &config.queries).await;
+        let summary: Vec<(&str, bool)> = outcomes
+            .iter()
+            .map(|o| (o.test_name.as_str(), o.passed()))
+            .collect();
+        assert_eq!(
+            summary,
+            [("two", true), ("#2", false), ("#1", false), ("#2", false)]
+        );
+        assert_eq!(outcomes[2].failures, ["Missing bind variables: x"]);
+        assert!(outcomes[3].failures[0].starts_with("Execution failed"));
+    }
+}
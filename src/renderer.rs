@@ -0,0 +1,288 @@
+//! The shapes a result page can take. A query may declare one with
+//! `renderer`; without it, the page shows the JSON and whatever graph the
+//! results turn out to be.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How the results of a query are presented.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub enum ResultRenderer {
+    Json,             // The pretty-printed JSON only
+    Table,            // One column per top-level attribute
+    Graph,            // Vertices and edges, which the results must be
+    Chart,            // Bars of a label and a number per row
+    Map,              // Points of a latitude and longitude per row
+    Template(String), // A template of the templates directory, e.g. "orders.html"
+}
+
+impl ResultRenderer {
+    pub const BUILT_IN: &'static [&'static str] = &["json", "table", "graph", "chart", "map"];
+
+    pub fn name(&self) -> &str {
+        match self {
+            ResultRenderer::Json => "json",
+            ResultRenderer::Table => "table",
+            ResultRenderer::Graph => "graph",
+            ResultRenderer::Chart => "chart",
+            ResultRenderer::Map => "map",
+            ResultRenderer::Template(name) => name,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(ResultRenderer::Json),
+            "table" => Some(ResultRenderer::Table),
+            "graph" => Some(ResultRenderer::Graph),
+            "chart" => Some(ResultRenderer::Chart),
+            "map" => Some(ResultRenderer::Map),
+            _ if name.ends_with(".html") => Some(ResultRenderer::Template(name.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<String> for ResultRenderer {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+        ResultRenderer::from_name(&name).ok_or_else(|| {
+            format!(
+                "Unknown renderer '{}', expected one of {} or a template ending in .html",
+                name,
+                ResultRenderer::BUILT_IN.join(", ")
+            )
+        })
+    }
+}
+
+impl From<ResultRenderer> for String {
+    fn from(renderer: ResultRenderer) -> String {
+        renderer.name().to_string()
+    }
+}
+
+#[derive(Debug, Serialize, Default, PartialEq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Name of the column of results that are not objects.
+const VALUE_COLUMN: &str = "value";
+
+/// The results as a table, with the attributes of all rows as columns in
+/// the order they first appear.
+pub fn table(results: &[Value]) -> Table {
+    let mut columns: Vec<String> = Vec::new();
+    for row in results {
+        let keys: Vec<&str> = match row {
+            Value::Object(object) => object.keys().map(String::as_str).collect(),
+            _ => vec![VALUE_COLUMN],
+        };
+        for key in keys {
+            if !columns.iter().any(|c| c == key) {
+                columns.push(key.to_string());
+            }
+        }
+    }
+    let rows = results
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| match row {
+                    Value::Object(object) => object.get(column).map(cell).unwrap_or_default(),
+                    _ if column == VALUE_COLUMN => cell(row),
+                    _ => String::new(),
+                })
+                .collect()
+        })
+        .collect();
+    Table { columns, rows }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Bar {
+    pub label: String,
+    pub value: f64,
+    pub percent: f64, // Of the largest value
+}
+
+/// Bars for the rows that have a number: the first numeric attribute is
+/// the value, the `label` attribute or else the first string one the label.
+pub fn chart(results: &[Value]) -> Vec<Bar> {
+    let mut bars: Vec<Bar> = results
+        .iter()
+        .enumerate()
+        .filter_map(|(i, row)| {
+            let (label, value) = match row {
+                Value::Number(n) => ((i + 1).to_string(), n.as_f64()?),
+                Value::Object(object) => {
+                    let value = object.values().find_map(Value::as_f64)?;
+                    let label = object
+                        .get("label")
+                        .or_else(|| object.values().find(|v| v.is_string()))
+                        .map(cell)
+                        .unwrap_or_else(|| (i + 1).to_string());
+                    (label, value)
+                }
+                _ => return None,
+            };
+            Some(Bar {
+                label,
+                value,
+                percent: 0.0,
+            })
+        })
+        .collect();
+    let max = bars.iter().map(|b| b.value.abs()).fold(0.0, f64::max);
+    if max > 0.0 {
+        for bar in &mut bars {
+            bar.percent = bar.value.abs() / max * 100.0;
+        }
+    }
+    bars
+}
+
+pub const MAP_WIDTH: f64 = 720.0;
+pub const MAP_HEIGHT: f64 = 360.0;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct MapPoint {
+    pub label: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub x: f64, // On a map of MAP_WIDTH by MAP_HEIGHT, equirectangular
+    pub y: f64,
+}
+
+/// The points of the rows that have coordinates, as `latitude`/`lat` and
+/// `longitude`/`lon`/`lng` attributes or as a GeoJSON point, the row
+/// itself or one of its attributes.
+pub fn points(results: &[Value]) -> Vec<MapPoint> {
+    results
+        .iter()
+        .enumerate()
+        .filter_map(|(i, row)| {
+            let (latitude, longitude) =
+                coordinates(row).or_else(|| row.as_object()?.values().find_map(geo_json_point))?;
+            let label = row
+                .get("label")
+                .or_else(|| row.get("name"))
+                .or_else(|| row.get("_key"))
+                .map(cell)
+                .unwrap_or_else(|| (i + 1).to_string());
+            Some(MapPoint {
+                label,
+                latitude,
+                longitude,
+                x: (longitude + 180.0) / 360.0 * MAP_WIDTH,
+                y: (90.0 - latitude) / 180.0 * MAP_HEIGHT,
+            })
+        })
+        .collect()
+}
+
+fn coordinates(row: &Value) -> Option<(f64, f64)> {
+    if let Some(point) = geo_json_point(row) {
+        return Some(point);
+    }
+    let attribute = |names: &[&str]| names.iter().find_map(|n| row.get(*n)?.as_f64());
+    let latitude = attribute(&["latitude", "lat"])?;
+    let longitude = attribute(&["longitude", "lon", "lng"])?;
+    valid(latitude, longitude)
+}
+
+/// `{"type": "Point", "coordinates": [longitude, latitude]}`
+fn geo_json_point(value: &Value) -> Option<(f64, f64)> {
+    if value.get("type")?.as_str()? != "Point" {
+        return None;
+    }
+    let coordinates = value.get("coordinates")?.as_array()?;
+    valid(
+        coordinates.get(1)?.as_f64()?,
+        coordinates.first()?.as_f64()?,
+    )
+}
+
+fn valid(latitude: f64, longitude: f64) -> Option<(f64, f64)> {
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+        .then_some((latitude, longitude))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_names() {
+        let renderer: ResultRenderer = serde_json::from_value(json!("orders.html")).unwrap();
+        assert_eq!(
+            renderer,
+            ResultRenderer::Template("orders.html".to_string())
+        );
+        assert_eq!(
+            serde_json::to_value(ResultRenderer::Map).unwrap(),
+            json!("map")
+        );
+        assert!(serde_json::from_value::<ResultRenderer>(json!("pie")).is_err());
+    }
+
+    #[test]
+    fn test_table() {
+        let table = table(&[
+            json!({"a": 1, "b": "x"}),
+            json!({"c": null, "a": [2]}),
+            json!(3),
+        ]);
+        assert_eq!(table.columns, ["a", "b", "c", "value"]);
+        assert_eq!(
+            table.rows,
+            [
+                vec!["1", "x", "", ""],
+                vec!["[2]", "", "", ""],
+                vec!["", "", "", "3"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chart_and_points() {
+        let bars = chart(&[
+            json!({"city": "Köln", "orders": 5}),
+            json!({"label": "Bonn", "orders": 10}),
+            json!("no number"),
+        ]);
+        assert_eq!(
+            bars.iter()
+                .map(|b| (b.label.as_str(), b.percent))
+                .collect::<Vec<_>>(),
+            [("Köln", 50.0), ("Bonn", 100.0)]
+        );
+
+        let points = points(&[
+            json!({"name": "a", "lat": 0.0, "lon": 0.0}),
+            json!({"_key": "b", "location": {"type": "Point", "coordinates": [180.0, -90.0]}}),
+            json!({"lat": 100.0, "lon": 0.0}),
+        ]);
+        assert_eq!(points.len(), 2);
+        assert_eq!(
+            (points[0].x, points[0].y),
+            (MAP_WIDTH / 2.0, MAP_HEIGHT / 2.0)
+        );
+        assert_eq!(points[1].label, "b");
+        assert_eq!((points[1].x, points[1].y), (MAP_WIDTH, MAP_HEIGHT));
+    }
+}
@@ -0,0 +1,372 @@
+//! Executes queries of the catalog with parameters given as strings, as they
+//! arrive from HTML forms or the command line.
+
+use reqwest::Url;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::arango::{Client, CursorOptions, QueryResult};
+use crate::config::{Configuration, QueryDefinition};
+use crate::cursors::CursorStore;
+use crate::document_cache::DocumentCache;
+use crate::error::{Error, Result};
+use crate::history::QueryHistory;
+use crate::saved_graphs::SavedGraphs;
+use crate::slow_log::SlowQueryLog;
+use crate::spill::{Rows, SpilledResults, Spiller};
+use crate::uploads::Uploads;
+use crate::usage::UsageStats;
+use crate::viewer::GraphStore;
+
+/// The outcome of a single query execution.
+pub struct Execution {
+    pub results: Vec<Value>,
+    pub stats: Value,
+    pub duration: Duration,
+}
+
+/// An execution whose results may have been spilled to disk, see `spill`.
+pub struct SpillingExecution {
+    pub rows: Rows,
+    pub stats: Value,
+    pub duration: Duration,
+}
+
+/// Converts form or command line parameters to bind variables, using the
+/// declared parameter types of the query.
+pub fn convert_bind_vars(
+    query: &QueryDefinition,
+    params: HashMap<String, String>,
+) -> Result<HashMap<String, Value>> {
+    params
+        .into_iter()
+        .map(|(k, v)| {
+            let param_type = query
+                .parameters
+                .iter()
+                .find(|p| p.name == k)
+                .map(|p| p.parameter_type.as_str())
+                .unwrap_or("string");
+
+            let value = match param_type {
+                "number" => Value::Number(v.parse().map_err(|_| {
+                    Error::InvalidParameter(format!("'{}' is not a number: {}", k, v))
+                })?),
+                "boolean" => Value::Bool(v.parse().map_err(|_| {
+                    Error::InvalidParameter(format!("'{}' is not a boolean: {}", k, v))
+                })?),
+                _ => Value::String(v),
+            };
+
+            Ok((k, value))
+        })
+        .collect()
+}
+
+/// Runs catalog queries against the configured database.
+pub struct QueryRunner {
+    config: Arc<Configuration>,
+    client: Client,
+    documents: DocumentCache, // Of the database of the client
+    spilled: SpilledResults,  // Of its queries, for download
+    graphs: Arc<GraphStore>,  // Kept for the viewer
+    saved_graphs: Arc<SavedGraphs>,
+    uploads: Arc<Uploads>,       // To Cytoscape
+    slow_log: Arc<SlowQueryLog>, // Of its queries, with their bind variables
+    cursors: Arc<CursorStore>,   // Open on its database, for scrolling
+    history: Arc<QueryHistory>,  // Of the runs of its queries
+    usage: Arc<UsageStats>,      // Of its queries, by user
+}
+
+impl QueryRunner {
+    pub fn new(config: Arc<Configuration>) -> Self {
+        let client = Client::from_config(&config);
+        Self::with_client(config, client)
+    }
+
+    /// A runner using another connection than the configured one, e.g. to
+    /// the database of a tenant.
+    pub fn with_client(config: Arc<Configuration>, client: Client) -> Self {
+        Self::build(config, client, None)
+    }
+
+    /// The runner of a tenant, with graphs, uploads, slow queries, cursors,
+    /// run history and usage statistics of its own that no other tenant
+    /// sees.
+    pub fn for_tenant(config: Arc<Configuration>, client: Client, tenant: &str) -> Self {
+        Self::build(config, client, Some(tenant))
+    }
+
+    fn build(config: Arc<Configuration>, client: Client, tenant: Option<&str>) -> Self {
+        let (saved_graphs, history, usage) = match tenant {
+            Some(tenant) => (
+                config.saved_graphs.for_tenant(tenant),
+                config.performance_history.for_tenant(tenant),
+                config.usage.for_tenant(tenant),
+            ),
+            None => (
+                config.saved_graphs.clone(),
+                config.performance_history.clone(),
+                config.usage.clone(),
+            ),
+        };
+        QueryRunner {
+            documents: DocumentCache::new(&config.document_cache),
+            spilled: SpilledResults::new(&config.spill),
+            graphs: Arc::new(GraphStore::new(&config.viewer)),
+            saved_graphs: Arc::new(SavedGraphs::new(&saved_graphs)),
+            uploads: Arc::new(Uploads::default()),
+            slow_log: Arc::new(SlowQueryLog::new(config.slow_query_log.clone())),
+            cursors: Arc::new(CursorStore::new(&config.cursors)),
+            history: Arc::new(QueryHistory::new(history)),
+            usage: Arc::new(UsageStats::new(usage)),
+            config,
+            client,
+        }
+    }
+
+    pub fn config(&self) -> &Configuration {
+        &self.config
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn documents(&self) -> &DocumentCache {
+        &self.documents
+    }
+
+    pub fn spilled(&self) -> &SpilledResults {
+        &self.spilled
+    }
+
+    pub fn graphs(&self) -> &Arc<GraphStore> {
+        &self.graphs
+    }
+
+    pub fn saved_graphs(&self) -> &Arc<SavedGraphs> {
+        &self.saved_graphs
+    }
+
+    pub fn uploads(&self) -> &Arc<Uploads> {
+        &self.uploads
+    }
+
+    pub fn slow_log(&self) -> &Arc<SlowQueryLog> {
+        &self.slow_log
+    }
+
+    pub fn cursors(&self) -> &Arc<CursorStore> {
+        &self.cursors
+    }
+
+    pub fn history(&self) -> &Arc<QueryHistory> {
+        &self.history
+    }
+
+    pub fn usage(&self) -> &Arc<UsageStats> {
+        &self.usage
+    }
+
+    /// Looks up a query by name, failing with a readable error.
+    pub fn find(&self, name: &str) -> Result<&QueryDefinition> {
+        self.config
+            .find_query(name)
+            .ok_or_else(|| Error::NotFound(format!("No query named '{}' in configuration", name)))
+    }
+
+    /// Refuses queries that may not run against the endpoint of this
+    /// runner, see [`QueryDefinition::allowed_endpoints`].
+    pub fn check_endpoint(&self, query: &QueryDefinition) -> Result<()> {
+        let endpoint = self.client.endpoint();
+        if query.allowed_endpoints.is_empty()
+            || query
+                .allowed_endpoints
+                .iter()
+                .any(|allowed| endpoint_allowed(endpoint, allowed))
+        {
+            return Ok(());
+        }
+        Err(Error::Forbidden(format!(
+            "Query '{}' may not run against {}",
+            query.name, endpoint
+        )))
+    }
+
+    /// Executes a query with already typed bind variables, if it may run
+    /// against this endpoint.
+    pub async fn execute(
+        &self,
+        query: &QueryDefinition,
+        bind_vars: HashMap<String, Value>,
+    ) -> Result<Execution> {
+        self.check_endpoint(query)?;
+        let started = Instant::now();
+        let QueryResult { results, stats } = self
+            .client
+            .execute_with(&query.query, bind_vars, &CursorOptions::of(query))
+            .await?;
+        Ok(Execution {
+            results,
+            stats,
+            duration: started.elapsed(),
+        })
+    }
+
+    /// Executes a query like [`QueryRunner::execute`], but writes its
+    /// results to a file once they exceed `spill.max_in_memory_bytes`.
+    pub async fn execute_spilling(
+        &self,
+        query: &QueryDefinition,
+        bind_vars: HashMap<String, Value>,
+    ) -> Result<SpillingExecution> {
+        self.check_endpoint(query)?;
+        let started = Instant::now();
+        let mut spiller = Spiller::new(&self.config.spill);
+        let executed = self
+            .client
+            .execute_batches(
+                &query.query,
+                bind_vars,
+                &CursorOptions::of(query),
+                &mut spiller,
+            )
+            .await;
+        // If writing the file failed, the writer tells why
+        let rows = spiller.finish().await?;
+        Ok(SpillingExecution {
+            stats: executed?,
+            rows,
+            duration: started.elapsed(),
+        })
+    }
+
+    /// Executes the named query after checking that all declared parameters
+    /// are given and converting them to their declared types.
+    pub async fn run(&self, name: &str, params: HashMap<String, String>) -> Result<Execution> {
+        let query = self.find(name)?;
+        let bind_vars = self.bind_vars(query, params)?;
+        self.execute(query, bind_vars).await
+    }
+
+    /// Checks that all declared parameters of `query` are given and
+    /// converts them to their declared types.
+    pub fn bind_vars(
+        &self,
+        query: &QueryDefinition,
+        params: HashMap<String, String>,
+    ) -> Result<HashMap<String, Value>> {
+        for param in &query.parameters {
+            if !params.contains_key(&param.name) {
+                return Err(Error::InvalidParameter(format!(
+                    "Missing parameter '{}'",
+                    param.name
+                )));
+            }
+        }
+        convert_bind_vars(query, params)
+    }
+}
+
+/// Whether `endpoint` is one of those `allowed` stands for: the same scheme,
+/// host and port, and a path within the allowed one. `http://staging` thus
+/// allows neither `http://staging-prod` nor `http://staging.example.com`.
+fn endpoint_allowed(endpoint: &str, allowed: &str) -> bool {
+    let (Ok(endpoint), Ok(allowed)) = (Url::parse(endpoint), Url::parse(allowed)) else {
+        return endpoint == allowed;
+    };
+    let prefix = allowed.path().trim_end_matches('/');
+    endpoint.scheme() == allowed.scheme()
+        && endpoint.host_str() == allowed.host_str()
+        && endpoint.port_or_known_default() == allowed.port_or_known_default()
+        && endpoint
+            .path()
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_convert_bind_vars() {
+        let query = QueryDefinition::for_test(
+            "q",
+            "RETURN [@n, @b, @s]",
+            &[("n", "number"), ("b", "boolean")],
+        );
+        let params: HashMap<String, String> = [("n", "1.5"), ("b", "true"), ("s", "x")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let bind_vars = convert_bind_vars(&query, params).unwrap();
+        assert_eq!(bind_vars["n"], json!(1.5));
+        assert_eq!(bind_vars["b"], json!(true));
+        assert_eq!(bind_vars["s"], json!("x"));
+
+        let params = HashMap::from([("n".to_string(), "many".to_string())]);
+        assert!(convert_bind_vars(&query, params).is_err());
+    }
+
+    #[test]
+    fn test_check_endpoint() {
+        let config: Configuration = serde_json::from_value(json!({
+            "arangodb_endpoint": "http://prod:8529/",
+            "username": "root",
+            "password": "",
+            "queries": [
+                { "name": "any", "description": "", "query": "RETURN 1", "parameters": [] },
+                {
+                    "name": "drop", "description": "", "query": "FOR d IN c REMOVE d IN c",
+                    "parameters": [], "allowed_endpoints": ["http://staging:8529/"]
+                }
+            ]
+        }))
+        .unwrap();
+        let config = Arc::new(config);
+        let prod = QueryRunner::new(Arc::clone(&config));
+        assert!(prod.check_endpoint(prod.find("any").unwrap()).is_ok());
+        assert!(matches!(
+            prod.check_endpoint(prod.find("drop").unwrap()),
+            Err(Error::Forbidden(_))
+        ));
+        let staging = QueryRunner::with_client(
+            Arc::clone(&config),
+            Client::new("http://staging:8529/_db/test/", "root", ""),
+        );
+        assert!(staging
+            .check_endpoint(staging.find("drop").unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_endpoint_allowed() {
+        assert!(endpoint_allowed("http://staging/", "http://staging"));
+        assert!(endpoint_allowed(
+            "http://staging:80/_db/x",
+            "http://staging/"
+        ));
+        assert!(endpoint_allowed(
+            "http://staging:8529/_db/test/",
+            "http://staging:8529/_db/test"
+        ));
+        // Hosts and paths that merely begin alike are others
+        assert!(!endpoint_allowed(
+            "http://staging-prod:8529",
+            "http://staging"
+        ));
+        assert!(!endpoint_allowed("http://staging.evil", "http://staging"));
+        assert!(!endpoint_allowed(
+            "http://staging:8529/_db/testing",
+            "http://staging:8529/_db/test"
+        ));
+        assert!(!endpoint_allowed("http://staging:8529", "http://staging"));
+        assert!(!endpoint_allowed("https://staging", "http://staging"));
+    }
+}
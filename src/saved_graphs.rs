@@ -0,0 +1,382 @@
+//! Graphs saved to disk from the viewer, so that they can be opened again
+//! or sent to Cytoscape without running their query again. Every graph is
+//! a JSON file of its own in the configured directory, zstd-compressed
+//! unless turned off, since big results take a lot of disk as plain JSON.
+//! Files written before compression was turned on or off are read either way.
+//! Beside every graph a small plain summary is kept, so that listing the
+//! graphs, e.g. on startup, does not decompress them all.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::error::{Error, Result};
+use crate::files::{tenant_file_name, write_atomically};
+use crate::viewer::{GraphRenderer, StoredGraph};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedGraphsConfig {
+    #[serde(default)]
+    pub directory: Option<String>, // Where graphs are saved; without it saving is off
+    #[serde(default = "default_compression_level")]
+    pub compression_level: i32, // Of zstd, 1 to 22; 0 saves plain JSON
+}
+
+fn default_compression_level() -> i32 {
+    3
+}
+
+impl SavedGraphsConfig {
+    /// The graphs of a tenant go to a subdirectory named after it.
+    pub fn for_tenant(&self, tenant: &str) -> SavedGraphsConfig {
+        let name = tenant_file_name(tenant);
+        SavedGraphsConfig {
+            directory: self
+                .directory
+                .as_ref()
+                .map(|d| Path::new(d).join(name).to_string_lossy().into_owned()),
+            ..self.clone()
+        }
+    }
+}
+
+impl Default for SavedGraphsConfig {
+    fn default() -> Self {
+        SavedGraphsConfig {
+            directory: None,
+            compression_level: default_compression_level(),
+        }
+    }
+}
+
+/// The extensions of plain and of compressed graph files.
+const PLAIN: &str = "json";
+const COMPRESSED: &str = "zst";
+/// The extension of the summary beside a graph file.
+const SUMMARY: &str = "summary";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedGraph {
+    pub id: u64,
+    pub name: String,
+    pub query_name: String,
+    pub renderer: GraphRenderer,
+    pub detected_at: DateTime<Utc>, // When the query ran
+    pub saved_at: DateTime<Utc>,
+    pub vertices: Vec<serde_json::Value>,
+    pub edges: Vec<serde_json::Value>,
+}
+
+/// A saved graph as listed, without its vertices and edges.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedGraphSummary {
+    pub id: u64,
+    pub name: String,
+    pub query_name: String,
+    pub saved_at: DateTime<Utc>,
+    pub vertices: usize,
+    pub edges: usize,
+}
+
+impl SavedGraph {
+    fn summary(&self) -> SavedGraphSummary {
+        SavedGraphSummary {
+            id: self.id,
+            name: self.name.clone(),
+            query_name: self.query_name.clone(),
+            saved_at: self.saved_at,
+            vertices: self.vertices.len(),
+            edges: self.edges.len(),
+        }
+    }
+}
+
+pub struct SavedGraphs {
+    directory: Option<PathBuf>,
+    compression_level: i32,
+    summaries: Mutex<Vec<SavedGraphSummary>>, // Of the files in the directory, oldest first
+}
+
+impl SavedGraphs {
+    pub fn new(config: &SavedGraphsConfig) -> Self {
+        let directory = config.directory.as_ref().map(PathBuf::from);
+        let mut summaries = Vec::new();
+        if let Some(directory) = &directory {
+            match load_summaries(directory) {
+                Ok(loaded) => {
+                    info!(
+                        directory = %directory.display(),
+                        graphs = loaded.len(),
+                        "Loaded saved graphs"
+                    );
+                    summaries = loaded;
+                }
+                Err(e) => warn!(
+                    directory = %directory.display(),
+                    "Could not load saved graphs: {}", e
+                ),
+            }
+        }
+        SavedGraphs {
+            directory,
+            compression_level: config.compression_level,
+            summaries: Mutex::new(summaries),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.directory.is_some()
+    }
+
+    fn directory(&self) -> Result<&Path> {
+        self.directory.as_deref().ok_or_else(|| {
+            Error::Config("Saving graphs needs a `saved_graphs.directory`".to_string())
+        })
+    }
+
+    /// Saves a kept graph under a new id, which is returned.
+    pub fn save(&self, graph: &StoredGraph, name: &str) -> Result<u64> {
+        let name = name.trim();
+        self.add(SavedGraph {
+            id: 0,
+            name: if name.is_empty() {
+                graph.query_name.clone()
+            } else {
+                name.to_string()
+            },
+            query_name: graph.query_name.clone(),
+            renderer: graph.renderer,
+            detected_at: graph.timestamp,
+            saved_at: Utc::now(),
+            vertices: graph.vertices.clone(),
+            edges: graph.edges.clone(),
+        })
+    }
+
+    /// Adds a graph saved elsewhere, e.g. from an archive of another
+    /// installation, under a new id, which is returned.
+    pub fn restore(&self, graph: SavedGraph) -> Result<u64> {
+        self.add(graph)
+    }
+
+    fn add(&self, mut saved: SavedGraph) -> Result<u64> {
+        let directory = self.directory()?;
+        let mut summaries = self.summaries.lock().unwrap();
+        let id = summaries.iter().map(|g| g.id).max().unwrap_or(0) + 1;
+        saved.id = id;
+        std::fs::create_dir_all(directory)?;
+        let extension = if self.compression_level > 0 {
+            COMPRESSED
+        } else {
+            PLAIN
+        };
+        let path = directory.join(format!("{}.{}", id, extension));
+        write_atomically(&path, &encode_graph(&saved, self.compression_level)?)?;
+        let summary = saved.summary();
+        write_summary(directory, &summary)?;
+        info!(id, name = saved.name.as_str(), "Saved graph");
+        summaries.push(summary);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: u64) -> Result<SavedGraph> {
+        let path = graph_file(self.directory()?, id)
+            .ok_or_else(|| Error::NotFound(format!("Saved graph {}", id)))?;
+        read_graph(&path)
+    }
+
+    /// All saved graphs, newest first.
+    pub fn list(&self) -> Vec<SavedGraphSummary> {
+        let mut summaries = self.summaries.lock().unwrap().clone();
+        summaries.reverse();
+        summaries
+    }
+
+    pub fn delete(&self, id: u64) -> Result<()> {
+        let directory = self.directory()?;
+        let mut summaries = self.summaries.lock().unwrap();
+        let path = graph_file(directory, id)
+            .ok_or_else(|| Error::NotFound(format!("Saved graph {}", id)))?;
+        std::fs::remove_file(&path)?;
+        // Without its graph a summary is ignored anyway
+        std::fs::remove_file(summary_file(directory, id)).ok();
+        summaries.retain(|g| g.id != id);
+        Ok(())
+    }
+}
+
+/// The file of a graph, compressed or not.
+fn graph_file(directory: &Path, id: u64) -> Option<PathBuf> {
+    [COMPRESSED, PLAIN]
+        .iter()
+        .map(|extension| directory.join(format!("{}.{}", id, extension)))
+        .find(|path| path.is_file())
+}
+
+fn summary_file(directory: &Path, id: u64) -> PathBuf {
+    directory.join(format!("{}.{}", id, SUMMARY))
+}
+
+fn write_summary(directory: &Path, summary: &SavedGraphSummary) -> Result<()> {
+    let json = serde_json::to_vec(summary).map_err(std::io::Error::from)?;
+    write_atomically(summary_file(directory, summary.id), &json)?;
+    Ok(())
+}
+
+/// The summary of the graph in `path`, from the file beside it. Graphs
+/// saved without one are read once to write it.
+fn read_summary(directory: &Path, path: &Path) -> Result<SavedGraphSummary> {
+    let id = path.file_stem().and_then(|s| s.to_str()?.parse().ok());
+    if let Some(json) = id.and_then(|id| std::fs::read(summary_file(directory, id)).ok()) {
+        if let Ok(summary) = serde_json::from_slice(&json) {
+            return Ok(summary);
+        }
+    }
+    let summary = read_graph(path)?.summary();
+    if let Err(e) = write_summary(directory, &summary) {
+        warn!(file = %path.display(), "Could not write the summary of a saved graph: {}", e);
+    }
+    Ok(summary)
+}
+
+fn encode_graph(graph: &SavedGraph, compression_level: i32) -> Result<Vec<u8>> {
+    if compression_level > 0 {
+        let mut encoder = zstd::Encoder::new(Vec::new(), compression_level)?;
+        serde_json::to_writer(&mut encoder, graph).map_err(std::io::Error::from)?;
+        Ok(encoder.finish()?)
+    } else {
+        Ok(serde_json::to_vec(graph).map_err(std::io::Error::from)?)
+    }
+}
+
+/// Reads a graph file, decompressing it while it is parsed.
+fn read_graph(path: &Path) -> Result<SavedGraph> {
+    let file = BufReader::new(File::open(path)?);
+    let parsed = if path.extension().and_then(|e| e.to_str()) == Some(COMPRESSED) {
+        serde_json::from_reader(zstd::Decoder::with_buffer(file)?)
+    } else {
+        serde_json::from_reader(file)
+    };
+    parsed.map_err(|e| {
+        Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Failed to parse {}: {}", path.display(), e),
+        ))
+    })
+}
+
+/// Reads the summaries of the graphs in the directory, skipping files that
+/// are no saved graphs.
+fn load_summaries(directory: &Path) -> Result<Vec<SavedGraphSummary>> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut summaries = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let extension = path.extension().and_then(|e| e.to_str());
+        if extension != Some(PLAIN) && extension != Some(COMPRESSED) {
+            continue;
+        }
+        match read_summary(directory, &path) {
+            Ok(summary) => summaries.push(summary),
+            Err(_) => warn!(file = %path.display(), "Skipping a file that is no saved graph"),
+        }
+    }
+    summaries.sort_by_key(|g| g.id);
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_save_and_reload() {
+        let directory = std::env::temp_dir().join(format!("saved-graphs-{}", uuid::Uuid::new_v4()));
+        let config = SavedGraphsConfig {
+            directory: Some(directory.to_str().unwrap().to_string()),
+            ..SavedGraphsConfig::default()
+        };
+        let graph = StoredGraph {
+            id: 7,
+            query_name: "Friendship graph".to_string(),
+            renderer: GraphRenderer::Vis,
+            timestamp: Utc::now(),
+            vertices: vec![json!({ "_id": "people/a" }), json!({ "_id": "people/b" })],
+            edges: vec![json!({ "_from": "people/a", "_to": "people/b" })],
+        };
+        let saved = SavedGraphs::new(&config);
+        let first = saved.save(&graph, " ").unwrap();
+        let second = saved.save(&graph, "Before the merge").unwrap();
+        assert_eq!(saved.list()[0].name, "Before the merge");
+        assert_eq!(saved.list()[1].name, "Friendship graph");
+
+        // Kept across restarts
+        let reloaded = SavedGraphs::new(&config);
+        assert_eq!(reloaded.list().len(), 2);
+        let opened = reloaded.get(second).unwrap();
+        assert_eq!(opened.renderer, GraphRenderer::Vis);
+        assert_eq!((opened.vertices.len(), opened.edges.len()), (2, 1));
+
+        assert!(directory.join(format!("{}.zst", second)).is_file());
+
+        // Listed from the summaries, without reading the graphs
+        std::fs::write(directory.join(format!("{}.zst", second)), b"not zstd").unwrap();
+        assert_eq!(SavedGraphs::new(&config).list().len(), 2);
+        // A corrupt graph is a problem of the file, not of the configuration
+        assert!(matches!(saved.get(second), Err(Error::Io(_))));
+        saved.delete(second).unwrap();
+        let second = saved.save(&graph, "Before the merge").unwrap();
+        // Graphs without a summary get one
+        std::fs::remove_file(summary_file(&directory, second)).unwrap();
+        assert_eq!(SavedGraphs::new(&config).list()[0].vertices, 2);
+        assert!(summary_file(&directory, second).is_file());
+
+        let reloaded = SavedGraphs::new(&config);
+        reloaded.delete(first).unwrap();
+        assert!(!summary_file(&directory, first).exists());
+        assert!(matches!(reloaded.get(first), Err(Error::NotFound(_))));
+        assert!(matches!(reloaded.delete(first), Err(Error::NotFound(_))));
+        assert_eq!(reloaded.list().len(), 1);
+
+        // Plain files next to compressed ones, e.g. once compression is off
+        let plain = SavedGraphs::new(&SavedGraphsConfig {
+            compression_level: 0,
+            ..config.clone()
+        });
+        let third = plain.save(&graph, "Plain").unwrap();
+        assert!(directory.join(format!("{}.json", third)).is_file());
+        let reloaded = SavedGraphs::new(&config);
+        assert_eq!(reloaded.list().len(), 2);
+        assert_eq!(reloaded.get(third).unwrap().name, "Plain");
+        assert_eq!(reloaded.get(second).unwrap().name, "Before the merge");
+
+        // Without a directory nothing is saved
+        let disabled = SavedGraphs::new(&SavedGraphsConfig::default());
+        assert!(!disabled.enabled());
+        assert!(matches!(disabled.save(&graph, ""), Err(Error::Config(_))));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_for_tenant() {
+        let config = SavedGraphsConfig {
+            directory: Some("/graphs".to_string()),
+            ..SavedGraphsConfig::default()
+        };
+        let directory = config.for_tenant("../team a").directory.unwrap();
+        assert_eq!(Path::new(&directory), Path::new("/graphs/___team_a"));
+        assert!(SavedGraphsConfig::default()
+            .for_tenant("a")
+            .directory
+            .is_none());
+    }
+}
@@ -0,0 +1,263 @@
+//! Full-text search through ArangoSearch views without writing AQL: the
+//! user picks a view, an attribute and a search text, and gets the matching
+//! documents shown like the result of any other query.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::arango::Client;
+use crate::config::QueryDefinition;
+use crate::error::{Error, Result};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchConfig {
+    #[serde(default = "default_analyzer")]
+    pub analyzer: String, // Preselected in the search form
+    #[serde(default = "default_max_results")]
+    pub max_results: u64,
+}
+
+fn default_analyzer() -> String {
+    "text_en".to_string()
+}
+
+fn default_max_results() -> u64 {
+    100
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            analyzer: default_analyzer(),
+            max_results: default_max_results(),
+        }
+    }
+}
+
+/// A collection indexed by a view, with the attributes it indexes.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ViewLink {
+    pub collection: String,
+    pub fields: Vec<String>, // Empty if all fields are included
+    pub analyzers: Vec<String>,
+}
+
+/// An ArangoSearch view and what it indexes.
+#[derive(Debug, Serialize, Clone)]
+pub struct View {
+    pub name: String,
+    pub kind: String, // arangosearch or search-alias
+    pub links: Vec<ViewLink>,
+}
+
+/// How the words of the search text are matched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchMode {
+    Any,    // Documents containing any of the words
+    All,    // Documents containing all of the words
+    Phrase, // Documents containing the words in this order
+}
+
+impl SearchMode {
+    pub const ALL: &'static [SearchMode] = &[SearchMode::Any, SearchMode::All, SearchMode::Phrase];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SearchMode::Any => "any",
+            SearchMode::All => "all",
+            SearchMode::Phrase => "phrase",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SearchMode> {
+        SearchMode::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
+    /// The SEARCH condition on `d.@attribute`.
+    fn condition(&self) -> &'static str {
+        match self {
+            SearchMode::Any => "ANALYZER(d.@attribute IN TOKENS(@text, @analyzer), @analyzer)",
+            SearchMode::All => "ANALYZER(TOKENS(@text, @analyzer) ALL == d.@attribute, @analyzer)",
+            SearchMode::Phrase => "PHRASE(d.@attribute, @text, @analyzer)",
+        }
+    }
+}
+
+/// The links of a view from its properties. `arangosearch` views list
+/// collections under `links`, `search-alias` views their inverted indexes
+/// under `indexes`.
+fn view_links(properties: &Value) -> Vec<ViewLink> {
+    let strings = |value: &Value| -> Vec<String> {
+        value
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect()
+    };
+    let mut links: Vec<ViewLink> = match properties["links"].as_object() {
+        Some(links) => links
+            .iter()
+            .map(|(collection, link)| ViewLink {
+                collection: collection.clone(),
+                fields: link["fields"]
+                    .as_object()
+                    .map(|f| f.keys().cloned().collect())
+                    .unwrap_or_default(),
+                analyzers: strings(&link["analyzers"]),
+            })
+            .collect(),
+        None => properties["indexes"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|index| {
+                Some(ViewLink {
+                    collection: index["collection"].as_str()?.to_string(),
+                    fields: Vec::new(),
+                    analyzers: Vec::new(),
+                })
+            })
+            .collect(),
+    };
+    links.sort_by(|a, b| a.collection.cmp(&b.collection));
+    links
+}
+
+async fn get(client: &Client, path: &str) -> Result<Value> {
+    let response: Value = client
+        .request(reqwest::Method::GET, path)
+        .send()
+        .await?
+        .json()
+        .await?;
+    if response["error"].as_bool().unwrap_or(false) {
+        return Err(Error::from_response(&response));
+    }
+    Ok(response)
+}
+
+/// Lists the views of the database with their links, sorted by name.
+pub async fn views(client: &Client) -> Result<Vec<View>> {
+    let response = get(client, "_api/view").await?;
+    let mut views = Vec::new();
+    for view in response["result"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+    {
+        let Some(name) = view["name"].as_str() else {
+            continue;
+        };
+        let properties = get(client, &format!("_api/view/{}/properties", name)).await?;
+        views.push(View {
+            name: name.to_string(),
+            kind: view["type"].as_str().unwrap_or_default().to_string(),
+            links: view_links(&properties),
+        });
+    }
+    views.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(views)
+}
+
+/// A search in a view as a query that runs through the usual pipeline,
+/// with its bind variables. Results are sorted by relevance (BM25).
+/// `attribute` may be a path into nested objects like `address.city`.
+pub fn search(
+    config: &SearchConfig,
+    view: &str,
+    attribute: &str,
+    text: &str,
+    mode: SearchMode,
+    analyzer: &str,
+) -> Result<(QueryDefinition, HashMap<String, Value>)> {
+    let path: Vec<&str> = attribute.split('.').map(str::trim).collect();
+    if path.iter().any(|p| p.is_empty()) {
+        return Err(Error::InvalidParameter(format!(
+            "Invalid attribute '{}'",
+            attribute
+        )));
+    }
+    if text.trim().is_empty() {
+        return Err(Error::InvalidParameter("Missing search text".to_string()));
+    }
+    let query = QueryDefinition {
+        name: format!("Search {} for \"{}\"", view, text),
+        description: format!("{} of the words in {}", mode.name(), attribute),
+        query: format!(
+            "FOR d IN @@view\n  SEARCH {}\n  SORT BM25(d) DESC\n  LIMIT @limit\n  RETURN d",
+            mode.condition()
+        ),
+        parameters: Vec::new(),
+        cytoscape: Default::default(),
+        graph_renderer: None,
+        renderer: None,
+        allowed_endpoints: Vec::new(),
+        max_concurrent: None,
+        stream: None,
+        aql_options: Default::default(),
+        alternative: None,
+        tests: Vec::new(),
+        graph: Default::default(),
+    };
+    let bind_vars = HashMap::from([
+        ("@view".to_string(), json!(view)),
+        ("attribute".to_string(), json!(path)),
+        ("text".to_string(), json!(text)),
+        ("analyzer".to_string(), json!(analyzer)),
+        ("limit".to_string(), json!(config.max_results)),
+    ]);
+    Ok((query, bind_vars))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let config = SearchConfig::default();
+        let (query, bind_vars) = search(
+            &config,
+            "products_view",
+            "details.title",
+            "red shoes",
+            SearchMode::Phrase,
+            "text_en",
+        )
+        .unwrap();
+        assert!(query
+            .query
+            .contains("SEARCH PHRASE(d.@attribute, @text, @analyzer)"));
+        assert_eq!(bind_vars["@view"], "products_view");
+        assert_eq!(bind_vars["attribute"], json!(["details", "title"]));
+        assert_eq!(bind_vars["limit"], 100);
+
+        let any = SearchMode::from_name("any").unwrap();
+        assert!(search(&config, "v", "title", " ", any, "text_en").is_err());
+        assert!(search(&config, "v", "a..b", "x", any, "text_en").is_err());
+    }
+
+    #[test]
+    fn test_view_links() {
+        let links = view_links(&json!({
+            "links": {
+                "products": { "fields": { "title": {}, "description": {} }, "analyzers": ["text_en"] },
+                "articles": { "includeAllFields": true }
+            }
+        }));
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].collection, "articles");
+        assert!(links[0].fields.is_empty());
+        assert_eq!(links[1].fields, vec!["description", "title"]);
+        assert_eq!(links[1].analyzers, vec!["text_en"]);
+
+        let alias = view_links(&json!({
+            "indexes": [{ "collection": "products", "index": "inv_title" }]
+        }));
+        assert_eq!(alias[0].collection, "products");
+    }
+}
@@ -0,0 +1,119 @@
+//! Provisions collections with fixture data from the `seed` section of the
+//! configuration, e.g. to set up demo graphs or test datasets.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::BufRead;
+use tracing::{info, instrument};
+
+use crate::arango::Client;
+use crate::error::{Error, Result};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SeedCollection {
+    pub name: String,
+    #[serde(default)]
+    pub edge: bool, // Create an edge collection
+    #[serde(default)]
+    pub documents: Vec<Value>,
+    #[serde(default)]
+    pub file: Option<String>, // JSON lines file with further documents
+}
+
+/// What seeding a single collection did.
+#[derive(Debug, Serialize)]
+pub struct SeedReport {
+    pub collection: String,
+    pub created_collection: bool,
+    pub imported: u64,
+    pub errors: u64,
+}
+
+/// Reads documents from a JSON lines file, skipping empty lines.
+fn read_jsonl(path: &str) -> Result<Vec<Value>> {
+    let file = std::fs::File::open(path)?;
+    let mut documents = Vec::new();
+    for (number, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let document = serde_json::from_str(&line)
+            .map_err(|e| Error::Config(format!("{} line {}: {}", path, number + 1, e)))?;
+        documents.push(document);
+    }
+    Ok(documents)
+}
+
+/// Collects the documents of a seed collection, inline ones first.
+pub fn documents(collection: &SeedCollection) -> Result<Vec<Value>> {
+    let mut documents = collection.documents.clone();
+    if let Some(path) = &collection.file {
+        documents.extend(read_jsonl(path)?);
+    }
+    Ok(documents)
+}
+
+/// Creates missing collections and imports their documents. Existing
+/// documents with the same `_key` are replaced, so seeding can be repeated.
+#[instrument(skip_all, fields(collections = collections.len()))]
+pub async fn seed(client: &Client, collections: &[SeedCollection]) -> Result<Vec<SeedReport>> {
+    let mut reports = Vec::new();
+    for collection in collections {
+        let documents = documents(collection)?;
+        let created_collection = client
+            .create_collection(&collection.name, collection.edge)
+            .await?;
+        let stats = if documents.is_empty() {
+            Value::Null
+        } else {
+            client.import(&collection.name, &documents).await?
+        };
+        let report = SeedReport {
+            collection: collection.name.clone(),
+            created_collection,
+            imported: stats["created"].as_u64().unwrap_or(0)
+                + stats["updated"].as_u64().unwrap_or(0),
+            errors: stats["errors"].as_u64().unwrap_or(0),
+        };
+        info!(
+            collection = report.collection.as_str(),
+            created = report.created_collection,
+            imported = report.imported,
+            errors = report.errors,
+            "Seeded collection"
+        );
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_documents_from_file() {
+        let path = std::env::temp_dir().join(format!("seed_test_{}.jsonl", std::process::id()));
+        std::fs::write(&path, "{\"_key\": \"b\"}\n\n{\"_key\": \"c\"}\n").unwrap();
+
+        let collection = SeedCollection {
+            name: "people".to_string(),
+            edge: false,
+            documents: vec![json!({"_key": "a"})],
+            file: Some(path.to_string_lossy().to_string()),
+        };
+        let keys: Vec<Value> = documents(&collection)
+            .unwrap()
+            .iter()
+            .map(|d| d["_key"].clone())
+            .collect();
+        assert_eq!(keys, vec![json!("a"), json!("b"), json!("c")]);
+
+        std::fs::write(&path, "{\"_key\": \"b\"}\nnot json\n").unwrap();
+        let err = documents(&collection).unwrap_err().to_string();
+        assert!(err.contains("line 2"), "{}", err);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+//! Sessions of browser users, identified by a cookie, remembering the
+//! parameter values they last ran each query with, so that the parameter
+//! form can be pre-filled with them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The cookie holding the session id.
+pub const SESSION_COOKIE: &str = "session";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64, // Sessions not used for this long are forgotten
+    #[serde(default = "default_max_sessions")]
+    pub max_sessions: usize, // The least recently used session is forgotten beyond this
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_idle_timeout_secs() -> u64 {
+    30 * 24 * 3600
+}
+
+fn default_max_sessions() -> usize {
+    1000
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            enabled: default_enabled(),
+            idle_timeout_secs: default_idle_timeout_secs(),
+            max_sessions: default_max_sessions(),
+        }
+    }
+}
+
+struct Session {
+    params: HashMap<String, HashMap<String, String>>, // By query name
+    last_used: Instant,
+}
+
+/// Sessions by id, kept in memory only.
+pub struct SessionStore {
+    config: SessionConfig,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl SessionStore {
+    pub fn new(config: &SessionConfig) -> Self {
+        SessionStore {
+            config: config.clone(),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The id of a known session, or of a new one if `id` is unknown, e.g.
+    /// because the session was forgotten. `None` if sessions are disabled.
+    pub fn session(&self, id: Option<&str>) -> Option<String> {
+        if !self.config.enabled {
+            return None;
+        }
+        let sessions = self.sessions.lock().unwrap();
+        match id {
+            Some(id) if sessions.contains_key(id) => Some(id.to_string()),
+            _ => Some(uuid::Uuid::new_v4().to_string()),
+        }
+    }
+
+    /// The cookie header value keeping a session for as long as it is kept
+    /// on the server.
+    pub fn cookie(&self, id: &str) -> String {
+        format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+            SESSION_COOKIE, id, self.config.idle_timeout_secs
+        )
+    }
+
+    /// Remembers the parameter values a query was run with.
+    pub fn remember(&self, id: &str, query_name: &str, params: &HashMap<String, String>) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut sessions = self.sessions.lock().unwrap();
+        self.evict(&mut sessions, id);
+        let session = sessions.entry(id.to_string()).or_insert_with(|| Session {
+            params: HashMap::new(),
+            last_used: Instant::now(),
+        });
+        session.last_used = Instant::now();
+        session
+            .params
+            .insert(query_name.to_string(), params.clone());
+    }
+
+    /// The parameter values a query was last run with in a session.
+    pub fn recall(&self, id: &str, query_name: &str) -> HashMap<String, String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get_mut(id) {
+            Some(session) => {
+                session.last_used = Instant::now();
+                session.params.get(query_name).cloned().unwrap_or_default()
+            }
+            None => HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets idle sessions, and the least recently used ones to make room
+    /// for the session `id` if it is new.
+    fn evict(&self, sessions: &mut HashMap<String, Session>, id: &str) {
+        let idle_timeout = Duration::from_secs(self.config.idle_timeout_secs);
+        sessions.retain(|_, s| s.last_used.elapsed() < idle_timeout);
+        if sessions.contains_key(id) {
+            return;
+        }
+        while !sessions.is_empty() && sessions.len() >= self.config.max_sessions {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, s)| s.last_used)
+                .map(|(id, _)| id.clone())
+                .unwrap_or_default();
+            sessions.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store() {
+        let store = SessionStore::new(&SessionConfig {
+            max_sessions: 2,
+            ..SessionConfig::default()
+        });
+        let params = HashMap::from([("minAge".to_string(), "30".to_string())]);
+        let first = store.session(None).unwrap();
+        assert!(store.recall(&first, "q").is_empty());
+        store.remember(&first, "q", &params);
+        assert_eq!(store.session(Some(&first)), Some(first.clone()));
+        assert_eq!(store.recall(&first, "q"), params);
+        assert!(store.recall(&first, "other").is_empty());
+        assert!(store
+            .cookie(&first)
+            .starts_with(&format!("session={};", first)));
+
+        // An unknown id gets a new session
+        assert_ne!(store.session(Some("forgotten")).unwrap(), "forgotten");
+
+        // The least recently used session makes room for a new one
+        let second = store.session(None).unwrap();
+        store.remember(&second, "q", &params);
+        store.recall(&first, "q");
+        let third = store.session(None).unwrap();
+        store.remember(&third, "q", &params);
+        assert_eq!(store.len(), 2);
+        assert!(store.recall(&second, "q").is_empty());
+        assert_eq!(store.recall(&first, "q"), params);
+
+        let disabled = SessionStore::new(&SessionConfig {
+            enabled: false,
+            ..SessionConfig::default()
+        });
+        assert!(disabled.session(None).is_none());
+    }
+}
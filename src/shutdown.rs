@@ -0,0 +1,93 @@
+//! Signal handling for graceful shutdown.
+
+use std::future::Future;
+use std::sync::Mutex;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// Completes when the process receives SIGINT (Ctrl-C) or SIGTERM.
+pub async fn signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("Shutdown signal received");
+}
+
+/// Work that goes on after its request was answered, e.g. uploads to
+/// Cytoscape, and that shutdown waits for.
+#[derive(Default)]
+pub struct BackgroundTasks {
+    tasks: Mutex<JoinSet<()>>,
+}
+
+impl BackgroundTasks {
+    pub fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let mut tasks = self.tasks.lock().unwrap();
+        // Forgets the tasks done by now
+        while tasks.try_join_next().is_some() {}
+        tasks.spawn(task);
+    }
+
+    /// Waits for the tasks to finish and aborts those still running at
+    /// `deadline`. Returns how many were aborted.
+    pub async fn drain(&self, deadline: Instant) -> usize {
+        let mut tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        let finished = tokio::time::timeout_at(deadline, async {
+            while tasks.join_next().await.is_some() {}
+        });
+        if finished.await.is_ok() {
+            return 0;
+        }
+        let abandoned = tasks.len();
+        warn!(
+            abandoned,
+            "Grace period elapsed, abandoning background tasks"
+        );
+        tasks.shutdown().await;
+        abandoned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_drain_waits_for_tasks() {
+        let tasks = BackgroundTasks::default();
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let done = Arc::clone(&done);
+            tasks.spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        tasks.spawn(std::future::pending());
+        let deadline = Instant::now() + Duration::from_millis(500);
+        assert_eq!(tasks.drain(deadline).await, 1);
+        assert_eq!(done.load(Ordering::SeqCst), 3);
+    }
+}
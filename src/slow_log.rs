@@ -0,0 +1,221 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::arango::RunningQuery;
+use crate::config::QueryDefinition;
+use crate::error::Result;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SlowLogConfig {
+    #[serde(default = "default_threshold_ms")]
+    pub threshold_ms: u64,
+    #[serde(default)]
+    pub file: Option<String>, // Append entries as JSON lines if set
+    #[serde(default = "default_keep")]
+    pub keep: usize, // Number of recent entries kept in memory
+}
+
+fn default_threshold_ms() -> u64 {
+    1000
+}
+
+fn default_keep() -> usize {
+    100
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SlowQueryEntry {
+    pub timestamp: DateTime<Utc>,
+    pub query_name: String,
+    pub duration_ms: u64,
+    pub bind_vars: HashMap<String, Value>,
+    pub stats: Value,
+}
+
+pub struct SlowQueryLog {
+    config: Option<SlowLogConfig>,
+    entries: Mutex<VecDeque<SlowQueryEntry>>,
+}
+
+impl SlowQueryLog {
+    pub fn new(config: Option<SlowLogConfig>) -> Self {
+        SlowQueryLog {
+            config,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn threshold_ms(&self) -> Option<u64> {
+        self.config.as_ref().map(|c| c.threshold_ms)
+    }
+
+    /// Records an execution if it took longer than the configured threshold.
+    /// Returns true if the execution was considered slow.
+    pub fn record(
+        &self,
+        query_name: &str,
+        bind_vars: &HashMap<String, Value>,
+        duration: Duration,
+        stats: &Value,
+    ) -> bool {
+        let config = match &self.config {
+            Some(c) => c,
+            None => return false,
+        };
+        let duration_ms = duration.as_millis() as u64;
+        if duration_ms < config.threshold_ms {
+            return false;
+        }
+
+        let entry = SlowQueryEntry {
+            timestamp: Utc::now(),
+            query_name: query_name.to_string(),
+            duration_ms,
+            bind_vars: bind_vars.clone(),
+            stats: stats.clone(),
+        };
+        warn!(
+            query = query_name,
+            duration_ms,
+            bind_vars = %serde_json::to_string(bind_vars).unwrap_or_default(),
+            stats = %stats,
+            "Slow query"
+        );
+
+        if let Some(file) = &config.file {
+            if let Err(e) = append_to_file(file, &entry) {
+                warn!(file = file.as_str(), "Failed to persist slow query: {}", e);
+            }
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_back(entry);
+        while entries.len() > config.keep {
+            entries.pop_front();
+        }
+        true
+    }
+
+    /// Returns up to `n` of the recorded entries, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<SlowQueryEntry> {
+        let mut entries: Vec<SlowQueryEntry> =
+            self.entries.lock().unwrap().iter().cloned().collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.duration_ms));
+        entries.truncate(n);
+        entries
+    }
+}
+
+/// An entry of the slow query history of the database, with the configured
+/// query it was run from if its AQL is the same.
+#[derive(Debug, Serialize, Clone)]
+pub struct ServerSlowQuery {
+    #[serde(flatten)]
+    pub query: RunningQuery,
+    pub query_name: Option<String>,
+}
+
+/// Matches slow queries of the database to the configured queries by their
+/// AQL, ignoring differences in whitespace. Other clients' queries stay
+/// without a name.
+pub fn correlate(definitions: &[QueryDefinition], slow: Vec<RunningQuery>) -> Vec<ServerSlowQuery> {
+    let known: HashMap<String, &str> = definitions
+        .iter()
+        .map(|d| (normalize(&d.query), d.name.as_str()))
+        .collect();
+    slow.into_iter()
+        .map(|query| ServerSlowQuery {
+            query_name: known.get(&normalize(&query.query)).map(|n| n.to_string()),
+            query,
+        })
+        .collect()
+}
+
+fn normalize(aql: &str) -> String {
+    aql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn append_to_file(path: &str, entry: &SlowQueryEntry) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(
+        file,
+        "{}",
+        serde_json::to_string(entry).map_err(std::io::Error::from)?
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(threshold_ms: u64, keep: usize) -> Option<SlowLogConfig> {
+        Some(SlowLogConfig {
+            threshold_ms,
+            file: None,
+            keep,
+        })
+    }
+
+    #[test]
+    fn test_threshold() {
+        let log = SlowQueryLog::new(config(100, 10));
+        let vars = HashMap::new();
+
+        assert!(!log.record("fast", &vars, Duration::from_millis(50), &json!({})));
+        assert!(log.record("slow", &vars, Duration::from_millis(150), &json!({})));
+        assert_eq!(log.slowest(10).len(), 1);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let log = SlowQueryLog::new(None);
+        let vars = HashMap::new();
+
+        assert!(!log.record("q", &vars, Duration::from_secs(60), &json!({})));
+        assert!(log.slowest(10).is_empty());
+    }
+
+    #[test]
+    fn test_slowest_order_and_capacity() {
+        let log = SlowQueryLog::new(config(0, 2));
+        let vars = HashMap::new();
+
+        log.record("a", &vars, Duration::from_millis(300), &json!({}));
+        log.record("b", &vars, Duration::from_millis(100), &json!({}));
+        log.record("c", &vars, Duration::from_millis(200), &json!({}));
+
+        let names: Vec<String> = log.slowest(10).into_iter().map(|e| e.query_name).collect();
+        assert_eq!(names, vec!["c", "b"]);
+    }
+
+    #[test]
+    fn test_correlate() {
+        let definitions: Vec<QueryDefinition> = serde_json::from_value(json!([{
+            "name": "Friends",
+            "description": "",
+            "query": "FOR v IN 1..2 OUTBOUND @start knows\n  RETURN v",
+            "parameters": []
+        }]))
+        .unwrap();
+        let slow: Vec<RunningQuery> = serde_json::from_value(json!([
+            { "id": "1", "query": "FOR v IN 1..2  OUTBOUND @start knows RETURN v", "runTime": 3.5 },
+            { "id": "2", "query": "FOR d IN logs RETURN d", "runTime": 2.0 }
+        ]))
+        .unwrap();
+
+        let correlated = correlate(&definitions, slow);
+        assert_eq!(correlated[0].query_name.as_deref(), Some("Friends"));
+        assert_eq!(correlated[1].query_name, None);
+    }
+}
@@ -0,0 +1,489 @@
+//! Results too big to keep in memory. Once the batches of a query add up
+//! to more than the configured threshold, they and all further batches go
+//! to a temporary file as JSON lines, so that a single runaway query cannot
+//! exhaust the memory of the server. Spilled results are shown from the
+//! start of that file and downloaded by streaming it.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::arango::BatchHandler;
+use crate::error::Result;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SpillConfig {
+    #[serde(default = "default_max_in_memory_bytes")]
+    pub max_in_memory_bytes: u64, // Of the results of one query, as JSON; 0 never spills
+    #[serde(default)]
+    pub directory: Option<String>, // For the files; else the temporary directory
+    #[serde(default = "default_keep")]
+    pub keep: usize, // Spilled results kept for download, the oldest file is deleted beyond
+}
+
+fn default_max_in_memory_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
+fn default_keep() -> usize {
+    10
+}
+
+impl Default for SpillConfig {
+    fn default() -> Self {
+        SpillConfig {
+            max_in_memory_bytes: default_max_in_memory_bytes(),
+            directory: None,
+            keep: default_keep(),
+        }
+    }
+}
+
+/// The results of a query, in memory or spilled to a file.
+pub enum Rows {
+    InMemory(Vec<Value>),
+    Spilled(SpillFile),
+}
+
+/// A file of results, one JSON document per line. It is deleted when
+/// dropped.
+#[derive(Debug)]
+pub struct SpillFile {
+    path: PathBuf,
+    rows: usize,
+    bytes: u64,
+}
+
+impl SpillFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Reads the results back from the start of the file.
+    pub fn reader(&self) -> Result<SpillReader> {
+        Ok(SpillReader {
+            lines: BufReader::new(File::open(&self.path)?).lines(),
+        })
+    }
+
+    /// Up to the first `n` results, read on a thread that may block.
+    pub async fn first_rows(&self, n: usize) -> Result<Vec<Value>> {
+        let mut reader = self.reader()?;
+        tokio::task::spawn_blocking(move || reader.next_chunk(n))
+            .await
+            .map_err(std::io::Error::from)?
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!(file = %self.path.display(), "Could not delete spilled results: {}", e);
+        }
+    }
+}
+
+/// Reads spilled results a chunk at a time.
+pub struct SpillReader {
+    lines: Lines<BufReader<File>>,
+}
+
+impl SpillReader {
+    /// Like [`SpillReader::next_chunk`], but reads on a thread that may
+    /// block, handing the reader back for the next chunk.
+    pub async fn read_chunk(mut self, n: usize) -> Result<(SpillReader, Vec<Value>)> {
+        tokio::task::spawn_blocking(move || {
+            let chunk = self.next_chunk(n)?;
+            Ok((self, chunk))
+        })
+        .await
+        .map_err(std::io::Error::from)?
+    }
+
+    /// Up to `n` further results; none once all are read.
+    pub fn next_chunk(&mut self, n: usize) -> Result<Vec<Value>> {
+        let mut chunk = Vec::new();
+        while chunk.len() < n {
+            let Some(line) = self.lines.next() else {
+                break;
+            };
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            chunk.push(serde_json::from_str(&line).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Corrupt spilled result: {}", e),
+                )
+            })?);
+        }
+        Ok(chunk)
+    }
+}
+
+/// Counts the bytes written through it, e.g. to measure rows as JSON
+/// without keeping the JSON.
+struct CountingWriter<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The size of `rows` as JSON lines.
+fn json_lines_bytes(rows: &[Value]) -> Result<u64> {
+    let mut counter = CountingWriter {
+        inner: std::io::sink(),
+        bytes: 0,
+    };
+    for row in rows {
+        serde_json::to_writer(&mut counter, row).map_err(std::io::Error::from)?;
+        counter.write_all(b"\n")?;
+    }
+    Ok(counter.bytes)
+}
+
+/// Takes the batches of one query, keeping them in memory until they
+/// exceed the threshold and writing them to a file from then on.
+pub struct Spiller {
+    max_in_memory_bytes: u64,
+    path: PathBuf,
+    rows: Vec<Value>,
+    bytes: u64,
+    writer: Option<SpillWriter>,
+    spilled_rows: usize,
+}
+
+/// Batches handed to the writer of a spill file but not yet written. Once
+/// that many wait, the query waits for the disk.
+const QUEUED_BATCHES: usize = 4;
+
+/// Writes the batches handed to it into the spill file on a thread that may
+/// block, so that the file I/O never stalls the async runtime.
+struct SpillWriter {
+    batches: mpsc::Sender<Option<Vec<Value>>>, // `None` once all are sent
+    written: JoinHandle<Result<u64>>,          // The size of the file
+}
+
+impl SpillWriter {
+    fn start(path: PathBuf) -> Self {
+        let (batches, mut received) = mpsc::channel(QUEUED_BATCHES);
+        let written = tokio::task::spawn_blocking(move || {
+            let written = write_file(&path, &mut received);
+            // Unless all batches arrived, e.g. because the query failed
+            // halfway, the file is of no use
+            if !matches!(written, Ok((_, true))) {
+                std::fs::remove_file(&path).ok();
+            }
+            match written? {
+                (bytes, true) => Ok(bytes),
+                (_, false) => Err(std::io::Error::new(
+                    std::io::ErrorKind::Interrupted,
+                    "Spilling results was aborted",
+                )
+                .into()),
+            }
+        });
+        SpillWriter { batches, written }
+    }
+
+    /// Hands a batch to the writer, waiting while it is behind.
+    async fn send(&self, batch: Vec<Value>) -> Result<()> {
+        self.batches
+            .send(Some(batch))
+            .await
+            .map_err(|_| std::io::Error::other("Writing spilled results failed").into())
+    }
+
+    /// Waits for all batches to be written, returning the size of the file.
+    async fn finish(self) -> Result<u64> {
+        // Fails if the writer stopped already; it tells why
+        self.batches.send(None).await.ok();
+        self.written.await.map_err(std::io::Error::from)?
+    }
+}
+
+/// Writes the received batches as JSON lines, returning the size of the
+/// file and whether all batches were sent.
+fn write_file(
+    path: &Path,
+    batches: &mut mpsc::Receiver<Option<Vec<Value>>>,
+) -> Result<(u64, bool)> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    let mut file = CountingWriter {
+        inner: BufWriter::new(File::create(path)?),
+        bytes: 0,
+    };
+    while let Some(batch) = batches.blocking_recv() {
+        let Some(batch) = batch else {
+            file.flush()?;
+            return Ok((file.bytes, true));
+        };
+        for row in batch {
+            serde_json::to_writer(&mut file, &row).map_err(std::io::Error::from)?;
+            file.write_all(b"\n")?;
+        }
+    }
+    Ok((file.bytes, false))
+}
+
+impl BatchHandler for &mut Spiller {
+    fn handle(&mut self, batch: Vec<Value>) -> impl Future<Output = Result<()>> + Send {
+        self.push_batch(batch)
+    }
+}
+
+/// Tells the files of concurrent queries apart.
+static NEXT_FILE: AtomicU64 = AtomicU64::new(1);
+
+impl Spiller {
+    pub fn new(config: &SpillConfig) -> Self {
+        let directory = config
+            .directory
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        let path = directory.join(format!(
+            "adb_query_runner-{}-{}.ndjson",
+            std::process::id(),
+            NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        Spiller {
+            max_in_memory_bytes: config.max_in_memory_bytes,
+            path,
+            rows: Vec::new(),
+            bytes: 0,
+            writer: None,
+            spilled_rows: 0,
+        }
+    }
+
+    /// Keeps a batch, or hands it to the writer of the file once spilling,
+    /// waiting while the writer is behind.
+    pub async fn push_batch(&mut self, batch: Vec<Value>) -> Result<()> {
+        if self.max_in_memory_bytes == 0 {
+            self.rows.extend(batch);
+            return Ok(());
+        }
+        let Some(writer) = &self.writer else {
+            self.bytes += json_lines_bytes(&batch)?;
+            self.rows.extend(batch);
+            if self.bytes > self.max_in_memory_bytes {
+                self.start_file().await?;
+            }
+            return Ok(());
+        };
+        self.spilled_rows += batch.len();
+        writer.send(batch).await
+    }
+
+    /// Moves the results kept so far into a new file.
+    async fn start_file(&mut self) -> Result<()> {
+        info!(
+            file = %self.path.display(),
+            threshold = self.max_in_memory_bytes,
+            "Spilling results to disk"
+        );
+        let writer = SpillWriter::start(self.path.clone());
+        self.spilled_rows = self.rows.len();
+        writer.send(std::mem::take(&mut self.rows)).await?;
+        self.writer = Some(writer);
+        Ok(())
+    }
+
+    pub async fn finish(mut self) -> Result<Rows> {
+        let Some(writer) = self.writer.take() else {
+            return Ok(Rows::InMemory(self.rows));
+        };
+        // Deletes the file from here on
+        let spilled = SpillFile {
+            path: self.path,
+            rows: self.spilled_rows,
+            bytes: writer.finish().await?,
+        };
+        Ok(Rows::Spilled(spilled))
+    }
+}
+
+/// A spilled result kept for download.
+#[derive(Debug)]
+pub struct SpilledResult {
+    pub id: u64,
+    pub query_name: String,
+    pub timestamp: DateTime<Utc>,
+    pub file: SpillFile,
+}
+
+/// The latest spilled results, the files of older ones deleted.
+pub struct SpilledResults {
+    keep: usize,
+    results: Mutex<VecDeque<Arc<SpilledResult>>>,
+    next_id: AtomicU64,
+}
+
+impl SpilledResults {
+    pub fn new(config: &SpillConfig) -> Self {
+        SpilledResults {
+            keep: config.keep.max(1),
+            results: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn insert(&self, query_name: &str, file: SpillFile) -> Arc<SpilledResult> {
+        let result = Arc::new(SpilledResult {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            query_name: query_name.to_string(),
+            timestamp: Utc::now(),
+            file,
+        });
+        let mut results = self.results.lock().unwrap();
+        results.push_back(Arc::clone(&result));
+        // A download still running keeps its file until it is done
+        while results.len() > self.keep {
+            results.pop_front();
+        }
+        result
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<SpilledResult>> {
+        self.results
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|r| r.id == id)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(max_in_memory_bytes: u64) -> SpillConfig {
+        SpillConfig {
+            max_in_memory_bytes,
+            ..SpillConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_small_results_stay_in_memory() {
+        let mut spiller = Spiller::new(&config(1024));
+        spiller.push_batch(vec![json!({ "a": 1 })]).await.unwrap();
+        spiller.push_batch(vec![json!({ "a": 2 })]).await.unwrap();
+        match spiller.finish().await.unwrap() {
+            Rows::InMemory(rows) => assert_eq!(rows.len(), 2),
+            Rows::Spilled(_) => panic!("spilled a small result"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spill_and_read_back() {
+        let mut spiller = Spiller::new(&config(20));
+        spiller
+            .push_batch(vec![json!({ "a": 1 }), json!({ "a": 2 })])
+            .await
+            .unwrap();
+        spiller
+            .push_batch(vec![json!({ "a": 3 }), json!({ "a": 4 })])
+            .await
+            .unwrap();
+        spiller.push_batch(vec![json!({ "a": 5 })]).await.unwrap();
+        let Rows::Spilled(file) = spiller.finish().await.unwrap() else {
+            panic!("kept a big result in memory");
+        };
+        assert_eq!(file.rows(), 5);
+        assert_eq!(file.bytes(), 5 * "{\"a\":1}\n".len() as u64);
+
+        let mut reader = file.reader().unwrap();
+        assert_eq!(reader.next_chunk(3).unwrap().len(), 3);
+        assert_eq!(
+            reader.next_chunk(3).unwrap(),
+            [json!({ "a": 4 }), json!({ "a": 5 })]
+        );
+        assert!(reader.next_chunk(3).unwrap().is_empty());
+
+        // The oldest file goes once more are kept
+        let path = file.path().to_path_buf();
+        let results = SpilledResults::new(&SpillConfig {
+            keep: 1,
+            ..SpillConfig::default()
+        });
+        let first = results.insert("big", file).id;
+        drop(reader);
+        let mut spiller = Spiller::new(&config(1));
+        spiller.push_batch(vec![json!(1), json!(2)]).await.unwrap();
+        let Rows::Spilled(second) = spiller.finish().await.unwrap() else {
+            panic!("kept a big result in memory");
+        };
+        results.insert("big", second);
+        assert!(results.get(first).is_none());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_file_is_deleted() {
+        let mut spiller = Spiller::new(&config(1));
+        spiller.push_batch(vec![json!(1), json!(2)]).await.unwrap();
+        // As if the query failed halfway and the spiller was dropped
+        let SpillWriter { batches, written } = spiller.writer.take().unwrap();
+        drop(batches);
+        assert!(written.await.unwrap().is_err());
+        assert!(!spiller.path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_push_waits_for_the_writer() {
+        let mut spiller = Spiller::new(&config(1));
+        // A writer that has fallen behind: nothing is received
+        let (batches, mut received) = mpsc::channel(QUEUED_BATCHES);
+        spiller.writer = Some(SpillWriter {
+            batches,
+            written: tokio::spawn(async { Ok(0) }),
+        });
+        for i in 0..QUEUED_BATCHES {
+            spiller.push_batch(vec![json!(i)]).await.unwrap();
+        }
+        let waiting = tokio::time::timeout(
+            std::time::Duration::from_millis(100),
+            spiller.push_batch(vec![json!("one too many")]),
+        );
+        assert!(waiting.await.is_err());
+
+        received.recv().await.unwrap();
+        spiller.push_batch(vec![json!("next")]).await.unwrap();
+    }
+}
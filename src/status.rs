@@ -0,0 +1,129 @@
+//! The state of the server for `/admin/status`: whether ArangoDB and
+//! Cytoscape answer, how many queries are running right now and how much
+//! the in-memory stores hold.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::arango::Client;
+use crate::cytoscape::{self, CytoscapeConfig};
+
+/// How long the status page waits for ArangoDB to answer.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Counts the query executions in progress.
+#[derive(Default)]
+pub struct Jobs {
+    running: AtomicUsize,
+}
+
+/// Counts a job as running until it is dropped, however the job ends.
+pub struct JobGuard {
+    jobs: Arc<Jobs>,
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        self.jobs.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Jobs {
+    pub fn start(self: &Arc<Self>) -> JobGuard {
+        self.running.fetch_add(1, Ordering::SeqCst);
+        JobGuard {
+            jobs: Arc::clone(self),
+        }
+    }
+
+    pub fn running(&self) -> usize {
+        self.running.load(Ordering::SeqCst)
+    }
+}
+
+/// Whether a service answers, with its version or why it does not.
+#[derive(Debug, Serialize)]
+pub struct Reachability {
+    pub reachable: bool,
+    pub detail: String,
+}
+
+pub async fn check_arangodb(client: &Client) -> Reachability {
+    match tokio::time::timeout(CHECK_TIMEOUT, client.version()).await {
+        Ok(Ok(version)) => Reachability {
+            reachable: true,
+            detail: format!("version {}", version),
+        },
+        Ok(Err(e)) => Reachability {
+            reachable: false,
+            detail: e.to_string(),
+        },
+        Err(_) => Reachability {
+            reachable: false,
+            detail: format!("no answer within {} s", CHECK_TIMEOUT.as_secs()),
+        },
+    }
+}
+
+pub async fn check_cytoscape(config: &CytoscapeConfig) -> Reachability {
+    let reachable = cytoscape::is_available(config).await;
+    Reachability {
+        reachable,
+        detail: if reachable {
+            format!("CyREST at {}", config.base_url())
+        } else {
+            format!("CyREST not reachable at {}", config.base_url())
+        },
+    }
+}
+
+/// How full one of the in-memory stores is.
+#[derive(Debug, Serialize)]
+pub struct StoreUsage {
+    pub name: &'static str,
+    pub entries: usize,
+    pub limit: usize,
+}
+
+/// A byte count for humans, e.g. `1.5 MiB`.
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["bytes", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jobs() {
+        let jobs = Arc::new(Jobs::default());
+        let first = jobs.start();
+        let second = jobs.start();
+        assert_eq!(jobs.running(), 2);
+        drop(first);
+        assert_eq!(jobs.running(), 1);
+        drop(second);
+        assert_eq!(jobs.running(), 0);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 bytes");
+        assert_eq!(format_bytes(1023), "1023 bytes");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
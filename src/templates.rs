@@ -0,0 +1,294 @@
+//! The HTML templates and static files of the web interface. They are built
+//! into the binary, and can be overridden by files in a directory on disk,
+//! e.g. to change the branding. Templates on disk are reloaded when they
+//! change, so they can be edited without restarting the server. The same
+//! holds for the translations in `locales/`, see [`crate::i18n`].
+
+use include_dir::{include_dir, Dir};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tera::Tera;
+use tracing::info;
+
+use crate::error::{Error, Result};
+use crate::i18n::{Locales, DEFAULT_LOCALE};
+
+// Include templates directory at compile time
+static TEMPLATES_DIR: Dir = include_dir!("templates");
+
+/// The color themes of the web interface. Every page loads
+/// `static/css/theme-{name}.css` after the common stylesheet.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub const ALL: &'static [Theme] = &[Theme::Light, Theme::Dark];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|t| t.name() == name)
+    }
+}
+
+/// When the templates and locales on disk were last changed, and how many
+/// there are, so that removed files are noticed too.
+type Stamp = (Option<SystemTime>, usize);
+
+struct Loaded {
+    tera: Tera,
+    locales: Locales,
+}
+
+pub struct Templates {
+    dir: Option<PathBuf>,
+    loaded: Mutex<(Arc<Loaded>, Stamp)>,
+}
+
+impl Templates {
+    /// The built-in templates and locales, overridden by the `*.html` files
+    /// in `dir` and the `*.json` files in `dir/locales`.
+    pub fn new(dir: Option<PathBuf>) -> Result<Self> {
+        let stamp = match &dir {
+            Some(dir) => stamp(dir)?,
+            None => (None, 0),
+        };
+        let loaded = load(dir.as_deref())?;
+        if let Some(dir) = &dir {
+            info!(dir = %dir.display(), overrides = stamp.1, "Using template overrides");
+        }
+        Ok(Templates {
+            dir,
+            loaded: Mutex::new((Arc::new(loaded), stamp)),
+        })
+    }
+
+    /// The current templates, reloaded first if the files on disk changed.
+    /// A broken template is reported until it is fixed.
+    fn loaded(&self) -> Result<Arc<Loaded>> {
+        let mut loaded = self.loaded.lock().unwrap();
+        if let Some(dir) = &self.dir {
+            let current = stamp(dir)?;
+            if current != loaded.1 {
+                let reloaded = load(Some(dir))?;
+                info!(dir = %dir.display(), "Reloaded changed templates");
+                *loaded = (Arc::new(reloaded), current);
+            }
+        }
+        Ok(Arc::clone(&loaded.0))
+    }
+
+    /// The locale to show pages in: the configured one, else the best one
+    /// for the `Accept-Language` header of the browser, else English.
+    pub fn locale(&self, configured: Option<&str>, accept_language: Option<&str>) -> String {
+        if let Some(locale) = configured {
+            return locale.to_string();
+        }
+        let loaded = Arc::clone(&self.loaded.lock().unwrap().0);
+        accept_language
+            .and_then(|header| loaded.locales.negotiate(header))
+            .unwrap_or_else(|| DEFAULT_LOCALE.to_string())
+    }
+
+    /// Renders a template with `theme`, `themes`, `locale` and the texts of
+    /// the locale as `tr` added to its context.
+    pub fn render<T: Serialize>(
+        &self,
+        template: &str,
+        context: &T,
+        theme: Theme,
+        locale: &str,
+    ) -> Result<String> {
+        let loaded = self.loaded()?;
+        let mut context = tera::Context::from_serialize(context)?;
+        context.insert("theme", theme.name());
+        context.insert(
+            "themes",
+            &Theme::ALL.iter().map(|t| t.name()).collect::<Vec<_>>(),
+        );
+        context.insert("locale", locale);
+        context.insert("tr", &loaded.locales.texts(locale));
+        Ok(loaded.tera.render(template, &context)?)
+    }
+
+    /// The contents of a file below `static/`, from disk if it is there.
+    pub fn static_file(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        let relative = Path::new(path);
+        // Only plain paths, nothing outside of the directory
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return None;
+        }
+        if let Some(dir) = &self.dir {
+            if let Ok(contents) = std::fs::read(dir.join("static").join(relative)) {
+                return Some(Cow::Owned(contents));
+            }
+        }
+        TEMPLATES_DIR
+            .get_file(Path::new("static").join(relative))
+            .map(|f| Cow::Borrowed(f.contents()))
+    }
+}
+
+/// The `*.html` files directly in `dir` and the `*.json` files in
+/// `dir/locales`.
+fn overrides(dir: &Path) -> Result<Vec<PathBuf>> {
+    let files = |dir: &Path, extension: &str| -> Result<Vec<PathBuf>> {
+        let entries = std::fs::read_dir(dir).map_err(|e| {
+            Error::Config(format!(
+                "Failed to read templates directory {}: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+        Ok(entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == extension))
+            .collect())
+    };
+    let mut found = files(dir, "html")?;
+    let locales = dir.join("locales");
+    if locales.is_dir() {
+        found.extend(files(&locales, "json")?);
+    }
+    Ok(found)
+}
+
+fn stamp(dir: &Path) -> Result<Stamp> {
+    let files = overrides(dir)?;
+    let modified = files
+        .iter()
+        .filter_map(|p| p.metadata().and_then(|m| m.modified()).ok())
+        .max();
+    Ok((modified, files.len()))
+}
+
+fn load(dir: Option<&Path>) -> Result<Loaded> {
+    let mut templates: Vec<(String, String)> = Vec::new();
+    let mut locales = Locales::default();
+
+    // Load all templates and locales from the embedded directory
+    for file in TEMPLATES_DIR.files().chain(
+        TEMPLATES_DIR
+            .get_dir("locales")
+            .into_iter()
+            .flat_map(|d| d.files()),
+    ) {
+        if let Some(name) = file.path().file_name().and_then(|n| n.to_str()) {
+            let content = std::str::from_utf8(file.contents())
+                .map_err(|e| Error::Config(format!("Template {} is not UTF-8: {}", name, e)))?;
+            if name.ends_with(".html") {
+                templates.push((name.to_string(), content.to_string()));
+            } else if let Some(locale) = name.strip_suffix(".json") {
+                locales.add(locale, content)?;
+            }
+        }
+    }
+
+    if let Some(dir) = dir {
+        for path in overrides(dir)? {
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default()
+                .to_string();
+            let content = std::fs::read_to_string(&path).map_err(|e| {
+                Error::Config(format!("Failed to read template {}: {}", path.display(), e))
+            })?;
+            if let Some(locale) = name.strip_suffix(".json") {
+                locales.add(locale, &content)?;
+            } else {
+                templates.retain(|(n, _)| *n != name);
+                templates.push((name, content));
+            }
+        }
+    }
+
+    // Added together, so templates may extend or include each other
+    let mut tera = Tera::default();
+    tera.add_raw_templates(templates)?;
+    Ok(Loaded { tera, locales })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_overrides() {
+        let dir = std::env::temp_dir().join(format!("templates-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("static/css")).unwrap();
+        let templates = Templates::new(Some(dir.clone())).unwrap();
+        let context = json!({ "status": 404, "message": "Query 7 not found" });
+        assert!(templates
+            .render("error.html", &context, Theme::Dark, "en")
+            .unwrap()
+            .contains("Query 7 not found"));
+
+        // Edited templates are picked up without a restart
+        std::fs::write(dir.join("error.html"), "Oops ({{ theme }}): {{ message }}").unwrap();
+        assert_eq!(
+            templates
+                .render("error.html", &context, Theme::Dark, "en")
+                .unwrap(),
+            "Oops (dark): Query 7 not found"
+        );
+        std::fs::write(dir.join("error.html"), "{{ broken").unwrap();
+        let far_future = SystemTime::now() + std::time::Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(dir.join("error.html"))
+            .unwrap()
+            .set_modified(far_future)
+            .unwrap();
+        assert!(templates
+            .render("error.html", &context, Theme::Dark, "en")
+            .is_err());
+        std::fs::remove_file(dir.join("error.html")).unwrap();
+        assert!(templates
+            .render("error.html", &context, Theme::Dark, "en")
+            .unwrap()
+            .contains("<html lang=\"en\">"));
+
+        std::fs::write(dir.join("static/css/style.css"), "body {}").unwrap();
+        assert_eq!(
+            templates.static_file("css/style.css").unwrap().as_ref(),
+            b"body {}"
+        );
+        assert!(Templates::new(None)
+            .unwrap()
+            .static_file("css/style.css")
+            .is_some_and(|css| css.as_ref() != b"body {}"));
+        assert!(templates.static_file("../error.html").is_none());
+
+        // Locales can be overridden and added as well
+        assert_eq!(templates.locale(None, Some("de-DE, en;q=0.5")), "de");
+        assert_eq!(templates.locale(Some("de"), Some("en")), "de");
+        assert_eq!(templates.locale(None, Some("fr")), "en");
+        std::fs::create_dir_all(dir.join("locales")).unwrap();
+        std::fs::write(dir.join("locales/fr.json"), r#"{"error": "Erreur"}"#).unwrap();
+        let page = templates
+            .render("error.html", &context, Theme::Light, "fr")
+            .unwrap();
+        assert!(page.contains("Erreur 404") && page.contains("Back to Menu"));
+        assert!(page.contains("This was not found."));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+//! Multi-tenant mode: users, authenticated by a reverse proxy in front of
+//! the runner, are mapped to the database of their tenant, so that one
+//! instance can serve several teams without their queries ever running
+//! against another team's database.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::arango::Client;
+use crate::config::Configuration;
+use crate::error::{Error, Result};
+use crate::runner::QueryRunner;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TenantConfig {
+    pub name: String,
+    pub database: String,
+    #[serde(default)]
+    pub users: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>, // Any user in one of these groups belongs to the tenant
+    #[serde(default)]
+    pub username: Option<String>, // ArangoDB credentials, else the global ones
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TenancyConfig {
+    #[serde(default = "default_user_header")]
+    pub user_header: String, // Set by the authenticating proxy
+    #[serde(default = "default_groups_header")]
+    pub groups_header: String, // Comma separated group names
+    pub tenants: Vec<TenantConfig>,
+    #[serde(default)]
+    pub admins: Vec<String>, // Change the catalog directly; others propose changes
+    #[serde(default)]
+    pub admin_groups: Vec<String>,
+}
+
+fn default_user_header() -> String {
+    "X-Forwarded-User".to_string()
+}
+
+fn default_groups_header() -> String {
+    "X-Forwarded-Groups".to_string()
+}
+
+impl TenantConfig {
+    /// The endpoint of the tenant's database on the configured server.
+    pub fn endpoint(&self, config: &Configuration) -> String {
+        format!("{}_db/{}/", config.arangodb_endpoint, self.database)
+    }
+}
+
+/// The query runners of all tenants. Without a `tenancy` section every
+/// request gets the runner of the configured database.
+pub struct Tenants {
+    tenancy: Option<TenancyConfig>,
+    default: Arc<QueryRunner>,
+    runners: HashMap<String, Arc<QueryRunner>>,
+}
+
+impl Tenants {
+    pub fn new(config: Arc<Configuration>, default: Arc<QueryRunner>) -> Self {
+        let tenancy = config.tenancy.clone();
+        let runners = tenancy
+            .iter()
+            .flat_map(|t| &t.tenants)
+            .map(|tenant| {
+                let client = Client::with_timeouts(
+                    &tenant.endpoint(&config),
+                    tenant.username.as_deref().unwrap_or(&config.username),
+                    tenant.password.as_deref().unwrap_or(&config.password),
+                    &config.timeouts,
+                );
+                let runner = QueryRunner::for_tenant(Arc::clone(&config), client, &tenant.name);
+                (tenant.name.clone(), Arc::new(runner))
+            })
+            .collect();
+        Tenants {
+            tenancy,
+            default,
+            runners,
+        }
+    }
+
+    /// The headers naming the user and their groups, if tenancy is on.
+    pub fn headers(&self) -> Option<(&str, &str)> {
+        self.tenancy
+            .as_ref()
+            .map(|t| (t.user_header.as_str(), t.groups_header.as_str()))
+    }
+
+    /// The tenant of a user: the one listing the user, else the first one
+    /// listing one of their groups.
+    pub fn tenant_of(&self, user: &str, groups: &[&str]) -> Option<&TenantConfig> {
+        let tenants = &self.tenancy.as_ref()?.tenants;
+        tenants
+            .iter()
+            .find(|t| t.users.iter().any(|u| u == user))
+            .or_else(|| {
+                tenants
+                    .iter()
+                    .find(|t| t.groups.iter().any(|g| groups.contains(&g.as_str())))
+            })
+    }
+
+    /// The runner for a request by `user` with the `groups` header value.
+    /// With tenancy, requests without user or tenant are refused.
+    pub fn runner(&self, user: Option<&str>, groups: Option<&str>) -> Result<Arc<QueryRunner>> {
+        if self.tenancy.is_none() {
+            return Ok(Arc::clone(&self.default));
+        }
+        let user = user
+            .map(str::trim)
+            .filter(|u| !u.is_empty())
+            .ok_or_else(|| Error::Forbidden("Not authenticated".to_string()))?;
+        let groups = split_groups(groups);
+        let tenant = self
+            .tenant_of(user, &groups)
+            .ok_or_else(|| Error::Forbidden(format!("User '{}' belongs to no tenant", user)))?;
+        Ok(Arc::clone(&self.runners[&tenant.name]))
+    }
+
+    /// Whether a user may change the catalog directly, rather than propose
+    /// changes for review. Without tenancy everybody may.
+    pub fn is_admin(&self, user: Option<&str>, groups: Option<&str>) -> bool {
+        let Some(tenancy) = &self.tenancy else {
+            return true;
+        };
+        let Some(user) = user.map(str::trim).filter(|u| !u.is_empty()) else {
+            return false;
+        };
+        tenancy.admins.iter().any(|a| a == user)
+            || split_groups(groups)
+                .iter()
+                .any(|g| tenancy.admin_groups.iter().any(|a| a == g))
+    }
+
+    /// The runners of the default database and of all tenants.
+    pub fn runners(&self) -> impl Iterator<Item = &Arc<QueryRunner>> {
+        std::iter::once(&self.default).chain(self.runners.values())
+    }
+
+    /// The clients of the default database and of all tenants.
+    pub fn clients(&self) -> Vec<&Client> {
+        self.runners().map(|r| r.client()).collect()
+    }
+}
+
+/// The group names of a groups header value.
+fn split_groups(groups: Option<&str>) -> Vec<&str> {
+    groups
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|g| !g.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn test_runner() {
+        let config: Configuration = serde_json::from_value(json!({
+            "arangodb_endpoint": "http://db:8529/",
+            "username": "root",
+            "password": "",
+            "tenancy": {
+                "tenants": [
+                    { "name": "a", "database": "team_a", "users": ["alice"], "username": "a" },
+                    { "name": "b", "database": "team_b", "users": ["bob"], "groups": ["b-team"] }
+                ],
+                "admins": ["alice"],
+                "admin_groups": ["ops"]
+            },
+            "queries": []
+        }))
+        .unwrap();
+        let config = Arc::new(config);
+        let default = Arc::new(QueryRunner::new(Arc::clone(&config)));
+        let tenants = Tenants::new(Arc::clone(&config), default);
+        assert_eq!(
+            tenants.headers(),
+            Some(("X-Forwarded-User", "X-Forwarded-Groups"))
+        );
+
+        let endpoint = |user, groups| {
+            tenants
+                .runner(user, groups)
+                .map(|r| r.client().endpoint().to_string())
+        };
+        assert_eq!(
+            endpoint(Some("alice"), None).unwrap(),
+            "http://db:8529/_db/team_a/"
+        );
+        assert_eq!(
+            endpoint(Some("carol"), Some("x, b-team")).unwrap(),
+            "http://db:8529/_db/team_b/"
+        );
+        // Users are matched before groups
+        assert_eq!(
+            endpoint(Some("alice"), Some("b-team")).unwrap(),
+            "http://db:8529/_db/team_a/"
+        );
+        assert!(matches!(
+            endpoint(Some("carol"), None),
+            Err(Error::Forbidden(_))
+        ));
+        assert!(matches!(
+            endpoint(None, Some("b-team")),
+            Err(Error::Forbidden(_))
+        ));
+        assert_eq!(tenants.clients().len(), 3);
+
+        // Uploads, graphs, slow queries and runs of one tenant are not seen by
+        // another
+        let alice = tenants.runner(Some("alice"), None).unwrap();
+        let upload = alice.uploads().start("q");
+        assert!(alice.uploads().get(upload).is_some());
+        let bob = tenants.runner(Some("bob"), None).unwrap();
+        assert!(bob.uploads().get(upload).is_none());
+        assert!(!Arc::ptr_eq(alice.graphs(), bob.graphs()));
+        assert!(!Arc::ptr_eq(alice.slow_log(), bob.slow_log()));
+        assert!(!Arc::ptr_eq(alice.cursors(), bob.cursors()));
+        alice.history().record("q", Duration::from_millis(5), 1);
+        assert_eq!(alice.history().runs("q").len(), 1);
+        assert!(bob.history().runs("q").is_empty());
+        assert!(!Arc::ptr_eq(alice.usage(), bob.usage()));
+
+        assert!(tenants.is_admin(Some("alice"), None));
+        assert!(tenants.is_admin(Some("bob"), Some("b-team, ops")));
+        assert!(!tenants.is_admin(Some("bob"), Some("b-team")));
+        assert!(!tenants.is_admin(None, Some("ops")));
+    }
+}
@@ -0,0 +1,355 @@
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use adb_query_runner::config::Configuration;
+use adb_query_runner::output;
+use adb_query_runner::runner::{convert_bind_vars, QueryRunner};
+
+const PAGE_SIZE: usize = 20;
+const MAX_COLUMN_WIDTH: usize = 40;
+
+enum Screen {
+    Queries,
+    Parameters {
+        values: Vec<String>,
+        field: usize,
+        error: Option<String>,
+    },
+    Results {
+        columns: Vec<String>,
+        rows: Vec<Vec<String>>,
+        first_column: usize,
+        summary: String,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    None,
+    Execute,
+    Quit,
+}
+
+struct App<'a> {
+    config: &'a Configuration,
+    runner: &'a QueryRunner,
+    queries: ListState,
+    screen: Screen,
+    table: TableState,
+}
+
+impl<'a> App<'a> {
+    fn new(runner: &'a QueryRunner) -> Self {
+        let config = runner.config();
+        let mut queries = ListState::default();
+        if !config.queries.is_empty() {
+            queries.select(Some(0));
+        }
+        App {
+            config,
+            runner,
+            queries,
+            screen: Screen::Queries,
+            table: TableState::default(),
+        }
+    }
+
+    fn selected_query(&self) -> Option<usize> {
+        self.queries.selected()
+    }
+
+    fn handle_key(&mut self, key: KeyCode) -> Action {
+        match &mut self.screen {
+            Screen::Queries => match key {
+                KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+                KeyCode::Up => self.queries.select_previous(),
+                KeyCode::Down => self.queries.select_next(),
+                KeyCode::Enter => {
+                    let Some(idx) = self.selected_query() else {
+                        return Action::None;
+                    };
+                    let count = self.config.queries[idx].parameters.len();
+                    self.screen = Screen::Parameters {
+                        values: vec![String::new(); count],
+                        field: 0,
+                        error: None,
+                    };
+                    if count == 0 {
+                        return Action::Execute;
+                    }
+                }
+                _ => {}
+            },
+            Screen::Parameters { values, field, .. } => match key {
+                KeyCode::Esc => self.screen = Screen::Queries,
+                KeyCode::Enter => return Action::Execute,
+                _ if values.is_empty() => {}
+                KeyCode::Tab | KeyCode::Down => *field = (*field + 1) % values.len(),
+                KeyCode::BackTab | KeyCode::Up => {
+                    *field = (*field + values.len() - 1) % values.len()
+                }
+                KeyCode::Backspace => {
+                    values[*field].pop();
+                }
+                KeyCode::Char(c) => values[*field].push(c),
+                _ => {}
+            },
+            Screen::Results {
+                columns,
+                first_column,
+                ..
+            } => match key {
+                KeyCode::Char('q') => return Action::Quit,
+                KeyCode::Esc => self.screen = Screen::Queries,
+                KeyCode::Up => self.table.select_previous(),
+                KeyCode::Down => self.table.select_next(),
+                KeyCode::PageUp => self.table.scroll_up_by(PAGE_SIZE as u16),
+                KeyCode::PageDown => self.table.scroll_down_by(PAGE_SIZE as u16),
+                KeyCode::Left => *first_column = first_column.saturating_sub(1),
+                KeyCode::Right if *first_column + 1 < columns.len() => *first_column += 1,
+                _ => {}
+            },
+        }
+        Action::None
+    }
+
+    async fn execute(&mut self) {
+        let Some(idx) = self.selected_query() else {
+            return;
+        };
+        let query = &self.config.queries[idx];
+        let values = match &self.screen {
+            Screen::Parameters { values, .. } => values.clone(),
+            _ => Vec::new(),
+        };
+        let params: HashMap<String, String> = query
+            .parameters
+            .iter()
+            .map(|p| p.name.clone())
+            .zip(values)
+            .collect();
+
+        let result = match convert_bind_vars(query, params) {
+            Ok(bind_vars) => self.runner.execute(query, bind_vars).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(result) => {
+                let columns = output::columns(&result.results);
+                let rows: Vec<Vec<String>> = result
+                    .results
+                    .iter()
+                    .map(|row| columns.iter().map(|c| output::cell(row, c)).collect())
+                    .collect();
+                let summary = format!(
+                    "{}: {} rows in {} ms",
+                    query.name,
+                    rows.len(),
+                    result.duration.as_millis()
+                );
+                self.table = TableState::default();
+                if !rows.is_empty() {
+                    self.table.select(Some(0));
+                }
+                self.screen = Screen::Results {
+                    columns,
+                    rows,
+                    first_column: 0,
+                    summary,
+                };
+            }
+            Err(e) => {
+                if let Screen::Parameters { error, .. } = &mut self.screen {
+                    *error = Some(format!("{:#}", e));
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+        let help = match &self.screen {
+            Screen::Queries => {
+                let items: Vec<ListItem> = self
+                    .config
+                    .queries
+                    .iter()
+                    .map(|q| ListItem::new(format!("{} - {}", q.name, q.description)))
+                    .collect();
+                let list = List::new(items)
+                    .block(Block::bordered().title("Queries"))
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+                frame.render_stateful_widget(list, main, &mut self.queries);
+                "Up/Down select, Enter run, q quit".to_string()
+            }
+            Screen::Parameters {
+                values,
+                field,
+                error,
+            } => {
+                let query = &self.config.queries[self.queries.selected().unwrap_or(0)];
+                let mut lines: Vec<Line> = query
+                    .parameters
+                    .iter()
+                    .zip(values.iter())
+                    .enumerate()
+                    .map(|(i, (param, value))| {
+                        let marker = if i == *field { ">" } else { " " };
+                        Line::from(format!(
+                            "{} {} ({}): {}",
+                            marker, param.name, param.parameter_type, value
+                        ))
+                    })
+                    .collect();
+                if let Some(error) = error {
+                    lines.push(Line::from(""));
+                    lines.push(Line::from(format!("Error: {}", error)));
+                }
+                let title = format!("Parameters - {}", query.name);
+                frame.render_widget(
+                    Paragraph::new(lines).block(Block::bordered().title(title)),
+                    main,
+                );
+                "Tab next field, Enter run, Esc back".to_string()
+            }
+            Screen::Results {
+                columns,
+                rows,
+                first_column,
+                summary,
+            } => {
+                let visible = &columns[(*first_column).min(columns.len())..];
+                let widths: Vec<Constraint> = visible
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        let width = rows
+                            .iter()
+                            .map(|r| r[first_column + i].chars().count())
+                            .chain(std::iter::once(c.chars().count()))
+                            .max()
+                            .unwrap_or(0)
+                            .min(MAX_COLUMN_WIDTH);
+                        Constraint::Length(width as u16)
+                    })
+                    .collect();
+                let table_rows = rows
+                    .iter()
+                    .map(|r| Row::new(r[*first_column..].iter().cloned()));
+                let table = Table::new(table_rows, widths)
+                    .header(
+                        Row::new(visible.iter().cloned())
+                            .style(Style::default().add_modifier(Modifier::BOLD)),
+                    )
+                    .block(Block::bordered().title(summary.as_str()))
+                    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+                frame.render_stateful_widget(table, main, &mut self.table);
+                "Up/Down/PgUp/PgDn scroll, Left/Right columns, Esc back, q quit".to_string()
+            }
+        };
+        frame.render_widget(Paragraph::new(help), footer);
+    }
+}
+
+async fn event_loop(terminal: &mut DefaultTerminal, runner: &QueryRunner) -> Result<()> {
+    let mut app = App::new(runner);
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match app.handle_key(key.code) {
+                Action::Quit => return Ok(()),
+                Action::Execute => app.execute().await,
+                Action::None => {}
+            }
+        }
+    }
+}
+
+/// Runs the interactive terminal interface until the user quits.
+pub async fn run(config: Configuration) -> Result<()> {
+    let runner = QueryRunner::new(Arc::new(config));
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &runner).await;
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runner() -> QueryRunner {
+        let config: Configuration = serde_json::from_value(serde_json::json!({
+            "arangodb_endpoint": "http://localhost:8529/",
+            "username": "root",
+            "password": "",
+            "queries": [
+                {
+                    "name": "a",
+                    "description": "",
+                    "query": "RETURN 1",
+                    "parameters": []
+                },
+                {
+                    "name": "b",
+                    "description": "",
+                    "query": "RETURN @x + @y",
+                    "parameters": [
+                        { "name": "x", "parameter_type": "number" },
+                        { "name": "y", "parameter_type": "number" }
+                    ]
+                }
+            ]
+        }))
+        .unwrap();
+        QueryRunner::new(Arc::new(config))
+    }
+
+    #[test]
+    fn test_parameter_entry() {
+        let runner = runner();
+        let mut app = App::new(&runner);
+
+        assert_eq!(app.handle_key(KeyCode::Down), Action::None);
+        assert_eq!(app.handle_key(KeyCode::Enter), Action::None);
+        app.handle_key(KeyCode::Char('4'));
+        app.handle_key(KeyCode::Char('2'));
+        app.handle_key(KeyCode::Backspace);
+        app.handle_key(KeyCode::Tab);
+        app.handle_key(KeyCode::Char('7'));
+        match &app.screen {
+            Screen::Parameters { values, field, .. } => {
+                assert_eq!(values, &vec!["4".to_string(), "7".to_string()]);
+                assert_eq!(*field, 1);
+            }
+            _ => panic!("expected parameter screen"),
+        }
+        assert_eq!(app.handle_key(KeyCode::Enter), Action::Execute);
+        app.handle_key(KeyCode::Esc);
+        assert_eq!(app.handle_key(KeyCode::Char('q')), Action::Quit);
+    }
+
+    #[test]
+    fn test_query_without_parameters_executes_immediately() {
+        let runner = runner();
+        let mut app = App::new(&runner);
+        assert_eq!(app.handle_key(KeyCode::Enter), Action::Execute);
+    }
+}
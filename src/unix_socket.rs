@@ -0,0 +1,141 @@
+//! ArangoDB endpoints on a Unix domain socket, e.g.
+//! `unix:///tmp/arangodb.sock/`. The HTTP client only speaks TCP, so the
+//! requests go through a bridge: a port on localhost that forwards every
+//! connection to the socket.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tokio::net::{TcpListener, UnixStream};
+use tracing::{debug, info, warn};
+
+pub const SCHEME: &str = "unix://";
+
+/// Splits a `unix://` endpoint into the socket and the HTTP path below it,
+/// e.g. `unix:///tmp/arangodb.sock/_db/sales/` into `/tmp/arangodb.sock`
+/// and `_db/sales/`. The socket is the first part of the path that exists
+/// and is no directory; if there is none, the whole path.
+pub fn split_endpoint(endpoint: &str) -> Option<(PathBuf, String)> {
+    let path = Path::new(endpoint.strip_prefix(SCHEME)?);
+    let socket = path
+        .ancestors()
+        .filter(|p| p.exists() && !p.is_dir())
+        .last()
+        .unwrap_or(path);
+    let rest = path.strip_prefix(socket).unwrap_or(Path::new(""));
+    let mut http_path = rest.to_string_lossy().into_owned();
+    if !http_path.is_empty() && endpoint.ends_with('/') {
+        http_path.push('/');
+    }
+    Some((socket.to_path_buf(), http_path))
+}
+
+/// The `http://127.0.0.1:<port>/` URL of the bridge to a socket. The bridge
+/// is started on first use and runs on a thread of its own until the
+/// process ends, so that it does not depend on the caller's runtime.
+pub fn bridge(socket: &Path) -> std::io::Result<String> {
+    static BRIDGES: OnceLock<Mutex<HashMap<PathBuf, String>>> = OnceLock::new();
+    let mut bridges = BRIDGES.get_or_init(Default::default).lock().unwrap();
+    if let Some(url) = bridges.get(socket) {
+        return Ok(url.clone());
+    }
+
+    let listener = std::net::TcpListener::bind(("127.0.0.1", 0))?;
+    listener.set_nonblocking(true)?;
+    let url = format!("http://{}/", listener.local_addr()?);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let target = socket.to_path_buf();
+    std::thread::Builder::new()
+        .name("unix-socket-bridge".to_string())
+        .spawn(move || runtime.block_on(forward(listener, target)))?;
+    info!(socket = %socket.display(), bridge = url.as_str(), "Bridging to Unix socket");
+    bridges.insert(socket.to_path_buf(), url.clone());
+    Ok(url)
+}
+
+async fn forward(listener: std::net::TcpListener, socket: PathBuf) {
+    let listener = match TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Unix socket bridge failed to start: {}", e);
+            return;
+        }
+    };
+    loop {
+        let mut incoming = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Unix socket bridge failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let socket = socket.clone();
+        tokio::spawn(async move {
+            match UnixStream::connect(&socket).await {
+                Ok(mut outgoing) => {
+                    if let Err(e) =
+                        tokio::io::copy_bidirectional(&mut incoming, &mut outgoing).await
+                    {
+                        debug!("Unix socket bridge connection ended: {}", e);
+                    }
+                }
+                Err(e) => warn!(socket = %socket.display(), "Failed to connect: {}", e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arango::Client;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn test_client_over_socket() {
+        let dir = std::env::temp_dir().join(format!("socket-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("arangodb.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let endpoint = format!("unix://{}/", socket.display());
+        assert_eq!(
+            split_endpoint(&format!("{}_db/sales/", endpoint)),
+            Some((socket.clone(), "_db/sales/".to_string()))
+        );
+        assert_eq!(
+            split_endpoint(&endpoint),
+            Some((socket.clone(), String::new()))
+        );
+        assert_eq!(split_endpoint("http://localhost:8529/"), None);
+
+        // A server answering a single request
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let read = stream.read(&mut request).await.unwrap();
+            let body = r#"{"server":"arango","version":"3.12.4"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request[..read]).into_owned()
+        });
+
+        let client = Client::new(&format!("{}_db/sales/", endpoint), "root", "");
+        assert_eq!(client.version().await.unwrap(), "3.12.4");
+        assert!(server
+            .await
+            .unwrap()
+            .starts_with("GET /_db/sales/_api/version HTTP/1.1"));
+        // The configured endpoint is kept, e.g. for `allowed_endpoints`
+        assert!(client.endpoint().starts_with("unix://"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
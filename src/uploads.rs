@@ -0,0 +1,149 @@
+//! Uploads of graphs to Cytoscape, which run in the background so that the
+//! result page shows right away. The page follows an upload by polling
+//! `/api/cytoscape/{id}` until it is done or failed.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::shutdown::BackgroundTasks;
+
+/// Finished uploads kept for the pages that still poll them.
+const KEEP_UPLOADS: usize = 100;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadState {
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Upload {
+    pub id: u64,
+    pub query_name: String,
+    pub started_at: DateTime<Utc>,
+    pub state: UploadState,
+    pub stage: String, // What a running upload is doing, e.g. "Applying layout"
+    pub networks: Vec<i64>, // SUIDs of the networks, once done; several if split by relation
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Uploads {
+    next_id: AtomicU64,
+    uploads: Mutex<VecDeque<Upload>>,
+    tasks: BackgroundTasks, // Sending the graphs, waited for on shutdown
+}
+
+impl Uploads {
+    /// Registers a new upload and returns its id.
+    pub fn start(&self, query_name: &str) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut uploads = self.uploads.lock().unwrap();
+        uploads.push_back(Upload {
+            id,
+            query_name: query_name.to_string(),
+            started_at: Utc::now(),
+            state: UploadState::Running,
+            stage: "Waiting for Cytoscape".to_string(),
+            networks: Vec::new(),
+            error: None,
+        });
+        // Running uploads are never dropped
+        while uploads.len() > KEEP_UPLOADS {
+            match uploads.iter().position(|u| u.state != UploadState::Running) {
+                Some(finished) => uploads.remove(finished),
+                None => break,
+            };
+        }
+        id
+    }
+
+    /// The tasks sending the graphs.
+    pub fn tasks(&self) -> &BackgroundTasks {
+        &self.tasks
+    }
+
+    fn update(&self, id: u64, change: impl FnOnce(&mut Upload)) {
+        if let Some(upload) = self.uploads.lock().unwrap().iter_mut().find(|u| u.id == id) {
+            change(upload);
+        }
+    }
+
+    pub fn stage(&self, id: u64, stage: &str) {
+        self.update(id, |upload| upload.stage = stage.to_string());
+    }
+
+    /// Records the outcome of an upload: the networks, or why it failed.
+    pub fn finish(&self, id: u64, outcome: std::result::Result<Vec<i64>, String>) {
+        self.update(id, |upload| {
+            upload.stage.clear();
+            match outcome {
+                Ok(networks) => {
+                    upload.state = UploadState::Done;
+                    upload.networks = networks;
+                }
+                Err(error) => {
+                    upload.state = UploadState::Failed;
+                    upload.error = Some(error);
+                }
+            }
+        });
+    }
+
+    pub fn get(&self, id: u64) -> Option<Upload> {
+        self.uploads
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|u| u.id == id)
+            .cloned()
+    }
+
+    pub fn running(&self) -> usize {
+        self.uploads
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|u| u.state == UploadState::Running)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uploads() {
+        let uploads = Uploads::default();
+        let first = uploads.start("q");
+        uploads.stage(first, "Applying layout");
+        assert_eq!(uploads.get(first).unwrap().stage, "Applying layout");
+        assert_eq!(uploads.running(), 1);
+
+        uploads.finish(first, Ok(vec![42]));
+        let done = uploads.get(first).unwrap();
+        assert_eq!(done.state, UploadState::Done);
+        assert_eq!(done.networks, [42]);
+
+        let failed = uploads.start("q");
+        uploads.finish(failed, Err("Cytoscape is not reachable".to_string()));
+        assert_eq!(uploads.get(failed).unwrap().state, UploadState::Failed);
+        assert_eq!(uploads.running(), 0);
+
+        // The oldest finished uploads make room, running ones stay
+        let running = uploads.start("q");
+        for _ in 0..KEEP_UPLOADS {
+            let id = uploads.start("q");
+            uploads.finish(id, Ok(vec![1]));
+        }
+        assert!(uploads.get(first).is_none());
+        assert!(uploads.get(running).is_some());
+        assert!(uploads.get(1000).is_none());
+    }
+}
@@ -0,0 +1,216 @@
+//! Usage statistics of the queries of the catalog: how often each one is
+//! executed from the web interface, by how many users, how long it takes
+//! and how often it fails. They show which queries are worth tuning and
+//! which ones nobody uses anymore.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::QueryDefinition;
+use crate::files::{tenant_file, write_atomically};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct UsageConfig {
+    #[serde(default)]
+    pub file: Option<String>, // Persist the counters as JSON and reload them at startup
+}
+
+impl UsageConfig {
+    /// The counters of a tenant go to a file of their own, see
+    /// [`tenant_file`].
+    pub fn for_tenant(&self, tenant: &str) -> UsageConfig {
+        UsageConfig {
+            file: self.file.as_ref().map(|f| tenant_file(f, tenant)),
+        }
+    }
+}
+
+/// The most users kept per query. Beyond that the page only says that
+/// there are more, so that the counters cannot grow without bound.
+const MAX_USERS: usize = 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct Counters {
+    executions: u64,
+    failures: u64,
+    total_ms: u64, // Of the successful executions
+    users: BTreeSet<String>,
+    last_run: Option<DateTime<Utc>>,
+}
+
+/// The usage of one query, as shown on the usage page.
+#[derive(Debug, Serialize)]
+pub struct QueryUsage {
+    pub name: String,
+    pub executions: u64,
+    pub failures: u64,
+    pub failure_percent: f64,
+    pub avg_ms: Option<u64>,
+    pub users: usize,
+    pub more_users: bool, // Than `users`, which stopped counting
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+pub struct UsageStats {
+    config: UsageConfig,
+    counters: Mutex<HashMap<String, Counters>>,
+}
+
+impl UsageStats {
+    pub fn new(config: UsageConfig) -> Self {
+        let counters = match &config.file {
+            Some(file) => match load(file) {
+                Ok(counters) => {
+                    info!(
+                        file = file.as_str(),
+                        queries = counters.len(),
+                        "Loaded usage statistics"
+                    );
+                    counters
+                }
+                Err(e) => {
+                    warn!(
+                        file = file.as_str(),
+                        "Could not load usage statistics: {}", e
+                    );
+                    HashMap::new()
+                }
+            },
+            None => HashMap::new(),
+        };
+        UsageStats {
+            config,
+            counters: Mutex::new(counters),
+        }
+    }
+
+    /// Counts an execution of a query by `user`, with its duration if it
+    /// succeeded.
+    pub fn record(&self, query_name: &str, user: &str, outcome: Option<Duration>) {
+        let mut counters = self.counters.lock().unwrap();
+        let entry = counters.entry(query_name.to_string()).or_default();
+        entry.executions += 1;
+        match outcome {
+            Some(duration) => entry.total_ms += duration.as_millis() as u64,
+            None => entry.failures += 1,
+        }
+        if entry.users.len() < MAX_USERS {
+            entry.users.insert(user.to_string());
+        }
+        entry.last_run = Some(Utc::now());
+        if let Some(file) = &self.config.file {
+            if let Err(e) = save(file, &counters) {
+                warn!(
+                    file = file.as_str(),
+                    "Failed to persist usage statistics: {}", e
+                );
+            }
+        }
+    }
+
+    /// The usage of all queries of the catalog, most executed first, so
+    /// that unused ones end up at the bottom.
+    pub fn summary(&self, queries: &[QueryDefinition]) -> Vec<QueryUsage> {
+        let counters = self.counters.lock().unwrap();
+        let mut usage: Vec<QueryUsage> = queries
+            .iter()
+            .map(|query| {
+                let c = counters.get(&query.name).cloned().unwrap_or_default();
+                let successes = c.executions - c.failures;
+                QueryUsage {
+                    name: query.name.clone(),
+                    executions: c.executions,
+                    failures: c.failures,
+                    failure_percent: if c.executions == 0 {
+                        0.0
+                    } else {
+                        100.0 * c.failures as f64 / c.executions as f64
+                    },
+                    avg_ms: (successes > 0).then(|| c.total_ms / successes),
+                    users: c.users.len(),
+                    more_users: c.users.len() >= MAX_USERS,
+                    last_run: c.last_run,
+                }
+            })
+            .collect();
+        usage.sort_by_key(|u| std::cmp::Reverse(u.executions));
+        usage
+    }
+}
+
+fn load(file: &str) -> std::io::Result<HashMap<String, Counters>> {
+    match std::fs::read_to_string(file) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn save(file: &str, counters: &HashMap<String, Counters>) -> std::io::Result<()> {
+    write_atomically(file, &serde_json::to_vec_pretty(counters)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_summary() {
+        let queries: Vec<QueryDefinition> = ["popular", "failing", "unused"]
+            .iter()
+            .map(|name| {
+                serde_json::from_value(json!({
+                    "name": name, "description": "", "query": "RETURN 1", "parameters": []
+                }))
+                .unwrap()
+            })
+            .collect();
+        let file = std::env::temp_dir().join(format!("usage-{}.json", uuid::Uuid::new_v4()));
+        let config = UsageConfig {
+            file: Some(file.to_string_lossy().into_owned()),
+        };
+        let usage = UsageStats::new(config.clone());
+        usage.record("failing", "alice", None);
+        usage.record("failing", "alice", Some(Duration::from_millis(30)));
+        for (user, ms) in [("alice", 10), ("bob", 20), ("bob", 30)] {
+            usage.record("popular", user, Some(Duration::from_millis(ms)));
+        }
+
+        // Reloaded from the file
+        let summary = UsageStats::new(config).summary(&queries);
+        let names: Vec<&str> = summary.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, ["popular", "failing", "unused"]);
+        assert_eq!(summary[0].executions, 3);
+        assert_eq!(summary[0].users, 2);
+        assert_eq!(summary[0].avg_ms, Some(20));
+        assert_eq!(summary[1].failure_percent, 50.0);
+        assert_eq!(summary[1].avg_ms, Some(30));
+        assert_eq!(summary[2].executions, 0);
+        assert_eq!(summary[2].avg_ms, None);
+        assert!(summary[2].last_run.is_none());
+        assert!(!summary[0].more_users);
+
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_users_are_capped() {
+        let query: QueryDefinition = serde_json::from_value(json!({
+            "name": "scripted", "description": "", "query": "RETURN 1", "parameters": []
+        }))
+        .unwrap();
+        let usage = UsageStats::new(UsageConfig::default());
+        for i in 0..MAX_USERS + 10 {
+            usage.record("scripted", &format!("user{}", i), None);
+        }
+        let summary = usage.summary(&[query]);
+        assert_eq!(summary[0].executions, MAX_USERS as u64 + 10);
+        assert_eq!(summary[0].users, MAX_USERS);
+        assert!(summary[0].more_users);
+    }
+}
@@ -0,0 +1,506 @@
+//! Static checks of a configuration, used by the `check` subcommand.
+
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+use crate::arango::{Client, CursorOptions};
+use crate::config::{Configuration, QueryDefinition, QueryParameter};
+use crate::error::Result;
+use crate::graph_filter::Predicate;
+use crate::{cytoscape, proxy};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub query: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &self.query {
+            Some(q) => write!(f, "{}: [{}] {}", severity, q, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+const PARAMETER_TYPES: &[&str] = &["string", "number", "boolean"];
+
+/// Returns the names of all bind variables referenced in an AQL query, as
+/// they appear as keys in `bindVars` (collection variables `@@coll` are
+/// returned as `@coll`). String literals and comments are skipped.
+pub fn bind_var_references(aql: &str) -> BTreeSet<String> {
+    scan_bind_vars(aql).into_iter().collect()
+}
+
+/// The bind variables of an AQL query in the order they appear, repeated
+/// as often as they are used.
+fn scan_bind_vars(aql: &str) -> Vec<String> {
+    let chars: Vec<char> = aql.chars().collect();
+    let mut vars = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            quote @ ('"' | '\'' | '`' | '´') => {
+                i += 1;
+                while i < chars.len() && chars[i] != quote {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            '@' => {
+                let mut name = String::new();
+                i += 1;
+                if chars.get(i) == Some(&'@') {
+                    name.push('@');
+                    i += 1;
+                }
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    name.push(chars[i]);
+                    i += 1;
+                }
+                if !name.is_empty() && name != "@" {
+                    vars.push(name);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    vars
+}
+
+/// The parameters the bind variables of an AQL query need, in the order
+/// they first appear. Declared parameters that are used are kept as they
+/// are. The types of the others are guessed from their names: collection
+/// variables (`@@coll`) are strings, `limit` or `minAge` numbers and
+/// `isActive` or `include_deleted` booleans; anything else is a string.
+pub fn discover_parameters(aql: &str, declared: &[QueryParameter]) -> Vec<QueryParameter> {
+    let mut parameters: Vec<QueryParameter> = Vec::new();
+    for var in scan_bind_vars(aql) {
+        if parameters.iter().any(|p| p.name == var) {
+            continue;
+        }
+        let parameter = declared
+            .iter()
+            .find(|p| p.name == var)
+            .cloned()
+            .unwrap_or_else(|| QueryParameter {
+                parameter_type: guess_type(&var).to_string(),
+                name: var,
+            });
+        parameters.push(parameter);
+    }
+    parameters
+}
+
+const NUMBER_WORDS: &[&str] = &[
+    "limit",
+    "offset",
+    "skip",
+    "count",
+    "min",
+    "max",
+    "age",
+    "size",
+    "page",
+    "depth",
+    "year",
+    "amount",
+    "price",
+    "threshold",
+    "top",
+    "number",
+    "num",
+];
+
+const BOOLEAN_WORDS: &[&str] = &["is", "has", "include", "with", "only", "enabled", "active"];
+
+fn guess_type(var: &str) -> &'static str {
+    if var.starts_with('@') {
+        return "string";
+    }
+    // The words of snake_case and camelCase names
+    let mut words: Vec<String> = Vec::new();
+    for (i, c) in var.chars().enumerate() {
+        if c == '_' {
+            words.push(String::new());
+        } else if i == 0 || c.is_uppercase() {
+            words.push(c.to_lowercase().collect());
+        } else if let Some(word) = words.last_mut() {
+            word.push(c);
+        }
+    }
+    let words: Vec<&str> = words
+        .iter()
+        .map(String::as_str)
+        .filter(|w| !w.is_empty())
+        .collect();
+    if words.first().is_some_and(|w| BOOLEAN_WORDS.contains(w)) {
+        "boolean"
+    } else if words.iter().any(|w| NUMBER_WORDS.contains(w)) {
+        "number"
+    } else {
+        "string"
+    }
+}
+
+/// Cross-checks the declared parameters of a query against the bind
+/// variables used in its AQL text.
+pub fn check_parameters(query: &QueryDefinition) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let issue = |severity, message: String| Issue {
+        severity,
+        query: Some(query.name.clone()),
+        message,
+    };
+
+    let used = bind_var_references(&query.query);
+    let mut declared = HashSet::new();
+    for param in &query.parameters {
+        if !declared.insert(param.name.as_str()) {
+            issues.push(issue(
+                Severity::Error,
+                format!("Parameter '{}' is declared more than once", param.name),
+            ));
+        }
+        if !PARAMETER_TYPES.contains(&param.parameter_type.as_str()) {
+            issues.push(issue(
+                Severity::Warning,
+                format!(
+                    "Parameter '{}' has unknown type '{}', it will be passed as a string",
+                    param.name, param.parameter_type
+                ),
+            ));
+        }
+        if !used.contains(&param.name) {
+            issues.push(issue(
+                Severity::Warning,
+                format!("Parameter '{}' is declared but not used", param.name),
+            ));
+        }
+    }
+    for var in &used {
+        if !declared.contains(var.as_str()) {
+            issues.push(issue(
+                Severity::Error,
+                format!("Bind variable '@{}' is used but not declared", var),
+            ));
+        }
+    }
+    // Both variants run with the same bind variables, which ArangoDB
+    // refuses if one of them does not use them all
+    if let Some(alternative) = &query.alternative {
+        let alternative = bind_var_references(alternative);
+        for var in used.symmetric_difference(&alternative) {
+            issues.push(issue(
+                Severity::Error,
+                format!(
+                    "Bind variable '@{}' is used by only one of the query and its alternative",
+                    var
+                ),
+            ));
+        }
+    }
+    issues
+}
+
+/// Checks that the regression tests of a query give its declared
+/// parameters and nothing else, and that their invariants parse.
+pub fn check_tests(query: &QueryDefinition) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for (index, test) in query.tests.iter().enumerate() {
+        let mut issue = |message: String| {
+            issues.push(Issue {
+                severity: Severity::Error,
+                query: Some(query.name.clone()),
+                message: format!("Test '{}': {}", test.label(index), message),
+            })
+        };
+        for param in &query.parameters {
+            if !test.bind_vars.contains_key(&param.name) {
+                issue(format!("Parameter '{}' is not given", param.name));
+            }
+        }
+        let mut names: Vec<&String> = test.bind_vars.keys().collect();
+        names.sort();
+        for name in names {
+            if !query.parameters.iter().any(|p| &p.name == name) {
+                issue(format!("Bind variable '{}' is not a parameter", name));
+            }
+        }
+        for invariant in &test.invariants {
+            if let Err(e) = Predicate::parse(invariant) {
+                issue(e.to_string());
+            }
+        }
+    }
+    issues
+}
+
+/// Runs all offline checks on a parsed configuration.
+pub fn check_configuration(config: &Configuration) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let global = |severity, message: String| Issue {
+        severity,
+        query: None,
+        message,
+    };
+
+    if !config.arangodb_endpoint.ends_with('/') {
+        issues.push(global(
+            Severity::Error,
+            format!(
+                "arangodb_endpoint '{}' must end with '/'",
+                config.arangodb_endpoint
+            ),
+        ));
+    }
+    if let Err(e) = proxy::builder(&config.proxy) {
+        issues.push(global(Severity::Error, e.to_string()));
+    }
+    if let Some(git) = &config.git_store {
+        if !std::path::Path::new(&git.repository).join(".git").exists() {
+            issues.push(global(
+                Severity::Error,
+                format!("The git store '{}' is no git repository", git.repository),
+            ));
+        }
+    }
+    if !cytoscape::LAYOUTS.contains(&config.cytoscape.layout.as_str()) {
+        issues.push(global(
+            Severity::Warning,
+            format!("Unknown Cytoscape layout '{}'", config.cytoscape.layout),
+        ));
+    }
+    if config.queries.is_empty() {
+        issues.push(global(
+            Severity::Warning,
+            "No queries configured".to_string(),
+        ));
+    }
+
+    if let Some(tenancy) = &config.tenancy {
+        let mut users = HashSet::new();
+        let mut tenants = HashSet::new();
+        for tenant in &tenancy.tenants {
+            if !tenants.insert(tenant.name.as_str()) {
+                issues.push(global(
+                    Severity::Error,
+                    format!("Tenant name '{}' is used more than once", tenant.name),
+                ));
+            }
+            if tenant.users.is_empty() && tenant.groups.is_empty() {
+                issues.push(global(
+                    Severity::Warning,
+                    format!("Tenant '{}' has no users and no groups", tenant.name),
+                ));
+            }
+            for user in &tenant.users {
+                if !users.insert(user.as_str()) {
+                    issues.push(global(
+                        Severity::Error,
+                        format!("User '{}' belongs to more than one tenant", user),
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut names = HashSet::new();
+    for query in &config.queries {
+        if !names.insert(query.name.as_str()) {
+            issues.push(global(
+                Severity::Error,
+                format!("Query name '{}' is used more than once", query.name),
+            ));
+        }
+        if query.query.trim().is_empty() {
+            issues.push(Issue {
+                severity: Severity::Error,
+                query: Some(query.name.clone()),
+                message: "Query text is empty".to_string(),
+            });
+        }
+        if let Some(layout) = &query.cytoscape.layout {
+            if !cytoscape::LAYOUTS.contains(&layout.as_str()) {
+                issues.push(Issue {
+                    severity: Severity::Warning,
+                    query: Some(query.name.clone()),
+                    message: format!("Unknown Cytoscape layout '{}'", layout),
+                });
+            }
+        }
+        for option in CursorOptions::of(query).conflicts() {
+            issues.push(Issue {
+                severity: Severity::Warning,
+                query: Some(query.name.clone()),
+                message: format!(
+                    "Option '{}' is not supported by streaming cursors, it is ignored with `stream: true`",
+                    option
+                ),
+            });
+        }
+        issues.extend(check_parameters(query));
+        issues.extend(check_tests(query));
+    }
+    issues
+}
+
+/// Lets the server parse every query (`POST /_api/query`) without executing
+/// it and reports syntax errors.
+pub async fn check_on_server(config: &Configuration) -> Result<Vec<Issue>> {
+    let client = Client::from_config(config);
+    let mut issues = Vec::new();
+
+    for query in &config.queries {
+        if let Some(error) = client.parse_query(&query.query).await? {
+            issues.push(Issue {
+                severity: Severity::Error,
+                query: Some(query.name.clone()),
+                message: format!("AQL does not parse: {}", error),
+            });
+        }
+        if let Some(alternative) = &query.alternative {
+            if let Some(error) = client.parse_query(alternative).await? {
+                issues.push(Issue {
+                    severity: Severity::Error,
+                    query: Some(query.name.clone()),
+                    message: format!("The alternative AQL does not parse: {}", error),
+                });
+            }
+        }
+    }
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QueryParameter;
+
+    #[test]
+    fn test_bind_var_references() {
+        let vars = bind_var_references(
+            "FOR u IN @@coll FILTER u.age >= @minAge && u.mail != 'a@b.c' // @ignored\n /* @also */ RETURN @minAge",
+        );
+        let expected: BTreeSet<String> =
+            ["@coll", "minAge"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(vars, expected);
+    }
+
+    #[test]
+    fn test_discover_parameters() {
+        let declared = [QueryParameter {
+            name: "name".to_string(),
+            parameter_type: "number".to_string(),
+        }];
+        let found = discover_parameters(
+            "FOR u IN @@users FILTER u.name == @name && u.age >= @minAge && u.active == @is_active LIMIT @limit RETURN @name",
+            &declared,
+        );
+        let found: Vec<(&str, &str)> = found
+            .iter()
+            .map(|p| (p.name.as_str(), p.parameter_type.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("@users", "string"),
+                ("name", "number"), // As declared
+                ("minAge", "number"),
+                ("is_active", "boolean"),
+                ("limit", "number"),
+            ]
+        );
+        assert!(discover_parameters("RETURN 1", &declared).is_empty());
+    }
+
+    #[test]
+    fn test_check_parameters() {
+        let issues = check_parameters(&QueryDefinition::for_test(
+            "q",
+            "FOR u IN users FILTER u.age >= @minAge RETURN u",
+            &[("minAge", "number")],
+        ));
+        assert!(issues.is_empty());
+
+        let issues = check_parameters(&QueryDefinition::for_test(
+            "q",
+            "FOR u IN users FILTER u.age >= @minAge RETURN u",
+            &[("maxAge", "integer")],
+        ));
+        let errors = issues
+            .iter()
+            .filter(|i| i.severity == Severity::Error)
+            .count();
+        let warnings = issues
+            .iter()
+            .filter(|i| i.severity == Severity::Warning)
+            .count();
+        assert_eq!(errors, 1); // minAge undeclared
+        assert_eq!(warnings, 2); // unknown type and unused maxAge
+
+        let mut rewrite = QueryDefinition::for_test(
+            "q",
+            "FOR u IN users FILTER u.age >= @minAge RETURN u",
+            &[("minAge", "number")],
+        );
+        rewrite.alternative = Some("FOR u IN users FILTER u.age > @minAge - 1 RETURN u".into());
+        assert!(check_parameters(&rewrite).is_empty());
+        rewrite.alternative = Some("FOR u IN users FILTER u.age >= @age RETURN u".into());
+        assert_eq!(check_parameters(&rewrite).len(), 2);
+    }
+
+    #[test]
+    fn test_check_tests() {
+        let mut adults = QueryDefinition::for_test(
+            "q",
+            "FOR u IN users FILTER u.age >= @minAge RETURN u",
+            &[("minAge", "number")],
+        );
+        adults.tests = serde_json::from_value(serde_json::json!([
+            { "bind_vars": { "minAge": 18 }, "invariants": ["age >= 18"] },
+            { "name": "typo", "bind_vars": { "minage": 18 }, "invariants": ["age"] }
+        ]))
+        .unwrap();
+        let messages: Vec<String> = check_tests(&adults)
+            .into_iter()
+            .map(|i| i.message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Test 'typo': Parameter 'minAge' is not given",
+                "Test 'typo': Bind variable 'minage' is not a parameter",
+                "Test 'typo': Invalid parameter: No comparison operator in filter 'age'",
+            ]
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! Earlier versions of the query definitions. Every change made at runtime,
+//! by editing a query, importing a catalog or rolling back, is kept with its
+//! author and time, so that versions can be compared and restored.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::config::QueryDefinition;
+use crate::error::{Error, Result};
+
+/// The author of the definitions found in the configuration file.
+pub const CONFIGURATION_AUTHOR: &str = "configuration";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VersionsConfig {
+    #[serde(default)]
+    pub file: Option<String>, // Persist versions as JSON lines and reload them at startup
+    #[serde(default = "default_keep_per_query")]
+    pub keep_per_query: usize,
+}
+
+fn default_keep_per_query() -> usize {
+    50
+}
+
+impl Default for VersionsConfig {
+    fn default() -> Self {
+        VersionsConfig {
+            file: None,
+            keep_per_query: default_keep_per_query(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueryVersion {
+    pub query_name: String,
+    pub version: usize, // Counting from 1 per query
+    pub author: String,
+    pub timestamp: DateTime<Utc>,
+    pub note: String, // e.g. "Edited" or "Rolled back to version 3"
+    pub definition: QueryDefinition,
+}
+
+pub struct QueryVersions {
+    config: VersionsConfig,
+    versions: Mutex<HashMap<String, Vec<QueryVersion>>>,
+}
+
+impl QueryVersions {
+    pub fn new(config: VersionsConfig) -> Self {
+        let versions = QueryVersions {
+            config,
+            versions: Mutex::new(HashMap::new()),
+        };
+        if let Some(file) = &versions.config.file {
+            match load_from_file(file) {
+                Ok(records) => {
+                    info!(
+                        file = file.as_str(),
+                        versions = records.len(),
+                        "Loaded query versions"
+                    );
+                    for record in records {
+                        versions.push(record);
+                    }
+                }
+                Err(e) => warn!(file = file.as_str(), "Could not load query versions: {}", e),
+            }
+        }
+        versions
+    }
+
+    fn push(&self, record: QueryVersion) {
+        let mut versions = self.versions.lock().unwrap();
+        let entries = versions.entry(record.query_name.clone()).or_default();
+        entries.push(record);
+        let excess = entries
+            .len()
+            .saturating_sub(self.config.keep_per_query.max(1));
+        entries.drain(..excess);
+    }
+
+    /// Records a definition as the latest version of its query, unless it
+    /// is the same as the latest one. Returns the version number.
+    pub fn record(&self, definition: &QueryDefinition, author: &str, note: &str) -> usize {
+        let latest = self.latest(&definition.name);
+        if let Some(latest) = &latest {
+            if same_definition(&latest.definition, definition) {
+                return latest.version;
+            }
+        }
+        let record = QueryVersion {
+            query_name: definition.name.clone(),
+            version: latest.map_or(1, |v| v.version + 1),
+            author: author.to_string(),
+            timestamp: Utc::now(),
+            note: note.to_string(),
+            definition: definition.clone(),
+        };
+        if let Some(file) = &self.config.file {
+            if let Err(e) = append_to_file(file, &record) {
+                warn!(file = file.as_str(), "Failed to persist version: {}", e);
+            }
+        }
+        let version = record.version;
+        self.push(record);
+        version
+    }
+
+    /// Records the definitions of the configuration file that differ from
+    /// their latest version, e.g. because the file was edited by hand.
+    pub fn record_configuration(&self, queries: &[QueryDefinition]) {
+        for query in queries {
+            self.record(query, CONFIGURATION_AUTHOR, "Loaded from the configuration");
+        }
+    }
+
+    /// Returns all kept versions of a query, oldest first.
+    pub fn versions(&self, query_name: &str) -> Vec<QueryVersion> {
+        self.versions
+            .lock()
+            .unwrap()
+            .get(query_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, query_name: &str, version: usize) -> Option<QueryVersion> {
+        self.versions
+            .lock()
+            .unwrap()
+            .get(query_name)?
+            .iter()
+            .find(|v| v.version == version)
+            .cloned()
+    }
+
+    pub fn latest(&self, query_name: &str) -> Option<QueryVersion> {
+        self.versions
+            .lock()
+            .unwrap()
+            .get(query_name)?
+            .last()
+            .cloned()
+    }
+
+    /// All kept versions, by query and oldest first.
+    pub fn all(&self) -> Vec<QueryVersion> {
+        let versions = self.versions.lock().unwrap();
+        let mut names: Vec<&String> = versions.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .flat_map(|name| versions[name].iter().cloned())
+            .collect()
+    }
+
+    /// Takes over the versions of queries that have none here yet, e.g.
+    /// from an archive of another installation. Returns how many were
+    /// taken over; the versions of queries known here are kept as they are.
+    pub fn restore(&self, records: Vec<QueryVersion>) -> usize {
+        let known: HashSet<String> = self.versions.lock().unwrap().keys().cloned().collect();
+        let mut restored = 0;
+        for record in records {
+            if known.contains(&record.query_name) {
+                continue;
+            }
+            if let Some(file) = &self.config.file {
+                if let Err(e) = append_to_file(file, &record) {
+                    warn!(file = file.as_str(), "Failed to persist version: {}", e);
+                }
+            }
+            self.push(record);
+            restored += 1;
+        }
+        restored
+    }
+}
+
+/// Whether two definitions are equal in everything they declare.
+pub fn same_definition(a: &QueryDefinition, b: &QueryDefinition) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// A definition as the text that is edited and compared.
+pub fn to_text(definition: &QueryDefinition) -> String {
+    serde_json::to_string_pretty(definition).unwrap_or_default()
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct DiffLine {
+    pub change: Change,
+    pub text: String,
+}
+
+/// The lines of `old` and `new` with those only in one of them marked, by
+/// the longest common subsequence of lines.
+pub fn diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // common[i][j]: length of the common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let line = |change, text: &str| DiffLine {
+        change,
+        text: text.to_string(),
+    };
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(line(Change::Same, old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(line(Change::Removed, old[i]));
+            i += 1;
+        } else {
+            lines.push(line(Change::Added, new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().map(|text| line(Change::Removed, text)));
+    lines.extend(new[j..].iter().map(|text| line(Change::Added, text)));
+    lines
+}
+
+fn load_from_file(path: &str) -> Result<Vec<QueryVersion>> {
+    let file = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut records = Vec::new();
+    for line in std::io::BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|e| Error::Config(format!("Invalid version entry in {}: {}", path, e)))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn append_to_file(path: &str, record: &QueryVersion) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(
+        file,
+        "{}",
+        serde_json::to_string(record).map_err(std::io::Error::from)?
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let path = std::env::temp_dir().join(format!("versions-{}.jsonl", uuid::Uuid::new_v4()));
+        let config = VersionsConfig {
+            file: Some(path.to_str().unwrap().to_string()),
+            keep_per_query: 2,
+        };
+        let versions = QueryVersions::new(config.clone());
+        versions.record_configuration(&[QueryDefinition::for_test("q", "RETURN 1", &[])]);
+        assert_eq!(
+            versions.record(
+                &QueryDefinition::for_test("q", "RETURN 1", &[]),
+                "alice",
+                "Edited"
+            ),
+            1
+        );
+        assert_eq!(
+            versions.record(
+                &QueryDefinition::for_test("q", "RETURN 2", &[]),
+                "alice",
+                "Edited"
+            ),
+            2
+        );
+        assert_eq!(
+            versions.record(
+                &QueryDefinition::for_test("q", "RETURN 3", &[]),
+                "bob",
+                "Edited"
+            ),
+            3
+        );
+
+        // Only the latest versions are kept, also after a restart
+        let reloaded = QueryVersions::new(config);
+        for versions in [&versions, &reloaded] {
+            let kept: Vec<usize> = versions.versions("q").iter().map(|v| v.version).collect();
+            assert_eq!(kept, [2, 3]);
+            assert_eq!(versions.latest("q").unwrap().author, "bob");
+            assert_eq!(versions.get("q", 2).unwrap().definition.query, "RETURN 2");
+            assert!(versions.get("q", 1).is_none());
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_diff() {
+        let lines = diff("a\nb\nc\n", "a\nc\nd\n");
+        let changes: Vec<(Change, &str)> =
+            lines.iter().map(|l| (l.change, l.text.as_str())).collect();
+        assert_eq!(
+            changes,
+            [
+                (Change::Same, "a"),
+                (Change::Removed, "b"),
+                (Change::Same, "c"),
+                (Change::Added, "d"),
+            ]
+        );
+        assert!(diff("", "").is_empty());
+    }
+}
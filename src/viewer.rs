@@ -0,0 +1,424 @@
+//! The graph viewer in the browser: recent graph results are kept in memory
+//! and rendered with cytoscape.js on `/graph/{id}`, so graphs can be looked
+//! at without a desktop Cytoscape.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::analytics;
+
+/// The JavaScript libraries that can draw a graph in the browser. Dense
+/// graphs often look better with vis-network's physics simulation.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphRenderer {
+    #[default]
+    Cytoscape, // cytoscape.js
+    Vis, // vis-network
+}
+
+impl GraphRenderer {
+    pub const ALL: &'static [GraphRenderer] = &[GraphRenderer::Cytoscape, GraphRenderer::Vis];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GraphRenderer::Cytoscape => "cytoscape",
+            GraphRenderer::Vis => "vis",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|r| r.name() == name)
+    }
+
+    /// The layouts the renderer offers: cytoscape.js layouts, or the physics
+    /// solvers of vis-network.
+    pub fn layouts(self) -> &'static [&'static str] {
+        match self {
+            GraphRenderer::Cytoscape => &[
+                "cose",
+                "breadthfirst",
+                "circle",
+                "concentric",
+                "grid",
+                "random",
+            ],
+            GraphRenderer::Vis => &["forceAtlas2Based", "barnesHut", "repulsion", "hierarchical"],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ViewerConfig {
+    #[serde(default = "default_keep_graphs")]
+    pub keep_graphs: usize, // Graph results kept for viewing, oldest are dropped
+    #[serde(default)]
+    pub renderer: GraphRenderer, // Unless a query sets its own
+    #[serde(default = "default_cytoscape_js_url")]
+    pub cytoscape_js_url: String, // Where browsers load the renderers from
+    #[serde(default = "default_vis_network_url")]
+    pub vis_network_url: String,
+}
+
+fn default_keep_graphs() -> usize {
+    20
+}
+
+fn default_cytoscape_js_url() -> String {
+    "https://unpkg.com/cytoscape@3.30.2/dist/cytoscape.min.js".to_string()
+}
+
+fn default_vis_network_url() -> String {
+    "https://unpkg.com/vis-network@9.1.9/standalone/umd/vis-network.min.js".to_string()
+}
+
+impl Default for ViewerConfig {
+    fn default() -> Self {
+        ViewerConfig {
+            keep_graphs: default_keep_graphs(),
+            renderer: GraphRenderer::default(),
+            cytoscape_js_url: default_cytoscape_js_url(),
+            vis_network_url: default_vis_network_url(),
+        }
+    }
+}
+
+impl ViewerConfig {
+    pub fn script_url(&self, renderer: GraphRenderer) -> &str {
+        match renderer {
+            GraphRenderer::Cytoscape => &self.cytoscape_js_url,
+            GraphRenderer::Vis => &self.vis_network_url,
+        }
+    }
+}
+
+/// A vertex as handed to the browser, independent of the renderer.
+#[derive(Debug, Serialize, Clone)]
+pub struct ViewerNode {
+    pub id: Value,
+    pub label: Value,
+    pub collection: String,       // Of its `_id`, to color it by
+    pub data: Map<String, Value>, // The document
+}
+
+/// An edge as handed to the browser, independent of the renderer.
+#[derive(Debug, Serialize, Clone)]
+pub struct ViewerEdge {
+    pub id: Value,
+    pub source: Value,
+    pub target: Value,
+    pub data: Map<String, Value>,
+}
+
+/// A graph in the form the browser renderers are fed with.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct GraphData {
+    pub nodes: Vec<ViewerNode>,
+    pub edges: Vec<ViewerEdge>,
+}
+
+/// A graph result as shown by the viewer.
+#[derive(Debug, Serialize, Clone)]
+pub struct StoredGraph {
+    pub id: u64,
+    pub query_name: String,
+    pub renderer: GraphRenderer,
+    pub timestamp: DateTime<Utc>,
+    pub vertices: Vec<Value>,
+    pub edges: Vec<Value>,
+}
+
+/// The label of a vertex: its `name` or `label` attribute, else its `_key`.
+fn label(vertex: &Map<String, Value>) -> Value {
+    ["name", "label", "_key", "_id"]
+        .iter()
+        .filter_map(|attr| vertex.get(*attr))
+        .find(|v| v.is_string() || v.is_number())
+        .map(|v| match v {
+            Value::String(s) => json!(s),
+            other => json!(other.to_string()),
+        })
+        .unwrap_or(Value::Null)
+}
+
+/// Colors for vertices of different collections, used in turn.
+const PALETTE: &[&str] = &[
+    "#3498db", "#e74c3c", "#2ecc71", "#f39c12", "#9b59b6", "#1abc9c", "#e67e22", "#34495e",
+    "#f1c40f", "#7f8c8d",
+];
+
+/// A color for every collection the vertices come from, the prefixes of
+/// their `_id`. Collections are assigned colors in alphabetical order, so
+/// the same set of collections always looks the same.
+pub fn collection_colors(vertices: &[Value]) -> BTreeMap<String, String> {
+    palette_colors(
+        vertices
+            .iter()
+            .filter_map(|v| v["_id"].as_str())
+            .map(analytics::collection),
+    )
+}
+
+/// A color for every value of a string attribute of the documents, assigned
+/// like those of [`collection_colors`].
+pub fn attribute_colors(documents: &[Value], attribute: &str) -> BTreeMap<String, String> {
+    palette_colors(documents.iter().filter_map(|d| d[attribute].as_str()))
+}
+
+/// The colors of the palette for the values, in alphabetical order.
+fn palette_colors<'a>(values: impl Iterator<Item = &'a str>) -> BTreeMap<String, String> {
+    let mut colors: BTreeMap<String, String> =
+        values.map(|c| (c.to_string(), String::new())).collect();
+    for (i, color) in colors.values_mut().enumerate() {
+        *color = PALETTE[i % PALETTE.len()].to_string();
+    }
+    colors
+}
+
+/// Colors for the `diff` attribute of a comparison of two graphs.
+pub fn diff_colors() -> BTreeMap<String, String> {
+    [
+        ("added", "#2ecc71"),
+        ("removed", "#e74c3c"),
+        ("changed", "#f39c12"),
+        ("unchanged", "#bdc3c7"),
+    ]
+    .iter()
+    .map(|(status, color)| (status.to_string(), color.to_string()))
+    .collect()
+}
+
+/// The id of an edge in the viewer, its `_id` or else its position.
+pub fn edge_id(edge: &Value, index: usize) -> Value {
+    edge.get("_id")
+        .cloned()
+        .unwrap_or_else(|| json!(format!("edge/{}", index)))
+}
+
+/// Converts vertices and edges for the browser. Documents without `_id`
+/// (or `_from` and `_to` for edges) are skipped. Edges without `_id` are
+/// numbered.
+pub fn graph_data(vertices: &[Value], edges: &[Value]) -> GraphData {
+    let nodes = vertices
+        .iter()
+        .filter_map(|v| {
+            let obj = v.as_object()?;
+            let id = obj.get("_id")?;
+            Some(ViewerNode {
+                id: id.clone(),
+                label: label(obj),
+                collection: analytics::collection(id.as_str().unwrap_or_default()).to_string(),
+                data: obj.clone(),
+            })
+        })
+        .collect();
+    let edges = edges
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| {
+            let obj = e.as_object()?;
+            Some(ViewerEdge {
+                id: edge_id(e, i),
+                source: obj.get("_from")?.clone(),
+                target: obj.get("_to")?.clone(),
+                data: obj.clone(),
+            })
+        })
+        .collect();
+    GraphData { nodes, edges }
+}
+
+/// A kept graph as listed for choosing one.
+#[derive(Debug, Serialize, Clone)]
+pub struct GraphSummary {
+    pub id: u64,
+    pub query_name: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Recent graph results, by id.
+pub struct GraphStore {
+    keep: usize,
+    graphs: Mutex<VecDeque<StoredGraph>>,
+    next_id: AtomicU64,
+}
+
+impl GraphStore {
+    pub fn new(config: &ViewerConfig) -> Self {
+        GraphStore {
+            keep: config.keep_graphs,
+            graphs: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Keeps a graph result and returns its id, or `None` if keeping graphs
+    /// is disabled.
+    pub fn insert(
+        &self,
+        query_name: &str,
+        renderer: GraphRenderer,
+        vertices: &[Value],
+        edges: &[Value],
+    ) -> Option<u64> {
+        if self.keep == 0 {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let graph = StoredGraph {
+            id,
+            query_name: query_name.to_string(),
+            renderer,
+            timestamp: Utc::now(),
+            vertices: vertices.to_vec(),
+            edges: edges.to_vec(),
+        };
+
+        let mut graphs = self.graphs.lock().unwrap();
+        graphs.push_back(graph);
+        while graphs.len() > self.keep {
+            graphs.pop_front();
+        }
+        Some(id)
+    }
+
+    /// Adds vertices and edges to a kept graph, e.g. when it is expanded in
+    /// the viewer. Returns `false` if the graph is no longer kept.
+    pub fn extend(&self, id: u64, vertices: &[Value], edges: &[Value]) -> bool {
+        let mut graphs = self.graphs.lock().unwrap();
+        match graphs.iter_mut().find(|g| g.id == id) {
+            Some(graph) => {
+                graph.vertices.extend_from_slice(vertices);
+                graph.edges.extend_from_slice(edges);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// All kept graphs, newest first.
+    pub fn list(&self) -> Vec<GraphSummary> {
+        self.graphs
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .map(|g| GraphSummary {
+                id: g.id,
+                query_name: g.query_name.clone(),
+                timestamp: g.timestamp,
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.graphs.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Roughly how much memory the kept graphs take: the size of their
+    /// vertices and edges as JSON.
+    pub fn buffered_bytes(&self) -> usize {
+        self.graphs
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|g| g.vertices.iter().chain(&g.edges))
+            .map(|v| serde_json::to_vec(v).map_or(0, |json| json.len()))
+            .sum()
+    }
+
+    pub fn get(&self, id: u64) -> Option<StoredGraph> {
+        self.graphs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|g| g.id == id)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph_data() {
+        let vertices = vec![
+            json!({ "_id": "people/alice", "_key": "alice", "name": "Alice" }),
+            json!({ "_id": "people/bob", "_key": "bob" }),
+            json!({ "name": "no id" }),
+        ];
+        let edges = vec![
+            json!({ "_id": "knows/1", "_from": "people/alice", "_to": "people/bob", "since": 2015 }),
+            json!({ "_from": "people/bob", "_to": "people/alice" }),
+        ];
+        let graph = graph_data(&vertices, &edges);
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.nodes[0].id, "people/alice");
+        assert_eq!(graph.nodes[0].label, "Alice");
+        assert_eq!(graph.nodes[1].label, "bob");
+        assert_eq!(graph.edges[0].source, "people/alice");
+        assert_eq!(graph.edges[0].data["since"], 2015);
+        assert_eq!(graph.edges[1].id, "edge/1");
+    }
+
+    #[test]
+    fn test_renderer_names() {
+        for renderer in GraphRenderer::ALL {
+            assert_eq!(GraphRenderer::from_name(renderer.name()), Some(*renderer));
+            assert_eq!(json!(renderer), json!(renderer.name()));
+        }
+        assert_eq!(GraphRenderer::from_name("d3"), None);
+    }
+
+    #[test]
+    fn test_store_keeps_recent_graphs() {
+        let store = GraphStore::new(&ViewerConfig {
+            keep_graphs: 2,
+            ..ViewerConfig::default()
+        });
+        let vertex = [json!({ "_id": "a/1" })];
+        let renderer = GraphRenderer::Vis;
+        let first = store.insert("q", renderer, &vertex, &[]).unwrap();
+        let second = store.insert("q", renderer, &vertex, &[]).unwrap();
+        let third = store.insert("q", renderer, &vertex, &[]).unwrap();
+        assert!(store.get(first).is_none());
+        assert_eq!(store.get(second).unwrap().vertices.len(), 1);
+        assert_eq!(store.get(third).unwrap().renderer, renderer);
+        let ids: Vec<u64> = store.list().iter().map(|g| g.id).collect();
+        assert_eq!(ids, vec![third, second]);
+        assert!(store.extend(third, &vertex, &[]));
+        assert_eq!(store.get(third).unwrap().vertices.len(), 2);
+        assert!(!store.extend(first, &vertex, &[]));
+        assert_eq!(store.len(), 2);
+        // `{"_id":"a/1"}` once in the second and twice in the third graph
+        assert_eq!(store.buffered_bytes(), 3 * 13);
+
+        let disabled = GraphStore::new(&ViewerConfig {
+            keep_graphs: 0,
+            ..ViewerConfig::default()
+        });
+        assert!(disabled.insert("q", renderer, &vertex, &[]).is_none());
+    }
+
+    #[test]
+    fn test_collection_colors() {
+        let vertices = vec![
+            json!({ "_id": "people/alice", "collection": "staff" }),
+            json!({ "_id": "cities/cologne" }),
+            json!({ "_id": "people/bob" }),
+        ];
+        let colors = collection_colors(&vertices);
+        assert_eq!(colors.len(), 2);
+        assert_eq!(colors["cities"], PALETTE[0]);
+        assert_eq!(colors["people"], PALETTE[1]);
+    }
+}
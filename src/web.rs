@@ -0,0 +1,3732 @@
+//! The web interface: query menu, parameter forms, result pages and
+//! administration pages.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tracing::{info, warn};
+use warp::{Filter, Rejection, Reply};
+
+use crate::analytics::{self, DiffSummary, GraphMetrics};
+use crate::arango::{Client, Cursor, CursorOptions, RunningQuery, ServerInfo};
+use crate::archive::{Archive, RestoreReport};
+use crate::benchmark::{self, BenchmarkReport};
+use crate::catalog::{self, CatalogBundle, ImportReport, ImportStrategy};
+use crate::compare::{self, Comparison};
+use crate::config::{Configuration, LimitsConfig, QueryDefinition};
+use crate::cursors::CursorStore;
+use crate::cytoscape::{ImageFormat, Presentation};
+use crate::error::{Error, Result};
+use crate::error_reporting::{self, ErrorReporter, RecentError};
+use crate::explorer::{self, Direction, EdgeSource};
+use crate::export::{self, ExportFormat};
+use crate::git_store::{self, GitStore};
+use crate::graph_analyzer::GraphDetection;
+use crate::graph_filter::{self, AttributeSummary, Predicate};
+use crate::history::{self, PerformanceSummary, QueryHistory, RunRecord};
+use crate::indexes::{self, CollectionIndexes};
+use crate::materialize::{self, SaveMode};
+use crate::output;
+use crate::proposals::{Proposal, ProposalStatus, Proposals};
+use crate::queue::ExecutionQueue;
+use crate::renderer::{self, Bar, MapPoint, ResultRenderer, Table};
+use crate::runner::{self, QueryRunner, SpillingExecution};
+use crate::saved_graphs::{SavedGraphSummary, SavedGraphs};
+use crate::search::{self, SearchMode, View};
+use crate::seed::{self, SeedReport};
+use crate::sessions::{SessionStore, SESSION_COOKIE};
+use crate::shutdown::{self, BackgroundTasks};
+use crate::slow_log::{self, ServerSlowQuery, SlowQueryEntry, SlowQueryLog};
+use crate::spill::{Rows, SpilledResult};
+use crate::status::{self, Jobs, Reachability, StoreUsage};
+use crate::templates::{Templates, Theme};
+use crate::tenants::Tenants;
+use crate::uploads::{UploadState, Uploads};
+use crate::usage::{QueryUsage, UsageStats};
+use crate::validate::{self, Issue, Severity};
+use crate::versions::{self, DiffLine, QueryVersion, QueryVersions};
+use crate::viewer::{self, GraphRenderer, GraphStore, GraphSummary, StoredGraph};
+use crate::{assets, cytoscape, gephi, graph_analyzer};
+
+#[derive(Debug, Serialize)]
+struct MenuContext {
+    queries: Vec<QueryDefinition>,
+}
+
+#[derive(Debug, Serialize)]
+struct ParameterFormContext {
+    query: QueryDefinition,
+    index: usize,
+    layouts: &'static [&'static str],
+    layout: String,                                     // Preselected Cytoscape layout
+    tuning: serde_json::Map<String, serde_json::Value>, // Configured force-directed parameters
+    push: bool,                                         // Tick sending graph results to Cytoscape
+    gephi: bool,                                        // Offer sending graph results to Gephi
+    save_modes: Vec<&'static str>,
+    values: HashMap<String, String>, // Parameter values the user last ran the query with
+    benchmark_runs: usize,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct ResultContext {
+    renderer: String, // See `renderer`; "auto" shows what the results turn out to be
+    result_json: String,
+    table: Option<Table>,
+    bars: Option<Vec<Bar>>,
+    points: Option<Vec<MapPoint>>,
+    map_width: f64,
+    map_height: f64,
+    results: Option<Vec<serde_json::Value>>, // For custom templates
+    is_it_graph: bool,
+    cytoscape_upload: Option<u64>, // Sending the graph to Cytoscape, see `uploads`
+    cytoscape_push: Option<String>, // How to send the kept graph (again), as JSON
+    graph_id: Option<u64>,         // Graph in the browser viewer
+    gephi_hint: Option<String>,    // Where the graph is being sent in Gephi
+    graph_svg: Option<String>,     // Small graphs rendered by Graphviz
+    svg_hint: Option<String>,      // Why rendering it failed
+    graph_warning: Option<String>, // Items left out of the graph
+    graph_metrics: Option<GraphMetrics>,
+    save_message: Option<String>, // Outcome of saving the results to a collection
+    spilled: Option<SpilledContext>, // Results too big for memory, see `spill`
+}
+
+#[derive(Debug, Serialize)]
+struct SpilledContext {
+    id: u64,
+    rows: usize,
+    megabytes: String,
+    shown: usize, // The first rows, shown as JSON
+}
+
+#[derive(Debug, Serialize)]
+struct ScrollContext {
+    query: QueryDefinition,
+    index: usize,
+    rows: Vec<String>,     // The first batch, one pretty-printed result each
+    token: Option<String>, // Cursor for the further batches
+}
+
+#[derive(Debug, Serialize)]
+struct GraphContext {
+    graph: StoredGraph,
+    graph_json: String, // Renderer independent, see `viewer::GraphData`
+    color_attribute: Option<&'static str>, // Vertices and edges are colored by it, else by collection
+    colors: BTreeMap<String, String>,      // Attribute value to color, for the legend
+    colors_json: String,                   // The same for the script
+    page_url: String,                      // This page, to add `renderer=...` to
+    other_graphs: Vec<GraphSummary>,       // To compare this one with
+    diff: Option<DiffSummary>,             // When showing a comparison
+    arrows: bool,                          // Unless the query's graph is undirected
+    group_by: Option<String>,              // Attribute of the compound nodes, as in Cytoscape
+    can_save: bool,                        // If a directory for saved graphs is configured
+    renderer: &'static str,
+    renderers: Vec<&'static str>,
+    layouts: &'static [&'static str],
+    script_url: String,
+    export_formats: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+struct SavedGraphsContext {
+    enabled: bool,
+    graphs: Vec<SavedGraphSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorContext {
+    status: u16,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct PreviewContext {
+    graph: StoredGraph,
+    vertex_attributes: Vec<AttributeSummary>,
+    edge_attributes: Vec<AttributeSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExploreGraph {
+    name: String,
+    vertex_collections: Vec<String>,
+    edge_collections: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExploreContext {
+    graphs: Vec<ExploreGraph>,
+    error: Option<String>, // Why the graphs could not be listed
+    directions: Vec<&'static str>,
+    max_depth: u32,
+    push: bool,
+    gephi: bool,
+}
+
+/// Body of `POST /api/expand`. The neighbors are taken from the named
+/// `graph`, from `edge_collections`, or else from the collections of the
+/// edges of the kept graph `graph_id`.
+#[derive(Debug, Deserialize)]
+struct ExpandRequest {
+    vertex: String,
+    direction: Option<String>, // ANY by default
+    depth: Option<u32>,        // 1 by default
+    graph: Option<String>,
+    #[serde(default)]
+    edge_collections: Vec<String>,
+    graph_id: Option<u64>,          // Only new elements are returned and kept
+    cytoscape_network: Option<i64>, // SUID of a network to add them to
+}
+
+#[derive(Debug, Serialize)]
+struct SearchContext {
+    views: Vec<View>,
+    error: Option<String>, // Why the views could not be listed
+    modes: Vec<&'static str>,
+    analyzer: String,
+    push: bool,
+    gephi: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct IndexesContext {
+    collections: Vec<CollectionIndexes>,
+    error: Option<String>, // Why the indexes could not be listed
+}
+
+#[derive(Debug, Serialize)]
+struct RunningQueriesContext {
+    endpoint: String,
+    queries: Vec<RunningQuery>,
+    killed: Option<String>, // Id of the query just killed
+    error: Option<String>,  // Why the queries could not be listed
+}
+
+#[derive(Debug, Serialize)]
+struct ServerSlowQueries {
+    queries: Vec<ServerSlowQuery>,
+    error: Option<String>, // Why the history could not be fetched
+}
+
+#[derive(Debug, Serialize)]
+struct AdminContext {
+    admin: bool, // Else the bind variables of slow queries are left out
+    slow_log_threshold_ms: Option<u64>,
+    slow_queries: Vec<SlowQueryEntry>,
+    server_slow_queries: Option<ServerSlowQueries>, // Only fetched for the page itself
+    server: Option<ServerInfo>,                     // Likewise
+    server_error: Option<String>,                   // Why the server could not be asked
+    seed_collections: Vec<String>,
+    seed_reports: Option<Vec<SeedReport>>,
+    queries: Vec<String>,
+    pending_proposals: usize,
+    import_strategies: Vec<&'static str>,
+    import_report: Option<ImportReport>,
+    restore_report: Option<RestoreReport>, // Of an archive, besides its catalog
+}
+
+impl AdminContext {
+    fn new(
+        config: &Configuration,
+        slow_log: &SlowQueryLog,
+        proposals: &Proposals,
+        admin: bool,
+    ) -> Self {
+        let mut slow_queries = slow_log.slowest(20);
+        // They may hold the data of other users of the tenant
+        if !admin {
+            for entry in &mut slow_queries {
+                entry.bind_vars.clear();
+            }
+        }
+        AdminContext {
+            admin,
+            slow_log_threshold_ms: slow_log.threshold_ms(),
+            slow_queries,
+            server_slow_queries: None,
+            server: None,
+            server_error: None,
+            seed_collections: config.seed.iter().map(|c| c.name.clone()).collect(),
+            seed_reports: None,
+            queries: config.queries.iter().map(|q| q.name.clone()).collect(),
+            pending_proposals: proposals.pending(),
+            import_strategies: ImportStrategy::ALL.iter().map(|s| s.name()).collect(),
+            import_report: None,
+            restore_report: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct QueryEditContext {
+    index: Option<usize>, // None for a new query
+    name: String,
+    definition: String,     // As pretty-printed JSON
+    version: Option<usize>, // The latest one
+    admin: bool,            // Else the change is proposed for review
+    issues: Vec<Issue>,     // Of the definition as it is
+}
+
+#[derive(Debug, Serialize)]
+struct ProposalsContext {
+    proposals: Vec<Proposal>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProposalContext {
+    proposal: Proposal,
+    diff: Vec<DiffLine>, // From the base, or the current definition once reviewed
+    outdated: bool,      // The query changed since it was proposed
+    admin: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct VersionsContext {
+    index: usize,
+    name: String,
+    versions: Vec<QueryVersion>, // Newest first
+    from: Option<usize>,
+    to: Option<usize>,
+    diff: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize)]
+struct StatusContext {
+    config_source: String,
+    loaded_at: Option<DateTime<Utc>>,
+    queries: usize,
+    arangodb_endpoint: String,
+    arangodb: Reachability,
+    cytoscape: Reachability,
+    running_queries: usize,
+    waiting_queries: usize, // In the lines of queries with `max_concurrent`
+    running_uploads: usize, // Graphs being sent to Cytoscape
+    stores: Vec<StoreUsage>,
+    buffered: String, // Memory taken by the kept graph results
+    recent_errors: Vec<RecentError>,
+}
+
+#[derive(Debug, Serialize)]
+struct UsageContext {
+    queries: Vec<QueryUsage>,
+}
+
+#[derive(Debug, Serialize)]
+struct PerformanceContext {
+    query: QueryDefinition,
+    index: usize,
+    runs: Vec<RunRecord>,
+    summary: Option<PerformanceSummary>,
+    chart_width: f64,
+    chart_height: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct BenchmarkContext {
+    query: QueryDefinition,
+    index: usize,
+    report: BenchmarkReport,
+}
+
+#[derive(Debug, Serialize)]
+struct CompareContext {
+    query: QueryDefinition,
+    index: usize,
+    comparison: Comparison,
+    original_examples: Vec<String>, // Pretty JSON of the rows only one variant returned
+    alternative_examples: Vec<String>,
+}
+
+/// What a query result goes through before it is shown: graph detection,
+/// local analytics, the browser viewer, Cytoscape, Graphviz and Gephi.
+struct GraphPipeline {
+    config: Arc<Configuration>,
+    runner: Arc<QueryRunner>,
+    reporter: Arc<ErrorReporter>,
+    graphs: Arc<GraphStore>,
+    uploads: Arc<Uploads>,
+}
+
+impl GraphPipeline {
+    /// Starts sending the graph result of a query to Cytoscape in the
+    /// background and returns the id of the upload to follow. Failed
+    /// uploads are reported. Graphs of explorations and searches have no
+    /// query to style them.
+    fn start_upload(
+        &self,
+        route: &str,
+        query_name: &str,
+        query: Option<&QueryDefinition>,
+        push: &CytoscapePush,
+        vertices: serde_json::Value,
+        edges: serde_json::Value,
+    ) -> u64 {
+        let id = self.uploads.start(query_name);
+        let config = Arc::clone(&self.config);
+        let reporter = Arc::clone(&self.reporter);
+        let uploads = Arc::clone(&self.uploads);
+        let route = route.to_string();
+        let query_name = query_name.to_string();
+        let options = query.map(|q| q.cytoscape.clone()).unwrap_or_default();
+        let mut presentation = Presentation {
+            network_name: config.cytoscape.network_name(&query_name, &push.bind_vars),
+            append_to: push.append_to,
+            directed: query.and_then(|q| q.graph.directed),
+            ..config
+                .cytoscape
+                .presentation(&options, push.layout.as_deref())
+        };
+        presentation
+            .layout_parameters
+            .extend(push.layout_parameters.clone());
+        let progress = Arc::clone(&uploads);
+        let upload = tokio::spawn(async move {
+            if !cytoscape::is_available(&config.cytoscape).await {
+                info!("Cytoscape not reachable, skipping graph upload");
+                return Err(format!(
+                    "Cytoscape is not reachable at {}. Start Cytoscape with CyREST \
+                     enabled and send the graph again.",
+                    config.cytoscape.base_url()
+                ));
+            }
+            cytoscape::send_with_progress(
+                &config.cytoscape,
+                &presentation,
+                &vertices,
+                &edges,
+                &|stage| progress.stage(id, stage),
+            )
+            .await
+            .map_err(|e| {
+                let message = format!("Cytoscape upload failed: {}", e);
+                warn!("{}", message);
+                reporter.report(&message, &[("route", &route), ("query", &query_name)]);
+                message
+            })
+        });
+        // Even an upload that panics ends as failed, not running forever
+        self.uploads.tasks().spawn(async move {
+            let outcome = upload
+                .await
+                .unwrap_or_else(|e| Err(format!("Cytoscape upload failed: {}", e)));
+            uploads.finish(id, outcome);
+        });
+        id
+    }
+
+    /// Starts sending a graph to the Gephi workspace in the background, the
+    /// way graphs go to Cytoscape. Failed uploads are reported; a Gephi
+    /// that is not running is not an error.
+    fn start_gephi_upload(
+        &self,
+        route: &str,
+        query_name: &str,
+        vertices: Vec<serde_json::Value>,
+        edges: Vec<serde_json::Value>,
+    ) {
+        let config = Arc::clone(&self.config);
+        let reporter = Arc::clone(&self.reporter);
+        let route = route.to_string();
+        let query_name = query_name.to_string();
+        self.uploads.tasks().spawn(async move {
+            if !gephi::is_available(&config.gephi).await {
+                info!("Gephi not reachable, skipping graph upload");
+                return;
+            }
+            if let Err(e) = gephi::send_to_gephi(&config.gephi, &vertices, &edges).await {
+                let message = format!("Gephi upload failed: {}", e);
+                warn!("{}", message);
+                reporter.report(&message, &[("route", &route), ("query", &query_name)]);
+            }
+        });
+    }
+
+    /// The result page for the results of a query. Failures of the
+    /// visualizations are reported on the page, not as errors.
+    async fn results(
+        &self,
+        results: &[serde_json::Value],
+        query: &QueryDefinition,
+        push: &CytoscapePush,
+        send: bool,  // To Cytoscape right away, else on request from the page
+        gephi: bool, // Also to Gephi, if enabled
+        route: &str,
+    ) -> ResultContext {
+        let result_json = serde_json::to_string_pretty(&results).unwrap_or_default();
+        let renderer = match &query.renderer {
+            None | Some(ResultRenderer::Graph) => query.renderer.clone(),
+            Some(other) => return shaped_results(other, results, result_json),
+        };
+        let graph_check = graph_analyzer::analyze(results, &query.graph);
+        let mut cytoscape_upload = None;
+        let mut cytoscape_push = None;
+        let mut graph_id = None;
+        let mut gephi_hint = None;
+        let mut graph_svg = None;
+        let mut svg_hint = None;
+        let mut graph_warning = None;
+        let mut graph_metrics = None;
+        let is_it_graph = match graph_check {
+            Ok(mut graph) => {
+                if query.graph.fetch_stubs && !graph.stubs.is_empty() {
+                    if let Err(e) = graph_analyzer::fetch_stubs(
+                        self.runner.client(),
+                        self.runner.documents(),
+                        &mut graph,
+                    )
+                    .await
+                    {
+                        warn!("Fetching the documents of stub vertices failed: {}", e);
+                    }
+                }
+                let (mut v, e) = (graph.vertices, graph.edges);
+                if let (Some(vertices), Some(edges)) = (v.as_array_mut(), e.as_array()) {
+                    analytics::annotate(vertices, edges);
+                }
+                if !graph.skipped.is_empty() {
+                    graph_warning = Some(format!(
+                        "{} items are neither vertices nor edges and are not shown in the graph, e.g.: {}",
+                        graph.skipped.len(),
+                        graph.skipped[0]
+                    ));
+                }
+                graph_id = self.graphs.insert(
+                    &query.name,
+                    query.graph_renderer.unwrap_or(self.config.viewer.renderer),
+                    v.as_array().map(Vec::as_slice).unwrap_or_default(),
+                    e.as_array().map(Vec::as_slice).unwrap_or_default(),
+                );
+                if send {
+                    cytoscape_upload = Some(self.start_upload(
+                        route,
+                        &query.name,
+                        Some(query),
+                        push,
+                        v.clone(),
+                        e.clone(),
+                    ));
+                }
+                if graph_id.is_some() {
+                    // For the button that sends the kept graph later, or
+                    // again after a failure; kept from closing the script
+                    // element it is embedded in
+                    cytoscape_push = serde_json::to_string(push)
+                        .ok()
+                        .map(|json| json.replace("</", "<\\/"));
+                }
+
+                let (vertices, edges) = (
+                    v.as_array().map(Vec::as_slice).unwrap_or_default(),
+                    e.as_array().map(Vec::as_slice).unwrap_or_default(),
+                );
+                graph_metrics = Some(analytics::metrics(vertices, edges));
+                if let Some(command) = &self.config.export.graphviz_command {
+                    if vertices.len() + edges.len() <= self.config.export.svg_max_elements {
+                        let dot = export::to_dot(&query.name, vertices, edges);
+                        match export::render_svg(command, &dot).await {
+                            Ok(svg) => graph_svg = Some(svg),
+                            Err(e) => {
+                                let message = format!("Rendering the graph as SVG failed: {}", e);
+                                warn!("{}", message);
+                                svg_hint = Some(message);
+                            }
+                        }
+                    }
+                }
+
+                if gephi && self.config.gephi.enabled {
+                    self.start_gephi_upload(route, &query.name, vertices.to_vec(), edges.to_vec());
+                    gephi_hint = Some(format!(
+                        "The graph is being sent to the Gephi workspace {} at {}.",
+                        self.config.gephi.workspace, self.config.gephi.url
+                    ));
+                }
+
+                true
+            }
+            Err(e) => {
+                if renderer.is_some() {
+                    graph_warning = Some(format!(
+                        "The query is meant to show a graph, but its results are none: {}",
+                        e
+                    ));
+                }
+                false
+            }
+        };
+        ResultContext {
+            renderer: renderer
+                .as_ref()
+                .map_or("auto", ResultRenderer::name)
+                .to_string(),
+            result_json,
+            is_it_graph,
+            cytoscape_upload,
+            cytoscape_push,
+            graph_id,
+            gephi_hint,
+            graph_svg,
+            svg_hint,
+            graph_warning,
+            graph_metrics,
+            ..ResultContext::default()
+        }
+    }
+}
+
+/// How a graph result is sent to Cytoscape, kept by the result page for
+/// sending it later.
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct CytoscapePush {
+    layout: Option<String>,
+    #[serde(default)]
+    layout_parameters: serde_json::Map<String, serde_json::Value>, // Over the configured ones
+    append_to: Option<i64>, // SUID of a network to add the graph to
+    #[serde(default)]
+    bind_vars: HashMap<String, serde_json::Value>, // For the network name
+}
+
+/// The template of the result page of a query.
+fn results_template(query: &QueryDefinition) -> &str {
+    match &query.renderer {
+        Some(ResultRenderer::Template(name)) => name,
+        _ => "results.html",
+    }
+}
+
+/// The context of a result page in a shape other than a graph.
+fn shaped_results(
+    renderer: &ResultRenderer,
+    results: &[serde_json::Value],
+    result_json: String,
+) -> ResultContext {
+    let mut context = ResultContext {
+        renderer: renderer.name().to_string(),
+        result_json,
+        ..ResultContext::default()
+    };
+    match renderer {
+        ResultRenderer::Table => context.table = Some(renderer::table(results)),
+        ResultRenderer::Chart => context.bars = Some(renderer::chart(results)),
+        ResultRenderer::Map => {
+            context.points = Some(renderer::points(results));
+            context.map_width = renderer::MAP_WIDTH;
+            context.map_height = renderer::MAP_HEIGHT;
+        }
+        ResultRenderer::Template(_) => context.results = Some(results.to_vec()),
+        ResultRenderer::Json | ResultRenderer::Graph => {}
+    }
+    context
+}
+
+/// How many rows of a spilled result are shown on its result page.
+const SPILLED_ROWS_SHOWN: usize = 100;
+
+/// The context of a result page for results spilled to disk: the first
+/// rows as JSON and a link to download them all.
+async fn spilled_results(spilled: &SpilledResult) -> Result<ResultContext> {
+    let shown = spilled.file.first_rows(SPILLED_ROWS_SHOWN).await?;
+    Ok(ResultContext {
+        renderer: ResultRenderer::Json.name().to_string(),
+        result_json: serde_json::to_string_pretty(&shown).unwrap_or_default(),
+        spilled: Some(SpilledContext {
+            id: spilled.id,
+            rows: spilled.file.rows(),
+            megabytes: format!("{:.1}", spilled.file.bytes() as f64 / (1024.0 * 1024.0)),
+            shown: shown.len(),
+        }),
+        ..ResultContext::default()
+    })
+}
+
+/// The context of the graph viewer page for a graph, shown with the
+/// renderer chosen by `?renderer=` or else that of the query, and colored
+/// with `colors`. The page URL is left for the caller to fill in.
+fn graph_context(
+    config: &Configuration,
+    graphs: &GraphStore,
+    graph: StoredGraph,
+    colors: BTreeMap<String, String>,
+    options: &HashMap<String, String>,
+) -> Result<GraphContext> {
+    let renderer = match options.get("renderer") {
+        Some(name) => GraphRenderer::from_name(name)
+            .ok_or_else(|| Error::InvalidParameter(format!("Unknown renderer '{}'", name)))?,
+        None => graph.renderer,
+    };
+    let query = config.queries.iter().find(|q| q.name == graph.query_name);
+    let undirected = query.is_some_and(|q| q.graph.directed == Some(false));
+    let group_by = query
+        .and_then(|q| q.cytoscape.group_by.clone())
+        .or_else(|| config.cytoscape.group_by.clone());
+    Ok(GraphContext {
+        // Keep the JSON from closing the script element it is embedded in
+        graph_json: serde_json::to_string(&viewer::graph_data(&graph.vertices, &graph.edges))
+            .unwrap_or_default()
+            .replace("</", "<\\/"),
+        color_attribute: None,
+        // Attribute values come from the documents, so the same applies
+        colors_json: serde_json::to_string(&colors)
+            .unwrap_or_default()
+            .replace("</", "<\\/"),
+        colors,
+        page_url: String::new(),
+        other_graphs: graphs
+            .list()
+            .into_iter()
+            .filter(|g| g.id != graph.id)
+            .collect(),
+        diff: None,
+        arrows: !undirected,
+        group_by,
+        can_save: config.saved_graphs.directory.is_some(),
+        graph,
+        renderer: renderer.name(),
+        renderers: GraphRenderer::ALL.iter().map(|r| r.name()).collect(),
+        layouts: renderer.layouts(),
+        script_url: config.viewer.script_url(renderer).to_string(),
+        export_formats: ExportFormat::ALL.iter().map(|f| f.name()).collect(),
+    })
+}
+
+/// Runs the neighborhood traversal of an expand request. With a kept graph,
+/// only the vertices and edges not yet in it are returned, and they are
+/// added to it. With a Cytoscape network, they are added there too.
+async fn expand_vertex(
+    config: &Configuration,
+    runner: &QueryRunner,
+    graphs: &GraphStore,
+    request: &ExpandRequest,
+) -> Result<serde_json::Value> {
+    let direction = match &request.direction {
+        Some(name) => Direction::from_name(name).ok_or_else(|| {
+            Error::InvalidParameter(format!("Unknown traversal direction '{}'", name))
+        })?,
+        None => Direction::Any,
+    };
+    let kept = match request.graph_id {
+        Some(id) => Some(
+            graphs
+                .get(id)
+                .ok_or_else(|| Error::NotFound(format!("Graph {}", id)))?,
+        ),
+        None => None,
+    };
+    let source = if let Some(graph) = &request.graph {
+        EdgeSource::Graph(graph.clone())
+    } else if !request.edge_collections.is_empty() {
+        EdgeSource::Collections(request.edge_collections.clone())
+    } else if let Some(graph) = &kept {
+        let mut collections: Vec<String> = graph
+            .edges
+            .iter()
+            .filter_map(|e| e["_id"].as_str())
+            .map(|id| analytics::collection(id).to_string())
+            .collect();
+        collections.sort();
+        collections.dedup();
+        EdgeSource::Collections(collections)
+    } else {
+        return Err(Error::InvalidParameter(
+            "One of 'graph', 'edge_collections' or 'graph_id' is needed".to_string(),
+        ));
+    };
+    let (query, bind_vars) = explorer::neighborhood(
+        &config.explorer,
+        &request.vertex,
+        direction,
+        request.depth.unwrap_or(1),
+        &source,
+    )?;
+    let results = runner.client().execute(&query, bind_vars).await?.results;
+
+    let detection = GraphDetection {
+        vertex_only: true,
+        lenient: true,
+        ..GraphDetection::default()
+    };
+    // An empty result, i.e. a vertex without neighbors, is no graph
+    let (mut vertices, mut edges) = match graph_analyzer::analyze(&results, &detection) {
+        Ok(graph) => (
+            graph.vertices.as_array().cloned().unwrap_or_default(),
+            graph.edges.as_array().cloned().unwrap_or_default(),
+        ),
+        Err(_) => (Vec::new(), Vec::new()),
+    };
+    if let Some(graph) = &kept {
+        let known: std::collections::HashSet<&str> = graph
+            .vertices
+            .iter()
+            .chain(&graph.edges)
+            .filter_map(|d| d["_id"].as_str())
+            .collect();
+        let is_new =
+            |d: &serde_json::Value| d["_id"].as_str().is_some_and(|id| !known.contains(id));
+        vertices.retain(is_new);
+        edges.retain(is_new);
+        graphs.extend(graph.id, &vertices, &edges);
+    }
+
+    let mut cytoscape_error = None;
+    if let Some(network) = request.cytoscape_network {
+        if !vertices.is_empty() || !edges.is_empty() {
+            if let Err(e) = cytoscape::send_to_cytoscape(
+                &config.cytoscape,
+                &Presentation {
+                    append_to: Some(network),
+                    ..config.cytoscape.presentation(&Default::default(), None)
+                },
+                &serde_json::Value::from(vertices.clone()),
+                &serde_json::Value::from(edges.clone()),
+            )
+            .await
+            {
+                warn!(
+                    "Adding the neighbors of {} to Cytoscape failed: {}",
+                    request.vertex, e
+                );
+                cytoscape_error = Some(e.to_string());
+            }
+        }
+    }
+
+    let mut expansion = serde_json::to_value(viewer::graph_data(&vertices, &edges))
+        .map_err(|e| Error::Config(e.to_string()))?;
+    expansion["cytoscape_error"] = serde_json::json!(cytoscape_error);
+    Ok(expansion)
+}
+
+/// The cookie remembering the theme a user chose.
+const THEME_COOKIE: &str = "theme";
+
+/// The templates with the theme and locale of the user.
+struct Page {
+    templates: Arc<Templates>,
+    theme: Theme,
+    locale: String,
+}
+
+fn render<T: Serialize>(
+    page: &Page,
+    template: &str,
+    context: &T,
+) -> Result<warp::reply::Html<String>> {
+    Ok(warp::reply::html(page.templates.render(
+        template,
+        context,
+        page.theme,
+        &page.locale,
+    )?))
+}
+
+/// Adds the cookie of a session to a response, renewing it.
+fn with_session(
+    reply: impl Reply,
+    sessions: &SessionStore,
+    session: Option<&str>,
+) -> warp::reply::Response {
+    let mut response = reply.into_response();
+    if let Some(cookie) =
+        session.and_then(|id| warp::http::HeaderValue::from_str(&sessions.cookie(id)).ok())
+    {
+        response
+            .headers_mut()
+            .insert(warp::http::header::SET_COOKIE, cookie);
+    }
+    response
+}
+
+fn query_at(config: &Configuration, idx: usize) -> Result<&QueryDefinition> {
+    config
+        .queries
+        .get(idx)
+        .ok_or_else(|| Error::NotFound(format!("Query {}", idx)))
+}
+
+/// The configuration of the running server, replaced as a whole when its
+/// catalog changes, and the git store keeping the catalog, if configured.
+struct LiveCatalog {
+    config: RwLock<Arc<Configuration>>,
+    git: Option<Arc<GitStore>>,
+    changing: tokio::sync::Mutex<()>, // One change or refresh at a time
+}
+
+impl LiveCatalog {
+    fn current(&self) -> Arc<Configuration> {
+        Arc::clone(&self.config.read().unwrap())
+    }
+
+    /// Where changes are saved: the git store or else the configuration
+    /// file, if there is one.
+    fn saved_to(&self) -> Option<String> {
+        match &self.git {
+            Some(git) => Some(git.config().catalog_path().display().to_string()),
+            None => self.current().source.clone(),
+        }
+    }
+
+    /// Changes the catalog of the running server and where it is saved.
+    /// `change` gets the current queries and returns the new ones. Changes
+    /// are made one after the other, so none is lost. They are saved off the
+    /// async runtime, and requests keep reading the old catalog meanwhile;
+    /// commits to the git store are pushed in the background afterwards.
+    async fn change<T>(
+        &self,
+        author: &str,
+        message: &str,
+        change: impl FnOnce(&[QueryDefinition]) -> Result<(Vec<QueryDefinition>, T)>,
+    ) -> Result<(Arc<Configuration>, T)> {
+        let _changing = self.changing.lock().await;
+        let current = self.current();
+        let (queries, result) = change(&current.queries)?;
+        let saved = queries.clone();
+        let (author, message) = (author.to_string(), message.to_string());
+        let save = match (&self.git, &current.source) {
+            (Some(git), _) => {
+                let git = Arc::clone(git);
+                tokio::task::spawn_blocking(move || git.save(&saved, &author, &message))
+            }
+            (None, Some(path)) => {
+                let path = path.clone();
+                tokio::task::spawn_blocking(move || catalog::save(&path, &saved))
+            }
+            (None, None) => tokio::task::spawn_blocking(|| Ok(())),
+        };
+        save.await.map_err(std::io::Error::from)??;
+        let config = Arc::new(Configuration {
+            queries,
+            ..(*current).clone()
+        });
+        *self.config.write().unwrap() = Arc::clone(&config);
+        if let Some(git) = &self.git {
+            let git = Arc::clone(git);
+            tokio::task::spawn_blocking(move || git.push());
+        }
+        Ok((config, result))
+    }
+
+    /// Takes over the catalog of the git store, after pulling it, if it
+    /// differs from the current one. Returns the new configuration then.
+    fn refresh(&self) -> Result<Option<Arc<Configuration>>> {
+        let Some(git) = &self.git else {
+            return Ok(None);
+        };
+        git.pull()?;
+        // Also retries pushing commits whose push failed
+        git.push();
+        // Read while no change is being saved
+        let _changing = self.changing.blocking_lock();
+        let mut live = self.config.write().unwrap();
+        let Some(queries) = git_store::read_catalog(git.config())? else {
+            return Ok(None);
+        };
+        if queries.len() == live.queries.len()
+            && queries
+                .iter()
+                .zip(&live.queries)
+                .all(|(a, b)| versions::same_definition(a, b))
+        {
+            return Ok(None);
+        }
+        *live = Arc::new(Configuration {
+            queries,
+            ..(**live).clone()
+        });
+        Ok(Some(Arc::clone(&live)))
+    }
+}
+
+/// Takes over the changes of the git store every `interval`, recording them
+/// as new versions, until shutdown. A refresh under way is finished first.
+async fn refresh_git_store(
+    catalog: Arc<LiveCatalog>,
+    versions: Arc<QueryVersions>,
+    interval: Duration,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
+) {
+    let mut ticks = tokio::time::interval(interval);
+    ticks.tick().await; // The first tick is immediate
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = shutdown.changed() => return,
+        }
+        let refreshing = Arc::clone(&catalog);
+        match tokio::task::spawn_blocking(move || refreshing.refresh()).await {
+            Ok(Ok(Some(config))) => {
+                info!(
+                    queries = config.queries.len(),
+                    "Query catalog changed in git"
+                );
+                for query in &config.queries {
+                    versions.record(query, GIT_AUTHOR, "Changed in git");
+                }
+            }
+            Ok(Ok(None)) => {}
+            Ok(Err(e)) => warn!("Failed to refresh the query catalog from git: {}", e),
+            Err(e) => warn!("Refreshing the query catalog from git failed: {}", e),
+        }
+    }
+}
+
+/// The author of versions found in the git store.
+const GIT_AUTHOR: &str = "git";
+
+/// The author of changes made without a user header.
+const ANONYMOUS: &str = "anonymous";
+
+/// The catalog with the query at `idx` replaced by `definition`, and the
+/// definition. Queries keep their name, which their versions are kept by.
+fn replace_query(
+    current: &[QueryDefinition],
+    idx: usize,
+    definition: QueryDefinition,
+) -> Result<(Vec<QueryDefinition>, QueryDefinition)> {
+    let existing = current
+        .get(idx)
+        .ok_or_else(|| Error::NotFound(format!("Query {}", idx)))?;
+    if existing.name != definition.name {
+        return Err(Error::InvalidParameter(format!(
+            "The query '{}' cannot be renamed",
+            existing.name
+        )));
+    }
+    let mut queries = current.to_vec();
+    queries[idx] = definition.clone();
+    Ok((queries, definition))
+}
+
+/// The catalog with `definition` added, and the definition.
+fn add_query(
+    current: &[QueryDefinition],
+    definition: QueryDefinition,
+) -> Result<(Vec<QueryDefinition>, QueryDefinition)> {
+    if definition.name.trim().is_empty() {
+        return Err(Error::InvalidParameter("A query needs a name".to_string()));
+    }
+    if current.iter().any(|q| q.name == definition.name) {
+        return Err(Error::InvalidParameter(format!(
+            "A query named '{}' exists already",
+            definition.name
+        )));
+    }
+    let mut queries = current.to_vec();
+    queries.push(definition.clone());
+    Ok((queries, definition))
+}
+
+/// The query definition sent in the `definition` field of a form.
+fn definition_field(form: &HashMap<String, String>) -> Result<QueryDefinition> {
+    let text = form
+        .get("definition")
+        .map(String::as_str)
+        .unwrap_or_default();
+    serde_json::from_str(text)
+        .map_err(|e| Error::InvalidParameter(format!("Invalid query definition: {}", e)))
+}
+
+/// Whether a query is still as a proposal found it: unchanged, or still
+/// missing for a new one.
+fn is_current(current: Option<&QueryDefinition>, base: Option<&QueryDefinition>) -> bool {
+    match (current, base) {
+        (Some(current), Some(base)) => versions::same_definition(current, base),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Imports a catalog bundle into the running server and the configuration
+/// file, recording the added and replaced queries as new versions.
+async fn import_catalog(
+    catalog: &LiveCatalog,
+    versions: &QueryVersions,
+    author: &str,
+    bundle: CatalogBundle,
+    strategy: ImportStrategy,
+) -> Result<(Arc<Configuration>, ImportReport)> {
+    let message = format!("Import query catalog ({})", strategy.name());
+    let (config, mut report) = catalog
+        .change(author, &message, |current| {
+            Ok(catalog::import(current, bundle.queries, strategy))
+        })
+        .await?;
+    report.saved_to = catalog.saved_to();
+    for name in report.added.iter().chain(&report.replaced) {
+        if let Some(query) = config.find_query(name) {
+            versions.record(query, author, "Imported");
+        }
+    }
+    info!(
+        added = report.added.len(),
+        replaced = report.replaced.len(),
+        removed = report.removed.len(),
+        conflicts = report.conflicts.len(),
+        "Imported query catalog"
+    );
+    Ok((config, report))
+}
+
+/// Refuses users who may only propose changes to the catalog.
+fn require_admin(admin: bool) -> Result<()> {
+    require_admin_to(admin, "change the query catalog")
+}
+
+/// Refuses users who are no admins, saying what they may not do.
+fn require_admin_to(admin: bool, action: &str) -> Result<()> {
+    if admin {
+        Ok(())
+    } else {
+        Err(Error::Forbidden(format!("Only admins may {}", action)))
+    }
+}
+
+/// The author of a proposal, who must be known to review it.
+fn proposer(user: Option<String>) -> Result<String> {
+    user.filter(|u| !u.trim().is_empty())
+        .ok_or_else(|| Error::Forbidden("Not authenticated".to_string()))
+}
+
+/// The collection the results of a query are saved into, if the form names
+/// one, and the save mode, `append` by default. Only admins may save
+/// results: the collection may be one other queries read, and appending
+/// replaces its documents with the same `_key` as well.
+fn save_target(
+    collection: Option<String>,
+    mode: Option<&str>,
+    admin: bool,
+) -> Result<Option<(String, SaveMode)>> {
+    let Some(collection) = collection
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+    else {
+        return Ok(None);
+    };
+    require_admin_to(admin, "save query results into a collection")?;
+    let mode = match mode {
+        None | Some("") => SaveMode::Append,
+        Some(name) => SaveMode::from_name(name)
+            .ok_or_else(|| Error::InvalidParameter(format!("Unknown save mode '{}'", name)))?,
+    };
+    Ok(Some((collection, mode)))
+}
+
+/// The page of this server a request came from, by its `Referer`, to return
+/// to. Only a path is taken: `//host` or `/\host` would be another site to
+/// browsers, so anything but a local path leads to the start page.
+fn referring_page(referer: Option<String>) -> String {
+    referer
+        .and_then(|r| warp::http::Uri::try_from(r).ok())
+        .and_then(|uri| uri.path_and_query().map(|p| p.to_string()))
+        .filter(|path| path.starts_with('/') && !path.starts_with("//") && !path.starts_with("/\\"))
+        .unwrap_or_else(|| "/".to_string())
+}
+
+/// Who an execution counts for in the usage statistics: the `tenancy` user,
+/// else the session the request came with, if it is known. Requests without
+/// one, e.g. from scripts, are all one anonymous user, not a new one each.
+fn usage_user(user: Option<String>, cookie: Option<String>, sessions: &SessionStore) -> String {
+    user.or_else(|| cookie.filter(|c| sessions.session(Some(c)).as_ref() == Some(c)))
+        .unwrap_or_else(|| ANONYMOUS.to_string())
+}
+
+/// A download of all results in progress, recorded in the slow query log,
+/// the run history and the usage statistics once it is complete.
+struct Download {
+    query_name: String,
+    bind_vars: HashMap<String, serde_json::Value>,
+    user: String,
+    started: Instant,
+    rows: usize, // Sent so far
+    stats: serde_json::Value,
+    pending: Option<Vec<serde_json::Value>>, // The first batch, not sent yet
+    cursor: Option<Cursor>,                  // For the batches after it
+    runner: Arc<QueryRunner>,
+}
+
+impl Download {
+    /// Records the download as a run, complete or failed halfway.
+    fn finished(&self, complete: bool) {
+        let duration = self.started.elapsed();
+        let usage = self.runner.usage();
+        if !complete {
+            usage.record(&self.query_name, &self.user, None);
+            return;
+        }
+        self.runner
+            .slow_log()
+            .record(&self.query_name, &self.bind_vars, duration, &self.stats);
+        self.runner
+            .history()
+            .record(&self.query_name, duration, self.rows);
+        usage.record(&self.query_name, &self.user, Some(duration));
+    }
+}
+
+/// A redirect to another page after a form was handled.
+fn see_other(location: &str) -> Result<warp::http::Response<String>> {
+    warp::http::Response::builder()
+        .status(warp::http::StatusCode::SEE_OTHER)
+        .header("Location", location)
+        .body(String::new())
+        .map_err(|e| Error::Config(e.to_string()))
+}
+
+/// The error for a graph that could not be kept, as `keep_graphs` is 0;
+/// like one no longer kept, it cannot be shown.
+fn not_kept() -> Error {
+    Error::NotFound("Graphs are not kept (`keep_graphs` is 0)".to_string())
+}
+
+/// The viewer page of a kept graph.
+fn graph_uri(id: u64) -> warp::http::Uri {
+    warp::http::Uri::try_from(format!("/graph/{}", id)).expect("a graph path is a valid URI")
+}
+
+/// The number of runs of a benchmark entered on the parameter form, else
+/// the configured default.
+fn benchmark_runs(params: &HashMap<String, String>, config: &Configuration) -> Result<usize> {
+    match params.get("_runs").filter(|r| !r.is_empty()) {
+        Some(runs) => runs
+            .parse()
+            .map_err(|_| Error::InvalidParameter(format!("'{}' is no number of runs", runs))),
+        None => Ok(config.benchmark.default_runs),
+    }
+}
+
+/// Takes the ticket the parameter form chose to follow the request's place
+/// in the line of its query, or makes one up.
+fn ticket(params: &mut HashMap<String, String>) -> String {
+    params
+        .remove("_ticket")
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// An event with the position of a waiting request whenever it changes,
+/// then a `done` event once it runs. The stream also ends if the ticket
+/// does not show up in a line for a while, e.g. because it ran right away.
+fn queue_events(
+    queue: Arc<ExecutionQueue>,
+    ticket: String,
+) -> impl futures::Stream<Item = std::result::Result<warp::sse::Event, Infallible>> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+    const MAX_UNSEEN_POLLS: u32 = 20;
+    futures::stream::unfold(
+        (None::<usize>, 0u32, false),
+        move |(last, mut polls, done)| {
+            let queue = Arc::clone(&queue);
+            let ticket = ticket.clone();
+            async move {
+                if done {
+                    return None;
+                }
+                loop {
+                    if polls > 0 {
+                        tokio::time::sleep(POLL_INTERVAL).await;
+                    }
+                    polls += 1;
+                    match queue.position(&ticket) {
+                        Some(position) if last != Some(position) => {
+                            let event = warp::sse::Event::default()
+                                .event("position")
+                                .data(position.to_string());
+                            return Some((Ok(event), (Some(position), polls, false)));
+                        }
+                        None if last.is_some() || polls > MAX_UNSEEN_POLLS => {
+                            let event = warp::sse::Event::default().event("done").data("");
+                            return Some((Ok(event), (last, polls, true)));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Refuses request bodies larger than `limit` bytes before they are read,
+/// and bodies of unknown length.
+fn body_limit(limit: u64) -> warp::filters::BoxedFilter<()> {
+    warp::header::optional::<u64>("content-length")
+        .and_then(move |length: Option<u64>| async move {
+            match length {
+                Some(length) if length > limit => Err(warp::reject::custom(Error::TooLarge(
+                    format!("{} bytes sent, at most {} bytes allowed", length, limit),
+                ))),
+                _ => Ok(()),
+            }
+        })
+        .untuple_one()
+        .and(warp::body::content_length_limit(limit))
+        .boxed()
+}
+
+/// The fields of a `multipart/form-data` body by name.
+async fn form_fields(form: warp::multipart::FormData) -> Result<HashMap<String, Vec<u8>>> {
+    use futures::TryStreamExt;
+    use warp::Buf;
+    form.and_then(|part| async move {
+        let name = part.name().to_string();
+        let data = part
+            .stream()
+            .try_fold(Vec::new(), |mut data, chunk| async move {
+                data.extend_from_slice(chunk.chunk());
+                Ok(data)
+            })
+            .await?;
+        Ok((name, data))
+    })
+    .try_collect()
+    .await
+    .map_err(|e| Error::InvalidParameter(format!("Invalid upload: {}", e)))
+}
+
+/// Turns errors raised by handlers into an error page with a matching HTTP
+/// status. Other rejections are left to warp's default handling.
+async fn handle_rejection(
+    err: Rejection,
+    page: Page,
+) -> std::result::Result<warp::reply::Response, Rejection> {
+    let Some(e) = err.find::<Error>() else {
+        return Err(err);
+    };
+    let context = ErrorContext {
+        status: e.status().as_u16(),
+        message: e.to_string(),
+    };
+    let body = match render(&page, "error.html", &context) {
+        Ok(html) => html.into_response(),
+        Err(_) => warp::reply::html(context.message).into_response(),
+    };
+    Ok(warp::reply::with_status(body, e.status()).into_response())
+}
+
+/// Starts the web interface on http://localhost:3030, or on the configured
+/// Unix socket, and runs until SIGINT or SIGTERM is received. Templates and
+/// static files in `templates_dir` override the built-in ones, see
+/// [`Templates`].
+pub async fn serve(config: Configuration, templates_dir: Option<PathBuf>) -> Result<()> {
+    let versions = Arc::new(QueryVersions::new(config.query_versions.clone()));
+    let proposals = Arc::new(Proposals::new(config.proposals.clone()));
+    versions.record_configuration(&config.queries);
+    let sessions = Arc::new(SessionStore::new(&config.sessions));
+    let reporter = Arc::new(ErrorReporter::new(config.error_reporting.clone())?);
+    let jobs = Arc::new(Jobs::default());
+    let queue = Arc::new(ExecutionQueue::default());
+    error_reporting::install_panic_hook(Arc::clone(&reporter));
+    let config = Arc::new(config);
+    let runner = Arc::new(QueryRunner::new(Arc::clone(&config)));
+    let tenants = Arc::new(Tenants::new(Arc::clone(&config), runner));
+    let user_tenants = Arc::clone(&tenants);
+    let admin_tenants = Arc::clone(&tenants);
+    let status_tenants = Arc::clone(&tenants);
+    let shutdown_timeout_secs = config.shutdown_timeout_secs;
+    let unix_socket = config.unix_socket.clone();
+    let limits = config.limits.clone();
+    let shutdown_tenants = Arc::clone(&tenants);
+    let (shutdown_tx, mut shutdown_rx) = tokio::sync::watch::channel(false);
+    let background = BackgroundTasks::default();
+
+    // Setup template engine
+    let tera = Arc::new(Templates::new(templates_dir)?);
+    let error_tera = Arc::clone(&tera);
+    let static_tera = Arc::clone(&tera);
+    let theme = config.theme;
+    let locale = config.locale.clone();
+    let error_locale = error_tera.locale(locale.as_deref(), None);
+
+    // Routes
+    // The configuration, replaced as a whole when a catalog is imported
+    let live_catalog = Arc::new(LiveCatalog {
+        git: config
+            .git_store
+            .clone()
+            .map(|git| Arc::new(GitStore::new(git))),
+        config: RwLock::new(config),
+        changing: tokio::sync::Mutex::new(()),
+    });
+    if let Some(refresh_secs) = live_catalog
+        .git
+        .as_ref()
+        .map(|git| git.config().refresh_secs)
+        .filter(|secs| *secs > 0)
+    {
+        background.spawn(refresh_git_store(
+            Arc::clone(&live_catalog),
+            Arc::clone(&versions),
+            Duration::from_secs(refresh_secs),
+            shutdown_rx.clone(),
+        ));
+    }
+    let current_catalog = Arc::clone(&live_catalog);
+    let config_filter = warp::any().map(move || current_catalog.current());
+    let live_catalog_filter = warp::any().map(move || Arc::clone(&live_catalog));
+    let tera_filter = warp::any()
+        .and(warp::cookie::optional::<String>(THEME_COOKIE))
+        .and(warp::header::optional::<String>("accept-language"))
+        .map(
+            move |chosen: Option<String>, accept_language: Option<String>| Page {
+                templates: Arc::clone(&tera),
+                theme: chosen
+                    .as_deref()
+                    .and_then(Theme::from_name)
+                    .unwrap_or(theme),
+                locale: tera.locale(locale.as_deref(), accept_language.as_deref()),
+            },
+        );
+    // The user named by the tenancy header
+    let user_filter = warp::header::headers_cloned().map(move |headers: warp::http::HeaderMap| {
+        user_tenants
+            .headers()
+            .and_then(|(user, _)| headers.get(user))
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    });
+    let versions_filter = warp::any().map(move || Arc::clone(&versions));
+    let proposals_filter = warp::any().map(move || Arc::clone(&proposals));
+    // Whether the user may change the catalog, rather than propose changes
+    let admin_filter = warp::header::headers_cloned().map(move |headers: warp::http::HeaderMap| {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        match admin_tenants.headers() {
+            Some((user, groups)) => admin_tenants.is_admin(header(user), header(groups)),
+            None => admin_tenants.is_admin(None, None),
+        }
+    });
+    // The runner for the database of the user's tenant
+    let runner_filter =
+        warp::header::headers_cloned().and_then(move |headers: warp::http::HeaderMap| {
+            let tenants = Arc::clone(&tenants);
+            async move {
+                let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+                let (user, groups) = match tenants.headers() {
+                    Some((user, groups)) => (header(user), header(groups)),
+                    None => (None, None),
+                };
+                Ok::<_, Rejection>(tenants.runner(user, groups)?)
+            }
+        });
+    let reporter_filter = warp::any().map(move || Arc::clone(&reporter));
+    // The graphs and uploads of the user's tenant, ids counting per tenant
+    let graphs_filter = runner_filter
+        .clone()
+        .map(|runner: Arc<QueryRunner>| Arc::clone(runner.graphs()));
+    let saved_graphs_filter = runner_filter
+        .clone()
+        .map(|runner: Arc<QueryRunner>| Arc::clone(runner.saved_graphs()));
+    let uploads_filter = runner_filter
+        .clone()
+        .map(|runner: Arc<QueryRunner>| Arc::clone(runner.uploads()));
+    let slow_log_filter = runner_filter
+        .clone()
+        .map(|runner: Arc<QueryRunner>| Arc::clone(runner.slow_log()));
+    // The run history and usage statistics of the user's tenant
+    let history_filter = runner_filter
+        .clone()
+        .map(|runner: Arc<QueryRunner>| Arc::clone(runner.history()));
+    let usage_filter = runner_filter
+        .clone()
+        .map(|runner: Arc<QueryRunner>| Arc::clone(runner.usage()))
+        .and(user_filter.clone());
+    let tenants_filter = warp::any().map(move || Arc::clone(&status_tenants));
+    let cursors_filter = runner_filter
+        .clone()
+        .map(|runner: Arc<QueryRunner>| Arc::clone(runner.cursors()));
+    let jobs_filter = warp::any().map(move || Arc::clone(&jobs));
+    let queue_filter = warp::any().map(move || Arc::clone(&queue));
+    // The store with the session of the user, if they have one
+    let session_filter = warp::any()
+        .map(move || Arc::clone(&sessions))
+        .and(warp::cookie::optional::<String>(SESSION_COOKIE));
+
+    // Menu page
+    let menu = warp::path::end()
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and_then(|config: Arc<Configuration>, tera: Page| async move {
+            let context = MenuContext {
+                queries: config.queries.clone(),
+            };
+            Ok::<_, Rejection>(render(&tera, "menu.html", &context)?)
+        });
+
+    // Parameter form page
+    let parameter_form = warp::path!("query" / usize)
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(session_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |idx: usize,
+             config: Arc<Configuration>,
+             tera: Page,
+             sessions: Arc<SessionStore>,
+             session: Option<String>,
+             admin: bool| async move {
+                let query = query_at(&config, idx)?;
+                let mut values = match &session {
+                    Some(id) => sessions.recall(id, &query.name),
+                    None => HashMap::new(),
+                };
+                for param in &query.parameters {
+                    values.entry(param.name.clone()).or_default();
+                }
+                let context = ParameterFormContext {
+                    query: query.clone(),
+                    index: idx,
+                    layouts: cytoscape::LAYOUTS,
+                    layout: config.cytoscape.presentation(&query.cytoscape, None).layout,
+                    tuning: config
+                        .cytoscape
+                        .presentation(&query.cytoscape, Some("force-directed"))
+                        .layout_parameters,
+                    push: config.cytoscape.push_by_default,
+                    gephi: config.gephi.enabled,
+                    // Saving results is offered to admins only
+                    save_modes: match admin {
+                        true => SaveMode::ALL.iter().map(|m| m.name()).collect(),
+                        false => Vec::new(),
+                    },
+                    values,
+                    benchmark_runs: config.benchmark.default_runs,
+                };
+                Ok::<_, Rejection>(render(&tera, "parameter_form.html", &context)?)
+            },
+        );
+
+    // Execute query and show results
+    let execute = warp::path!("execute" / usize)
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(slow_log_filter.clone())
+        .and(history_filter.clone())
+        .and(runner_filter.clone())
+        .and(reporter_filter.clone())
+        .and(graphs_filter.clone())
+        .and(admin_filter.clone())
+        .and(session_filter.clone())
+        .and(usage_filter.clone())
+        .and(jobs_filter.clone())
+        .and(queue_filter.clone())
+        .and_then(
+            |idx: usize,
+             mut params: HashMap<String, String>,
+             config: Arc<Configuration>,
+             tera: Page,
+             slow_log: Arc<SlowQueryLog>,
+             history: Arc<QueryHistory>,
+             runner: Arc<QueryRunner>,
+             reporter: Arc<ErrorReporter>,
+             graphs: Arc<GraphStore>,
+             admin: bool,
+             sessions: Arc<SessionStore>,
+             session: Option<String>,
+             usage: Arc<UsageStats>,
+             user: Option<String>,
+             jobs: Arc<Jobs>,
+             queue: Arc<ExecutionQueue>| async move {
+                let query = query_at(&config, idx)?;
+                // Refused before anything is saved or reported as failure
+                runner.check_endpoint(query)?;
+
+                // The layout chosen in the form is not a bind variable
+                let layout = params.remove("_layout");
+                if let Some(layout) = &layout {
+                    if !cytoscape::LAYOUTS.contains(&layout.as_str()) {
+                        return Err(Error::InvalidParameter(format!(
+                            "Unknown layout '{}'",
+                            layout
+                        ))
+                        .into());
+                    }
+                }
+
+                // Tuning of the layout, e.g. `_layout.numIterations`
+                let layout_parameters: serde_json::Map<_, _> = params
+                    .iter()
+                    .filter_map(|(name, value)| {
+                        let name = name.strip_prefix("_layout.")?;
+                        (!value.trim().is_empty())
+                            .then(|| (name.to_string(), cytoscape::layout_parameter_value(value)))
+                    })
+                    .collect();
+                params.retain(|name, _| !name.starts_with("_layout."));
+
+                let append_to = match params.remove("_append_to").as_deref() {
+                    None | Some("") => None,
+                    Some(suid) => Some(suid.parse::<i64>().map_err(|_| {
+                        Error::InvalidParameter(format!("Invalid network SUID '{}'", suid))
+                    })?),
+                };
+
+                // Graphs go to Cytoscape and Gephi only if asked to
+                let send = params.remove("_cytoscape").is_some();
+                let to_gephi = params.remove("_gephi").is_some();
+
+                let save_to = save_target(
+                    params.remove("_save_to"),
+                    params.remove("_save_mode").as_deref(),
+                    admin,
+                )?;
+                let ticket = ticket(&mut params);
+
+                // Convert parameters to proper types based on configuration
+                let entered = params.clone();
+                let bind_vars = runner::convert_bind_vars(query, params)?;
+                let user = usage_user(user, session.clone(), &sessions);
+                let session = sessions.session(session.as_deref());
+                if let Some(id) = &session {
+                    sessions.remember(id, &query.name, &entered);
+                }
+
+                let route = format!("/execute/{}", idx);
+                // Expensive queries wait in line for their turn
+                let permit = match query.max_concurrent {
+                    Some(max) => Some(queue.enter(&query.name, max, &ticket).await),
+                    None => None,
+                };
+                let job = jobs.start();
+                let SpillingExecution {
+                    rows,
+                    stats,
+                    duration,
+                } = match runner.execute_spilling(query, bind_vars.clone()).await {
+                    Ok(r) => r,
+                    Err(e) => {
+                        usage.record(&query.name, &user, None);
+                        let message = format!("Query execution failed: {}", e);
+                        reporter.report(&message, &[("route", &route), ("query", &query.name)]);
+                        return Err(e.into());
+                    }
+                };
+                let row_count = match &rows {
+                    Rows::InMemory(results) => results.len(),
+                    Rows::Spilled(file) => file.rows(),
+                };
+                slow_log.record(&query.name, &bind_vars, duration, &stats);
+                history.record(&query.name, duration, row_count);
+                usage.record(&query.name, &user, Some(duration));
+                drop(job);
+                drop(permit);
+
+                // The query succeeded, so failing to save is only reported
+                let save_message = match &save_to {
+                    Some((collection, save_mode)) => Some(
+                        match match &rows {
+                            Rows::InMemory(results) => {
+                                materialize::save(runner.client(), collection, *save_mode, results)
+                                    .await
+                            }
+                            Rows::Spilled(file) => {
+                                materialize::save_spilled(
+                                    runner.client(),
+                                    collection,
+                                    *save_mode,
+                                    file,
+                                )
+                                .await
+                            }
+                        } {
+                            Ok(report) => format!(
+                                "Saved {} documents to {}collection {} ({} errors).",
+                                report.imported,
+                                if report.created_collection {
+                                    "new "
+                                } else {
+                                    ""
+                                },
+                                report.collection,
+                                report.errors
+                            ),
+                            Err(e) => {
+                                let message = format!("Saving the results failed: {}", e);
+                                warn!("{}", message);
+                                message
+                            }
+                        },
+                    ),
+                    None => None,
+                };
+
+                let results = match rows {
+                    Rows::InMemory(results) => results,
+                    Rows::Spilled(file) => {
+                        // Too big to show, or to look for a graph in
+                        let spilled = runner.spilled().insert(&query.name, file);
+                        let context = ResultContext {
+                            save_message,
+                            ..spilled_results(&spilled).await?
+                        };
+                        let page = render(&tera, "results.html", &context)?;
+                        return Ok(with_session(page, &sessions, session.as_deref()));
+                    }
+                };
+                let pipeline = GraphPipeline {
+                    config: Arc::clone(&config),
+                    uploads: Arc::clone(runner.uploads()),
+                    runner,
+                    reporter,
+                    graphs,
+                };
+                let mut context = pipeline
+                    .results(
+                        &results,
+                        query,
+                        &CytoscapePush {
+                            layout,
+                            layout_parameters,
+                            append_to,
+                            bind_vars,
+                        },
+                        send,
+                        to_gephi,
+                        &route,
+                    )
+                    .await;
+                context.save_message = save_message;
+
+                let page = render(&tera, results_template(query), &context)?;
+                Ok::<_, Rejection>(with_session(page, &sessions, session.as_deref()))
+            },
+        );
+
+    // Results shown batch by batch: the page starts with the first batch
+    // and fetches the others from `/api/cursor/{token}/next` on scrolling
+    let scroll = warp::path!("scroll" / usize)
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(runner_filter.clone())
+        .and(cursors_filter.clone())
+        .and(session_filter.clone())
+        .and(user_filter.clone())
+        .and(queue_filter.clone())
+        .and_then(
+            |idx: usize,
+             mut params: HashMap<String, String>,
+             config: Arc<Configuration>,
+             tera: Page,
+             runner: Arc<QueryRunner>,
+             cursors: Arc<CursorStore>,
+             sessions: Arc<SessionStore>,
+             session: Option<String>,
+             user: Option<String>,
+             queue: Arc<ExecutionQueue>| async move {
+                let query = query_at(&config, idx)?;
+                runner.check_endpoint(query)?;
+                let ticket = ticket(&mut params);
+                // Options of the parameter form are no bind variables
+                params.retain(|name, _| !name.starts_with('_'));
+                let entered = params.clone();
+                let bind_vars = runner::convert_bind_vars(query, params)?;
+                let user = usage_user(user, session.clone(), &sessions);
+                let session = sessions.session(session.as_deref());
+                if let Some(id) = &session {
+                    sessions.remember(id, &query.name, &entered);
+                }
+                // Only the first batch waits in line
+                let _permit = match query.max_concurrent {
+                    Some(max) => Some(queue.enter(&query.name, max, &ticket).await),
+                    None => None,
+                };
+                let started = Instant::now();
+                let opened = runner
+                    .client()
+                    .open_cursor(
+                        &query.query,
+                        bind_vars.clone(),
+                        &CursorOptions::of(query),
+                        config.cursors.batch_size,
+                        config.cursors.idle_timeout_secs,
+                    )
+                    .await;
+                // Recorded with the time and rows up to the first batch, as
+                // the rest may never be fetched
+                let duration = started.elapsed();
+                let (batch, cursor) = match opened {
+                    Ok(opened) => opened,
+                    Err(e) => {
+                        runner.usage().record(&query.name, &user, None);
+                        return Err(e.into());
+                    }
+                };
+                runner
+                    .slow_log()
+                    .record(&query.name, &bind_vars, duration, &batch.stats);
+                runner
+                    .history()
+                    .record(&query.name, duration, batch.results.len());
+                runner.usage().record(&query.name, &user, Some(duration));
+                let context = ScrollContext {
+                    query: query.clone(),
+                    index: idx,
+                    rows: batch
+                        .results
+                        .iter()
+                        .map(|r| serde_json::to_string_pretty(r).unwrap_or_default())
+                        .collect(),
+                    token: cursor.map(|c| cursors.insert(c, &query.name)),
+                };
+                let page = render(&tera, "scroll.html", &context)?;
+                Ok::<_, Rejection>(with_session(page, &sessions, session.as_deref()))
+            },
+        );
+
+    // All results as JSON lines, each batch sent on as it arrives instead
+    // of collecting the whole result first
+    let download = warp::path!("download" / usize)
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(config_filter.clone())
+        .and(runner_filter.clone())
+        .and(session_filter.clone())
+        .and(user_filter.clone())
+        .and(queue_filter.clone())
+        .and_then(
+            |idx: usize,
+             mut params: HashMap<String, String>,
+             config: Arc<Configuration>,
+             runner: Arc<QueryRunner>,
+             sessions: Arc<SessionStore>,
+             session: Option<String>,
+             user: Option<String>,
+             queue: Arc<ExecutionQueue>| async move {
+                let query = query_at(&config, idx)?;
+                runner.check_endpoint(query)?;
+                let ticket = ticket(&mut params);
+                params.retain(|name, _| !name.starts_with('_'));
+                let bind_vars = runner::convert_bind_vars(query, params)?;
+                let user = usage_user(user, session, &sessions);
+                // Only the first batch waits in line, as when scrolling
+                let permit = match query.max_concurrent {
+                    Some(max) => Some(queue.enter(&query.name, max, &ticket).await),
+                    None => None,
+                };
+                let started = Instant::now();
+                let opened = runner
+                    .client()
+                    .open_cursor(
+                        &query.query,
+                        bind_vars.clone(),
+                        &CursorOptions::of(query),
+                        config.cursors.batch_size,
+                        config.cursors.idle_timeout_secs,
+                    )
+                    .await;
+                drop(permit);
+                let (first, cursor) = match opened {
+                    Ok(opened) => opened,
+                    Err(e) => {
+                        runner.usage().record(&query.name, &user, None);
+                        return Err(e.into());
+                    }
+                };
+
+                // A failure halfway aborts the response, so that the
+                // download is not taken for complete
+                let download = Download {
+                    query_name: query.name.clone(),
+                    bind_vars,
+                    user,
+                    started,
+                    rows: 0,
+                    stats: first.stats,
+                    pending: Some(first.results),
+                    cursor,
+                    runner,
+                };
+                let batches = futures::stream::unfold(Some(download), |download| async move {
+                    let mut download = download?;
+                    if let Some(results) = download.pending.take() {
+                        download.rows += results.len();
+                        let lines = output::to_json_lines(&results).into_bytes();
+                        return Some((Ok(lines), Some(download)));
+                    }
+                    let Some(mut cursor) = download.cursor.take() else {
+                        download.finished(true);
+                        return None;
+                    };
+                    match download.runner.client().next_batch(&mut cursor).await {
+                        Ok(batch) => {
+                            download.rows += batch.results.len();
+                            if !batch.stats.is_null() {
+                                download.stats = batch.stats;
+                            }
+                            download.cursor = batch.has_more.then_some(cursor);
+                            let lines = output::to_json_lines(&batch.results).into_bytes();
+                            Some((Ok(lines), Some(download)))
+                        }
+                        Err(e) => {
+                            warn!("Download aborted: {}", e);
+                            download.finished(false);
+                            Some((Err(std::io::Error::other(e)), None))
+                        }
+                    }
+                });
+                Ok::<_, Rejection>(
+                    warp::http::Response::builder()
+                        .header("Content-Type", "application/x-ndjson")
+                        .header(
+                            "Content-Disposition",
+                            format!("attachment; filename=\"query-{}.ndjson\"", idx),
+                        )
+                        .body(warp::hyper::Body::wrap_stream(batches))
+                        .map_err(|e| Error::Config(e.to_string()))?,
+                )
+            },
+        );
+
+    // The next batch of a cursor opened by the scroll page. The token is
+    // returned again as long as there are more batches.
+    let cursor_next = warp::path!("api" / "cursor" / String / "next")
+        .and(runner_filter.clone())
+        .and(cursors_filter.clone())
+        .and_then(
+            |token: String, runner: Arc<QueryRunner>, cursors: Arc<CursorStore>| async move {
+                let mut open = cursors.take(&token).ok_or_else(|| {
+                    Error::NotFound(format!("Cursor {} (idle cursors are released)", token))
+                })?;
+                let batch = runner.client().next_batch(&mut open.cursor).await?;
+                let token = if batch.has_more {
+                    cursors.put_back(&token, open);
+                    Some(token)
+                } else {
+                    None
+                };
+                Ok::<_, Rejection>(warp::reply::json(&serde_json::json!({
+                    "results": batch.results,
+                    "has_more": batch.has_more,
+                    "token": token,
+                })))
+            },
+        );
+
+    // The position of a waiting request in the line of its query, as
+    // server-sent events until it runs
+    let queue_position = warp::path!("api" / "queue" / String)
+        .and(warp::get())
+        .and(queue_filter.clone())
+        .map(|ticket: String, queue: Arc<ExecutionQueue>| {
+            warp::sse::reply(warp::sse::keep_alive().stream(queue_events(queue, ticket)))
+        });
+
+    // Traversal of a named graph, chosen in a form instead of written in AQL
+    let explore = warp::path!("explore")
+        .and(warp::get())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(runner_filter.clone())
+        .and_then(
+            |config: Arc<Configuration>, tera: Page, runner: Arc<QueryRunner>| async move {
+                let (graphs, error) = match explorer::named_graphs(runner.client()).await {
+                    Ok(graphs) => (graphs, None),
+                    Err(e) => (Vec::new(), Some(e.to_string())),
+                };
+                let context = ExploreContext {
+                    graphs: graphs
+                        .iter()
+                        .map(|g| ExploreGraph {
+                            name: g.name.clone(),
+                            vertex_collections: g.vertex_collections(),
+                            edge_collections: g
+                                .edge_definitions
+                                .iter()
+                                .map(|d| d.collection.clone())
+                                .collect(),
+                        })
+                        .collect(),
+                    error,
+                    directions: Direction::ALL.iter().map(|d| d.name()).collect(),
+                    max_depth: config.explorer.max_depth,
+                    push: config.cytoscape.push_by_default,
+                    gephi: config.gephi.enabled,
+                };
+                Ok::<_, Rejection>(render(&tera, "explore.html", &context)?)
+            },
+        );
+
+    let explore_run = warp::path!("explore")
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(runner_filter.clone())
+        .and(reporter_filter.clone())
+        .and(graphs_filter.clone())
+        .and(uploads_filter.clone())
+        .and_then(
+            |form: HashMap<String, String>,
+             config: Arc<Configuration>,
+             tera: Page,
+             runner: Arc<QueryRunner>,
+             reporter: Arc<ErrorReporter>,
+             graphs: Arc<GraphStore>,
+             uploads: Arc<Uploads>| async move {
+                let field = |name: &str| {
+                    form.get(name)
+                        .map(|v| v.trim())
+                        .filter(|v| !v.is_empty())
+                        .ok_or_else(|| Error::InvalidParameter(format!("Missing '{}'", name)))
+                };
+                let direction = Direction::from_name(field("direction")?).ok_or_else(|| {
+                    Error::InvalidParameter("Unknown traversal direction".to_string())
+                })?;
+                let depth = field("depth")?
+                    .parse::<u32>()
+                    .map_err(|_| Error::InvalidParameter("Invalid depth".to_string()))?;
+                let (query, bind_vars) = explorer::traversal(
+                    &config.explorer,
+                    field("graph")?,
+                    field("start")?,
+                    direction,
+                    depth,
+                )?;
+
+                let execution = runner.execute(&query, bind_vars.clone()).await?;
+                let send = form.contains_key("_cytoscape");
+                let to_gephi = form.contains_key("_gephi");
+                let pipeline = GraphPipeline {
+                    config,
+                    runner,
+                    reporter,
+                    graphs,
+                    uploads,
+                };
+                let context = pipeline
+                    .results(
+                        &execution.results,
+                        &query,
+                        &CytoscapePush {
+                            bind_vars,
+                            ..CytoscapePush::default()
+                        },
+                        send,
+                        to_gephi,
+                        "/explore",
+                    )
+                    .await;
+                Ok::<_, Rejection>(render(&tera, "results.html", &context)?)
+            },
+        );
+
+    // Neighbors of a vertex, for expanding a graph step by step in the
+    // viewer or in Cytoscape
+    let expand = warp::path!("api" / "expand")
+        .and(warp::post())
+        .and(body_limit(limits.max_body_bytes))
+        .and(warp::body::json())
+        .and(config_filter.clone())
+        .and(runner_filter.clone())
+        .and(graphs_filter.clone())
+        .and_then(
+            |request: ExpandRequest,
+             config: Arc<Configuration>,
+             runner: Arc<QueryRunner>,
+             graphs: Arc<GraphStore>| async move {
+                let expansion = expand_vertex(&config, &runner, &graphs, &request).await?;
+                Ok::<_, Rejection>(warp::reply::json(&expansion))
+            },
+        );
+
+    // Searches in ArangoSearch views, chosen in a form instead of written
+    // in AQL
+    let search_form = warp::path!("search")
+        .and(warp::get())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(runner_filter.clone())
+        .and_then(
+            |config: Arc<Configuration>, tera: Page, runner: Arc<QueryRunner>| async move {
+                let (views, error) = match search::views(runner.client()).await {
+                    Ok(views) => (views, None),
+                    Err(e) => (Vec::new(), Some(e.to_string())),
+                };
+                let context = SearchContext {
+                    views,
+                    error,
+                    modes: SearchMode::ALL.iter().map(|m| m.name()).collect(),
+                    analyzer: config.search.analyzer.clone(),
+                    push: config.cytoscape.push_by_default,
+                    gephi: config.gephi.enabled,
+                };
+                Ok::<_, Rejection>(render(&tera, "search.html", &context)?)
+            },
+        );
+
+    let search_run = warp::path!("search")
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(runner_filter.clone())
+        .and(reporter_filter.clone())
+        .and(graphs_filter.clone())
+        .and(uploads_filter.clone())
+        .and_then(
+            |form: HashMap<String, String>,
+             config: Arc<Configuration>,
+             tera: Page,
+             runner: Arc<QueryRunner>,
+             reporter: Arc<ErrorReporter>,
+             graphs: Arc<GraphStore>,
+             uploads: Arc<Uploads>| async move {
+                let field = |name: &str| {
+                    form.get(name)
+                        .map(|v| v.trim())
+                        .filter(|v| !v.is_empty())
+                        .ok_or_else(|| Error::InvalidParameter(format!("Missing '{}'", name)))
+                };
+                let mode = SearchMode::from_name(field("mode")?)
+                    .ok_or_else(|| Error::InvalidParameter("Unknown search mode".to_string()))?;
+                let (query, bind_vars) = search::search(
+                    &config.search,
+                    field("view")?,
+                    field("attribute")?,
+                    field("text")?,
+                    mode,
+                    field("analyzer")?,
+                )?;
+
+                let execution = runner.execute(&query, bind_vars.clone()).await?;
+                let send = form.contains_key("_cytoscape");
+                let to_gephi = form.contains_key("_gephi");
+                let pipeline = GraphPipeline {
+                    config,
+                    runner,
+                    reporter,
+                    graphs,
+                    uploads,
+                };
+                let context = pipeline
+                    .results(
+                        &execution.results,
+                        &query,
+                        &CytoscapePush {
+                            bind_vars,
+                            ..CytoscapePush::default()
+                        },
+                        send,
+                        to_gephi,
+                        "/search",
+                    )
+                    .await;
+                Ok::<_, Rejection>(render(&tera, "results.html", &context)?)
+            },
+        );
+
+    // Indexes of all collections next to the queries using them
+    let index_overview = warp::path!("indexes")
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(runner_filter.clone())
+        .and_then(
+            |config: Arc<Configuration>, tera: Page, runner: Arc<QueryRunner>| async move {
+                let context = match indexes::overview(runner.client(), &config.queries).await {
+                    Ok(collections) => IndexesContext {
+                        collections,
+                        error: None,
+                    },
+                    Err(e) => IndexesContext {
+                        collections: Vec::new(),
+                        error: Some(e.to_string()),
+                    },
+                };
+                Ok::<_, Rejection>(render(&tera, "indexes.html", &context)?)
+            },
+        );
+
+    // How often the queries of the catalog are used
+    let usage_overview = warp::path!("usage")
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(usage_filter.clone())
+        .and_then(
+            |config: Arc<Configuration>,
+             tera: Page,
+             usage: Arc<UsageStats>,
+             _user: Option<String>| async move {
+                let context = UsageContext {
+                    queries: usage.summary(&config.queries),
+                };
+                Ok::<_, Rejection>(render(&tera, "usage.html", &context)?)
+            },
+        );
+
+    // Performance history of a query
+    let performance = warp::path!("performance" / usize)
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(history_filter.clone())
+        .and_then(
+            |idx: usize,
+             config: Arc<Configuration>,
+             tera: Page,
+             history: Arc<QueryHistory>| async move {
+                let query = query_at(&config, idx)?;
+                let runs = history.runs(&query.name);
+                let context = PerformanceContext {
+                    query: query.clone(),
+                    index: idx,
+                    summary: history::summarize(&runs),
+                    runs,
+                    chart_width: history::CHART_WIDTH,
+                    chart_height: history::CHART_HEIGHT,
+                };
+                Ok::<_, Rejection>(render(&tera, "performance.html", &context)?)
+            },
+        );
+
+    // The query run over and over with the parameters of the form, its
+    // results dropped, to measure its latency
+    let benchmark = warp::path!("benchmark" / usize)
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(runner_filter.clone())
+        .and(queue_filter.clone())
+        .and_then(
+            |idx: usize,
+             mut params: HashMap<String, String>,
+             config: Arc<Configuration>,
+             tera: Page,
+             runner: Arc<QueryRunner>,
+             queue: Arc<ExecutionQueue>| async move {
+                let query = query_at(&config, idx)?;
+                let ticket = ticket(&mut params);
+                let runs = benchmark_runs(&params, &config)?;
+                params.retain(|name, _| !name.starts_with('_'));
+                let bind_vars = runner::convert_bind_vars(query, params)?;
+                // The runs wait in line once, as one long execution
+                let _permit = match query.max_concurrent {
+                    Some(max) => Some(queue.enter(&query.name, max, &ticket).await),
+                    None => None,
+                };
+                let report =
+                    benchmark::run(&runner, &config.benchmark, query, bind_vars, runs).await?;
+                let context = BenchmarkContext {
+                    query: query.clone(),
+                    index: idx,
+                    report,
+                };
+                Ok::<_, Rejection>(render(&tera, "benchmark.html", &context)?)
+            },
+        );
+
+    // The query and its alternative run with the parameters of the form,
+    // their results and latencies compared
+    let compare = warp::path!("compare" / usize)
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(runner_filter.clone())
+        .and(queue_filter.clone())
+        .and_then(
+            |idx: usize,
+             mut params: HashMap<String, String>,
+             config: Arc<Configuration>,
+             tera: Page,
+             runner: Arc<QueryRunner>,
+             queue: Arc<ExecutionQueue>| async move {
+                let query = query_at(&config, idx)?;
+                let ticket = ticket(&mut params);
+                let runs = benchmark_runs(&params, &config)?;
+                params.retain(|name, _| !name.starts_with('_'));
+                let bind_vars = runner::convert_bind_vars(query, params)?;
+                let _permit = match query.max_concurrent {
+                    Some(max) => Some(queue.enter(&query.name, max, &ticket).await),
+                    None => None,
+                };
+                let comparison =
+                    compare::compare(&runner, &config.benchmark, query, bind_vars, runs).await?;
+                let pretty = |rows: &[serde_json::Value]| {
+                    rows.iter()
+                        .map(|r| serde_json::to_string_pretty(r).unwrap_or_default())
+                        .collect()
+                };
+                let context = CompareContext {
+                    query: query.clone(),
+                    index: idx,
+                    original_examples: pretty(&comparison.diff.original_examples),
+                    alternative_examples: pretty(&comparison.diff.alternative_examples),
+                    comparison,
+                };
+                Ok::<_, Rejection>(render(&tera, "compare.html", &context)?)
+            },
+        );
+
+    // Graph result in the browser viewer, with the renderer of the query
+    // unless another one is chosen with `?renderer=`
+    let graph = warp::path!("graph" / u64)
+        .and(warp::query::<HashMap<String, String>>())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(graphs_filter.clone())
+        .and_then(
+            |id: u64,
+             options: HashMap<String, String>,
+             config: Arc<Configuration>,
+             tera: Page,
+             graphs: Arc<GraphStore>| async move {
+                let graph = graphs.get(id).ok_or_else(|| {
+                    Error::NotFound(format!("Graph {} (only recent graphs are kept)", id))
+                })?;
+                let colors = viewer::collection_colors(&graph.vertices);
+                let context = GraphContext {
+                    page_url: format!("/graph/{}?", id),
+                    ..graph_context(&config, &graphs, graph, colors, &options)?
+                };
+                Ok::<_, Rejection>(render(&tera, "graph.html", &context)?)
+            },
+        );
+
+    // Two graph results combined, with differences color-coded: the graph
+    // `{id}` compared to the older graph `?with=...`
+    let graph_diff = warp::path!("graph" / u64 / "diff")
+        .and(warp::query::<HashMap<String, String>>())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(graphs_filter.clone())
+        .and_then(
+            |id: u64,
+             options: HashMap<String, String>,
+             config: Arc<Configuration>,
+             tera: Page,
+             graphs: Arc<GraphStore>| async move {
+                let stored = |id: u64| {
+                    graphs.get(id).ok_or_else(|| {
+                        Error::NotFound(format!("Graph {} (only recent graphs are kept)", id))
+                    })
+                };
+                let new = stored(id)?;
+                let with = options
+                    .get("with")
+                    .and_then(|w| w.parse::<u64>().ok())
+                    .ok_or_else(|| {
+                        Error::InvalidParameter("'with' must be the id of a graph".to_string())
+                    })?;
+                let old = stored(with)?;
+                let (vertices, edges, summary) =
+                    analytics::diff(&old.vertices, &old.edges, &new.vertices, &new.edges);
+                let combined = StoredGraph {
+                    query_name: format!(
+                        "{} (#{}) compared to {} (#{})",
+                        new.query_name, new.id, old.query_name, old.id
+                    ),
+                    vertices,
+                    edges,
+                    ..new
+                };
+                let context = GraphContext {
+                    color_attribute: Some("diff"),
+                    page_url: format!("/graph/{}/diff?with={}&", id, with),
+                    diff: Some(summary),
+                    ..graph_context(&config, &graphs, combined, viewer::diff_colors(), &options)?
+                };
+                Ok::<_, Rejection>(render(&tera, "graph.html", &context)?)
+            },
+        );
+
+    // Attribute summaries of a graph, with a form to filter it
+    let graph_preview = warp::path!("graph" / u64 / "preview")
+        .and(tera_filter.clone())
+        .and(graphs_filter.clone())
+        .and_then(|id: u64, tera: Page, graphs: Arc<GraphStore>| async move {
+            let graph = graphs.get(id).ok_or_else(|| {
+                Error::NotFound(format!("Graph {} (only recent graphs are kept)", id))
+            })?;
+            let context = PreviewContext {
+                vertex_attributes: graph_filter::summarize(&graph.vertices),
+                edge_attributes: graph_filter::summarize(&graph.edges),
+                graph,
+            };
+            Ok::<_, Rejection>(render(&tera, "preview.html", &context)?)
+        });
+
+    // Keeps the part of a graph that fulfills the predicates of the preview
+    // form as a new graph and shows it in the viewer
+    let graph_filter = warp::path!("graph" / u64 / "filter")
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(graphs_filter.clone())
+        .and_then(
+            |id: u64, form: HashMap<String, String>, graphs: Arc<GraphStore>| async move {
+                let graph = graphs
+                    .get(id)
+                    .ok_or_else(|| Error::NotFound(format!("Graph {}", id)))?;
+                let predicates = |field: &str| {
+                    Predicate::parse_lines(form.get(field).map(String::as_str).unwrap_or(""))
+                };
+                let (mut vertices, edges) = graph_filter::filter(
+                    &graph.vertices,
+                    &graph.edges,
+                    &predicates("vertex_filter")?,
+                    &predicates("edge_filter")?,
+                );
+                // Components and centralities change with the graph
+                analytics::annotate(&mut vertices, &edges);
+                let filtered = graphs
+                    .insert(
+                        &format!("{} (filtered)", graph.query_name),
+                        graph.renderer,
+                        &vertices,
+                        &edges,
+                    )
+                    .ok_or_else(not_kept)?;
+                Ok::<_, Rejection>(warp::redirect::see_other(graph_uri(filtered)))
+            },
+        );
+
+    // The graph of a result as JSON, for other renderers and tools
+    let graph_data = warp::path!("graph" / u64 / "data")
+        .and(graphs_filter.clone())
+        .and_then(|id: u64, graphs: Arc<GraphStore>| async move {
+            let graph = graphs
+                .get(id)
+                .ok_or_else(|| Error::NotFound(format!("Graph {}", id)))?;
+            Ok::<_, Rejection>(warp::reply::json(&viewer::graph_data(
+                &graph.vertices,
+                &graph.edges,
+            )))
+        });
+
+    // Shortest path between two vertices of a graph, for highlighting it in
+    // the viewer: `?from=...&to=...`, optionally `&weight=<attribute>` and
+    // `&directed=true`
+    let graph_path = warp::path!("graph" / u64 / "path")
+        .and(warp::query::<HashMap<String, String>>())
+        .and(graphs_filter.clone())
+        .and_then(
+            |id: u64, options: HashMap<String, String>, graphs: Arc<GraphStore>| async move {
+                let graph = graphs
+                    .get(id)
+                    .ok_or_else(|| Error::NotFound(format!("Graph {}", id)))?;
+                let vertex = |name: &str| {
+                    options
+                        .get(name)
+                        .filter(|v| !v.is_empty())
+                        .ok_or_else(|| Error::InvalidParameter(format!("Missing '{}'", name)))
+                };
+                let (from, to) = (vertex("from")?, vertex("to")?);
+                let path = analytics::shortest_path(
+                    &graph.vertices,
+                    &graph.edges,
+                    from,
+                    to,
+                    options
+                        .get("weight")
+                        .map(String::as_str)
+                        .filter(|w| !w.is_empty()),
+                    options.get("directed").is_some_and(|d| d == "true"),
+                )
+                .map_err(Error::InvalidParameter)?
+                .ok_or_else(|| Error::NotFound(format!("Path from '{}' to '{}'", from, to)))?;
+                let edges: Vec<serde_json::Value> = path
+                    .edges
+                    .iter()
+                    .map(|i| viewer::edge_id(&graph.edges[*i], *i))
+                    .collect();
+                Ok::<_, Rejection>(warp::reply::json(&serde_json::json!({
+                    "vertices": path.vertices,
+                    "edges": edges,
+                    "length": path.length,
+                })))
+            },
+        );
+
+    // Sends a kept graph to Cytoscape on request from the result page. The
+    // body is the `CytoscapePush` the page was rendered with.
+    let graph_cytoscape = warp::path!("graph" / u64 / "cytoscape")
+        .and(warp::post())
+        .and(body_limit(limits.max_body_bytes))
+        .and(warp::body::json())
+        .and(config_filter.clone())
+        .and(runner_filter.clone())
+        .and(reporter_filter.clone())
+        .and(graphs_filter.clone())
+        .and(uploads_filter.clone())
+        .and_then(
+            |id: u64,
+             push: CytoscapePush,
+             config: Arc<Configuration>,
+             runner: Arc<QueryRunner>,
+             reporter: Arc<ErrorReporter>,
+             graphs: Arc<GraphStore>,
+             uploads: Arc<Uploads>| async move {
+                let graph = graphs
+                    .get(id)
+                    .ok_or_else(|| Error::NotFound(format!("Graph {}", id)))?;
+                if let Some(layout) = &push.layout {
+                    if !cytoscape::LAYOUTS.contains(&layout.as_str()) {
+                        return Err(Error::InvalidParameter(format!(
+                            "Unknown layout '{}'",
+                            layout
+                        ))
+                        .into());
+                    }
+                }
+                let query = config.queries.iter().find(|q| q.name == graph.query_name);
+                let route = format!("/graph/{}/cytoscape", id);
+                let pipeline = GraphPipeline {
+                    config: Arc::clone(&config),
+                    runner,
+                    reporter,
+                    graphs,
+                    uploads,
+                };
+                let upload = pipeline.start_upload(
+                    &route,
+                    &graph.query_name,
+                    query,
+                    &push,
+                    serde_json::Value::from(graph.vertices),
+                    serde_json::Value::from(graph.edges),
+                );
+                Ok::<_, Rejection>(warp::reply::json(&serde_json::json!({ "upload": upload })))
+            },
+        );
+
+    // Saves a kept graph to disk under the name of the form, see
+    // `saved_graphs`
+    let graph_save = warp::path!("graph" / u64 / "save")
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(graphs_filter.clone())
+        .and(saved_graphs_filter.clone())
+        .and_then(
+            |id: u64,
+             form: HashMap<String, String>,
+             graphs: Arc<GraphStore>,
+             saved_graphs: Arc<SavedGraphs>| async move {
+                let graph = graphs
+                    .get(id)
+                    .ok_or_else(|| Error::NotFound(format!("Graph {}", id)))?;
+                let name = form.get("name").map(String::as_str).unwrap_or("");
+                saved_graphs.save(&graph, name)?;
+                Ok::<_, Rejection>(warp::redirect::see_other(warp::http::Uri::from_static(
+                    "/graphs",
+                )))
+            },
+        );
+
+    // The saved graphs, to open them again or send them to Cytoscape
+    let saved_graphs_page = warp::path!("graphs")
+        .and(warp::get())
+        .and(tera_filter.clone())
+        .and(saved_graphs_filter.clone())
+        .and_then(|tera: Page, saved_graphs: Arc<SavedGraphs>| async move {
+            let context = SavedGraphsContext {
+                enabled: saved_graphs.enabled(),
+                graphs: saved_graphs.list(),
+            };
+            Ok::<_, Rejection>(render(&tera, "graphs.html", &context)?)
+        });
+
+    // Keeps a saved graph again, as if its query had just run, and shows it
+    // in the viewer
+    let saved_graph_open = warp::path!("graphs" / u64 / "open")
+        .and(warp::post())
+        .and(graphs_filter.clone())
+        .and(saved_graphs_filter.clone())
+        .and_then(
+            |id: u64, graphs: Arc<GraphStore>, saved_graphs: Arc<SavedGraphs>| async move {
+                let saved = saved_graphs.get(id)?;
+                let kept = graphs
+                    .insert(
+                        &saved.query_name,
+                        saved.renderer,
+                        &saved.vertices,
+                        &saved.edges,
+                    )
+                    .ok_or_else(not_kept)?;
+                Ok::<_, Rejection>(warp::redirect::see_other(graph_uri(kept)))
+            },
+        );
+
+    // Sends a saved graph to Cytoscape, styled by its query if that still
+    // exists; the page follows the upload like the result page
+    let saved_graph_cytoscape = warp::path!("graphs" / u64 / "cytoscape")
+        .and(warp::post())
+        .and(config_filter.clone())
+        .and(runner_filter.clone())
+        .and(reporter_filter.clone())
+        .and(graphs_filter.clone())
+        .and(uploads_filter.clone())
+        .and(saved_graphs_filter.clone())
+        .and_then(
+            |id: u64,
+             config: Arc<Configuration>,
+             runner: Arc<QueryRunner>,
+             reporter: Arc<ErrorReporter>,
+             graphs: Arc<GraphStore>,
+             uploads: Arc<Uploads>,
+             saved_graphs: Arc<SavedGraphs>| async move {
+                let saved = saved_graphs.get(id)?;
+                let query = config.queries.iter().find(|q| q.name == saved.query_name);
+                let route = format!("/graphs/{}/cytoscape", id);
+                let pipeline = GraphPipeline {
+                    config: Arc::clone(&config),
+                    runner,
+                    reporter,
+                    graphs,
+                    uploads,
+                };
+                let upload = pipeline.start_upload(
+                    &route,
+                    &saved.name,
+                    query,
+                    &CytoscapePush::default(),
+                    serde_json::Value::from(saved.vertices),
+                    serde_json::Value::from(saved.edges),
+                );
+                Ok::<_, Rejection>(warp::reply::json(&serde_json::json!({ "upload": upload })))
+            },
+        );
+
+    let saved_graph_delete = warp::path!("graphs" / u64 / "delete")
+        .and(warp::post())
+        .and(saved_graphs_filter.clone())
+        .and_then(|id: u64, saved_graphs: Arc<SavedGraphs>| async move {
+            saved_graphs.delete(id)?;
+            Ok::<_, Rejection>(warp::redirect::see_other(warp::http::Uri::from_static(
+                "/graphs",
+            )))
+        });
+
+    // The networks in Cytoscape, asked for by the parameter form only once
+    // results are to be sent there, as Cytoscape may take a while to answer
+    let cytoscape_networks = warp::path!("api" / "cytoscape" / "networks")
+        .and(warp::get())
+        .and(config_filter.clone())
+        .and_then(|config: Arc<Configuration>| async move {
+            let networks = cytoscape::list_networks(&config.cytoscape)
+                .await
+                .unwrap_or_default();
+            Ok::<_, Rejection>(warp::reply::json(&networks))
+        });
+
+    // The state of a Cytoscape upload, polled by the result page
+    let upload_status = warp::path!("api" / "cytoscape" / u64)
+        .and(warp::get())
+        .and(uploads_filter.clone())
+        .and_then(|id: u64, uploads: Arc<Uploads>| async move {
+            let upload = uploads
+                .get(id)
+                .ok_or_else(|| Error::NotFound(format!("Upload {}", id)))?;
+            Ok::<_, Rejection>(warp::reply::json(&upload))
+        });
+
+    // A network of a finished upload as rendered by Cytoscape, the first
+    // one unless `?network=` picks another
+    let upload_image = warp::path!("api" / "cytoscape" / u64 / "image" / String)
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(config_filter.clone())
+        .and(uploads_filter.clone())
+        .and_then(
+            |id: u64,
+             format: String,
+             query: HashMap<String, String>,
+             config: Arc<Configuration>,
+             uploads: Arc<Uploads>| async move {
+                let upload = uploads
+                    .get(id)
+                    .ok_or_else(|| Error::NotFound(format!("Upload {}", id)))?;
+                let format = ImageFormat::from_name(&format)
+                    .ok_or_else(|| Error::NotFound(format!("Image format '{}'", format)))?;
+                if upload.state != UploadState::Done {
+                    return Err(Error::InvalidParameter(format!(
+                        "Upload {} has not created a network",
+                        id
+                    ))
+                    .into());
+                }
+                let network = match query.get("network") {
+                    Some(suid) => upload
+                        .networks
+                        .iter()
+                        .copied()
+                        .find(|network| network.to_string() == *suid),
+                    None => upload.networks.first().copied(),
+                }
+                .ok_or_else(|| Error::NotFound(format!("Network of upload {}", id)))?;
+                let body = cytoscape::export_image(&config.cytoscape, network, format).await?;
+                let file_name = format!("network-{}.{}", network, format.name());
+                Ok::<_, Rejection>(
+                    warp::http::Response::builder()
+                        .header("Content-Type", format.content_type())
+                        .header(
+                            "Content-Disposition",
+                            format!("attachment; filename=\"{}\"", file_name),
+                        )
+                        .body(body)
+                        .map_err(|e| Error::Config(e.to_string()))?,
+                )
+            },
+        );
+
+    // A result spilled to disk, streamed from its file as JSON lines
+    let spilled_download = warp::path!("results" / u64 / "download")
+        .and(warp::get())
+        .and(runner_filter.clone())
+        .and_then(|id: u64, runner: Arc<QueryRunner>| async move {
+            let spilled = runner
+                .spilled()
+                .get(id)
+                .ok_or_else(|| Error::NotFound(format!("Spilled result {}", id)))?;
+            let file = tokio::fs::File::open(spilled.file.path())
+                .await
+                .map_err(Error::from)?;
+            // Holds on to the result, so that its file is not deleted while
+            // it is sent
+            let chunks =
+                futures::stream::unfold((file, spilled), |(mut file, spilled)| async move {
+                    let mut buffer = vec![0; 64 * 1024];
+                    match file.read(&mut buffer).await {
+                        Ok(0) => None,
+                        Ok(n) => {
+                            buffer.truncate(n);
+                            Some((Ok(buffer), (file, spilled)))
+                        }
+                        Err(e) => Some((Err(e), (file, spilled))),
+                    }
+                });
+            Ok::<_, Rejection>(
+                warp::http::Response::builder()
+                    .header("Content-Type", "application/x-ndjson")
+                    .header(
+                        "Content-Disposition",
+                        format!("attachment; filename=\"results-{}.ndjson\"", id),
+                    )
+                    .body(warp::hyper::Body::wrap_stream(chunks))
+                    .map_err(|e| Error::Config(e.to_string()))?,
+            )
+        });
+
+    // The graph of a result as a file for other graph tools
+    let graph_export = warp::path!("graph" / u64 / "export" / String)
+        .and(config_filter.clone())
+        .and(graphs_filter.clone())
+        .and_then(
+            |id: u64, format: String, config: Arc<Configuration>, graphs: Arc<GraphStore>| async move {
+                let graph = graphs
+                    .get(id)
+                    .ok_or_else(|| Error::NotFound(format!("Graph {}", id)))?;
+                let format = ExportFormat::from_name(&format)
+                    .ok_or_else(|| Error::NotFound(format!("Export format '{}'", format)))?;
+                let body = export::export(
+                    format,
+                    &config.export,
+                    &graph.query_name,
+                    &graph.vertices,
+                    &graph.edges,
+                )?;
+                let file_name = format!("graph-{}-{}.{}", id, format.name(), format.extension());
+                Ok::<_, Rejection>(
+                    warp::http::Response::builder()
+                        .header("Content-Type", format.content_type())
+                        .header(
+                            "Content-Disposition",
+                            format!("attachment; filename=\"{}\"", file_name),
+                        )
+                        .body(body)
+                        .map_err(|e| Error::Config(e.to_string()))?,
+                )
+            },
+        );
+
+    // Admin page
+    let admin = warp::path!("admin")
+        .and(warp::get())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(slow_log_filter.clone())
+        .and(proposals_filter.clone())
+        .and(runner_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |config: Arc<Configuration>,
+             tera: Page,
+             slow_log: Arc<SlowQueryLog>,
+             proposals: Arc<Proposals>,
+             runner: Arc<QueryRunner>,
+             admin: bool| async move {
+                let client = runner.client();
+                // The database's own history, next to the runner's log, for
+                // admins only as its bind variables may hold others' data
+                let slow = async {
+                    if admin {
+                        Some(client.slow_queries().await)
+                    } else {
+                        None
+                    }
+                };
+                let (server, slow) = tokio::join!(client.server_info(), slow);
+                let (server, server_error) = match server {
+                    Ok(server) => (Some(server), None),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+                let server_slow_queries = slow.map(|slow| match slow {
+                    Ok(slow) => ServerSlowQueries {
+                        queries: slow_log::correlate(&config.queries, slow),
+                        error: None,
+                    },
+                    Err(e) => ServerSlowQueries {
+                        queries: Vec::new(),
+                        error: Some(e.to_string()),
+                    },
+                });
+                let context = AdminContext {
+                    server_slow_queries,
+                    server,
+                    server_error,
+                    ..AdminContext::new(&config, &slow_log, &proposals, admin)
+                };
+                Ok::<_, Rejection>(render(&tera, "admin.html", &context)?)
+            },
+        );
+
+    // Health of the server and its connections
+    let admin_status = warp::path!("admin" / "status")
+        .and(warp::get())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(reporter_filter.clone())
+        .and(tenants_filter)
+        .and(session_filter.clone())
+        .and(jobs_filter.clone())
+        .and(queue_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |config: Arc<Configuration>,
+             tera: Page,
+             reporter: Arc<ErrorReporter>,
+             tenants: Arc<Tenants>,
+             sessions: Arc<SessionStore>,
+             _session: Option<String>,
+             jobs: Arc<Jobs>,
+             queue: Arc<ExecutionQueue>,
+             admin: bool| async move {
+                // Endpoints, the configuration file and errors of all users
+                require_admin_to(admin, "see the server status")?;
+                let client = Client::from_config(&config);
+                // Every tenant keeps graphs, uploads and cursors of its own
+                let runners: Vec<&Arc<QueryRunner>> = tenants.runners().collect();
+                let (arangodb, cytoscape) = tokio::join!(
+                    status::check_arangodb(&client),
+                    status::check_cytoscape(&config.cytoscape)
+                );
+                let context = StatusContext {
+                    config_source: config
+                        .source
+                        .clone()
+                        .unwrap_or_else(|| "built-in demo".to_string()),
+                    loaded_at: config.loaded_at,
+                    queries: config.queries.len(),
+                    arangodb_endpoint: config.arangodb_endpoint.clone(),
+                    arangodb,
+                    cytoscape,
+                    running_queries: jobs.running(),
+                    waiting_queries: queue.waiting(),
+                    running_uploads: runners.iter().map(|r| r.uploads().running()).sum(),
+                    stores: vec![
+                        StoreUsage {
+                            name: "Kept graphs",
+                            entries: runners.iter().map(|r| r.graphs().len()).sum(),
+                            limit: config.viewer.keep_graphs * runners.len(),
+                        },
+                        StoreUsage {
+                            name: "Open cursors",
+                            entries: runners.iter().map(|r| r.cursors().len()).sum(),
+                            limit: config.cursors.max_open * runners.len(),
+                        },
+                        StoreUsage {
+                            name: "Sessions",
+                            entries: sessions.len(),
+                            limit: config.sessions.max_sessions,
+                        },
+                    ],
+                    buffered: status::format_bytes(
+                        runners.iter().map(|r| r.graphs().buffered_bytes()).sum(),
+                    ),
+                    recent_errors: reporter.recent(),
+                };
+                Ok::<_, Rejection>(render(&tera, "status.html", &context)?)
+            },
+        );
+
+    // The AQL queries running in the database of the user, e.g. to find
+    // and kill an exploration that went wrong
+    let running_queries = warp::path!("admin" / "queries")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(tera_filter.clone())
+        .and(runner_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |options: HashMap<String, String>,
+             tera: Page,
+             runner: Arc<QueryRunner>,
+             admin: bool| async move {
+                // Their bind variables may hold the data of other users
+                require_admin_to(admin, "list running queries")?;
+                let (queries, error) = match runner.client().running_queries().await {
+                    Ok(queries) => (queries, None),
+                    Err(e) => (Vec::new(), Some(e.to_string())),
+                };
+                let context = RunningQueriesContext {
+                    endpoint: runner.client().endpoint().to_string(),
+                    queries,
+                    killed: options.get("killed").cloned(),
+                    error,
+                };
+                Ok::<_, Rejection>(render(&tera, "running_queries.html", &context)?)
+            },
+        );
+
+    let running_query_kill = warp::path!("admin" / "queries" / String / "kill")
+        .and(warp::post())
+        .and(runner_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |id: String, runner: Arc<QueryRunner>, admin: bool| async move {
+                require_admin_to(admin, "kill queries")?;
+                runner.client().kill_query(&id).await?;
+                Ok::<_, Rejection>(see_other(&format!("/admin/queries?killed={}", id))?)
+            },
+        );
+
+    // The same as JSON, and killing a query by `DELETE`
+    let running_queries_api = warp::path!("api" / "queries" / "running")
+        .and(warp::get())
+        .and(runner_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(|runner: Arc<QueryRunner>, admin: bool| async move {
+            require_admin_to(admin, "list running queries")?;
+            let queries = runner.client().running_queries().await?;
+            Ok::<_, Rejection>(warp::reply::json(&queries))
+        });
+
+    // The slow query history of the database, named like on the admin page
+    let slow_queries_api = warp::path!("api" / "queries" / "slow")
+        .and(warp::get())
+        .and(config_filter.clone())
+        .and(runner_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |config: Arc<Configuration>, runner: Arc<QueryRunner>, admin: bool| async move {
+                require_admin_to(admin, "list slow queries")?;
+                let slow = runner.client().slow_queries().await?;
+                Ok::<_, Rejection>(warp::reply::json(&slow_log::correlate(
+                    &config.queries,
+                    slow,
+                )))
+            },
+        );
+
+    let running_query_kill_api = warp::path!("api" / "queries" / "running" / String)
+        .and(warp::delete())
+        .and(runner_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |id: String, runner: Arc<QueryRunner>, admin: bool| async move {
+                require_admin_to(admin, "kill queries")?;
+                runner.client().kill_query(&id).await?;
+                Ok::<_, Rejection>(warp::reply::json(&serde_json::json!({ "killed": id })))
+            },
+        );
+
+    // Load the seed data of the configuration into the database
+    let admin_seed = warp::path!("admin" / "seed")
+        .and(warp::post())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(slow_log_filter.clone())
+        .and(runner_filter.clone())
+        .and(proposals_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |config: Arc<Configuration>,
+             tera: Page,
+             slow_log: Arc<SlowQueryLog>,
+             runner: Arc<QueryRunner>,
+             proposals: Arc<Proposals>,
+             admin: bool| async move {
+                require_admin_to(admin, "seed the database")?;
+                let reports = seed::seed(runner.client(), &config.seed).await?;
+                let context = AdminContext {
+                    seed_reports: Some(reports),
+                    ..AdminContext::new(&config, &slow_log, &proposals, admin)
+                };
+                Ok::<_, Rejection>(render(&tera, "admin.html", &context)?)
+            },
+        );
+
+    // The query catalog as a bundle to import elsewhere
+    let catalog_export = warp::path!("admin" / "catalog" / "export")
+        .and(warp::get())
+        .and(config_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(|config: Arc<Configuration>, admin: bool| async move {
+            // The AQL of every query, including those of other teams
+            require_admin_to(admin, "export the query catalog")?;
+            let bundle = catalog::export(&config);
+            let body = serde_json::to_string_pretty(&bundle).map_err(|e| Error::Io(e.into()))?;
+            Ok::<_, Rejection>(
+                warp::http::Response::builder()
+                    .header("Content-Type", "application/json")
+                    .header(
+                        "Content-Disposition",
+                        "attachment; filename=\"catalog.json\"",
+                    )
+                    .body(body)
+                    .map_err(|e| Error::Config(e.to_string()))?,
+            )
+        });
+
+    // Imports an uploaded bundle into the catalog of the running server and
+    // of the configuration file
+    let catalog_import = warp::path!("admin" / "catalog" / "import")
+        .and(warp::post())
+        .and(warp::multipart::form().max_length(limits.max_body_bytes))
+        .and(live_catalog_filter.clone())
+        .and(tera_filter.clone())
+        .and(slow_log_filter.clone())
+        .and(versions_filter.clone())
+        .and(user_filter.clone())
+        .and(admin_filter.clone())
+        .and(proposals_filter.clone())
+        .and_then(
+            |form: warp::multipart::FormData,
+             catalog: Arc<LiveCatalog>,
+             tera: Page,
+             slow_log: Arc<SlowQueryLog>,
+             versions: Arc<QueryVersions>,
+             user: Option<String>,
+             admin: bool,
+             proposals: Arc<Proposals>| async move {
+                require_admin(admin)?;
+                let fields = form_fields(form).await?;
+                let strategy = fields
+                    .get("strategy")
+                    .map(|s| String::from_utf8_lossy(s).into_owned())
+                    .unwrap_or_default();
+                let strategy = ImportStrategy::from_name(&strategy).ok_or_else(|| {
+                    Error::InvalidParameter(format!("Unknown import strategy '{}'", strategy))
+                })?;
+                let bundle =
+                    catalog::parse_bundle(fields.get("bundle").ok_or_else(|| {
+                        Error::InvalidParameter("No bundle uploaded".to_string())
+                    })?)?;
+
+                let author = user.as_deref().unwrap_or(ANONYMOUS);
+                let (config, report) =
+                    import_catalog(&catalog, &versions, author, bundle, strategy).await?;
+
+                let context = AdminContext {
+                    import_report: Some(report),
+                    ..AdminContext::new(&config, &slow_log, &proposals, admin)
+                };
+                Ok::<_, Rejection>(render(&tera, "admin.html", &context)?)
+            },
+        );
+
+    // Everything the runner keeps as one tar.gz, see `archive`
+    let archive_export = warp::path!("admin" / "archive" / "export")
+        .and(warp::get())
+        .and(config_filter.clone())
+        .and(versions_filter.clone())
+        .and(history_filter.clone())
+        .and(saved_graphs_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |config: Arc<Configuration>,
+             versions: Arc<QueryVersions>,
+             history: Arc<QueryHistory>,
+             saved_graphs: Arc<SavedGraphs>,
+             admin: bool| async move {
+                require_admin_to(admin, "export the archive")?;
+                let archive = Archive::collect(&config, &versions, &history, &saved_graphs)?;
+                let body = archive.to_tar_gz()?;
+                let filename = format!(
+                    "adb_query_runner-{}.tar.gz",
+                    archive.manifest.exported_at.format("%Y%m%d-%H%M%S")
+                );
+                Ok::<_, Rejection>(
+                    warp::http::Response::builder()
+                        .header("Content-Type", "application/gzip")
+                        .header(
+                            "Content-Disposition",
+                            format!("attachment; filename=\"{}\"", filename),
+                        )
+                        .body(body)
+                        .map_err(|e| Error::Config(e.to_string()))?,
+                )
+            },
+        );
+
+    // Restores an uploaded archive, its catalog imported like a bundle
+    let archive_limits = {
+        let limits = limits.clone();
+        warp::any().map(move || limits.clone())
+    };
+    let archive_import = warp::path!("admin" / "archive" / "import")
+        .and(warp::post())
+        .and(warp::multipart::form().max_length(limits.max_body_bytes))
+        .and(live_catalog_filter.clone())
+        .and(tera_filter.clone())
+        .and(slow_log_filter.clone())
+        .and(versions_filter.clone())
+        .and(history_filter.clone())
+        .and(saved_graphs_filter.clone())
+        .and(user_filter.clone())
+        .and(admin_filter.clone())
+        .and(proposals_filter.clone())
+        .and(archive_limits)
+        .and_then(
+            |form: warp::multipart::FormData,
+             catalog: Arc<LiveCatalog>,
+             tera: Page,
+             slow_log: Arc<SlowQueryLog>,
+             versions: Arc<QueryVersions>,
+             history: Arc<QueryHistory>,
+             saved_graphs: Arc<SavedGraphs>,
+             user: Option<String>,
+             admin: bool,
+             proposals: Arc<Proposals>,
+             limits: LimitsConfig| async move {
+                require_admin_to(admin, "restore an archive")?;
+                let fields = form_fields(form).await?;
+                let strategy = fields
+                    .get("strategy")
+                    .map(|s| String::from_utf8_lossy(s).into_owned())
+                    .unwrap_or_default();
+                let strategy = ImportStrategy::from_name(&strategy).ok_or_else(|| {
+                    Error::InvalidParameter(format!("Unknown import strategy '{}'", strategy))
+                })?;
+                let archive = Archive::from_tar_gz(
+                    fields.get("archive").ok_or_else(|| {
+                        Error::InvalidParameter("No archive uploaded".to_string())
+                    })?,
+                    &limits,
+                )?;
+
+                let (bundle, restore_report) =
+                    archive.restore(&versions, &history, &saved_graphs)?;
+                let author = user.as_deref().unwrap_or(ANONYMOUS);
+                let (config, report) =
+                    import_catalog(&catalog, &versions, author, bundle, strategy).await?;
+                info!(
+                    versions = restore_report.versions,
+                    runs = restore_report.runs,
+                    saved_graphs = restore_report.saved_graphs,
+                    "Restored archive"
+                );
+
+                let context = AdminContext {
+                    import_report: Some(report),
+                    restore_report: Some(restore_report),
+                    ..AdminContext::new(&config, &slow_log, &proposals, admin)
+                };
+                Ok::<_, Rejection>(render(&tera, "admin.html", &context)?)
+            },
+        );
+
+    // Form to edit a query definition
+    let query_edit = warp::path!("admin" / "query" / usize / "edit")
+        .and(warp::get())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(versions_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |idx: usize,
+             config: Arc<Configuration>,
+             tera: Page,
+             versions: Arc<QueryVersions>,
+             admin: bool| async move {
+                let query = query_at(&config, idx)?;
+                let context = QueryEditContext {
+                    index: Some(idx),
+                    name: query.name.clone(),
+                    definition: versions::to_text(query),
+                    version: versions.latest(&query.name).map(|v| v.version),
+                    admin,
+                    issues: validate::check_parameters(query),
+                };
+                Ok::<_, Rejection>(render(&tera, "query_edit.html", &context)?)
+            },
+        );
+
+    // Saves an edited query definition as its new version, or proposes it
+    // for review
+    let query_save = warp::path!("admin" / "query" / usize / "edit")
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(live_catalog_filter.clone())
+        .and(versions_filter.clone())
+        .and(user_filter.clone())
+        .and(admin_filter.clone())
+        .and(proposals_filter.clone())
+        .and_then(
+            |idx: usize,
+             form: HashMap<String, String>,
+             catalog: Arc<LiveCatalog>,
+             versions: Arc<QueryVersions>,
+             user: Option<String>,
+             admin: bool,
+             proposals: Arc<Proposals>| async move {
+                let definition = definition_field(&form)?;
+                if !admin {
+                    let current = catalog.current();
+                    let (_, definition) = replace_query(&current.queries, idx, definition)?;
+                    let base = query_at(&current, idx)?.clone();
+                    let id = proposals.propose(&proposer(user)?, Some(base), definition);
+                    return Ok::<_, Rejection>(see_other(&format!("/proposals/{}", id))?);
+                }
+                let author = user.as_deref().unwrap_or(ANONYMOUS);
+                let message = format!("Edit query '{}'", definition.name);
+                let (_, definition) = catalog
+                    .change(author, &message, |current| {
+                        replace_query(current, idx, definition)
+                    })
+                    .await?;
+                let version = versions.record(&definition, author, "Edited");
+                info!(
+                    query = definition.name.as_str(),
+                    version, author, "Query edited"
+                );
+                Ok::<_, Rejection>(see_other(&format!("/admin/query/{}/versions", idx))?)
+            },
+        );
+
+    // Form for a new query
+    let query_new = warp::path!("admin" / "query" / "new")
+        .and(warp::get())
+        .and(tera_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(|tera: Page, admin: bool| async move {
+            let skeleton = serde_json::json!({
+                "name": "",
+                "description": "",
+                "query": "",
+                "parameters": []
+            });
+            let context = QueryEditContext {
+                index: None,
+                name: String::new(),
+                definition: serde_json::to_string_pretty(&skeleton).unwrap_or_default(),
+                version: None,
+                admin,
+                issues: Vec::new(),
+            };
+            Ok::<_, Rejection>(render(&tera, "query_edit.html", &context)?)
+        });
+
+    // Adds a new query to the catalog, or proposes it for review
+    let query_create = warp::path!("admin" / "query" / "new")
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(live_catalog_filter.clone())
+        .and(versions_filter.clone())
+        .and(user_filter.clone())
+        .and(admin_filter.clone())
+        .and(proposals_filter.clone())
+        .and_then(
+            |form: HashMap<String, String>,
+             catalog: Arc<LiveCatalog>,
+             versions: Arc<QueryVersions>,
+             user: Option<String>,
+             admin: bool,
+             proposals: Arc<Proposals>| async move {
+                let mut definition = definition_field(&form)?;
+                if definition.parameters.is_empty() {
+                    definition.parameters = validate::discover_parameters(&definition.query, &[]);
+                }
+                if !admin {
+                    add_query(&catalog.current().queries, definition.clone())?;
+                    let id = proposals.propose(&proposer(user)?, None, definition);
+                    return Ok::<_, Rejection>(see_other(&format!("/proposals/{}", id))?);
+                }
+                let author = user.as_deref().unwrap_or(ANONYMOUS);
+                let message = format!("Add query '{}'", definition.name);
+                let (config, definition) = catalog
+                    .change(author, &message, |current| add_query(current, definition))
+                    .await?;
+                versions.record(&definition, author, "Created");
+                info!(query = definition.name.as_str(), author, "Query added");
+                let idx = config.queries.len() - 1;
+                Ok::<_, Rejection>(see_other(&format!("/admin/query/{}/versions", idx))?)
+            },
+        );
+
+    // The proposed changes, newest first
+    let proposal_list = warp::path!("proposals")
+        .and(warp::get())
+        .and(tera_filter.clone())
+        .and(proposals_filter.clone())
+        .and_then(|tera: Page, proposals: Arc<Proposals>| async move {
+            let context = ProposalsContext {
+                proposals: proposals.list(),
+            };
+            Ok::<_, Rejection>(render(&tera, "proposals.html", &context)?)
+        });
+
+    // A proposed change with its diff, to be reviewed by an admin
+    let proposal_view = warp::path!("proposals" / u64)
+        .and(warp::get())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(proposals_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |id: u64,
+             config: Arc<Configuration>,
+             tera: Page,
+             proposals: Arc<Proposals>,
+             admin: bool| async move {
+                let proposal = proposals.get(id)?;
+                let current = config.find_query(&proposal.definition.name);
+                let outdated = proposal.status == ProposalStatus::Pending
+                    && !is_current(current, proposal.base.as_ref());
+                let before = match proposal.status {
+                    ProposalStatus::Pending => proposal.base.as_ref(),
+                    _ => current,
+                };
+                let context = ProposalContext {
+                    diff: versions::diff(
+                        &before.map(versions::to_text).unwrap_or_default(),
+                        &versions::to_text(&proposal.definition),
+                    ),
+                    proposal,
+                    outdated,
+                    admin,
+                };
+                Ok::<_, Rejection>(render(&tera, "proposal.html", &context)?)
+            },
+        );
+
+    // Approves or rejects a proposal. Approved changes are made by the
+    // catalog like any other, with the proposer as author.
+    let proposal_review = warp::path!("proposals" / u64)
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(live_catalog_filter.clone())
+        .and(versions_filter.clone())
+        .and(proposals_filter.clone())
+        .and(user_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |id: u64,
+             form: HashMap<String, String>,
+             catalog: Arc<LiveCatalog>,
+             versions: Arc<QueryVersions>,
+             proposals: Arc<Proposals>,
+             user: Option<String>,
+             admin: bool| async move {
+                require_admin(admin)?;
+                let reviewer = user.as_deref().unwrap_or(ANONYMOUS);
+                let comment = form.get("comment").map(String::as_str).unwrap_or_default();
+                let proposal = proposals.get(id)?;
+                if form.get("decision").map(String::as_str) != Some("approve") {
+                    proposals.review(id, ProposalStatus::Rejected, reviewer, comment)?;
+                    info!(proposal = id, reviewer, "Proposal rejected");
+                    return Ok::<_, Rejection>(see_other(&format!("/proposals/{}", id))?);
+                }
+
+                let name = proposal.definition.name.clone();
+                let message = format!("Change query '{}', approved by {}", name, reviewer);
+                catalog
+                    .change(&proposal.author, &message, |current| {
+                        let existing = current.iter().find(|q| q.name == name);
+                        if !is_current(existing, proposal.base.as_ref()) {
+                            return Err(Error::InvalidParameter(format!(
+                                "'{}' changed since it was proposed",
+                                name
+                            )));
+                        }
+                        let mut queries = current.to_vec();
+                        match queries.iter_mut().find(|q| q.name == name) {
+                            Some(query) => *query = proposal.definition.clone(),
+                            None => queries.push(proposal.definition.clone()),
+                        }
+                        Ok((queries, ()))
+                    })
+                    .await?;
+                proposals.review(id, ProposalStatus::Approved, reviewer, comment)?;
+                versions.record(
+                    &proposal.definition,
+                    &proposal.author,
+                    &format!("Approved by {}", reviewer),
+                );
+                info!(
+                    proposal = id,
+                    reviewer,
+                    query = name.as_str(),
+                    "Proposal approved"
+                );
+                Ok::<_, Rejection>(see_other(&format!("/proposals/{}", id))?)
+            },
+        );
+
+    // Lints a query definition while it is edited
+    let query_lint = warp::path!("api" / "lint")
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::bytes())
+        .map(|body: warp::hyper::body::Bytes| {
+            let issues = match serde_json::from_slice::<QueryDefinition>(&body) {
+                Ok(definition) => validate::check_parameters(&definition),
+                Err(e) => vec![Issue {
+                    severity: Severity::Error,
+                    query: None,
+                    message: format!("Invalid query definition: {}", e),
+                }],
+            };
+            warp::reply::json(&issues)
+        });
+
+    // A query definition with the parameters its AQL needs
+    let query_discover = warp::path!("api" / "discover")
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::bytes())
+        .and_then(|body: warp::hyper::body::Bytes| async move {
+            let mut definition: QueryDefinition = serde_json::from_slice(&body)
+                .map_err(|e| Error::InvalidParameter(format!("Invalid query definition: {}", e)))?;
+            definition.parameters =
+                validate::discover_parameters(&definition.query, &definition.parameters);
+            Ok::<_, Rejection>(warp::reply::json(&definition))
+        });
+
+    // The versions of a query, with the changes between two of them
+    let query_versions = warp::path!("admin" / "query" / usize / "versions")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(config_filter.clone())
+        .and(tera_filter.clone())
+        .and(versions_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |idx: usize,
+             params: HashMap<String, String>,
+             config: Arc<Configuration>,
+             tera: Page,
+             versions: Arc<QueryVersions>,
+             admin: bool| async move {
+                // Earlier definitions with their authors are for admins only
+                require_admin_to(admin, "see the versions of a query")?;
+                let query = query_at(&config, idx)?;
+                let kept = versions.versions(&query.name);
+                let chosen = |key: &str| params.get(key).and_then(|v| v.parse::<usize>().ok());
+                // By default the latest change
+                let to = chosen("to").or_else(|| kept.last().map(|v| v.version));
+                let from = chosen("from")
+                    .or_else(|| kept.iter().rev().map(|v| v.version).find(|v| Some(*v) < to));
+                let text = |version: Option<usize>| {
+                    version
+                        .and_then(|v| versions.get(&query.name, v))
+                        .map(|v| versions::to_text(&v.definition))
+                        .unwrap_or_default()
+                };
+                let context = VersionsContext {
+                    index: idx,
+                    name: query.name.clone(),
+                    diff: versions::diff(&text(from), &text(to)),
+                    versions: kept.into_iter().rev().collect(),
+                    from,
+                    to,
+                };
+                Ok::<_, Rejection>(render(&tera, "versions.html", &context)?)
+            },
+        );
+
+    // Restores an earlier version of a query as its new version
+    let query_rollback = warp::path!("admin" / "query" / usize / "rollback")
+        .and(warp::post())
+        .and(body_limit(limits.max_form_bytes))
+        .and(warp::body::form())
+        .and(live_catalog_filter.clone())
+        .and(versions_filter.clone())
+        .and(user_filter.clone())
+        .and(admin_filter.clone())
+        .and_then(
+            |idx: usize,
+             form: HashMap<String, String>,
+             catalog: Arc<LiveCatalog>,
+             versions: Arc<QueryVersions>,
+             user: Option<String>,
+             admin: bool| async move {
+                require_admin(admin)?;
+                let version = form
+                    .get("version")
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .ok_or_else(|| Error::InvalidParameter("No version chosen".to_string()))?;
+                let name = query_at(&catalog.current(), idx)?.name.clone();
+                let earlier = versions
+                    .get(&name, version)
+                    .ok_or_else(|| Error::NotFound(format!("Version {} of '{}'", version, name)))?;
+                let author = user.as_deref().unwrap_or(ANONYMOUS);
+                let message = format!("Roll back query '{}' to version {}", name, version);
+                let (_, definition) = catalog
+                    .change(author, &message, |current| {
+                        replace_query(current, idx, earlier.definition)
+                    })
+                    .await?;
+                versions.record(
+                    &definition,
+                    author,
+                    &format!("Rolled back to version {}", version),
+                );
+                info!(
+                    query = definition.name.as_str(),
+                    version, author, "Query rolled back"
+                );
+                Ok::<_, Rejection>(see_other(&format!("/admin/query/{}/versions", idx))?)
+            },
+        );
+
+    // Remembers the theme chosen by a user and returns to the page they
+    // came from
+    let choose_theme = warp::path!("theme" / String)
+        .and(warp::header::optional::<String>("referer"))
+        .and_then(|name: String, referer: Option<String>| async move {
+            let theme = Theme::from_name(&name)
+                .ok_or_else(|| Error::NotFound(format!("Theme '{}'", name)))?;
+            Ok::<_, Rejection>(
+                warp::http::Response::builder()
+                    .status(warp::http::StatusCode::SEE_OTHER)
+                    .header("Location", referring_page(referer))
+                    .header(
+                        "Set-Cookie",
+                        format!(
+                            "{}={}; Path=/; Max-Age=31536000; SameSite=Lax",
+                            THEME_COOKIE,
+                            theme.name()
+                        ),
+                    )
+                    .body(String::new())
+                    .map_err(|e| Error::Config(e.to_string()))?,
+            )
+        });
+
+    // Static files (stylesheets, scripts, fonts, images) with caching headers
+    let static_files = warp::path("static")
+        .and(warp::path::tail())
+        .and(warp::header::optional::<String>("if-none-match"))
+        .map(
+            move |path: warp::path::Tail, if_none_match: Option<String>| {
+                assets::response(
+                    path.as_str(),
+                    static_tera.static_file(path.as_str()).as_deref(),
+                    if_none_match.as_deref(),
+                )
+            },
+        );
+
+    // Combine routes
+    // Results fetched or downloaded after the result page, boxed like the
+    // administration pages below
+    let result_routes = scroll
+        .or(download)
+        .or(cursor_next)
+        .or(spilled_download)
+        .boxed();
+    // The graph viewer and its exports, boxed like the administration pages
+    // below
+    let graph_routes = graph
+        .or(graph_diff)
+        .or(graph_preview)
+        .or(graph_filter)
+        .or(graph_data)
+        .or(graph_path)
+        .or(graph_export)
+        .or(graph_cytoscape)
+        .boxed();
+    // Saved graphs, boxed like the administration pages below
+    let saved_graph_routes = graph_save
+        .or(saved_graphs_page)
+        .or(saved_graph_open)
+        .or(saved_graph_cytoscape)
+        .or(saved_graph_delete)
+        .boxed();
+    // Moving the catalog between installations, boxed for the same reason
+    let catalog_routes = catalog_export
+        .or(catalog_import)
+        .or(archive_export)
+        .or(archive_import)
+        .boxed();
+    // The administration pages, boxed so that the type of all routes stays
+    // within the compiler's limits
+    let admin_routes = admin
+        .or(admin_status)
+        .or(admin_seed)
+        .or(catalog_routes)
+        .or(query_edit)
+        .or(query_save)
+        .or(query_new)
+        .or(query_create)
+        .or(query_lint)
+        .or(query_discover)
+        .or(query_versions)
+        .or(query_rollback)
+        .or(proposal_list)
+        .or(proposal_view)
+        .or(proposal_review)
+        .or(running_queries)
+        .or(running_query_kill)
+        .or(running_queries_api)
+        .or(slow_queries_api)
+        .or(running_query_kill_api)
+        .boxed();
+
+    let routes = menu
+        .or(parameter_form)
+        .or(execute)
+        .or(result_routes)
+        .or(queue_position)
+        .or(performance)
+        .or(benchmark)
+        .or(compare)
+        .or(graph_routes)
+        .or(saved_graph_routes)
+        .or(cytoscape_networks)
+        .or(upload_status)
+        .or(upload_image)
+        .or(explore)
+        .or(explore_run)
+        .or(expand)
+        .or(search_form)
+        .or(search_run)
+        .or(index_overview)
+        .or(usage_overview)
+        .or(admin_routes)
+        .or(choose_theme)
+        .or(static_files)
+        .recover(move |err| {
+            handle_rejection(
+                err,
+                Page {
+                    templates: Arc::clone(&error_tera),
+                    theme,
+                    locale: error_locale.clone(),
+                },
+            )
+        });
+
+    // Stop accepting connections on SIGINT/SIGTERM and let in-flight
+    // requests finish within the configured grace period
+    let signal = async move {
+        shutdown::signal().await;
+        let _ = shutdown_tx.send(true);
+    };
+    let server: futures::future::BoxFuture<'static, ()> = match &unix_socket {
+        #[cfg(unix)]
+        Some(path) => {
+            // A socket left behind by an earlier run would make binding fail
+            let _ = std::fs::remove_file(path);
+            let listener = tokio::net::UnixListener::bind(path)?;
+            let incoming = futures::stream::unfold(listener, |listener| async move {
+                let stream = listener.accept().await.map(|(stream, _)| stream);
+                Some((stream, listener))
+            });
+            info!(socket = path.as_str(), "Server starting on Unix socket");
+            Box::pin(warp::serve(routes).serve_incoming_with_graceful_shutdown(incoming, signal))
+        }
+        #[cfg(not(unix))]
+        Some(_) => {
+            return Err(Error::Config(
+                "unix_socket is only supported on Unix".to_string(),
+            ))
+        }
+        None => {
+            let (_, server) =
+                warp::serve(routes).bind_with_graceful_shutdown(([127, 0, 0, 1], 3030), signal);
+            info!("Server starting on http://localhost:3030");
+            Box::pin(server)
+        }
+    };
+    let server = tokio::spawn(server);
+    let _ = shutdown_rx.changed().await;
+
+    // Requests and the work they left running share the grace period
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(shutdown_timeout_secs);
+    match tokio::time::timeout_at(deadline, server).await {
+        Ok(_) => info!("All requests finished"),
+        Err(_) => warn!("Grace period elapsed, abandoning in-flight requests"),
+    }
+    background.drain(deadline).await;
+    for runner in shutdown_tenants.runners() {
+        runner.uploads().tasks().drain(deadline).await;
+    }
+
+    for client in shutdown_tenants.clients() {
+        client.delete_active_cursors().await;
+    }
+    info!("Shutdown complete");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_target() {
+        assert!(save_target(None, Some("append"), false).unwrap().is_none());
+        assert!(save_target(Some(" ".to_string()), None, false)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            save_target(Some(" cache ".to_string()), None, true).unwrap(),
+            Some(("cache".to_string(), SaveMode::Append))
+        );
+        assert_eq!(
+            save_target(Some("cache".to_string()), Some("overwrite"), true).unwrap(),
+            Some(("cache".to_string(), SaveMode::Overwrite))
+        );
+        // Appending replaces documents too, so users may not save at all
+        for mode in [None, Some("append"), Some("overwrite")] {
+            assert!(matches!(
+                save_target(Some("cache".to_string()), mode, false),
+                Err(Error::Forbidden(_))
+            ));
+        }
+        assert!(matches!(
+            save_target(Some("cache".to_string()), Some("drop"), true),
+            Err(Error::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_referring_page() {
+        let back = |referer: &str| referring_page(Some(referer.to_string()));
+        assert_eq!(back("http://runner:8080/queries?tag=x"), "/queries?tag=x");
+        assert_eq!(back("/history"), "/history");
+        assert_eq!(back("//evil.example.com/x"), "/");
+        assert_eq!(back("/\\evil.example.com"), "/");
+        assert_eq!(back("http://runner:8080//evil.example.com/x"), "/");
+        assert_eq!(back("not a uri"), "/");
+        assert_eq!(referring_page(None), "/");
+    }
+}